@@ -0,0 +1,22 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/pngme.proto");
+        let fds = protox::compile(["proto/pngme.proto"], ["proto"])?;
+        tonic_prost_build::configure()
+            .build_client(false)
+            .compile_fds(fds)?;
+    }
+    #[cfg(feature = "capi")]
+    {
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        cbindgen::Builder::new()
+            .with_src("src/capi.rs")
+            .with_config(cbindgen::Config::from_file("cbindgen.toml")?)
+            .generate()?
+            .write_to_file("include/pngme.h");
+    }
+    #[cfg(feature = "node")]
+    napi_build::setup();
+    Ok(())
+}
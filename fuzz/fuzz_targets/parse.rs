@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::fuzz::{parse, Input};
+
+fuzz_target!(|input: Input| parse(&input));
@@ -0,0 +1,106 @@
+#ifndef PNGME_H
+#define PNGME_H
+
+/* Generated by cbindgen from src/capi.rs with `cargo build --features capi`. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The call succeeded.
+ */
+#define PNGME_OK 0
+
+/**
+ * The call failed, see `pngme_last_error`.
+ */
+#define PNGME_ERROR -1
+
+/**
+ * The PNG has no matching chunk.
+ */
+#define PNGME_NOT_FOUND 1
+
+/**
+ * Bytes allocated by pngme, released with `pngme_buffer_free`.
+ */
+typedef struct PngmeBuffer {
+  uint8_t *data;
+  size_t len;
+} PngmeBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Add `message` to the PNG in a chunk of type `chunk_type`, storing the new
+ * PNG in `out`.
+ *
+ * # Safety
+ *
+ * `buf` must point to `len` readable bytes, `chunk_type` and `message` to
+ * NUL-terminated strings and `out` to writable memory for a `PngmeBuffer`.
+ */
+int pngme_encode(const uint8_t *buf,
+                 size_t len,
+                 const char *chunk_type,
+                 const char *message,
+                 struct PngmeBuffer *out);
+
+/**
+ * Store the message in the first chunk of type `chunk_type` in `out`, as a
+ * NUL-terminated string released with `pngme_string_free`.  Returns
+ * `PNGME_NOT_FOUND`, leaving `out` alone, if there is no such chunk.
+ *
+ * # Safety
+ *
+ * `buf` must point to `len` readable bytes, `chunk_type` to a
+ * NUL-terminated string and `out` to a writable `char *`.
+ */
+int pngme_decode(const uint8_t *buf, size_t len, const char *chunk_type, char **out);
+
+/**
+ * Remove the first chunk of type `chunk_type`, storing the new PNG in
+ * `out`.  Returns `PNGME_NOT_FOUND`, leaving `out` alone, if there is no
+ * such chunk.
+ *
+ * # Safety
+ *
+ * `buf` must point to `len` readable bytes, `chunk_type` to a
+ * NUL-terminated string and `out` to writable memory for a `PngmeBuffer`.
+ */
+int pngme_remove(const uint8_t *buf, size_t len, const char *chunk_type, struct PngmeBuffer *out);
+
+/**
+ * Release a buffer filled in by pngme.
+ *
+ * # Safety
+ *
+ * `buffer` must come from pngme and not have been released before.
+ */
+void pngme_buffer_free(struct PngmeBuffer buffer);
+
+/**
+ * Release a string filled in by pngme.
+ *
+ * # Safety
+ *
+ * `s` must come from pngme and not have been released before.
+ */
+void pngme_string_free(char *s);
+
+/**
+ * Description of the last failure on this thread, or NULL.  Valid until
+ * the next pngme call on the same thread.
+ */
+const char *pngme_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PNGME_H */
@@ -1,7 +1,18 @@
-use crate::chunk_type::ChunkType;
-use reqwest::Url;
-use std::path::PathBuf;
+use crate::chunk::MAXIMUM_LENGTH;
+use crate::chunk_type::{ChunkType, RawChunkType};
+use crate::error::PngmeError;
+use crate::remote::{self, RateLimit};
+use crate::watermark::Position;
+use glob::Pattern;
+use reqwest::{Method, Url};
+use std::collections::VecDeque;
+use std::mem;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::SystemTime;
 use structopt::clap::AppSettings;
 use structopt::StructOpt;
 
@@ -10,28 +21,650 @@ pngme encode ./dice.png ruSt "This is a secret message!
 pngme decode ./dice.png ruSt
 pngme remove ./dice.png ruSt
 pngme print ./dice.png
+pngme verify ./dice.png ./other.png
+pngme decode '*.png' ruSt --exclude 'vendor-*.png'
+pngme grep ./assets --type ruSt --pattern "secret"
+pngme serve --listen 127.0.0.1:8080                          (with --features server)
+curl -F image=@dice.png -F chunk_type=ruSt -F message=hi http://127.0.0.1:8080/encode > out.png
+pngme serve --grpc --listen 127.0.0.1:50051                  (with --features grpc, see proto/pngme.proto)
+pngme config set chunk_type ruSt
+pngme config set output_format json
+pngme --profile work config set passphrase "correct horse"
+PNGME_PASSPHRASE="correct horse" pngme decode ./dice.png ruSt
+pngme print-r https://example.com/private.png -H "X-Api-Key: abc" --basic-auth me:hunter2
+pngme print-r https://example.com/dice.png -A "Mozilla/5.0" -H "Referer: https://example.com/"
+pngme print-r https://assets.internal/dice.png --cacert ca.pem --client-cert me.pem --client-key me.key
+pngme encode-r https://example.com/in.png ruSt "This is a secret message!" --upload https://example.com/out.png
+pngme encode ./dice.png ruSt "This is a secret message!" --notify-url https://hooks.example.com/pngme
+pngme print-r ipfs://bafybeigdyrztra3Vq/turtle.png
+pngme encode-r ipfs://bafybeigdyrztra3Vq/turtle.png ruSt "This is a secret message!" --pin-to-ipfs
+pngme print-r sftp://me@files.example.com/srv/dice.png --ssh-key ~/.ssh/id_ed25519   (with --features sftp)
+pngme print-r "data:image/png;base64,iVBORw0KGgo..."
+pngme encode ./dice.png ruSt "This is a secret message!" --output-data-uri
+pngme decode-r https://cdn.example.com/dice.png ruSt --mirror https://backup.example.com/dice.png
+pngme decode-r --urls-from urls.txt ruSt --jobs 8
+pngme decode-r --urls-from urls.txt ruSt --concurrency 2 --rate-limit 5
+pngme encode-r davs://cloud.example.com/dav/me/dice.png ruSt "hi" --upload davs://cloud.example.com/dav/me/out.png
+pngme decode-r s3://assets/dice.png ruSt                    (built with --features s3)
+pngme decode-r gs://assets/dice.png ruSt                    (built with --features gcs)
+pngme encode-r az://assets/dice.png ruSt "hi" --upload az://assets/out.png   (built with --features azure)
+curl ... | pngme encode - ruSt "This is a secret message!" - > out.png
+convert in.jpg png:- | pngme encode --stdin --stdout ruSt "This is a secret message!" | convert - out.webp
+pngme encode ./dice.png ruSt --message-file ./letter.txt ./out.png
+pngme encode ./dice.png --chunk ruSt="first" --chunk teSt="second" ./out.png
+pngme strip ./dice.png --preset social-media --keep ruSt
+pngme --relaxed remove ./odd.png r2St
+pngme apply plan.yaml > report.ndjson
+pngme info ./dice.png --exiftool
+pngme --journal remove ./dice.png ruSt && pngme undo ./dice.png
+pngme enc ./dice.png -t ruSt -m "This is a secret message!" -o ./out.png
+pngme dec ./dice.png
 */
 
 #[derive(StructOpt)]
 #[structopt(global_settings(&[AppSettings::VersionlessSubcommands]))]
 pub(crate) struct Cli {
+    #[structopt(
+        short,
+        long,
+        global = true,
+        parse(from_occurrences),
+        help = "Log more detail (-v debug, -vv trace)"
+    )]
+    pub(crate) verbose: u8,
+    #[structopt(
+        long,
+        global = true,
+        default_value = "text",
+        possible_values = &["text", "json"],
+        help = "Log output format"
+    )]
+    pub(crate) log_format: LogFormat,
+    #[structopt(long, global = true, help = "Hide progress bars")]
+    pub(crate) no_progress: bool,
+    #[structopt(
+        long,
+        global = true,
+        parse(from_os_str),
+        help = "Config file (default: ~/.config/pngme/config.toml)"
+    )]
+    pub(crate) config: Option<PathBuf>,
+    #[structopt(
+        long,
+        global = true,
+        help = "Don't keep .bak copies of overwritten PNGs"
+    )]
+    pub(crate) no_backup: bool,
+    #[structopt(
+        long,
+        global = true,
+        help = "Don't lock PNGs while changing them, for callers that lock them themselves"
+    )]
+    pub(crate) no_lock: bool,
+    #[structopt(
+        long,
+        global = true,
+        help = "Record each change to a PNG in its audit chunk, shown by history"
+    )]
+    pub(crate) audit: bool,
+    #[structopt(
+        long = "journal",
+        global = true,
+        help = "Keep an undo journal beside each PNG changed in place, for undo"
+    )]
+    pub(crate) record_journal: bool,
+    #[structopt(
+        long,
+        global = true,
+        help = "Make the output depend only on the inputs, for reproducible builds"
+    )]
+    pub(crate) deterministic: bool,
+    #[structopt(
+        long,
+        global = true,
+        help = "Read chunk types that aren't four ASCII letters instead of failing, in print and remove"
+    )]
+    pub(crate) relaxed: bool,
+    #[structopt(
+        long,
+        global = true,
+        help = "Use the named profile from the config file"
+    )]
+    pub(crate) profile: Option<String>,
+    #[structopt(
+        long,
+        global = true,
+        env = "PNGME_PASSPHRASE",
+        hide_env_values = true,
+        help = "Encrypt and decrypt messages with this passphrase"
+    )]
+    pub(crate) passphrase: Option<String>,
+    #[structopt(
+        long,
+        global = true,
+        env = "PNGME_KEY_FILE",
+        parse(from_os_str),
+        help = "Encrypt and decrypt messages with the passphrase in this file"
+    )]
+    pub(crate) key_file: Option<PathBuf>,
+    #[structopt(
+        long,
+        global = true,
+        env = "PNGME_HTTP_TOKEN",
+        hide_env_values = true,
+        help = "Bearer token for remote requests"
+    )]
+    pub(crate) http_token: Option<String>,
+    #[structopt(
+        long,
+        global = true,
+        value_name = "BYTES",
+        help = "Hold at most this much of a download in memory before spilling to a temp file"
+    )]
+    pub(crate) max_memory: Option<usize>,
+    #[structopt(
+        long,
+        global = true,
+        value_name = "BYTES",
+        parse(try_from_str = parse_chunk_size),
+        help = "Fail on any chunk holding more data than this instead of reading it"
+    )]
+    pub(crate) max_chunk_size: Option<u32>,
     #[structopt(subcommand)]
     pub(crate) subcommand: Subcommand,
 }
 
+/// Format of log lines on stderr.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format '{}'", s)),
+        }
+    }
+}
+
+/// Where `encode` puts the new chunk.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Place {
+    /// Right after IHDR.
+    Start,
+    /// Before the first IDAT, where readers that stop at the image data
+    /// still see it.
+    BeforeIdat,
+    /// Before IEND.
+    End,
+}
+
+impl FromStr for Place {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(Place::Start),
+            "before-idat" => Ok(Place::BeforeIdat),
+            "end" => Ok(Place::End),
+            _ => Err(format!("unknown place '{}'", s)),
+        }
+    }
+}
+
+/// How `decode` prints each message.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum Encoding {
+    /// As text, after the chunk type.
+    #[default]
+    Utf8,
+    /// Its bytes as base64, after the chunk type.
+    Base64,
+    /// Its bytes as lowercase hex, after the chunk type.
+    Hex,
+    /// Just its bytes, with no chunk type or newline.
+    Raw,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "utf8" => Ok(Encoding::Utf8),
+            "base64" => Ok(Encoding::Base64),
+            "hex" => Ok(Encoding::Hex),
+            "raw" => Ok(Encoding::Raw),
+            _ => Err(format!("unknown encoding '{}'", s)),
+        }
+    }
+}
+
+/// Which chunks `find` searches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum TypeFilter {
+    All,
+    /// Only ancillary chunks, skipping the image header, palette and data.
+    Ancillary,
+}
+
+impl FromStr for TypeFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(TypeFilter::All),
+            "ancillary" => Ok(TypeFilter::Ancillary),
+            _ => Err(format!("unknown chunk types '{}'", s)),
+        }
+    }
+}
+
+#[derive(StructOpt, Debug, Default, PartialEq)]
+pub(crate) struct GlobArgs {
+    #[structopt(
+        long,
+        number_of_values = 1,
+        parse(try_from_str = Pattern::new),
+        help = "Skip inputs matching this glob pattern (repeatable)"
+    )]
+    pub(crate) exclude: Vec<Pattern>,
+}
+
+/// Which of several chunks of the same type a command acts on.  The first,
+/// unless told otherwise.
+#[derive(StructOpt, Debug, Default, PartialEq)]
+pub(crate) struct SelectArgs {
+    #[structopt(
+        long,
+        conflicts_with = "nth",
+        help = "Every chunk of the type, not just the first"
+    )]
+    pub(crate) all: bool,
+    #[structopt(long, help = "Only the Nth chunk of the type, counting from 0")]
+    pub(crate) nth: Option<usize>,
+}
+
+impl SelectArgs {
+    /// The selected items out of the chunks of one type, in order, with
+    /// their index among those chunks.
+    pub(crate) fn pick<T>(
+        &self,
+        chunks: impl Iterator<Item = T>,
+    ) -> impl Iterator<Item = (usize, T)> {
+        let (all, nth) = (self.all, self.nth.unwrap_or(0));
+        chunks.enumerate().filter(move |(n, _)| all || *n == nth)
+    }
+}
+
+/// Fields of an iTXt chunk holding the message.
+#[derive(StructOpt, Debug, Default, PartialEq)]
+pub(crate) struct ItxtArgs {
+    #[structopt(
+        long,
+        help = "Store the message as iTXt text in this language (like 'de'), making iTXt the default chunk type"
+    )]
+    pub(crate) lang: Option<String>,
+    #[structopt(
+        long,
+        help = "iTXt keyword saying what the text is (default: Description)"
+    )]
+    pub(crate) keyword: Option<String>,
+    #[structopt(long, help = "iTXt keyword in the language of the text")]
+    pub(crate) translated_keyword: Option<String>,
+}
+
+impl ItxtArgs {
+    /// Were any of the iTXt fields given.
+    pub(crate) fn is_set(&self) -> bool {
+        *self != ItxtArgs::default()
+    }
+}
+
+/// Provenance stored with the message.  Giving any stores it with the time
+/// it was encoded, unless the output is to be deterministic.
+#[derive(StructOpt, Debug, Default, PartialEq)]
+pub(crate) struct EnvelopeArgs {
+    #[structopt(long, help = "Record who wrote the message")]
+    pub(crate) author: Option<String>,
+    #[structopt(
+        long,
+        help = "Record the media type of the message (like 'text/plain')"
+    )]
+    pub(crate) content_type: Option<String>,
+    #[structopt(
+        long,
+        parse(try_from_str = humantime::parse_rfc3339_weak),
+        help = "Refuse to decode the message from this time on (like '2025-01-01T00:00:00Z')"
+    )]
+    pub(crate) expires: Option<SystemTime>,
+}
+
+impl EnvelopeArgs {
+    /// Were any of the envelope fields given.
+    pub(crate) fn is_set(&self) -> bool {
+        *self != EnvelopeArgs::default()
+    }
+}
+
+/// Overrides for chunk types other tools reject.
+#[derive(StructOpt, Debug, Default, PartialEq)]
+pub(crate) struct TypeGuardArgs {
+    #[structopt(long, help = "Allow a critical chunk type (uppercase first letter)")]
+    pub(crate) allow_critical: bool,
+    #[structopt(long, help = "Allow a chunk type with the reserved bit set")]
+    pub(crate) allow_reserved: bool,
+}
+
+impl TypeGuardArgs {
+    /// `Err` unless a chunk of the given type may be written.
+    pub(crate) fn check(&self, chunk_type: &ChunkType) -> crate::Result<()> {
+        if !chunk_type.is_valid() && !self.allow_reserved {
+            Err(format!(
+                "'{}' has the reserved bit set (lowercase third letter), pass --allow-reserved to write it anyway",
+                chunk_type
+            ))?
+        }
+        if chunk_type.is_critical() && !self.allow_critical {
+            Err(format!(
+                "'{}' is a critical chunk type that decoders must understand, pass --allow-critical to write it anyway",
+                chunk_type
+            ))?
+        }
+        Ok(())
+    }
+}
+
+/// Request options shared by the remote (`-r`) commands.
+#[derive(StructOpt, Clone, Debug, Default, PartialEq)]
+pub(crate) struct RemoteArgs {
+    #[structopt(
+        short = "H",
+        long = "header",
+        number_of_values = 1,
+        parse(try_from_str = parse_header),
+        help = "Extra request header as 'Name: value' (repeatable)"
+    )]
+    pub(crate) headers: Vec<(String, String)>,
+    #[structopt(
+        short = "A",
+        long,
+        help = "User-Agent to send, overriding the config's (default: pngme/<version>)"
+    )]
+    pub(crate) user_agent: Option<String>,
+    #[structopt(
+        long,
+        conflicts_with = "basic-auth",
+        help = "Bearer token for this request, overriding --http-token"
+    )]
+    pub(crate) bearer_token: Option<String>,
+    #[structopt(
+        long,
+        value_name = "USER:PASS",
+        parse(try_from_str = parse_basic_auth),
+        help = "Basic auth credentials"
+    )]
+    pub(crate) basic_auth: Option<(String, Option<String>)>,
+    #[structopt(
+        long,
+        parse(try_from_str = Url::from_str),
+        help = "Proxy URL (http, https or socks5), instead of HTTP_PROXY/HTTPS_PROXY"
+    )]
+    pub(crate) proxy: Option<Url>,
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "PEM file of CA certificates to trust, on top of the system's"
+    )]
+    pub(crate) cacert: Option<PathBuf>,
+    #[structopt(
+        long,
+        parse(from_os_str),
+        requires = "client-key",
+        help = "PEM client certificate (chain) for mutual TLS"
+    )]
+    pub(crate) client_cert: Option<PathBuf>,
+    #[structopt(
+        long,
+        parse(from_os_str),
+        requires = "client-cert",
+        help = "PEM private key of --client-cert (PKCS#8, PKCS#1 or SEC1)"
+    )]
+    pub(crate) client_key: Option<PathBuf>,
+    #[structopt(
+        long,
+        value_name = "SECS",
+        help = "Give up on a request after this long, overriding the config timeout"
+    )]
+    pub(crate) timeout: Option<u64>,
+    #[structopt(long, help = "Retries after a transient failure (default: 3)")]
+    pub(crate) retries: Option<u32>,
+    #[structopt(
+        long,
+        value_name = "MS",
+        help = "Delay before the first retry, doubled for each one after (default: 500)"
+    )]
+    pub(crate) retry_backoff: Option<u64>,
+    #[structopt(long, help = "Always download, bypassing the cache in ~/.cache/pngme")]
+    pub(crate) no_cache: bool,
+    #[structopt(
+        long,
+        value_name = "HEX",
+        parse(try_from_str = parse_sha256),
+        help = "Fail unless the downloaded bytes have this SHA-256 digest"
+    )]
+    pub(crate) expected_sha256: Option<String>,
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "Private key for sftp:// URLs (default: the SSH agent)"
+    )]
+    pub(crate) ssh_key: Option<PathBuf>,
+    #[structopt(
+        long = "mirror",
+        number_of_values = 1,
+        parse(try_from_str = Url::from_str),
+        help = "Fallback URL of the same image, tried in order if the main one fails (repeatable)"
+    )]
+    pub(crate) mirrors: Vec<Url>,
+    #[structopt(
+        long,
+        value_name = "BYTES",
+        help = "Fail instead of downloading more than this many bytes"
+    )]
+    pub(crate) max_download: Option<u64>,
+    #[structopt(
+        long,
+        help = "Skip the HEAD request that checks the size and content type before downloading"
+    )]
+    pub(crate) no_preflight: bool,
+    /// `--rate-limit` shared by every request of a batch, if any.
+    #[structopt(skip)]
+    pub(crate) rate_limit: Option<Arc<RateLimit>>,
+}
+
+/// Split a `Name: value` header.
+fn parse_header(s: &str) -> Result<(String, String), String> {
+    match s.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("expected 'Name: value', got '{}'", s)),
+    }
+}
+
+/// Lowercase a hex SHA-256 digest.
+fn parse_sha256(s: &str) -> Result<String, String> {
+    if s.len() != 64 || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("expected 64 hex digits, got '{}'", s));
+    }
+    Ok(s.to_ascii_lowercase())
+}
+
+/// HTTP method from its name, in any case.
+fn parse_method(s: &str) -> Result<Method, String> {
+    Method::from_str(&s.to_ascii_uppercase()).map_err(|e| e.to_string())
+}
+
+/// Opacity from 0 to 1.
+fn parse_opacity(s: &str) -> Result<f32, String> {
+    match f32::from_str(s) {
+        Ok(opacity) if (0.0..=1.0).contains(&opacity) => Ok(opacity),
+        _ => Err(format!("opacity must be from 0 to 1, not '{}'", s)),
+    }
+}
+
+/// Split a `TYPE=MESSAGE` chunk, checking the type.
+fn parse_chunk_pair(s: &str) -> Result<(ChunkType, String), String> {
+    let (chunk_type, message) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected 'TYPE=MESSAGE', got '{}'", s))?;
+    let chunk_type = ChunkType::from_str(chunk_type).map_err(|e| e.to_string())?;
+    Ok((chunk_type, message.to_string()))
+}
+
+/// Rate of at least one a day, like `2` or `0.5`.
+fn parse_rate(s: &str) -> Result<f64, String> {
+    match f64::from_str(s) {
+        Ok(rate) if rate >= remote::MIN_RATE && rate.is_finite() => Ok(rate),
+        _ => Err(format!(
+            "rate must be a number of at least {} (one a day), not '{}'",
+            remote::MIN_RATE,
+            s
+        )),
+    }
+}
+
+/// Chunk data size no bigger than a chunk can hold.
+fn parse_chunk_size(s: &str) -> Result<u32, String> {
+    match u32::from_str(s) {
+        Ok(size) if size <= MAXIMUM_LENGTH => Ok(size),
+        _ => Err(format!(
+            "chunk size must be a number of bytes up to {}, not '{}'",
+            MAXIMUM_LENGTH, s
+        )),
+    }
+}
+
+/// Split `user:pass` credentials.  The password is optional.
+fn parse_basic_auth(s: &str) -> Result<(String, Option<String>), String> {
+    match s.split_once(':') {
+        Some((user, pass)) => Ok((user.to_string(), Some(pass.to_string()))),
+        None => Ok((s.to_string(), None)),
+    }
+}
+
 #[derive(StructOpt, Debug, PartialEq)]
 pub(crate) struct EncodeArgs {
-    #[structopt(parse(from_os_str), help = "Path to the input PNG")]
-    pub(crate) input_file_path: PathBuf,
     #[structopt(
+        parse(from_os_str),
+        required_unless = "stdin",
+        help = "Path or glob pattern of the input PNG ('-' for stdin)"
+    )]
+    pub(crate) input_file_path: Option<PathBuf>,
+    #[structopt(
+        long,
+        help = "Read the input PNG from stdin, taking every positional as ARGS"
+    )]
+    pub(crate) stdin: bool,
+    #[structopt(
+        long,
+        conflicts_with_all = &["out-dir", "output-data-uri"],
+        help = "Write the encoded PNG to stdout, with any other output on stderr"
+    )]
+    pub(crate) stdout: bool,
+    #[structopt(
+        name = "ARGS",
+        help = "Chunk type, secret message and output PNG (optional), unless given by flag"
+    )]
+    pub(crate) positionals: Vec<String>,
+    #[structopt(
+            short = "t",
+            long = "type",
             parse(try_from_str = ChunkType::from_str),
             help = "Chunk type (like 'ruSt')"
         )]
-    pub(crate) chunk_type: ChunkType,
-    #[structopt(help = "Your secret message")]
-    pub(crate) message: String,
-    #[structopt(parse(from_os_str), help = "Path to the output PNG (optional)")]
+    pub(crate) chunk_type: Option<ChunkType>,
+    #[structopt(short, long, help = "Your secret message")]
+    pub(crate) message: Option<String>,
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with = "message",
+        help = "Read the secret message, as is, from this file ('-' for stdin)"
+    )]
+    pub(crate) message_file: Option<PathBuf>,
+    #[structopt(
+        long,
+        conflicts_with_all = &["message", "message-file"],
+        help = "Read the secret message, as is, from stdin"
+    )]
+    pub(crate) message_stdin: bool,
+    #[structopt(
+        long = "chunk",
+        alias = "pair",
+        value_name = "TYPE=MESSAGE",
+        number_of_values = 1,
+        parse(try_from_str = parse_chunk_pair),
+        help = "Another chunk to add in the same pass (repeatable; a lone ARG is then the output)"
+    )]
+    pub(crate) chunks: Vec<(ChunkType, String)>,
+    #[structopt(
+        long,
+        help = "Replace any chunks of the type instead of adding another"
+    )]
+    pub(crate) replace: bool,
+    #[structopt(
+        long,
+        possible_values = &["start", "before-idat", "end"],
+        help = "Where to put the chunk (default: end, just before IEND)"
+    )]
+    pub(crate) place: Option<Place>,
+    #[structopt(flatten)]
+    pub(crate) guard: TypeGuardArgs,
+    #[structopt(flatten)]
+    pub(crate) itxt: ItxtArgs,
+    #[structopt(flatten)]
+    pub(crate) envelope: EnvelopeArgs,
+    #[structopt(
+        short = "o",
+        long = "output",
+        parse(from_os_str),
+        help = "Path to the output PNG (optional, '-' for stdout)"
+    )]
     pub(crate) output_file_path: Option<PathBuf>,
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with = "output-file-path",
+        help = "Write each encoded PNG under this directory, mirroring the input paths"
+    )]
+    pub(crate) out_dir: Option<PathBuf>,
+    #[structopt(
+        long,
+        conflicts_with = "out-dir",
+        help = "Print the encoded PNG as a data: URI instead of writing it"
+    )]
+    pub(crate) output_data_uri: bool,
+    #[structopt(
+        long,
+        help = "Check that the encoded PNG decodes to the same pixels as the input"
+    )]
+    pub(crate) verify_pixels: bool,
+    #[structopt(
+        long,
+        parse(try_from_str = Url::from_str),
+        help = "POST a JSON summary of each change to this URL"
+    )]
+    pub(crate) notify_url: Option<Url>,
+    #[structopt(flatten)]
+    pub(crate) glob: GlobArgs,
 }
 
 #[derive(StructOpt, Debug, PartialEq)]
@@ -41,51 +674,228 @@ pub(crate) struct EncodeRArgs {
             help = "URL of the input PNG"
         )]
     pub(crate) url: Url,
+    #[structopt(flatten)]
+    pub(crate) remote: RemoteArgs,
     #[structopt(
+        name = "ARGS",
+        help = "Chunk type, secret message and local output PNG, unless given by flag"
+    )]
+    pub(crate) positionals: Vec<String>,
+    #[structopt(
+            short = "t",
+            long = "type",
             parse(try_from_str = ChunkType::from_str),
             help = "Chunk type (like 'ruSt')"
         )]
-    pub(crate) chunk_type: ChunkType,
-    #[structopt(help = "Your secret message")]
-    pub(crate) message: String,
-    #[structopt(parse(from_os_str), help = "Local path to the output PNG")]
-    pub(crate) output_file_path: PathBuf,
+    pub(crate) chunk_type: Option<ChunkType>,
+    #[structopt(short, long, help = "Your secret message")]
+    pub(crate) message: Option<String>,
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with = "message",
+        help = "Read the secret message, as is, from this file ('-' for stdin)"
+    )]
+    pub(crate) message_file: Option<PathBuf>,
+    #[structopt(
+        long,
+        conflicts_with_all = &["message", "message-file"],
+        help = "Read the secret message, as is, from stdin"
+    )]
+    pub(crate) message_stdin: bool,
+    #[structopt(
+        long,
+        help = "Replace any chunks of the type instead of adding another"
+    )]
+    pub(crate) replace: bool,
+    #[structopt(
+        long,
+        possible_values = &["start", "before-idat", "end"],
+        help = "Where to put the chunk (default: end, just before IEND)"
+    )]
+    pub(crate) place: Option<Place>,
+    #[structopt(flatten)]
+    pub(crate) guard: TypeGuardArgs,
+    #[structopt(
+        short = "o",
+        long = "output",
+        parse(from_os_str),
+        help = "Local path to the output PNG ('-' for stdout, optional when publishing)"
+    )]
+    pub(crate) output_file_path: Option<PathBuf>,
+    #[structopt(
+        long,
+        parse(try_from_str = Url::from_str),
+        help = "Send the encoded PNG to this URL"
+    )]
+    pub(crate) upload: Option<Url>,
+    #[structopt(
+        long,
+        default_value = "PUT",
+        possible_values = &["PUT", "POST"],
+        case_insensitive = true,
+        parse(try_from_str = parse_method),
+        help = "HTTP method for --upload (only PUT is retried, as a POST may have gone through)"
+    )]
+    pub(crate) upload_method: Method,
+    #[structopt(long, help = "Add the encoded PNG to IPFS and print its CID")]
+    pub(crate) pin_to_ipfs: bool,
+    #[structopt(long, help = "Print the encoded PNG as a data: URI")]
+    pub(crate) output_data_uri: bool,
+    #[structopt(
+        long,
+        parse(try_from_str = Url::from_str),
+        help = "POST a JSON summary of each change to this URL"
+    )]
+    pub(crate) notify_url: Option<Url>,
 }
 
 #[derive(StructOpt, Debug, PartialEq)]
 pub(crate) struct DecodeArgs {
-    #[structopt(parse(from_os_str), help = "Path to the PNG")]
-    pub(crate) file_path: PathBuf,
     #[structopt(
+        parse(from_os_str),
+        required = true,
+        help = "Paths or glob patterns of the PNGs ('-' for stdin), then the chunk type unless given by flag"
+    )]
+    pub(crate) file_paths: Vec<PathBuf>,
+    #[structopt(flatten)]
+    pub(crate) glob: GlobArgs,
+    #[structopt(
+            short = "t",
+            long = "type",
             parse(try_from_str = ChunkType::from_str),
             help = "Chunk type (like 'ruSt')"
         )]
-    pub(crate) chunk_type: ChunkType,
+    pub(crate) chunk_type: Option<ChunkType>,
+    #[structopt(flatten)]
+    pub(crate) select: SelectArgs,
+    #[structopt(
+        long,
+        help = "Only iTXt text in this language (like 'de'), making iTXt the default chunk type"
+    )]
+    pub(crate) lang: Option<String>,
+    #[structopt(
+        long,
+        help = "Also print the author, time and content type of each message"
+    )]
+    pub(crate) show_meta: bool,
+    #[structopt(long, help = "Decode expired messages too, with a warning")]
+    pub(crate) ignore_expiry: bool,
+    #[structopt(long, help = "Show each message as a QR code")]
+    pub(crate) qr: bool,
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with = "all",
+        help = "Also save the message as a QR code PNG at this path"
+    )]
+    pub(crate) qr_png: Option<PathBuf>,
+    #[structopt(
+        long,
+        possible_values = &["utf8", "base64", "hex", "raw"],
+        help = "How to print each message ('raw' writes just its bytes, unescaped, for piping) [default: the config's decode_encoding, else utf8]"
+    )]
+    pub(crate) encoding: Option<Encoding>,
 }
 
 #[derive(StructOpt, Debug, PartialEq)]
 pub(crate) struct DecodeRArgs {
+    /// URL of the input PNG, taken from the positionals.
+    #[structopt(skip)]
+    pub(crate) url: Option<Url>,
+    #[structopt(flatten)]
+    pub(crate) remote: RemoteArgs,
     #[structopt(
-            parse(try_from_str = Url::from_str),
-            help = "URL of the input PNG"
-        )]
-    pub(crate) url: Url,
+        name = "ARGS",
+        help = "URL of the input PNG (unless --urls-from), then the chunk type unless given by flag"
+    )]
+    pub(crate) positionals: Vec<String>,
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "Decode every URL listed in this file ('-' for stdin), printing NDJSON"
+    )]
+    pub(crate) urls_from: Option<PathBuf>,
+    #[structopt(
+        short,
+        long,
+        alias = "concurrency",
+        default_value = "4",
+        help = "Downloads to run at once with --urls-from"
+    )]
+    pub(crate) jobs: NonZeroUsize,
+    #[structopt(
+        long,
+        value_name = "PER-SECOND",
+        parse(try_from_str = parse_rate),
+        help = "Send at most this many requests a second with --urls-from, retries and mirrors included"
+    )]
+    pub(crate) rate_limit: Option<f64>,
     #[structopt(
+            short = "t",
+            long = "type",
             parse(try_from_str = ChunkType::from_str),
             help = "Chunk type (like 'ruSt')"
         )]
-    pub(crate) chunk_type: ChunkType,
+    pub(crate) chunk_type: Option<ChunkType>,
+    #[structopt(flatten)]
+    pub(crate) select: SelectArgs,
+    #[structopt(long, help = "Decode expired messages too, with a warning")]
+    pub(crate) ignore_expiry: bool,
 }
 
 #[derive(StructOpt, Debug, PartialEq)]
 pub(crate) struct RemoveArgs {
-    #[structopt(parse(from_os_str), help = "Path to the PNG")]
-    pub(crate) file_path: PathBuf,
     #[structopt(
-            parse(try_from_str = ChunkType::from_str),
-            help = "Chunk type (like 'ruSt')"
+        parse(from_os_str),
+        required = true,
+        help = "Paths or glob patterns of the PNGs ('-' for stdin), then the chunk type unless given by flag"
+    )]
+    pub(crate) file_paths: Vec<PathBuf>,
+    #[structopt(flatten)]
+    pub(crate) glob: GlobArgs,
+    #[structopt(
+            short = "t",
+            long = "type",
+            parse(try_from_str = RawChunkType::from_str),
+            help = "Chunk type (like 'ruSt'; with --relaxed, any 4 printable ASCII characters)"
         )]
-    pub(crate) chunk_type: ChunkType,
+    pub(crate) chunk_type: Option<RawChunkType>,
+    #[structopt(flatten)]
+    pub(crate) select: SelectArgs,
+    #[structopt(
+        long,
+        parse(try_from_str = Url::from_str),
+        help = "POST a JSON summary of each change to this URL"
+    )]
+    pub(crate) notify_url: Option<Url>,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct StripArgs {
+    #[structopt(
+        parse(from_os_str),
+        required = true,
+        help = "Paths or glob patterns of the PNGs ('-' for stdin)"
+    )]
+    pub(crate) file_paths: Vec<PathBuf>,
+    #[structopt(flatten)]
+    pub(crate) glob: GlobArgs,
+    #[structopt(
+        long,
+        default_value = "social-media",
+        help = "Which chunks to remove: social-media, archive, forensic, or one from the config's [strip_presets]"
+    )]
+    pub(crate) preset: String,
+    #[structopt(
+        long,
+        number_of_values = 1,
+        parse(try_from_str = ChunkType::from_str),
+        help = "Keep chunks of this type whatever the preset says (repeatable)"
+    )]
+    pub(crate) keep: Vec<ChunkType>,
+    #[structopt(long, help = "Show what would be removed without changing the PNG")]
+    pub(crate) dry_run: bool,
 }
 
 #[derive(StructOpt, Debug, PartialEq)]
@@ -95,19 +905,47 @@ pub(crate) struct RemoveRArgs {
             help = "URL of the input PNG"
         )]
     pub(crate) url: Url,
+    #[structopt(flatten)]
+    pub(crate) remote: RemoteArgs,
+    #[structopt(
+        name = "ARGS",
+        help = "Chunk type and local output PNG, unless given by flag"
+    )]
+    pub(crate) positionals: Vec<String>,
     #[structopt(
+            short = "t",
+            long = "type",
             parse(try_from_str = ChunkType::from_str),
             help = "Chunk type (like 'ruSt')"
         )]
-    pub(crate) chunk_type: ChunkType,
-    #[structopt(parse(from_os_str), help = "Local path to the output PNG")]
-    pub(crate) output_file_path: PathBuf,
+    pub(crate) chunk_type: Option<ChunkType>,
+    #[structopt(flatten)]
+    pub(crate) select: SelectArgs,
+    #[structopt(
+        short = "o",
+        long = "output",
+        parse(from_os_str),
+        help = "Local path to the output PNG ('-' for stdout)"
+    )]
+    pub(crate) output_file_path: Option<PathBuf>,
+    #[structopt(
+        long,
+        parse(try_from_str = Url::from_str),
+        help = "POST a JSON summary of each change to this URL"
+    )]
+    pub(crate) notify_url: Option<Url>,
 }
 
 #[derive(StructOpt, Debug, PartialEq)]
 pub(crate) struct PrintArgs {
-    #[structopt(parse(from_os_str), help = "Path to the PNG")]
-    pub(crate) file_path: PathBuf,
+    #[structopt(
+        parse(from_os_str),
+        required = true,
+        help = "Paths or glob patterns of the PNGs ('-' for stdin)"
+    )]
+    pub(crate) file_paths: Vec<PathBuf>,
+    #[structopt(flatten)]
+    pub(crate) glob: GlobArgs,
 }
 
 #[derive(StructOpt, Debug, PartialEq)]
@@ -117,117 +955,1385 @@ pub(crate) struct PrintRArgs {
             help = "URL of the PNG"
         )]
     pub(crate) url: Url,
+    #[structopt(flatten)]
+    pub(crate) remote: RemoteArgs,
 }
 
-#[derive(Debug, StructOpt, PartialEq)]
-pub(crate) enum Subcommand {
-    #[structopt(about = "Add a secret message to a PNG")]
-    Encode(EncodeArgs),
-    #[structopt(about = "Add a secret message to a remote PNG")]
-    EncodeR(EncodeRArgs),
-    #[structopt(about = "Show the secret message in a PNG")]
-    Decode(DecodeArgs),
-    #[structopt(about = "Show the secret message in a remote PNG")]
-    DecodeR(DecodeRArgs),
-    #[structopt(about = "Remove a secret message from a PNG")]
-    Remove(RemoveArgs),
-    #[structopt(about = "Remove a secret message from a remote PNG")]
-    RemoveR(RemoveRArgs),
-    #[structopt(about = "Print every chunk in a PNG")]
-    Print(PrintArgs),
-    #[structopt(about = "Print every chunk in a remote PNG")]
-    PrintR(PrintRArgs),
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct VerifyArgs {
+    #[structopt(
+        parse(from_os_str),
+        required = true,
+        help = "Paths or glob patterns of the PNGs ('-' for stdin)"
+    )]
+    pub(crate) file_paths: Vec<PathBuf>,
+    #[structopt(flatten)]
+    pub(crate) glob: GlobArgs,
 }
 
-mod test {
-    #[allow(unused_imports)]
-    use super::*;
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct MigrateFormatArgs {
+    #[structopt(
+        parse(from_os_str),
+        required = true,
+        help = "Paths or glob patterns of the PNGs ('-' for stdin), then the chunk type unless given by flag"
+    )]
+    pub(crate) file_paths: Vec<PathBuf>,
+    #[structopt(flatten)]
+    pub(crate) glob: GlobArgs,
+    #[structopt(
+            short = "t",
+            long = "type",
+            parse(try_from_str = ChunkType::from_str),
+            help = "Chunk type (like 'ruSt')"
+        )]
+    pub(crate) chunk_type: Option<ChunkType>,
+}
 
-    #[test]
-    pub(crate) fn test_encode() {
-        let expected = Subcommand::Encode(EncodeArgs {
-            input_file_path: PathBuf::from("/a/b/c"),
-            chunk_type: ChunkType::from_str("RuSt").unwrap(),
-            message: "Secret decoder ring".to_string(),
-            output_file_path: None,
-        });
-        let cli = Cli::from_iter(vec![
-            "pngme",
-            "encode",
-            "/a/b/c",
-            "RuSt",
-            "Secret decoder ring",
-        ]);
-        let actual = cli.subcommand;
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct SalvageArgs {
+    #[structopt(
+        parse(from_os_str),
+        help = "Path of the PNG, possibly cut short ('-' for stdin)"
+    )]
+    pub(crate) file_path: PathBuf,
+    #[structopt(parse(try_from_str = ChunkType::from_str), help = "Chunk type (like 'ruSt')")]
+    pub(crate) chunk_type: ChunkType,
+    #[structopt(
+        long,
+        help = "Print the messages as they are, without escaping control characters"
+    )]
+    pub(crate) raw: bool,
+}
 
-        assert_eq!(expected, actual);
-    }
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct GrepArgs {
+    #[structopt(parse(from_os_str), help = "Directory to search recursively")]
+    pub(crate) dir: PathBuf,
+    #[structopt(
+        short = "t",
+        long = "type",
+        parse(try_from_str = ChunkType::from_str),
+        help = "Only match chunks of this type (default: any private chunk)"
+    )]
+    pub(crate) chunk_type: Option<ChunkType>,
+    #[structopt(long, help = "Only match chunks whose data matches this regex")]
+    pub(crate) pattern: Option<String>,
+}
 
-    #[test]
-    pub(crate) fn test_encoder() {
-        let expected = Subcommand::EncodeR(EncodeRArgs {
-            url: Url::parse(
-                "https://raw.githubusercontent.com/jacksonneal/pngme/master/turtle.png",
-            )
-            .unwrap(),
-            chunk_type: ChunkType::from_str("RuSt").unwrap(),
-            message: "Secret decoder ring".to_string(),
-            output_file_path: PathBuf::from("/a/b/c"),
-        });
-        let cli = Cli::from_iter(vec![
-            "pngme",
-            "encode-r",
-            "https://raw.githubusercontent.com/jacksonneal/pngme/master/turtle.png",
-            "RuSt",
-            "Secret decoder ring",
-            "/a/b/c",
-        ]);
-        let actual = cli.subcommand;
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct FindArgs {
+    #[structopt(
+        parse(from_os_str),
+        required = true,
+        help = "Paths or glob patterns of the PNGs ('-' for stdin)"
+    )]
+    pub(crate) file_paths: Vec<PathBuf>,
+    #[structopt(flatten)]
+    pub(crate) glob: GlobArgs,
+    #[structopt(long, help = "Regex to search the chunk data for")]
+    pub(crate) pattern: String,
+    #[structopt(
+        long,
+        default_value = "all",
+        possible_values = &["all", "ancillary"],
+        help = "Which chunks to search"
+    )]
+    pub(crate) types: TypeFilter,
+    #[structopt(long, help = "Search the text of compressed zTXt and iTXt chunks too")]
+    pub(crate) decompress: bool,
+}
 
-        assert_eq!(expected, actual);
-    }
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct ScanArgs {
+    #[structopt(parse(from_os_str), help = "Directory to sweep recursively")]
+    pub(crate) dir: PathBuf,
+}
 
-    #[test]
-    pub(crate) fn test_encode_with_output_file() {
-        let expected = Subcommand::Encode(EncodeArgs {
-            input_file_path: PathBuf::from("/a/b/c"),
-            chunk_type: ChunkType::from_str("RuSt").unwrap(),
-            message: "Secret decoder ring".to_string(),
-            output_file_path: Some(PathBuf::from("/output/file/path")),
-        });
-        let cli = Cli::from_iter(vec![
-            "pngme",
-            "encode",
-            "/a/b/c",
-            "RuSt",
-            "Secret decoder ring",
-            "/output/file/path",
-        ]);
-        let actual = cli.subcommand;
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct ApplyArgs {
+    #[structopt(
+        parse(from_os_str),
+        help = "YAML manifest of files and the operations to run on each ('-' for stdin)"
+    )]
+    pub(crate) plan_path: PathBuf,
+    #[structopt(long, help = "Run the operations without writing any file")]
+    pub(crate) dry_run: bool,
+    #[structopt(flatten)]
+    pub(crate) guard: TypeGuardArgs,
+}
 
-        assert_eq!(expected, actual);
-    }
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct UndoArgs {
+    #[structopt(parse(from_os_str), help = "Path of the PNG")]
+    pub(crate) file_path: PathBuf,
+    #[structopt(
+        long,
+        help = "Undo even if something else changed the PNG since, losing that change"
+    )]
+    pub(crate) force: bool,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct HistoryArgs {
+    #[structopt(parse(from_os_str), help = "Path of the PNG ('-' for stdin)")]
+    pub(crate) file_path: PathBuf,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct PruneArgs {
+    #[structopt(parse(from_os_str), help = "Directory to prune recursively")]
+    pub(crate) dir: PathBuf,
+    #[structopt(
+        short = "t",
+        long = "type",
+        parse(try_from_str = ChunkType::from_str),
+        help = "Only remove expired chunks of this type (default: any)"
+    )]
+    pub(crate) chunk_type: Option<ChunkType>,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct WatermarkArgs {
+    #[structopt(parse(from_os_str), help = "Path of the PNG ('-' for stdin)")]
+    pub(crate) file_path: PathBuf,
+    #[structopt(long, help = "Text to draw")]
+    pub(crate) text: String,
+    #[structopt(
+        long = "pos",
+        default_value = "bottom-right",
+        possible_values = &["top-left", "top-right", "bottom-left", "bottom-right", "center"],
+        help = "Where to draw the text"
+    )]
+    pub(crate) position: Position,
+    #[structopt(
+        long,
+        default_value = "0.3",
+        parse(try_from_str = parse_opacity),
+        help = "Opacity of the text, from 0 to 1"
+    )]
+    pub(crate) opacity: f32,
+    #[structopt(
+        short = "o",
+        long = "output",
+        parse(from_os_str),
+        help = "Path to the output PNG (default: the input, '-' for stdout)"
+    )]
+    pub(crate) output_file_path: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct StatsArgs {
+    #[structopt(
+        parse(from_os_str),
+        required = true,
+        help = "Paths or glob patterns of the PNGs ('-' for stdin)"
+    )]
+    pub(crate) file_paths: Vec<PathBuf>,
+    #[structopt(flatten)]
+    pub(crate) glob: GlobArgs,
+    #[structopt(
+        long,
+        default_value = "5",
+        help = "How many of the largest chunks to list"
+    )]
+    pub(crate) top: usize,
+    #[structopt(
+        long,
+        help = "Print the statistics as JSON instead of a table (default if the config's output_format is json)"
+    )]
+    pub(crate) json: bool,
+    #[structopt(
+        long,
+        conflicts_with = "json",
+        help = "Print a table even if the config's output_format is json"
+    )]
+    pub(crate) table: bool,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct InfoArgs {
+    #[structopt(
+        parse(from_os_str),
+        required = true,
+        help = "Paths or glob patterns of the PNGs ('-' for stdin)"
+    )]
+    pub(crate) file_paths: Vec<PathBuf>,
+    #[structopt(flatten)]
+    pub(crate) glob: GlobArgs,
+    #[structopt(
+        long,
+        help = "Print the tags as exiftool does, for scripts that parse its output"
+    )]
+    pub(crate) exiftool: bool,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct SealArgs {
+    #[structopt(parse(from_os_str), help = "Path of the PNG to seal")]
+    pub(crate) file_path: PathBuf,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct CheckSealArgs {
+    #[structopt(
+        parse(from_os_str),
+        required = true,
+        help = "Paths or glob patterns of the PNGs ('-' for stdin)"
+    )]
+    pub(crate) file_paths: Vec<PathBuf>,
+    #[structopt(flatten)]
+    pub(crate) glob: GlobArgs,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct SamePixelsArgs {
+    #[structopt(parse(from_os_str), help = "Path of the first PNG ('-' for stdin)")]
+    pub(crate) first: PathBuf,
+    #[structopt(parse(from_os_str), help = "Path of the second PNG")]
+    pub(crate) second: PathBuf,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct ThumbArgs {
+    #[structopt(parse(from_os_str), help = "Path of the PNG ('-' for stdin)")]
+    pub(crate) file_path: PathBuf,
+    #[structopt(
+        parse(from_os_str),
+        help = "Path of the thumbnail PNG to write ('-' for stdout)"
+    )]
+    pub(crate) output_file_path: PathBuf,
+    #[structopt(
+        long,
+        default_value = "128",
+        help = "Largest width and height of the thumbnail, in pixels"
+    )]
+    pub(crate) size: u32,
+    #[structopt(
+        long,
+        help = "Extract the thumbnail embedded in the PNG's Exif data instead, at its own size"
+    )]
+    pub(crate) embedded: bool,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct ExportLayoutArgs {
+    #[structopt(parse(from_os_str), help = "Path of the PNG ('-' for stdin)")]
+    pub(crate) input_file_path: PathBuf,
+    #[structopt(
+        parse(from_os_str),
+        help = "Path of the JSON layout to write (default: stdout)"
+    )]
+    pub(crate) output_file_path: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct ImportLayoutArgs {
+    #[structopt(parse(from_os_str), help = "Path of the JSON layout ('-' for stdin)")]
+    pub(crate) layout_path: PathBuf,
+    #[structopt(parse(from_os_str), help = "Path of the PNG to write ('-' for stdout)")]
+    pub(crate) output_file_path: PathBuf,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct ChunkExportArgs {
+    #[structopt(parse(from_os_str), help = "Path of the PNG ('-' for stdin)")]
+    pub(crate) file_path: PathBuf,
+    #[structopt(parse(try_from_str = ChunkType::from_str), help = "Chunk type (like 'ruSt')")]
+    pub(crate) chunk_type: ChunkType,
+    #[structopt(
+        parse(from_os_str),
+        help = "File to write the raw chunk data to ('-' for stdout)"
+    )]
+    pub(crate) data_path: PathBuf,
+    #[structopt(long, help = "Export the Nth chunk of the type, counting from 0")]
+    pub(crate) nth: Option<usize>,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct ChunkImportArgs {
+    #[structopt(parse(from_os_str), help = "Path of the PNG ('-' for stdin)")]
+    pub(crate) file_path: PathBuf,
+    #[structopt(parse(try_from_str = ChunkType::from_str), help = "Chunk type (like 'ruSt')")]
+    pub(crate) chunk_type: ChunkType,
+    #[structopt(
+        parse(from_os_str),
+        help = "File holding the raw chunk data ('-' for stdin)"
+    )]
+    pub(crate) data_path: PathBuf,
+    #[structopt(
+        long,
+        help = "Replace any chunks of the type instead of adding another"
+    )]
+    pub(crate) replace: bool,
+    #[structopt(
+        long,
+        possible_values = &["start", "before-idat", "end"],
+        help = "Where to put the chunk (default: end, just before IEND)"
+    )]
+    pub(crate) place: Option<Place>,
+    #[structopt(flatten)]
+    pub(crate) guard: TypeGuardArgs,
+    #[structopt(
+        short = "o",
+        long = "output",
+        parse(from_os_str),
+        help = "Path to the output PNG (default: the input PNG, '-' for stdout)"
+    )]
+    pub(crate) output_file_path: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) enum ChunkCommand {
+    #[structopt(about = "Write the raw data of a chunk to a file")]
+    Export(ChunkExportArgs),
+    #[structopt(about = "Add a chunk holding the raw data of a file")]
+    Import(ChunkImportArgs),
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct ServeArgs {
+    #[structopt(long, default_value = "127.0.0.1:8080", help = "Address to listen on")]
+    pub(crate) listen: SocketAddr,
+    #[structopt(long, help = "Serve encode, decode and print over gRPC instead")]
+    pub(crate) grpc: bool,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) struct ConfigSetArgs {
+    #[structopt(help = "Config key, like chunk_type or timeout")]
+    pub(crate) key: String,
+    #[structopt(help = "New value")]
+    pub(crate) value: String,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub(crate) enum ConfigCommand {
+    #[structopt(about = "Print the current configuration")]
+    Show,
+    #[structopt(about = "Set a configuration value")]
+    Set(ConfigSetArgs),
+}
+
+#[derive(Debug, StructOpt, PartialEq)]
+pub(crate) enum Subcommand {
+    #[structopt(about = "Add a secret message to a PNG", alias = "enc")]
+    Encode(EncodeArgs),
+    #[structopt(about = "Add a secret message to a remote PNG", alias = "enc-r")]
+    EncodeR(EncodeRArgs),
+    #[structopt(about = "Show the secret message in a PNG", alias = "dec")]
+    Decode(DecodeArgs),
+    #[structopt(about = "Show the secret message in a remote PNG", alias = "dec-r")]
+    DecodeR(DecodeRArgs),
+    #[structopt(about = "Remove a secret message from a PNG", alias = "rm")]
+    Remove(RemoveArgs),
+    #[structopt(about = "Remove a secret message from a remote PNG", alias = "rm-r")]
+    RemoveR(RemoveRArgs),
+    #[structopt(about = "Print every chunk in a PNG", alias = "ls")]
+    Print(PrintArgs),
+    #[structopt(about = "Print every chunk in a remote PNG", alias = "ls-r")]
+    PrintR(PrintRArgs),
+    #[structopt(about = "Check that a PNG is well formed")]
+    Verify(VerifyArgs),
+    #[structopt(about = "Show whatever is left of the secret messages in a cut-off PNG")]
+    Salvage(SalvageArgs),
+    #[structopt(about = "Remove metadata chunks from PNGs by preset, for privacy")]
+    Strip(StripArgs),
+    #[structopt(about = "Upgrade secret messages in a PNG to the current format")]
+    MigrateFormat(MigrateFormatArgs),
+    #[structopt(about = "Find PNGs in a directory that contain matching chunks")]
+    Grep(GrepArgs),
+    #[structopt(about = "Search the chunk data of PNGs for a regex")]
+    Find(FindArgs),
+    #[structopt(
+        about = "Flag PNGs in a directory that could be hiding data: private chunks, data after IEND or high entropy",
+        alias = "audit"
+    )]
+    Scan(ScanArgs),
+    #[structopt(about = "Remove expired messages from the PNGs in a directory")]
+    Prune(PruneArgs),
+    #[structopt(about = "Show the changes recorded in a PNG's audit chunk")]
+    History(HistoryArgs),
+    #[structopt(about = "Revert the last change pngme made to a PNG, from its undo journal")]
+    Undo(UndoArgs),
+    #[structopt(about = "Draw visible text onto a PNG's image, recording it in a chunk")]
+    Watermark(WatermarkArgs),
+    #[structopt(about = "Write a small preview of a PNG, or the thumbnail embedded in it")]
+    Thumb(ThumbArgs),
+    #[structopt(about = "Sum up the chunks of PNGs by type, to spot metadata bloat")]
+    Stats(StatsArgs),
+    #[structopt(about = "Show the image header and metadata of PNGs as exiftool's tags")]
+    Info(InfoArgs),
+    #[structopt(about = "Store a hash of a PNG's pixels in it, to detect later edits")]
+    Seal(SealArgs),
+    #[structopt(about = "Check that a PNG's pixels still match its seal")]
+    CheckSeal(CheckSealArgs),
+    #[structopt(about = "Check that two PNGs decode to the same pixels")]
+    SamePixels(SamePixelsArgs),
+    #[structopt(about = "Write every chunk of a PNG, with its data, as JSON")]
+    ExportLayout(ExportLayoutArgs),
+    #[structopt(about = "Build a PNG from a JSON layout written by export-layout")]
+    ImportLayout(ImportLayoutArgs),
+    #[structopt(about = "Copy raw chunk data to or from a file")]
+    Chunk(ChunkCommand),
+    #[structopt(
+        about = "Run the encode, remove, strip and text-set operations a YAML manifest lists"
+    )]
+    Apply(ApplyArgs),
+    #[structopt(about = "Show or change the configuration file")]
+    Config(ConfigCommand),
+    #[structopt(about = "Serve encode, decode and remove over HTTP, or gRPC")]
+    Serve(ServeArgs),
+}
+
+/// Fill `slot` from the front of `rest` unless a flag already did.
+fn fill<T: From<String>>(slot: &mut Option<T>, rest: &mut VecDeque<String>) {
+    if slot.is_none() {
+        *slot = rest.pop_front().map(T::from);
+    }
+}
+
+/// Take the chunk type from the front of `rest` unless a flag gave it.  The
+/// default stands in when no more than `needed` positionals remain for the
+/// arguments after it.
+fn fill_chunk_type(
+    chunk_type: &mut Option<ChunkType>,
+    rest: &mut VecDeque<String>,
+    needed: usize,
+    default: Option<&ChunkType>,
+) -> crate::Result<()> {
+    if chunk_type.is_some() {
+        return Ok(());
+    }
+    match default {
+        Some(default) if rest.len() <= needed => *chunk_type = Some(*default),
+        _ => {
+            if let Some(s) = rest.pop_front() {
+                *chunk_type = Some(ChunkType::from_str(&s)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Take the chunk type from the last of several paths unless a flag gave it.
+/// With a default, the last path is only taken if it is a valid chunk type.
+fn fill_trailing_chunk_type(
+    chunk_type: &mut Option<ChunkType>,
+    paths: &mut Vec<PathBuf>,
+    default: Option<&ChunkType>,
+) -> crate::Result<()> {
+    fill_trailing_chunk_type_with(chunk_type, paths, default, ChunkType::from_str)
+}
+
+/// Like `fill_trailing_chunk_type`, parsing the chunk type with `parse`.
+fn fill_trailing_chunk_type_with<T: Clone>(
+    chunk_type: &mut Option<T>,
+    paths: &mut Vec<PathBuf>,
+    default: Option<&T>,
+    parse: fn(&str) -> Result<T, PngmeError>,
+) -> crate::Result<()> {
+    if chunk_type.is_some() {
+        return Ok(());
+    }
+    if let [_, .., last] = paths.as_slice() {
+        match (parse(&last.to_string_lossy()), default) {
+            (Ok(ct), _) => {
+                paths.pop();
+                *chunk_type = Some(ct);
+                return Ok(());
+            }
+            (Err(e), None) => return Err(e.into()),
+            (Err(_), Some(_)) => {}
+        }
+    }
+    *chunk_type = default.cloned();
+    Ok(())
+}
+
+/// `Err` if any positional is left over.
+fn no_extra(rest: VecDeque<String>) -> crate::Result<()> {
+    match rest.front() {
+        Some(arg) => Err(format!("Unexpected argument '{}'", arg))?,
+        None => Ok(()),
+    }
+}
+
+impl Subcommand {
+    /// Hand positional arguments to the fields no flag filled, in their
+    /// original order, falling back to the default chunk type.
+    pub(crate) fn resolve(&mut self, default: Option<&ChunkType>) -> crate::Result<()> {
+        match self {
+            Subcommand::Encode(args) => {
+                let mut rest = VecDeque::from(mem::take(&mut args.positionals));
+                if args.stdin {
+                    // What looked like the input path is the first of ARGS.
+                    let first = args.input_file_path.replace(PathBuf::from("-"));
+                    if let Some(first) = first.filter(|first| first != Path::new("-")) {
+                        rest.push_front(first.to_string_lossy().into_owned());
+                    }
+                }
+                let given =
+                    args.message.is_some() || args.message_file.is_some() || args.message_stdin;
+                let needed = usize::from(!given);
+                let default = if args.itxt.lang.is_some() {
+                    Some(&ChunkType::ITXT)
+                } else {
+                    default
+                };
+                // With --chunk, a lone positional is the output path.
+                if args.chunks.is_empty() || given || rest.len() > 1 {
+                    fill_chunk_type(&mut args.chunk_type, &mut rest, needed, default)?;
+                    if !given {
+                        fill(&mut args.message, &mut rest);
+                    }
+                }
+                if !args.stdout {
+                    fill(&mut args.output_file_path, &mut rest);
+                } else if args.output_file_path.is_some() {
+                    Err("--stdout can't be used with an output path")?
+                } else {
+                    args.output_file_path = Some(PathBuf::from("-"));
+                }
+                if args.out_dir.is_some() && args.output_file_path.is_some() {
+                    Err("--out-dir can't be used with an output path")?
+                }
+                if args.output_data_uri && args.output_file_path.is_some() {
+                    Err("--output-data-uri can't be used with an output path")?
+                }
+                no_extra(rest)
+            }
+            Subcommand::EncodeR(args) => {
+                let mut rest = VecDeque::from(mem::take(&mut args.positionals));
+                // With --upload or --pin-to-ipfs the local output is optional.
+                let published = args.upload.is_some() || args.pin_to_ipfs || args.output_data_uri;
+                let given =
+                    args.message.is_some() || args.message_file.is_some() || args.message_stdin;
+                let needed = usize::from(!given)
+                    + usize::from(args.output_file_path.is_none() && !published);
+                fill_chunk_type(&mut args.chunk_type, &mut rest, needed, default)?;
+                if !given {
+                    fill(&mut args.message, &mut rest);
+                }
+                fill(&mut args.output_file_path, &mut rest);
+                no_extra(rest)
+            }
+            Subcommand::Decode(args) => {
+                let default = if args.lang.is_some() {
+                    Some(&ChunkType::ITXT)
+                } else {
+                    default
+                };
+                fill_trailing_chunk_type(&mut args.chunk_type, &mut args.file_paths, default)?;
+                if args.qr_png.is_some() && args.file_paths.len() > 1 {
+                    Err("--qr-png needs a single input PNG")?
+                }
+                if args.qr && args.encoding.is_some_and(|e| e != Encoding::Utf8) {
+                    Err("--qr can't be used with --encoding")?
+                }
+                if args.encoding == Some(Encoding::Raw) && (args.select.all || args.show_meta) {
+                    Err("--encoding raw writes a single message, without --all or --show-meta")?
+                }
+                if args.encoding == Some(Encoding::Raw) && args.file_paths.len() > 1 {
+                    Err("--encoding raw writes a single message, from a single input PNG")?
+                }
+                Ok(())
+            }
+            Subcommand::DecodeR(args) => {
+                let mut rest = VecDeque::from(mem::take(&mut args.positionals));
+                if args.urls_from.is_some() && args.select.all {
+                    Err("--all can't be used with --urls-from")?
+                }
+                if args.urls_from.is_none() {
+                    let url = rest.pop_front().ok_or("Missing URL")?;
+                    args.url = Some(Url::from_str(&url)?);
+                }
+                fill_chunk_type(&mut args.chunk_type, &mut rest, 0, default)?;
+                no_extra(rest)
+            }
+            Subcommand::Remove(args) => fill_trailing_chunk_type_with(
+                &mut args.chunk_type,
+                &mut args.file_paths,
+                default.copied().map(RawChunkType::from).as_ref(),
+                RawChunkType::from_str,
+            ),
+            Subcommand::MigrateFormat(args) => {
+                fill_trailing_chunk_type(&mut args.chunk_type, &mut args.file_paths, default)
+            }
+            Subcommand::RemoveR(args) => {
+                let mut rest = VecDeque::from(mem::take(&mut args.positionals));
+                let needed = usize::from(args.output_file_path.is_none());
+                fill_chunk_type(&mut args.chunk_type, &mut rest, needed, default)?;
+                fill(&mut args.output_file_path, &mut rest);
+                no_extra(rest)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+mod test {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    pub(crate) fn test_encode() {
+        let expected = Subcommand::Encode(EncodeArgs {
+            input_file_path: Some(PathBuf::from("/a/b/c")),
+            stdin: false,
+            stdout: false,
+            positionals: vec![],
+            chunk_type: Some(ChunkType::from_str("RuSt").unwrap()),
+            message: Some("Secret decoder ring".to_string()),
+            message_file: None,
+            message_stdin: false,
+            chunks: vec![],
+            replace: false,
+            place: None,
+            guard: TypeGuardArgs::default(),
+            itxt: ItxtArgs::default(),
+            envelope: EnvelopeArgs::default(),
+            output_file_path: None,
+            out_dir: None,
+            output_data_uri: false,
+            verify_pixels: false,
+            glob: GlobArgs::default(),
+            notify_url: None,
+        });
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "/a/b/c",
+            "RuSt",
+            "Secret decoder ring",
+        ]);
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    pub(crate) fn test_encoder() {
+        let expected = Subcommand::EncodeR(EncodeRArgs {
+            url: Url::parse(
+                "https://raw.githubusercontent.com/jacksonneal/pngme/master/turtle.png",
+            )
+            .unwrap(),
+            remote: RemoteArgs::default(),
+            positionals: vec![],
+            chunk_type: Some(ChunkType::from_str("RuSt").unwrap()),
+            message: Some("Secret decoder ring".to_string()),
+            message_file: None,
+            message_stdin: false,
+            replace: false,
+            place: None,
+            guard: TypeGuardArgs::default(),
+            output_file_path: Some(PathBuf::from("/a/b/c")),
+            upload: None,
+            upload_method: Method::PUT,
+            pin_to_ipfs: false,
+            output_data_uri: false,
+            notify_url: None,
+        });
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "encode-r",
+            "https://raw.githubusercontent.com/jacksonneal/pngme/master/turtle.png",
+            "RuSt",
+            "Secret decoder ring",
+            "/a/b/c",
+        ]);
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    pub(crate) fn test_encode_with_output_file() {
+        let expected = Subcommand::Encode(EncodeArgs {
+            input_file_path: Some(PathBuf::from("/a/b/c")),
+            stdin: false,
+            stdout: false,
+            positionals: vec![],
+            chunk_type: Some(ChunkType::from_str("RuSt").unwrap()),
+            message: Some("Secret decoder ring".to_string()),
+            message_file: None,
+            message_stdin: false,
+            chunks: vec![],
+            replace: false,
+            place: None,
+            guard: TypeGuardArgs::default(),
+            itxt: ItxtArgs::default(),
+            envelope: EnvelopeArgs::default(),
+            output_file_path: Some(PathBuf::from("/output/file/path")),
+            out_dir: None,
+            output_data_uri: false,
+            verify_pixels: false,
+            glob: GlobArgs::default(),
+            notify_url: None,
+        });
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "/a/b/c",
+            "RuSt",
+            "Secret decoder ring",
+            "/output/file/path",
+        ]);
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    pub(crate) fn test_encode_with_out_dir() {
+        let expected = Subcommand::Encode(EncodeArgs {
+            input_file_path: Some(PathBuf::from("assets/*.png")),
+            stdin: false,
+            stdout: false,
+            positionals: vec![],
+            chunk_type: Some(ChunkType::from_str("RuSt").unwrap()),
+            message: Some("Secret decoder ring".to_string()),
+            message_file: None,
+            message_stdin: false,
+            chunks: vec![],
+            replace: false,
+            place: None,
+            guard: TypeGuardArgs::default(),
+            itxt: ItxtArgs::default(),
+            envelope: EnvelopeArgs::default(),
+            output_file_path: None,
+            out_dir: Some(PathBuf::from("/out")),
+            output_data_uri: false,
+            verify_pixels: false,
+            glob: GlobArgs::default(),
+            notify_url: None,
+        });
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "assets/*.png",
+            "RuSt",
+            "Secret decoder ring",
+            "--out-dir",
+            "/out",
+        ]);
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    pub(crate) fn test_encode_out_dir_conflicts_with_output_file() {
+        let mut subcommand = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "/a/b/c",
+            "RuSt",
+            "Secret decoder ring",
+            "/output/file/path",
+            "--out-dir",
+            "/out",
+        ])
+        .subcommand;
+
+        assert!(subcommand.resolve(None).is_err());
+
+        let result = Cli::from_iter_safe(vec![
+            "pngme",
+            "encode",
+            "/a/b/c",
+            "RuSt",
+            "Secret decoder ring",
+            "-o",
+            "/output/file/path",
+            "--out-dir",
+            "/out",
+        ]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_encode_with_flags() {
+        let expected = Subcommand::Encode(EncodeArgs {
+            input_file_path: Some(PathBuf::from("/a/b/c")),
+            stdin: false,
+            stdout: false,
+            positionals: vec![],
+            chunk_type: Some(ChunkType::from_str("RuSt").unwrap()),
+            message: Some("Secret decoder ring".to_string()),
+            message_file: None,
+            message_stdin: false,
+            chunks: vec![],
+            replace: false,
+            place: None,
+            guard: TypeGuardArgs::default(),
+            itxt: ItxtArgs::default(),
+            envelope: EnvelopeArgs::default(),
+            output_file_path: Some(PathBuf::from("/output/file/path")),
+            out_dir: None,
+            output_data_uri: false,
+            verify_pixels: false,
+            glob: GlobArgs::default(),
+            notify_url: None,
+        });
+        let mut flags = Cli::from_iter(vec![
+            "pngme",
+            "enc",
+            "/a/b/c",
+            "-m",
+            "Secret decoder ring",
+            "-o",
+            "/output/file/path",
+            "--type",
+            "RuSt",
+        ])
+        .subcommand;
+        flags.resolve(None).unwrap();
+        let mut mixed = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "/a/b/c",
+            "-t",
+            "RuSt",
+            "Secret decoder ring",
+            "/output/file/path",
+        ])
+        .subcommand;
+        mixed.resolve(None).unwrap();
+
+        assert_eq!(expected, flags);
+        assert_eq!(expected, mixed);
+    }
+
+    #[test]
+    pub(crate) fn test_encode_default_chunk_type() {
+        let default = ChunkType::from_str("ruSt").unwrap();
+        let mut subcommand =
+            Cli::from_iter(vec!["pngme", "encode", "/a/b/c", "Secret decoder ring"]).subcommand;
+        subcommand.resolve(Some(&default)).unwrap();
+
+        match subcommand {
+            Subcommand::Encode(args) => {
+                assert_eq!(Some(default), args.chunk_type);
+                assert_eq!(Some("Secret decoder ring".to_string()), args.message);
+            }
+            _ => panic!("expected encode"),
+        }
+    }
+
+    #[test]
+    pub(crate) fn test_encode_stdin_stdout() {
+        let resolved = |argv: Vec<&str>| {
+            let mut subcommand = Cli::from_iter(argv).subcommand;
+            subcommand.resolve(None).map(|_| subcommand)
+        };
+        let argv = vec!["pngme", "encode", "--stdin", "--stdout", "ruSt", "msg"];
+        match resolved(argv).unwrap() {
+            Subcommand::Encode(args) => {
+                assert_eq!(args.input_file_path, Some(PathBuf::from("-")));
+                assert_eq!(args.chunk_type, Some(ChunkType::from_str("ruSt").unwrap()));
+                assert_eq!(args.message.as_deref(), Some("msg"));
+                assert_eq!(args.output_file_path, Some(PathBuf::from("-")));
+            }
+            _ => panic!("expected encode"),
+        }
+
+        let argv = vec!["pngme", "encode", "--stdin", "-t", "ruSt", "-m", "msg"];
+        match resolved(argv).unwrap() {
+            Subcommand::Encode(args) => {
+                assert_eq!(args.input_file_path, Some(PathBuf::from("-")));
+                assert_eq!(args.output_file_path, None);
+            }
+            _ => panic!("expected encode"),
+        }
+
+        let argv = vec![
+            "pngme", "encode", "a.png", "ruSt", "msg", "out.png", "--stdout",
+        ];
+        assert!(resolved(argv).is_err());
+
+        // Stdin is only read when asked for, never for a missing path.
+        let argv = vec!["pngme", "encode", "-t", "ruSt", "-m", "msg"];
+        let missing = structopt::clap::ErrorKind::MissingRequiredArgument;
+        assert!(matches!(Cli::from_iter_safe(argv), Err(e) if e.kind == missing));
+        let argv = vec!["pngme", "encode", "-", "ruSt", "msg"];
+        match resolved(argv).unwrap() {
+            Subcommand::Encode(args) => {
+                assert_eq!(args.input_file_path, Some(PathBuf::from("-")));
+            }
+            _ => panic!("expected encode"),
+        }
+    }
+
+    #[test]
+    pub(crate) fn test_encode_message_file() {
+        let argv = vec![
+            "pngme",
+            "encode",
+            "a.png",
+            "ruSt",
+            "--message-file",
+            "m.txt",
+            "b.png",
+        ];
+        let mut subcommand = Cli::from_iter(argv).subcommand;
+        subcommand.resolve(None).unwrap();
+        match subcommand {
+            Subcommand::Encode(args) => {
+                assert_eq!(args.message, None);
+                assert_eq!(args.message_file, Some(PathBuf::from("m.txt")));
+                assert_eq!(args.output_file_path, Some(PathBuf::from("b.png")));
+            }
+            _ => panic!("expected encode"),
+        }
+
+        let argv = vec![
+            "pngme",
+            "encode-r",
+            "https://example.com/a.png",
+            "ruSt",
+            "--message-stdin",
+            "b.png",
+        ];
+        let mut subcommand = Cli::from_iter(argv).subcommand;
+        subcommand.resolve(None).unwrap();
+        match subcommand {
+            Subcommand::EncodeR(args) => {
+                assert!(args.message_stdin);
+                assert_eq!(args.output_file_path, Some(PathBuf::from("b.png")));
+            }
+            _ => panic!("expected encode-r"),
+        }
+
+        let argv = vec![
+            "pngme",
+            "encode",
+            "a.png",
+            "ruSt",
+            "-m",
+            "hi",
+            "--message-stdin",
+        ];
+        assert!(Cli::from_iter_safe(argv).is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_encode_chunks() {
+        let argv = vec![
+            "pngme", "encode", "a.png", "--chunk", "ruSt=one", "--pair", "teSt=a=b", "out.png",
+        ];
+        let mut subcommand = Cli::from_iter(argv).subcommand;
+        subcommand
+            .resolve(Some(&ChunkType::from_str("dfLt").unwrap()))
+            .unwrap();
+        match subcommand {
+            Subcommand::Encode(args) => {
+                assert_eq!(args.chunk_type, None);
+                assert_eq!(args.message, None);
+                assert_eq!(args.output_file_path, Some(PathBuf::from("out.png")));
+                let expected = vec![
+                    (ChunkType::from_str("ruSt").unwrap(), "one".to_string()),
+                    (ChunkType::from_str("teSt").unwrap(), "a=b".to_string()),
+                ];
+                assert_eq!(args.chunks, expected);
+            }
+            _ => panic!("expected encode"),
+        }
+
+        let argv = vec![
+            "pngme",
+            "encode",
+            "a.png",
+            "ruSt",
+            "main",
+            "--chunk",
+            "teSt=more",
+        ];
+        let mut subcommand = Cli::from_iter(argv).subcommand;
+        subcommand.resolve(None).unwrap();
+        match subcommand {
+            Subcommand::Encode(args) => {
+                assert_eq!(args.chunk_type, Some(ChunkType::from_str("ruSt").unwrap()));
+                assert_eq!(args.message.as_deref(), Some("main"));
+                assert_eq!(args.output_file_path, None);
+            }
+            _ => panic!("expected encode"),
+        }
+
+        for bad in ["ruSt", "ru1t=x"] {
+            let argv = vec!["pngme", "encode", "a.png", "--chunk", bad];
+            assert!(Cli::from_iter_safe(argv).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    pub(crate) fn test_encode_output_data_uri() {
+        let mut subcommand = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "/a/b/c",
+            "RuSt",
+            "msg",
+            "--output-data-uri",
+        ])
+        .subcommand;
+        subcommand.resolve(None).unwrap();
+
+        match subcommand {
+            Subcommand::Encode(args) => assert!(args.output_data_uri),
+            _ => panic!("expected encode"),
+        }
+
+        let mut subcommand = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "/a/b/c",
+            "RuSt",
+            "msg",
+            "/out",
+            "--output-data-uri",
+        ])
+        .subcommand;
+        assert!(subcommand.resolve(None).is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_encode_place() {
+        for (arg, place) in [
+            ("start", Place::Start),
+            ("before-idat", Place::BeforeIdat),
+            ("end", Place::End),
+        ] {
+            let cli = Cli::from_iter(vec![
+                "pngme", "encode", "a.png", "ruSt", "msg", "--place", arg,
+            ]);
+            match cli.subcommand {
+                Subcommand::Encode(args) => assert_eq!(args.place, Some(place)),
+                _ => panic!("expected encode"),
+            }
+        }
+        let result = Cli::from_iter_safe(vec![
+            "pngme", "encode", "a.png", "ruSt", "msg", "--place", "middle",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_type_guard() {
+        let guard = TypeGuardArgs::default();
+        assert!(guard.check(&ChunkType::from_str("ruSt").unwrap()).is_ok());
+        assert!(guard.check(&ChunkType::from_str("IDAT").unwrap()).is_err());
+        assert!(guard.check(&ChunkType::from_str("rust").unwrap()).is_err());
+
+        let mut subcommand = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "a.png",
+            "Rust",
+            "msg",
+            "--allow-critical",
+            "--allow-reserved",
+        ])
+        .subcommand;
+        subcommand.resolve(None).unwrap();
+        match subcommand {
+            Subcommand::Encode(args) => {
+                assert!(args.guard.check(args.chunk_type.as_ref().unwrap()).is_ok())
+            }
+            _ => panic!("expected encode"),
+        }
+    }
+
+    #[test]
+    pub(crate) fn test_encode_extra_argument() {
+        let mut subcommand = Cli::from_iter(vec![
+            "pngme", "encode", "/a/b/c", "RuSt", "msg", "/out", "/extra",
+        ])
+        .subcommand;
+
+        assert!(subcommand.resolve(None).is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_decode_with_flag_and_alias() {
+        let expected = Subcommand::Decode(DecodeArgs {
+            file_paths: vec![PathBuf::from("/a/b/c"), PathBuf::from("/d/e/f")],
+            glob: GlobArgs::default(),
+            chunk_type: Some(ChunkType::from_str("PnGm").unwrap()),
+            select: SelectArgs::default(),
+            lang: None,
+            show_meta: false,
+            ignore_expiry: false,
+            qr: false,
+            qr_png: None,
+            encoding: None,
+        });
+        let mut actual =
+            Cli::from_iter(vec!["pngme", "dec", "-t", "PnGm", "/a/b/c", "/d/e/f"]).subcommand;
+        actual.resolve(None).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    pub(crate) fn test_decode_default_chunk_type() {
+        let default = ChunkType::from_str("ruSt").unwrap();
+        let mut actual = Cli::from_iter(vec!["pngme", "dec", "a.png", "b.png"]).subcommand;
+        actual.resolve(Some(&default)).unwrap();
+
+        let expected = Subcommand::Decode(DecodeArgs {
+            file_paths: vec![PathBuf::from("a.png"), PathBuf::from("b.png")],
+            glob: GlobArgs::default(),
+            chunk_type: Some(default),
+            select: SelectArgs::default(),
+            lang: None,
+            show_meta: false,
+            ignore_expiry: false,
+            qr: false,
+            qr_png: None,
+            encoding: None,
+        });
+        assert_eq!(expected, actual);
+
+        let mut invalid = Cli::from_iter(vec!["pngme", "dec", "a.png", "b.png"]).subcommand;
+        assert!(invalid.resolve(None).is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_decode_select() {
+        let mut actual =
+            Cli::from_iter(vec!["pngme", "decode", "a.png", "ruSt", "--nth", "2"]).subcommand;
+        actual.resolve(None).unwrap();
+        match actual {
+            Subcommand::Decode(args) => assert_eq!(
+                args.select,
+                SelectArgs {
+                    all: false,
+                    nth: Some(2)
+                }
+            ),
+            _ => panic!("expected decode"),
+        }
+
+        let both = Cli::from_iter_safe(vec![
+            "pngme", "decode", "a.png", "ruSt", "--all", "--nth", "1",
+        ]);
+        assert!(both.is_err());
+
+        let mut remove =
+            Cli::from_iter(vec!["pngme", "remove", "a.png", "ruSt", "--all"]).subcommand;
+        remove.resolve(None).unwrap();
+        match remove {
+            Subcommand::Remove(args) => assert!(args.select.all),
+            _ => panic!("expected remove"),
+        }
+
+        let mut listed = Cli::from_iter(vec![
+            "pngme",
+            "decode-r",
+            "--urls-from",
+            "urls.txt",
+            "ruSt",
+            "--all",
+        ])
+        .subcommand;
+        assert!(listed.resolve(None).is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_aliases() {
+        for alias in ["rm", "ls"] {
+            let result = Cli::from_iter_safe(vec!["pngme", alias, "/a/b/c", "PnGm"]);
+            assert!(result.is_ok());
+        }
+    }
+
+    #[test]
+    pub(crate) fn test_decode() {
+        let expected = Subcommand::Decode(DecodeArgs {
+            file_paths: vec![PathBuf::from("/a/b/c")],
+            glob: GlobArgs::default(),
+            chunk_type: Some(ChunkType::from_str("PnGm").unwrap()),
+            select: SelectArgs::default(),
+            lang: None,
+            show_meta: false,
+            ignore_expiry: false,
+            qr: false,
+            qr_png: None,
+            encoding: None,
+        });
+        let cli = Cli::from_iter(vec!["pngme", "decode", "/a/b/c", "PnGm"]);
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    pub(crate) fn test_encoder_upload() {
+        let default = ChunkType::from_str("ruSt").unwrap();
+        let mut actual = Cli::from_iter(vec![
+            "pngme",
+            "enc-r",
+            "https://example.com/in.png",
+            "Secret decoder ring",
+            "--upload",
+            "https://example.com/out.png",
+            "--upload-method",
+            "post",
+        ])
+        .subcommand;
+        actual.resolve(Some(&default)).unwrap();
+
+        match actual {
+            Subcommand::EncodeR(args) => {
+                assert_eq!(Some(default), args.chunk_type);
+                assert_eq!(Some("Secret decoder ring".to_string()), args.message);
+                assert_eq!(None, args.output_file_path);
+                assert_eq!(
+                    Some(Url::parse("https://example.com/out.png").unwrap()),
+                    args.upload
+                );
+                assert_eq!(Method::POST, args.upload_method);
+            }
+            _ => panic!("expected encode-r"),
+        }
+    }
+
+    #[test]
+    pub(crate) fn test_notify_url() {
+        let hook = Url::parse("https://hooks.example.com/pngme").unwrap();
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "remove",
+            "/a/b/c",
+            "imAG",
+            "--notify-url",
+            hook.as_str(),
+        ]);
+        match cli.subcommand {
+            Subcommand::Remove(args) => assert_eq!(Some(hook.clone()), args.notify_url),
+            _ => panic!("expected remove"),
+        }
+
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "/a/b/c",
+            "RuSt",
+            "Secret",
+            "--notify-url",
+            hook.as_str(),
+        ]);
+        match cli.subcommand {
+            Subcommand::Encode(args) => assert_eq!(Some(hook), args.notify_url),
+            _ => panic!("expected encode"),
+        }
+    }
 
     #[test]
-    pub(crate) fn test_decode() {
-        let expected = Subcommand::Decode(DecodeArgs {
-            file_path: PathBuf::from("/a/b/c"),
-            chunk_type: ChunkType::from_str("PnGm").unwrap(),
-        });
-        let cli = Cli::from_iter(vec!["pngme", "decode", "/a/b/c", "PnGm"]);
-        let actual = cli.subcommand;
+    pub(crate) fn test_remote_args() {
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "print-r",
+            "https://example.com/a.png",
+            "-H",
+            "X-Api-Key: abc",
+            "--header",
+            "Accept:image/png",
+            "-A",
+            "Mozilla/5.0",
+            "--basic-auth",
+            "me:p:w",
+            "--proxy",
+            "socks5://localhost:1080",
+            "--cacert",
+            "ca.pem",
+            "--client-cert",
+            "me.pem",
+            "--client-key",
+            "me.key",
+            "--timeout",
+            "5",
+            "--retries",
+            "0",
+            "--retry-backoff",
+            "250",
+            "--no-cache",
+            "--expected-sha256",
+            &"AB".repeat(32),
+            "--mirror",
+            "https://mirror.example.com/a.png",
+            "--max-download",
+            "1000000",
+            "--no-preflight",
+        ]);
+        let expected = RemoteArgs {
+            headers: vec![
+                ("X-Api-Key".to_string(), "abc".to_string()),
+                ("Accept".to_string(), "image/png".to_string()),
+            ],
+            user_agent: Some("Mozilla/5.0".to_string()),
+            bearer_token: None,
+            basic_auth: Some(("me".to_string(), Some("p:w".to_string()))),
+            proxy: Some(Url::parse("socks5://localhost:1080").unwrap()),
+            cacert: Some(PathBuf::from("ca.pem")),
+            client_cert: Some(PathBuf::from("me.pem")),
+            client_key: Some(PathBuf::from("me.key")),
+            timeout: Some(5),
+            retries: Some(0),
+            retry_backoff: Some(250),
+            no_cache: true,
+            expected_sha256: Some("ab".repeat(32)),
+            ssh_key: None,
+            mirrors: vec![Url::parse("https://mirror.example.com/a.png").unwrap()],
+            max_download: Some(1_000_000),
+            no_preflight: true,
+            rate_limit: None,
+        };
 
-        assert_eq!(expected, actual);
+        match cli.subcommand {
+            Subcommand::PrintR(args) => assert_eq!(expected, args.remote),
+            _ => panic!("expected print-r"),
+        }
+    }
+
+    #[test]
+    pub(crate) fn test_remote_args_invalid() {
+        for args in [
+            vec!["-H", "no colon"],
+            vec!["--proxy", "not a url"],
+            vec!["--retries", "-1"],
+            vec!["--expected-sha256", "abc"],
+            vec!["--bearer-token", "t", "--basic-auth", "me"],
+        ] {
+            let mut argv = vec!["pngme", "print-r", "https://example.com/a.png"];
+            argv.extend(args);
+            assert!(Cli::from_iter_safe(argv).is_err());
+        }
     }
 
     #[test]
     pub(crate) fn test_decoder() {
         let expected = Subcommand::DecodeR(DecodeRArgs {
-            url: Url::parse(
-                "https://raw.githubusercontent.com/jacksonneal/pngme/master/turtle.png",
-            )
-            .unwrap(),
-            chunk_type: ChunkType::from_str("PnGm").unwrap(),
+            url: Some(
+                Url::parse("https://raw.githubusercontent.com/jacksonneal/pngme/master/turtle.png")
+                    .unwrap(),
+            ),
+            remote: RemoteArgs::default(),
+            positionals: vec![],
+            urls_from: None,
+            jobs: NonZeroUsize::new(4).unwrap(),
+            rate_limit: None,
+            chunk_type: Some(ChunkType::from_str("PnGm").unwrap()),
+            select: SelectArgs::default(),
+            ignore_expiry: false,
         });
         let cli = Cli::from_iter(vec![
             "pngme",
@@ -235,23 +2341,90 @@ mod test {
             "https://raw.githubusercontent.com/jacksonneal/pngme/master/turtle.png",
             "PnGm",
         ]);
-        let actual = cli.subcommand;
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
 
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    pub(crate) fn test_decoder_urls_from() {
+        let mut actual = Cli::from_iter(vec![
+            "pngme",
+            "decode-r",
+            "--urls-from",
+            "urls.txt",
+            "--concurrency",
+            "8",
+            "--rate-limit",
+            "2.5",
+            "ruSt",
+        ])
+        .subcommand;
+        actual.resolve(None).unwrap();
+
+        let expected = Subcommand::DecodeR(DecodeRArgs {
+            url: None,
+            remote: RemoteArgs::default(),
+            positionals: vec![],
+            urls_from: Some(PathBuf::from("urls.txt")),
+            jobs: NonZeroUsize::new(8).unwrap(),
+            rate_limit: Some(2.5),
+            chunk_type: Some(ChunkType::from_str("ruSt").unwrap()),
+            select: SelectArgs::default(),
+            ignore_expiry: false,
+        });
+        assert_eq!(expected, actual);
+
+        let mut missing = Cli::from_iter(vec!["pngme", "decode-r"]).subcommand;
+        assert!(missing.resolve(None).is_err());
+
+        for rate in ["0", "-1", "0.00001", "inf", "fast"] {
+            let argv = vec![
+                "pngme",
+                "decode-r",
+                "--urls-from",
+                "urls.txt",
+                "--rate-limit",
+                rate,
+            ];
+            assert!(Cli::from_iter_safe(argv).is_err(), "{}", rate);
+        }
+    }
+
     #[test]
     pub(crate) fn test_remove() {
         let expected = Subcommand::Remove(RemoveArgs {
-            file_path: PathBuf::from("/a/b/c"),
-            chunk_type: ChunkType::from_str("imAG").unwrap(),
+            file_paths: vec![PathBuf::from("/a/b/c")],
+            glob: GlobArgs::default(),
+            chunk_type: Some(RawChunkType::from_str("imAG").unwrap()),
+            notify_url: None,
+            select: SelectArgs::default(),
         });
         let cli = Cli::from_iter(vec!["pngme", "remove", "/a/b/c", "imAG"]);
-        let actual = cli.subcommand;
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
 
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    pub(crate) fn test_remove_raw_type() {
+        let cli = Cli::from_iter(vec!["pngme", "--relaxed", "remove", "a.png", "r2St"]);
+        assert!(cli.relaxed);
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
+        match actual {
+            Subcommand::Remove(args) => {
+                assert_eq!(args.chunk_type.unwrap().to_string(), "r2St");
+                assert_eq!(args.file_paths, [PathBuf::from("a.png")]);
+            }
+            _ => panic!("expected remove"),
+        }
+        let mut subcommand = Cli::from_iter(vec!["pngme", "remove", "a.png", "r St"]).subcommand;
+        assert!(subcommand.resolve(None).is_err());
+    }
+
     #[test]
     pub(crate) fn test_remover() {
         let expected = Subcommand::RemoveR(RemoveRArgs {
@@ -259,8 +2432,12 @@ mod test {
                 "https://raw.githubusercontent.com/jacksonneal/pngme/master/turtle.png",
             )
             .unwrap(),
-            chunk_type: ChunkType::from_str("imAG").unwrap(),
-            output_file_path: PathBuf::from("/a/b/c"),
+            remote: RemoteArgs::default(),
+            positionals: vec![],
+            chunk_type: Some(ChunkType::from_str("imAG").unwrap()),
+            output_file_path: Some(PathBuf::from("/a/b/c")),
+            notify_url: None,
+            select: SelectArgs::default(),
         });
         let cli = Cli::from_iter(vec![
             "pngme",
@@ -269,7 +2446,8 @@ mod test {
             "imAG",
             "/a/b/c",
         ]);
-        let actual = cli.subcommand;
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
 
         assert_eq!(expected, actual);
     }
@@ -277,10 +2455,12 @@ mod test {
     #[test]
     pub(crate) fn test_print() {
         let expected = Subcommand::Print(PrintArgs {
-            file_path: PathBuf::from("/a/b/c"),
+            file_paths: vec![PathBuf::from("/a/b/c")],
+            glob: GlobArgs::default(),
         });
         let cli = Cli::from_iter(vec!["pngme", "print", "/a/b/c"]);
-        let actual = cli.subcommand;
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
 
         assert_eq!(expected, actual);
     }
@@ -292,17 +2472,696 @@ mod test {
                 "https://raw.githubusercontent.com/jacksonneal/pngme/master/turtle.png",
             )
             .unwrap(),
+            remote: RemoteArgs::default(),
         });
         let cli = Cli::from_iter(vec![
             "pngme",
             "print-r",
             "https://raw.githubusercontent.com/jacksonneal/pngme/master/turtle.png",
         ]);
-        let actual = cli.subcommand;
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    pub(crate) fn test_decode_many() {
+        let expected = Subcommand::Decode(DecodeArgs {
+            file_paths: vec![PathBuf::from("/a/b/c"), PathBuf::from("/d/e/f")],
+            glob: GlobArgs::default(),
+            chunk_type: Some(ChunkType::from_str("PnGm").unwrap()),
+            select: SelectArgs::default(),
+            lang: None,
+            show_meta: false,
+            ignore_expiry: false,
+            qr: false,
+            qr_png: None,
+            encoding: None,
+        });
+        let cli = Cli::from_iter(vec!["pngme", "decode", "/a/b/c", "/d/e/f", "PnGm"]);
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    pub(crate) fn test_remove_many() {
+        let expected = Subcommand::Remove(RemoveArgs {
+            file_paths: vec![PathBuf::from("/a/b/c"), PathBuf::from("/d/e/f")],
+            glob: GlobArgs::default(),
+            chunk_type: Some(RawChunkType::from_str("imAG").unwrap()),
+            notify_url: None,
+            select: SelectArgs::default(),
+        });
+        let cli = Cli::from_iter(vec!["pngme", "remove", "/a/b/c", "/d/e/f", "imAG"]);
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    pub(crate) fn test_layout() {
+        let cli = Cli::from_iter(vec!["pngme", "export-layout", "a.png"]);
+        let expected = Subcommand::ExportLayout(ExportLayoutArgs {
+            input_file_path: PathBuf::from("a.png"),
+            output_file_path: None,
+        });
+        assert_eq!(cli.subcommand, expected);
+
+        let cli = Cli::from_iter(vec!["pngme", "import-layout", "a.json", "b.png"]);
+        let expected = Subcommand::ImportLayout(ImportLayoutArgs {
+            layout_path: PathBuf::from("a.json"),
+            output_file_path: PathBuf::from("b.png"),
+        });
+        assert_eq!(cli.subcommand, expected);
+    }
+
+    #[test]
+    pub(crate) fn test_chunk_command() {
+        let cli = Cli::from_iter(vec!["pngme", "chunk", "export", "a.png", "ruSt", "out.bin"]);
+        let expected = Subcommand::Chunk(ChunkCommand::Export(ChunkExportArgs {
+            file_path: PathBuf::from("a.png"),
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            data_path: PathBuf::from("out.bin"),
+            nth: None,
+        }));
+        assert_eq!(cli.subcommand, expected);
+
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "chunk",
+            "import",
+            "a.png",
+            "ruSt",
+            "in.bin",
+            "--replace",
+        ]);
+        match cli.subcommand {
+            Subcommand::Chunk(ChunkCommand::Import(args)) => {
+                assert_eq!(args.data_path, PathBuf::from("in.bin"));
+                assert!(args.replace);
+                assert_eq!(args.output_file_path, None);
+            }
+            _ => panic!("expected chunk import"),
+        }
+        let missing = Cli::from_iter_safe(vec!["pngme", "chunk", "import", "a.png", "ruSt"]);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_salvage() {
+        let cli = Cli::from_iter(vec!["pngme", "salvage", "cut.png", "ruSt"]);
+        let expected = Subcommand::Salvage(SalvageArgs {
+            file_path: PathBuf::from("cut.png"),
+            chunk_type: ChunkType::from_str("ruSt").unwrap(),
+            raw: false,
+        });
+        assert_eq!(cli.subcommand, expected);
+        assert!(Cli::from_iter_safe(vec!["pngme", "salvage", "cut.png"]).is_err());
+        let cli = Cli::from_iter(vec!["pngme", "salvage", "cut.png", "ruSt", "--raw"]);
+        assert!(matches!(cli.subcommand, Subcommand::Salvage(args) if args.raw));
+    }
+
+    #[test]
+    pub(crate) fn test_verify() {
+        let expected = Subcommand::Verify(VerifyArgs {
+            file_paths: vec![PathBuf::from("/a/b/c"), PathBuf::from("/d/e/f")],
+            glob: GlobArgs::default(),
+        });
+        let cli = Cli::from_iter(vec!["pngme", "verify", "/a/b/c", "/d/e/f"]);
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    pub(crate) fn test_print_exclude() {
+        let expected = Subcommand::Print(PrintArgs {
+            file_paths: vec![PathBuf::from("assets/**/*.png")],
+            glob: GlobArgs {
+                exclude: vec![
+                    Pattern::new("assets/a.png").unwrap(),
+                    Pattern::new("*.tmp.png").unwrap(),
+                ],
+            },
+        });
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "print",
+            "assets/**/*.png",
+            "--exclude",
+            "assets/a.png",
+            "--exclude",
+            "*.tmp.png",
+        ]);
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    pub(crate) fn test_grep() {
+        let expected = Subcommand::Grep(GrepArgs {
+            dir: PathBuf::from("/a/b/c"),
+            chunk_type: Some(ChunkType::from_str("ruSt").unwrap()),
+            pattern: Some("sec.*t".to_string()),
+        });
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "grep",
+            "/a/b/c",
+            "--type",
+            "ruSt",
+            "--pattern",
+            "sec.*t",
+        ]);
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    pub(crate) fn test_no_lock() {
+        let cli = Cli::from_iter(vec!["pngme", "remove", "a.png", "ruSt", "--no-lock"]);
+        assert!(cli.no_lock);
+
+        let cli = Cli::from_iter(vec!["pngme", "remove", "a.png", "ruSt"]);
+        assert!(!cli.no_lock);
+    }
+
+    #[test]
+    pub(crate) fn test_migrate_format() {
+        let default = ChunkType::from_str("ruSt").unwrap();
+        let mut actual =
+            Cli::from_iter(vec!["pngme", "migrate-format", "a.png", "b.png"]).subcommand;
+        actual.resolve(Some(&default)).unwrap();
+        let expected = Subcommand::MigrateFormat(MigrateFormatArgs {
+            file_paths: vec![PathBuf::from("a.png"), PathBuf::from("b.png")],
+            glob: GlobArgs::default(),
+            chunk_type: Some(default),
+        });
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    pub(crate) fn test_envelope() {
+        let args = |argv: Vec<&str>| match Cli::from_iter(argv).subcommand {
+            Subcommand::Encode(args) => args.envelope,
+            _ => panic!("expected encode"),
+        };
+        let envelope = args(vec!["pngme", "encode", "a.png", "ruSt", "hi"]);
+        assert!(!envelope.is_set());
+        let envelope = args(vec![
+            "pngme",
+            "encode",
+            "a.png",
+            "ruSt",
+            "hi",
+            "--author",
+            "alice",
+            "--content-type",
+            "text/plain",
+        ]);
+        assert_eq!(envelope.author.as_deref(), Some("alice"));
+        assert_eq!(envelope.content_type.as_deref(), Some("text/plain"));
+        assert!(envelope.is_set());
+
+        let cli = Cli::from_iter(vec!["pngme", "decode", "a.png", "ruSt", "--show-meta"]);
+        assert!(matches!(cli.subcommand, Subcommand::Decode(args) if args.show_meta));
+    }
+
+    #[test]
+    pub(crate) fn test_expiry() {
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "a.png",
+            "ruSt",
+            "hi",
+            "--expires",
+            "2025-01-01T00:00:00Z",
+        ]);
+        match cli.subcommand {
+            Subcommand::Encode(args) => {
+                let expires = args.envelope.expires.unwrap();
+                assert_eq!(
+                    humantime::format_rfc3339(expires).to_string(),
+                    "2025-01-01T00:00:00Z"
+                );
+                assert!(args.envelope.is_set());
+            }
+            _ => panic!("expected encode"),
+        }
+        let invalid = vec![
+            "pngme",
+            "encode",
+            "a.png",
+            "ruSt",
+            "hi",
+            "--expires",
+            "soon",
+        ];
+        assert!(Cli::from_iter_safe(invalid).is_err());
+
+        let cli = Cli::from_iter(vec!["pngme", "decode", "a.png", "ruSt", "--ignore-expiry"]);
+        assert!(matches!(cli.subcommand, Subcommand::Decode(args) if args.ignore_expiry));
+
+        let cli = Cli::from_iter(vec!["pngme", "prune", "assets", "-t", "ruSt"]);
+        let expected = Subcommand::Prune(PruneArgs {
+            dir: PathBuf::from("assets"),
+            chunk_type: Some(ChunkType::from_str("ruSt").unwrap()),
+        });
+        assert_eq!(cli.subcommand, expected);
+    }
+
+    #[test]
+    pub(crate) fn test_encoding() {
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "decode",
+            "a.png",
+            "ruSt",
+            "--encoding",
+            "hex",
+        ]);
+        assert!(
+            matches!(cli.subcommand, Subcommand::Decode(args) if args.encoding == Some(Encoding::Hex))
+        );
+        let unknown = vec!["pngme", "decode", "a.png", "--encoding", "utf16"];
+        assert!(Cli::from_iter_safe(unknown).is_err());
+
+        for conflicting in [
+            vec![
+                "pngme",
+                "decode",
+                "a.png",
+                "ruSt",
+                "--encoding",
+                "raw",
+                "--all",
+            ],
+            vec![
+                "pngme",
+                "decode",
+                "a.png",
+                "b.png",
+                "ruSt",
+                "--encoding",
+                "raw",
+            ],
+            vec![
+                "pngme",
+                "decode",
+                "a.png",
+                "ruSt",
+                "--encoding",
+                "base64",
+                "--qr",
+            ],
+        ] {
+            let mut subcommand = Cli::from_iter(conflicting).subcommand;
+            assert!(subcommand.resolve(None).is_err());
+        }
+    }
+
+    #[test]
+    pub(crate) fn test_qr() {
+        let cli = Cli::from_iter(vec!["pngme", "decode", "a.png", "ruSt", "--qr"]);
+        assert!(matches!(cli.subcommand, Subcommand::Decode(args) if args.qr));
+
+        let cli = Cli::from_iter(vec!["pngme", "decode", "a.png", "--qr-png", "qr.png"]);
+        match cli.subcommand {
+            Subcommand::Decode(args) => assert_eq!(args.qr_png, Some(PathBuf::from("qr.png"))),
+            _ => panic!("expected decode"),
+        }
+        let all = vec!["pngme", "decode", "a.png", "--qr-png", "qr.png", "--all"];
+        assert!(Cli::from_iter_safe(all).is_err());
+        let mut many = Cli::from_iter(vec![
+            "pngme", "decode", "a.png", "b.png", "ruSt", "--qr-png", "q.png",
+        ])
+        .subcommand;
+        assert!(many.resolve(None).is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_itxt() {
+        let mut actual = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "a.png",
+            "Hallo",
+            "--lang",
+            "de",
+            "--translated-keyword",
+            "Beschreibung",
+        ])
+        .subcommand;
+        actual
+            .resolve(Some(&ChunkType::from_str("ruSt").unwrap()))
+            .unwrap();
+        match actual {
+            Subcommand::Encode(args) => {
+                assert_eq!(args.chunk_type, Some(ChunkType::ITXT));
+                assert_eq!(args.message.as_deref(), Some("Hallo"));
+                assert_eq!(args.itxt.lang.as_deref(), Some("de"));
+                assert_eq!(
+                    args.itxt.translated_keyword.as_deref(),
+                    Some("Beschreibung")
+                );
+                assert!(args.itxt.is_set());
+            }
+            _ => panic!("expected encode"),
+        }
+
+        let mut actual =
+            Cli::from_iter(vec!["pngme", "decode", "a.png", "--lang", "de"]).subcommand;
+        actual
+            .resolve(Some(&ChunkType::from_str("ruSt").unwrap()))
+            .unwrap();
+        match actual {
+            Subcommand::Decode(args) => {
+                assert_eq!(args.chunk_type, Some(ChunkType::ITXT));
+                assert_eq!(args.lang.as_deref(), Some("de"));
+            }
+            _ => panic!("expected decode"),
+        }
+    }
+
+    #[test]
+    pub(crate) fn test_audit() {
+        let cli = Cli::from_iter(vec!["pngme", "remove", "a.png", "ruSt", "--audit"]);
+        assert!(cli.audit);
+        let cli = Cli::from_iter(vec!["pngme", "history", "a.png"]);
+        assert!(!cli.audit);
+        let expected = Subcommand::History(HistoryArgs {
+            file_path: PathBuf::from("a.png"),
+        });
+        assert_eq!(cli.subcommand, expected);
+    }
+
+    #[test]
+    pub(crate) fn test_watermark() {
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "watermark",
+            "a.png",
+            "--text",
+            "CONFIDENTIAL",
+            "--pos",
+            "top-left",
+        ]);
+        let expected = Subcommand::Watermark(WatermarkArgs {
+            file_path: PathBuf::from("a.png"),
+            text: "CONFIDENTIAL".to_string(),
+            position: Position::TopLeft,
+            opacity: 0.3,
+            output_file_path: None,
+        });
+        assert_eq!(cli.subcommand, expected);
+
+        let opaque = vec![
+            "pngme",
+            "watermark",
+            "a.png",
+            "--text",
+            "x",
+            "--opacity",
+            "1.5",
+        ];
+        assert!(Cli::from_iter_safe(opaque).is_err());
+        assert!(Cli::from_iter_safe(vec!["pngme", "watermark", "a.png"]).is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_find() {
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "find",
+            "a.png",
+            "--pattern",
+            "secret",
+            "--types",
+            "ancillary",
+            "--decompress",
+        ]);
+        let expected = Subcommand::Find(FindArgs {
+            file_paths: vec![PathBuf::from("a.png")],
+            glob: GlobArgs::default(),
+            pattern: "secret".to_string(),
+            types: TypeFilter::Ancillary,
+            decompress: true,
+        });
+        assert_eq!(cli.subcommand, expected);
+        assert!(Cli::from_iter_safe(vec!["pngme", "find", "a.png"]).is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_scan() {
+        let cli = Cli::from_iter(vec!["pngme", "scan", "assets"]);
+        let expected = Subcommand::Scan(ScanArgs {
+            dir: PathBuf::from("assets"),
+        });
+        assert_eq!(cli.subcommand, expected);
+        let cli = Cli::from_iter(vec!["pngme", "audit", "assets"]);
+        assert_eq!(cli.subcommand, expected);
+        let cli = Cli::from_iter(vec!["pngme", "--audit", "audit", "assets"]);
+        assert!(cli.audit);
+        assert_eq!(cli.subcommand, expected);
+    }
+
+    #[test]
+    pub(crate) fn test_strip() {
+        let cli = Cli::from_iter(vec!["pngme", "strip", "a.png", "--keep", "ruSt"]);
+        let expected = Subcommand::Strip(StripArgs {
+            file_paths: vec![PathBuf::from("a.png")],
+            glob: GlobArgs::default(),
+            preset: "social-media".to_string(),
+            keep: vec![ChunkType::from_str("ruSt").unwrap()],
+            dry_run: false,
+        });
+        assert_eq!(cli.subcommand, expected);
+        let cli = Cli::from_iter(vec!["pngme", "strip", "a.png", "--preset", "forensic"]);
+        assert!(matches!(cli.subcommand, Subcommand::Strip(args) if args.preset == "forensic"));
+    }
+
+    #[test]
+    pub(crate) fn test_undo() {
+        let cli = Cli::from_iter(vec!["pngme", "--journal", "undo", "a.png", "--force"]);
+        let expected = Subcommand::Undo(UndoArgs {
+            file_path: PathBuf::from("a.png"),
+            force: true,
+        });
+        assert_eq!(cli.subcommand, expected);
+        assert!(cli.record_journal);
+    }
+
+    #[test]
+    pub(crate) fn test_apply() {
+        let cli = Cli::from_iter(vec!["pngme", "apply", "plan.yaml", "--dry-run"]);
+        let expected = Subcommand::Apply(ApplyArgs {
+            plan_path: PathBuf::from("plan.yaml"),
+            dry_run: true,
+            guard: TypeGuardArgs::default(),
+        });
+        assert_eq!(cli.subcommand, expected);
+        assert!(Cli::from_iter_safe(vec!["pngme", "apply"]).is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_stats() {
+        let cli = Cli::from_iter(vec!["pngme", "stats", "a.png", "b.png", "--json"]);
+        let expected = Subcommand::Stats(StatsArgs {
+            file_paths: vec![PathBuf::from("a.png"), PathBuf::from("b.png")],
+            glob: GlobArgs::default(),
+            top: 5,
+            json: true,
+            table: false,
+        });
+        assert_eq!(cli.subcommand, expected);
+        let both = vec!["pngme", "stats", "a.png", "--json", "--table"];
+        assert!(Cli::from_iter_safe(both).is_err());
+    }
+
+    #[test]
+    pub(crate) fn test_info() {
+        let cli = Cli::from_iter(vec!["pngme", "info", "a.png", "--exiftool"]);
+        let expected = Subcommand::Info(InfoArgs {
+            file_paths: vec![PathBuf::from("a.png")],
+            glob: GlobArgs::default(),
+            exiftool: true,
+        });
+        assert_eq!(cli.subcommand, expected);
+    }
+
+    #[test]
+    pub(crate) fn test_seal() {
+        let cli = Cli::from_iter(vec!["pngme", "seal", "a.png"]);
+        let expected = Subcommand::Seal(SealArgs {
+            file_path: PathBuf::from("a.png"),
+        });
+        assert_eq!(cli.subcommand, expected);
+        let cli = Cli::from_iter(vec!["pngme", "check-seal", "a.png", "b.png"]);
+        assert!(
+            matches!(cli.subcommand, Subcommand::CheckSeal(args) if args.file_paths.len() == 2)
+        );
+    }
+
+    #[test]
+    pub(crate) fn test_verify_pixels() {
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "a.png",
+            "ruSt",
+            "msg",
+            "--verify-pixels",
+        ]);
+        assert!(matches!(cli.subcommand, Subcommand::Encode(args) if args.verify_pixels));
+        let cli = Cli::from_iter(vec!["pngme", "same-pixels", "a.png", "b.png"]);
+        let expected = Subcommand::SamePixels(SamePixelsArgs {
+            first: PathBuf::from("a.png"),
+            second: PathBuf::from("b.png"),
+        });
+        assert_eq!(cli.subcommand, expected);
+    }
+
+    #[test]
+    pub(crate) fn test_thumb() {
+        let cli = Cli::from_iter(vec!["pngme", "thumb", "a.png", "--size", "64", "out.png"]);
+        let expected = Subcommand::Thumb(ThumbArgs {
+            file_path: PathBuf::from("a.png"),
+            output_file_path: PathBuf::from("out.png"),
+            size: 64,
+            embedded: false,
+        });
+        assert_eq!(cli.subcommand, expected);
+        let cli = Cli::from_iter(vec!["pngme", "thumb", "a.png", "out.png", "--embedded"]);
+        assert!(
+            matches!(cli.subcommand, Subcommand::Thumb(args) if args.embedded && args.size == 128)
+        );
+    }
+
+    #[test]
+    pub(crate) fn test_deterministic() {
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "encode",
+            "a.png",
+            "ruSt",
+            "msg",
+            "--deterministic",
+        ]);
+        assert!(cli.deterministic);
+    }
+
+    #[test]
+    pub(crate) fn test_no_progress() {
+        let cli = Cli::from_iter(vec!["pngme", "print", "/a/b/c", "--no-progress"]);
+        assert!(cli.no_progress);
+
+        let cli = Cli::from_iter(vec!["pngme", "print", "/a/b/c"]);
+        assert!(!cli.no_progress);
+    }
+
+    #[test]
+    pub(crate) fn test_serve() {
+        let cli = Cli::from_iter(vec!["pngme", "serve"]);
+        let expected = Subcommand::Serve(ServeArgs {
+            listen: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            grpc: false,
+        });
+        assert_eq!(expected, cli.subcommand);
+
+        let cli = Cli::from_iter(vec!["pngme", "serve", "--listen", "0.0.0.0:9000"]);
+        let expected = Subcommand::Serve(ServeArgs {
+            listen: SocketAddr::from(([0, 0, 0, 0], 9000)),
+            grpc: false,
+        });
+        assert_eq!(expected, cli.subcommand);
+
+        let cli = Cli::from_iter(vec!["pngme", "serve", "--grpc"]);
+        let expected = Subcommand::Serve(ServeArgs {
+            listen: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            grpc: true,
+        });
+        assert_eq!(expected, cli.subcommand);
+    }
+
+    #[test]
+    pub(crate) fn test_config_show() {
+        let cli = Cli::from_iter(vec!["pngme", "config", "show", "--config", "/a/b/c"]);
+
+        assert_eq!(Subcommand::Config(ConfigCommand::Show), cli.subcommand);
+        assert_eq!(Some(PathBuf::from("/a/b/c")), cli.config);
+    }
+
+    #[test]
+    pub(crate) fn test_config_set() {
+        let expected = Subcommand::Config(ConfigCommand::Set(ConfigSetArgs {
+            key: "chunk_type".to_string(),
+            value: "ruSt".to_string(),
+        }));
+        let cli = Cli::from_iter(vec!["pngme", "config", "set", "chunk_type", "ruSt"]);
+        let mut actual = cli.subcommand;
+        actual.resolve(None).unwrap();
 
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    pub(crate) fn test_profile() {
+        let cli = Cli::from_iter(vec!["pngme", "print", "/a/b/c", "--profile", "work"]);
+
+        assert_eq!(Some("work".to_string()), cli.profile);
+    }
+
+    #[test]
+    pub(crate) fn test_secret_flags() {
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "decode-r",
+            "https://raw.githubusercontent.com/jacksonneal/pngme/master/turtle.png",
+            "PnGm",
+            "--passphrase",
+            "hunter2",
+            "--key-file",
+            "/a/b/c",
+            "--http-token",
+            "t0ken",
+        ]);
+
+        assert_eq!(Some("hunter2".to_string()), cli.passphrase);
+        assert_eq!(Some(PathBuf::from("/a/b/c")), cli.key_file);
+        assert_eq!(Some("t0ken".to_string()), cli.http_token);
+    }
+
+    #[test]
+    pub(crate) fn test_verbosity() {
+        let cli = Cli::from_iter(vec!["pngme", "print", "/a/b/c"]);
+        assert_eq!(0, cli.verbose);
+        assert_eq!(LogFormat::Text, cli.log_format);
+
+        let cli = Cli::from_iter(vec![
+            "pngme",
+            "-vv",
+            "print",
+            "/a/b/c",
+            "--log-format",
+            "json",
+        ]);
+        assert_eq!(2, cli.verbose);
+        assert_eq!(LogFormat::Json, cli.log_format);
+
+        let result = Cli::from_iter_safe(vec!["pngme", "print", "/a/b/c", "--log-format", "xml"]);
+        assert!(result.is_err());
+    }
+
     #[test]
     pub(crate) fn test_unknown_subcommand() {
         let result = Cli::from_iter_safe(vec!["pngme", "blah-blah", "some-argument"]);
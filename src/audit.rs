@@ -0,0 +1,130 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::envelope::Envelope;
+use crate::png::Png;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{env, fmt};
+
+/// Chunk type of the audit trail: private, ancillary and safe to copy, so
+/// other tools keep it.
+pub(crate) const AUDIT: ChunkType = ChunkType::new_unchecked(*b"auDt");
+
+/// One change to a Png, a line of JSON in its audit chunk.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub(crate) struct AuditEntry {
+    /// Name and version of the tool that made the change.
+    pub(crate) tool: String,
+    /// What it did, like `encode`.
+    pub(crate) operation: String,
+    /// When, as RFC 3339.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) at: Option<String>,
+    /// Login of the user who ran it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) user: Option<String>,
+    /// SHA-256 of the chunk data added or removed, as hex.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) digest: Option<String>,
+}
+
+impl AuditEntry {
+    /// Entry for the given operation by this pngme, on the given payload if
+    /// any.  Deterministic entries leave out the time and user.
+    pub(crate) fn new(operation: &str, payload: Option<&[u8]>, deterministic: bool) -> Self {
+        let user = env::var("USER").or_else(|_| env::var("USERNAME")).ok();
+        AuditEntry {
+            tool: concat!("pngme ", env!("CARGO_PKG_VERSION")).to_string(),
+            operation: operation.to_string(),
+            at: (!deterministic).then(Envelope::now),
+            user: user.filter(|_| !deterministic),
+            digest: payload.map(|data| format!("{:x}", Sha256::digest(data))),
+        }
+    }
+}
+
+/// `at<TAB>user<TAB>operation<TAB>tool<TAB>digest`, with `-` for any left
+/// out.
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let or_dash = |field: &Option<String>| field.clone().unwrap_or_else(|| "-".to_string());
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}",
+            or_dash(&self.at),
+            or_dash(&self.user),
+            self.operation,
+            self.tool,
+            or_dash(&self.digest)
+        )
+    }
+}
+
+/// Every entry in the audit trail of the Png, oldest first.  Lines that
+/// aren't entries are skipped.
+pub(crate) fn history(png: &Png) -> Vec<AuditEntry> {
+    png.chunks_by_type(AUDIT)
+        .flat_map(|chunk| chunk.data().split(|b| *b == b'\n'))
+        .filter_map(|line| serde_json::from_slice(line).ok())
+        .collect()
+}
+
+/// Add the entry to the end of the Png's audit chunk, first adding the
+/// chunk before IEND if there is none.
+pub(crate) fn record(png: &mut Png, entry: &AuditEntry) -> crate::Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    match png.chunk_by_type(AUDIT) {
+        Some(trail) => {
+            let data = [trail.data(), &line].concat();
+            png.replace_chunk(AUDIT, Chunk::new(AUDIT, data)?)?;
+        }
+        None => {
+            let end = png.position_of(ChunkType::IEND).unwrap_or(png.len());
+            png.insert_chunk_at(end, Chunk::new(AUDIT, line)?);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    #[test]
+    fn test_record() {
+        let mut png = Png::try_from(TURTLE).unwrap();
+        assert!(history(&png).is_empty());
+
+        let first = AuditEntry::new("encode", Some(b"hi"), true);
+        record(&mut png, &first).unwrap();
+        let end = png.position_of(ChunkType::IEND).unwrap();
+        assert_eq!(png[end - 1].chunk_type(), &AUDIT);
+
+        let second = AuditEntry::new("remove", None, false);
+        record(&mut png, &second).unwrap();
+        assert_eq!(png.chunks_by_type(AUDIT).count(), 1);
+        assert_eq!(history(&png), [first, second]);
+    }
+
+    #[test]
+    fn test_entry() {
+        let entry = AuditEntry::new("encode", Some(b"hi"), true);
+        assert!(entry.tool.starts_with("pngme "));
+        assert_eq!((entry.at, entry.user), (None, None));
+        assert_eq!(
+            entry.digest.as_deref(),
+            Some("8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4")
+        );
+        assert!(AuditEntry::new("encode", None, false).at.is_some());
+
+        let line = AuditEntry::new("prune", None, true).to_string();
+        assert_eq!(
+            line,
+            format!("-\t-\tprune\tpngme {}\t-", env!("CARGO_PKG_VERSION"))
+        );
+    }
+}
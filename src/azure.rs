@@ -0,0 +1,316 @@
+#[cfg(not(feature = "azure"))]
+use crate::args::RemoteArgs;
+#[cfg(not(feature = "azure"))]
+use crate::config::Config;
+use reqwest::Url;
+
+/// URL scheme of Azure Storage blobs.
+pub(crate) const SCHEME: &str = "az";
+
+/// Host of every storage account's blob endpoint, after the account name.
+const HOST_SUFFIX: &str = ".blob.core.windows.net";
+
+/// Is the url a blob on an account's own https endpoint, like
+/// `https://account.blob.core.windows.net/container/blob?<SAS token>`.
+pub(crate) fn is_blob_url(url: &Url) -> bool {
+    url.scheme() == "https"
+        && url
+            .host_str()
+            .is_some_and(|host| host.len() > HOST_SUFFIX.len() && host.ends_with(HOST_SUFFIX))
+}
+
+/// Container and blob name of an `az://container/blob` url.
+pub(crate) fn location(url: &Url) -> crate::Result<(&str, &str)> {
+    let container = url.host_str().filter(|c| !c.is_empty());
+    let blob = url.path().trim_start_matches('/');
+    match container {
+        Some(container) if !blob.is_empty() => Ok((container, blob)),
+        _ => Err(format!("Expected az://container/blob, got '{}'", url))?,
+    }
+}
+
+/// Endpoint url of the blob at an `az://` url, in the given account.
+#[cfg_attr(not(feature = "azure"), allow(dead_code))]
+fn blob_url(url: &Url, account: &str) -> crate::Result<Url> {
+    let (container, blob) = location(url)?;
+    let endpoint = format!("https://{}{}/{}/{}", account, HOST_SUFFIX, container, blob);
+    Ok(Url::parse(&endpoint)?)
+}
+
+/// Does the url carry its own SAS token.
+#[cfg_attr(not(feature = "azure"), allow(dead_code))]
+fn has_sas(url: &Url) -> bool {
+    url.query_pairs().any(|(name, _)| name == "sig")
+}
+
+/// What a Shared Key request signs: the method, the standard headers that
+/// are set (just the length and type of an upload here), the `x-ms-*`
+/// headers sorted by name, then the blob's path in the account.
+#[cfg_attr(not(feature = "azure"), allow(dead_code))]
+fn string_to_sign(
+    method: &str,
+    upload: Option<(usize, &str)>,
+    ms_headers: &[(&str, &str)],
+    account: &str,
+    path: &str,
+) -> String {
+    let (length, content_type) = match upload {
+        Some((length, content_type)) => (length.to_string(), content_type),
+        None => (String::new(), ""),
+    };
+    // Content-Encoding, -Language, -Length, -MD5, -Type, Date, If-Modified-
+    // Since, If-Match, If-None-Match, If-Unmodified-Since, Range.
+    let standard = ["", "", &length, "", content_type, "", "", "", "", "", ""];
+    let mut lines = vec![method.to_string()];
+    lines.extend(standard.iter().map(|value| value.to_string()));
+    lines.extend(
+        ms_headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}", name, value)),
+    );
+    lines.push(format!("/{}{}", account, path));
+    lines.join("\n")
+}
+
+#[cfg(feature = "azure")]
+mod storage {
+    use super::{blob_url, has_sas, location, string_to_sign, HOST_SUFFIX};
+    use crate::args::RemoteArgs;
+    use crate::config::Config;
+    use crate::remote::{self, Retry};
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use hmac::{Hmac, Mac};
+    use reqwest::blocking::Response;
+    use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+    use reqwest::{Method, Url};
+    use sha2::Sha256;
+    use std::env;
+    use std::io::Write;
+    use std::time::SystemTime;
+    use tracing::debug;
+
+    /// Storage REST API version the requests are signed for.
+    const VERSION: &str = "2021-08-06";
+
+    /// How requests to a blob are authorized.
+    enum Credentials {
+        /// Nothing to add: the url has its own SAS token.
+        InUrl,
+        /// SAS token from `AZURE_STORAGE_SAS_TOKEN`, added to the url.
+        Sas(String),
+        /// Account key from `AZURE_STORAGE_KEY`, signing each request.
+        SharedKey { account: String, key: Vec<u8> },
+    }
+
+    impl Credentials {
+        /// Credentials for the given account from the environment.
+        fn from_env(account: &str) -> crate::Result<Credentials> {
+            if let Ok(sas) = env::var("AZURE_STORAGE_SAS_TOKEN") {
+                return Ok(Credentials::Sas(sas.trim_start_matches('?').to_string()));
+            }
+            if let Ok(key) = env::var("AZURE_STORAGE_KEY") {
+                let key = STANDARD
+                    .decode(key.trim())
+                    .map_err(|e| format!("AZURE_STORAGE_KEY isn't base64: {}", e))?;
+                let account = account.to_string();
+                return Ok(Credentials::SharedKey { account, key });
+            }
+            Err("Azure blobs need AZURE_STORAGE_SAS_TOKEN, AZURE_STORAGE_KEY or a SAS url")?
+        }
+    }
+
+    /// Endpoint url of the blob at an `az://` or https url, and how to
+    /// authorize requests to it.
+    fn endpoint(url: &Url) -> crate::Result<(Url, Credentials)> {
+        let (mut endpoint, account) = if url.scheme() == super::SCHEME {
+            location(url)?;
+            let account = env::var("AZURE_STORAGE_ACCOUNT")
+                .map_err(|_| "az:// URLs need AZURE_STORAGE_ACCOUNT")?;
+            (blob_url(url, &account)?, account)
+        } else if has_sas(url) {
+            return Ok((url.clone(), Credentials::InUrl));
+        } else {
+            let host = url.host_str().unwrap_or_default();
+            let account = host.trim_end_matches(HOST_SUFFIX).to_string();
+            (url.clone(), account)
+        };
+        let credentials = Credentials::from_env(&account)?;
+        if let Credentials::Sas(sas) = &credentials {
+            endpoint.set_query(Some(sas));
+        }
+        Ok((endpoint, credentials))
+    }
+
+    /// Send a request to the blob at the given url, uploading PNG bytes if
+    /// given.
+    fn send(
+        url: &Url,
+        remote: &RemoteArgs,
+        config: &Config,
+        upload: Option<Vec<u8>>,
+    ) -> crate::Result<Response> {
+        let (endpoint, credentials) = endpoint(url)?;
+        let client = remote::client(remote, config)?;
+        let method = match upload {
+            Some(_) => Method::PUT,
+            None => Method::GET,
+        };
+        remote::send(Retry::from_args(remote), method, |method| {
+            let date = httpdate::fmt_http_date(SystemTime::now());
+            let mut ms_headers = vec![("x-ms-date", date.as_str()), ("x-ms-version", VERSION)];
+            if upload.is_some() {
+                ms_headers.insert(0, ("x-ms-blob-type", "BlockBlob"));
+            }
+            let mut request =
+                remote::request_without_auth(&client, method.clone(), &endpoint, remote, config);
+            for (name, value) in &ms_headers {
+                request = request.header(*name, *value);
+            }
+            if let Some(bytes) = &upload {
+                request = request
+                    .header(CONTENT_TYPE, "image/png")
+                    .body(bytes.clone());
+            }
+            if let Credentials::SharedKey { account, key } = &credentials {
+                let signed = string_to_sign(
+                    method.as_str(),
+                    upload.as_ref().map(|bytes| (bytes.len(), "image/png")),
+                    &ms_headers,
+                    account,
+                    endpoint.path(),
+                );
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("any key length");
+                mac.update(signed.as_bytes());
+                let signature = STANDARD.encode(mac.finalize().into_bytes());
+                let authorization = format!("SharedKey {}:{}", account, signature);
+                request = request.header(AUTHORIZATION, authorization);
+            }
+            request
+        })
+    }
+
+    /// Download the blob at the given url into `out`.
+    pub(crate) fn get(
+        url: &Url,
+        remote: &RemoteArgs,
+        config: &Config,
+        out: &mut dyn Write,
+    ) -> crate::Result<()> {
+        let mut response = send(url, remote, config, None)?;
+        debug!(content_length = ?response.content_length(), "fetched {}", url);
+        response.copy_to(out)?;
+        Ok(())
+    }
+
+    /// Upload PNG bytes as the blob at the given url.
+    pub(crate) fn put(
+        url: &Url,
+        remote: &RemoteArgs,
+        config: &Config,
+        bytes: Vec<u8>,
+    ) -> crate::Result<()> {
+        send(url, remote, config, Some(bytes))?;
+        debug!("uploaded to {}", url);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "azure")]
+pub(crate) use storage::{get, put};
+
+#[cfg(not(feature = "azure"))]
+const NOT_BUILT: &str = "Azure blob URLs need pngme built with the `azure` feature";
+
+/// Download the blob at the given url into `out`.
+#[cfg(not(feature = "azure"))]
+pub(crate) fn get(
+    url: &Url,
+    _remote: &RemoteArgs,
+    _config: &Config,
+    _out: &mut dyn std::io::Write,
+) -> crate::Result<()> {
+    if url.scheme() == SCHEME {
+        location(url)?;
+    }
+    Err(NOT_BUILT)?
+}
+
+/// Upload PNG bytes as the blob at the given url.
+#[cfg(not(feature = "azure"))]
+pub(crate) fn put(
+    url: &Url,
+    _remote: &RemoteArgs,
+    _config: &Config,
+    _bytes: Vec<u8>,
+) -> crate::Result<()> {
+    if url.scheme() == SCHEME {
+        location(url)?;
+    }
+    Err(NOT_BUILT)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        let url = Url::parse("az://assets/carriers/turtle.png").unwrap();
+        assert_eq!(location(&url).unwrap(), ("assets", "carriers/turtle.png"));
+        assert_eq!(
+            blob_url(&url, "acme").unwrap().as_str(),
+            "https://acme.blob.core.windows.net/assets/carriers/turtle.png"
+        );
+
+        for bad in ["az://assets", "az://assets/", "az:///blob"] {
+            assert!(location(&Url::parse(bad).unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_blob_urls() {
+        let sas = Url::parse("https://acme.blob.core.windows.net/assets/a.png?sv=2021&sig=abc");
+        let sas = sas.unwrap();
+        assert!(is_blob_url(&sas));
+        assert!(has_sas(&sas));
+
+        let plain = Url::parse("https://acme.blob.core.windows.net/assets/a.png").unwrap();
+        assert!(is_blob_url(&plain));
+        assert!(!has_sas(&plain));
+
+        for other in [
+            "http://acme.blob.core.windows.net/assets/a.png",
+            "https://blob.core.windows.net/assets/a.png",
+            "https://example.com/a.png",
+        ] {
+            assert!(!is_blob_url(&Url::parse(other).unwrap()), "{}", other);
+        }
+    }
+
+    #[test]
+    fn test_string_to_sign() {
+        let ms_headers = [
+            ("x-ms-blob-type", "BlockBlob"),
+            ("x-ms-date", "Wed, 21 Oct 2015 07:28:00 GMT"),
+            ("x-ms-version", "2021-08-06"),
+        ];
+        let signed = string_to_sign(
+            "PUT",
+            Some((42, "image/png")),
+            &ms_headers,
+            "acme",
+            "/assets/a.png",
+        );
+        assert_eq!(
+            signed,
+            "PUT\n\n\n42\n\nimage/png\n\n\n\n\n\n\n\
+             x-ms-blob-type:BlockBlob\n\
+             x-ms-date:Wed, 21 Oct 2015 07:28:00 GMT\n\
+             x-ms-version:2021-08-06\n\
+             /acme/assets/a.png"
+        );
+        let signed = string_to_sign("GET", None, &ms_headers[1..], "acme", "/assets/a.png");
+        assert!(signed.starts_with("GET\n\n\n\n\n\n\n\n\n\n\n\nx-ms-date:"));
+    }
+}
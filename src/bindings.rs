@@ -0,0 +1,43 @@
+//! What the wasm, Node and C bindings do to PNG bytes, shared so they
+//! behave the same.
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png, Result};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// The PNG with `message` added before IEND in a chunk of the given type.
+pub(crate) fn encode(png: &[u8], chunk_type: &str, message: &str) -> Result<Vec<u8>> {
+    let mut png = Png::try_from(png)?;
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    png.append_chunk(Chunk::new(chunk_type, message.as_bytes().to_vec())?);
+    Ok(png.as_bytes())
+}
+
+/// The message in the first chunk of the given type, `None` if none.
+pub(crate) fn decode(png: &[u8], chunk_type: &str) -> Result<Option<String>> {
+    let png = Png::try_from(png)?;
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    png.chunk_by_type(chunk_type)
+        .map(Chunk::data_as_string)
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    #[test]
+    fn test_encode_then_decode() {
+        let encoded = encode(TURTLE, "teSt", "Secret").unwrap();
+        assert_eq!(
+            decode(&encoded, "teSt").unwrap(),
+            Some("Secret".to_string())
+        );
+        assert_eq!(decode(TURTLE, "teSt").unwrap(), None);
+        assert!(encode(b"not a png", "teSt", "Secret").is_err());
+        assert!(decode(b"not a png", "teSt").is_err());
+        assert!(encode(TURTLE, "Ru1t", "Secret").is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::chunk::{Chunk, MAXIMUM_LENGTH};
+use crate::chunk_type::ChunkType;
+use crate::error::{PngmeError, Result};
+use crate::png::{ColorType, Png};
+use crate::text::keyword_bytes;
+
+/// Most image data the builder makes up itself, when no IDAT is given.
+const MAX_BLANK_DATA: usize = 64 << 20;
+
+/// Builds a Png chunk by chunk, in the order the PNG specification wants:
+/// IHDR, any other chunks, then IDAT and IEND.
+#[derive(Clone, Debug)]
+pub struct PngBuilder {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: ColorType,
+    chunks: Vec<Chunk>,
+    texts: Vec<(String, String)>,
+    idat: Vec<Vec<u8>>,
+}
+
+#[allow(dead_code)]
+impl PngBuilder {
+    /// Image header fields.  Without them, the Png is a single 8-bit
+    /// grayscale pixel.
+    pub fn ihdr(mut self, width: u32, height: u32, bit_depth: u8, color_type: ColorType) -> Self {
+        self.width = width;
+        self.height = height;
+        self.bit_depth = bit_depth;
+        self.color_type = color_type;
+        self
+    }
+
+    /// Add an IDAT chunk holding the given zlib-compressed image data.
+    /// Without any, the image is all zero samples.
+    pub fn idat(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.idat.push(data.into());
+        self
+    }
+
+    /// Add a tEXt chunk with the given keyword and Latin-1 text.
+    pub fn text(mut self, keyword: &str, text: &str) -> Self {
+        self.texts.push((keyword.to_string(), text.to_string()));
+        self
+    }
+
+    /// Add the given chunk before the image data.
+    pub fn chunk(mut self, chunk: Chunk) -> Self {
+        self.chunks.push(chunk);
+        self
+    }
+
+    /// The Png.  `Err` if the header fields don't make a valid image, a
+    /// text doesn't fit in a tEXt chunk, or without IDAT data, the image is
+    /// too large to make up its zero samples.
+    pub fn build(self) -> Result<Png> {
+        let invalid = |reason: String| PngmeError::InvalidImage(reason);
+        if self.width == 0 || self.height == 0 {
+            return Err(invalid("width and height must be at least 1".into()));
+        }
+        if self.width > MAXIMUM_LENGTH || self.height > MAXIMUM_LENGTH {
+            return Err(invalid("width and height must be at most 2^31 - 1".into()));
+        }
+        let depths: &[u8] = match self.color_type {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            _ => &[8, 16],
+        };
+        if !depths.contains(&self.bit_depth) {
+            return Err(invalid(format!(
+                "bit depth {} isn't allowed for {:?}",
+                self.bit_depth, self.color_type
+            )));
+        }
+
+        let blank_len = match self.idat.is_empty() {
+            true => Some(self.blank_data_len()?),
+            false => None,
+        };
+
+        let mut header = Vec::with_capacity(13);
+        header.extend(self.width.to_be_bytes());
+        header.extend(self.height.to_be_bytes());
+        header.extend([self.bit_depth, self.color_type.byte(), 0, 0, 0]);
+        let mut chunks = vec![Chunk::new(ChunkType::IHDR, header)?];
+
+        for (keyword, text) in &self.texts {
+            chunks.push(Chunk::new(ChunkType::TEXT, text_data(keyword, text)?)?);
+        }
+        let has_palette = self
+            .chunks
+            .iter()
+            .any(|c| c.chunk_type() == &ChunkType::PLTE);
+        chunks.extend(self.chunks);
+        if self.color_type == ColorType::Indexed && !has_palette {
+            chunks.push(Chunk::new(ChunkType::PLTE, vec![0, 0, 0])?);
+        }
+
+        let idat = match blank_len {
+            Some(len) => vec![zlib_stored(&vec![0; len])],
+            None => self.idat,
+        };
+        for data in idat {
+            chunks.push(Chunk::new(ChunkType::IDAT, data)?);
+        }
+        chunks.push(Chunk::new(ChunkType::IEND, Vec::new())?);
+        Ok(Png::from_chunks(chunks))
+    }
+}
+
+impl PngBuilder {
+    /// Length of all-zero image data for the header fields: a filter byte
+    /// and the samples of each row.  `Err` past `MAX_BLANK_DATA`.
+    fn blank_data_len(&self) -> Result<usize> {
+        let bits =
+            u64::from(self.width) * self.color_type.channels() as u64 * u64::from(self.bit_depth);
+        let len = (1 + bits.div_ceil(8))
+            .checked_mul(u64::from(self.height))
+            .filter(|&len| len <= MAX_BLANK_DATA as u64)
+            .ok_or_else(|| {
+                PngmeError::InvalidImage(format!(
+                    "a {}x{} image is too large to build without image data",
+                    self.width, self.height
+                ))
+            })?;
+        Ok(len as usize)
+    }
+}
+
+impl Png {
+    /// Start building a Png from scratch.
+    pub fn builder() -> PngBuilder {
+        PngBuilder {
+            width: 1,
+            height: 1,
+            bit_depth: 8,
+            color_type: ColorType::Grayscale,
+            chunks: Vec::new(),
+            texts: Vec::new(),
+            idat: Vec::new(),
+        }
+    }
+}
+
+/// tEXt data: a keyword of 1 to 79 Latin-1 characters, a null separator,
+/// then the Latin-1 text.
+fn text_data(keyword: &str, text: &str) -> Result<Vec<u8>> {
+    let text_bytes: Vec<u8> = text
+        .chars()
+        .map(|c| u8::try_from(u32::from(c)).ok())
+        .collect::<Option<_>>()
+        .ok_or_else(|| PngmeError::InvalidImage(format!("{:?} isn't Latin-1 text", text)))?;
+    Ok([keyword_bytes(keyword)?, vec![0], text_bytes].concat())
+}
+
+/// `raw` as a zlib stream of uncompressed deflate blocks.
+fn zlib_stored(raw: &[u8]) -> Vec<u8> {
+    // No compression, 32K window; the header is a multiple of 31.
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(u8::from(blocks.peek().is_none()));
+        let len = block.len() as u16;
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in raw {
+        a = (a + u32::from(*byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend(((b << 16) | a).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_build_minimal() {
+        let png = Png::builder().build().unwrap();
+        let types: Vec<String> = png.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "IEND"]);
+        assert_eq!((png.width(), png.height()), (Some(1), Some(1)));
+        assert!(Png::validate(&png.as_bytes()).is_empty());
+        // One row: filter byte and sample, stored.
+        assert_eq!(
+            png[1].data(),
+            [0x78, 0x01, 1, 2, 0, 0xfd, 0xff, 0, 0, 0, 2, 0, 1]
+        );
+    }
+
+    #[test]
+    fn test_build() {
+        let rust = ChunkType::from_str("ruSt").unwrap();
+        let png = Png::builder()
+            .ihdr(3, 2, 4, ColorType::Indexed)
+            .text("Author", "me")
+            .chunk(Chunk::new(rust, b"hi".to_vec()).unwrap())
+            .idat(vec![1, 2, 3])
+            .build()
+            .unwrap();
+        let types: Vec<String> = png.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "tEXt", "ruSt", "PLTE", "IDAT", "IEND"]);
+        assert_eq!(png.bit_depth(), Some(4));
+        assert_eq!(png.color_type(), Some(ColorType::Indexed));
+        assert_eq!(png[1].data(), b"Author\0me");
+        assert_eq!(png[4].data(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_build_invalid() {
+        let invalid =
+            |builder: PngBuilder| matches!(builder.build(), Err(PngmeError::InvalidImage(_)));
+        assert!(invalid(Png::builder().ihdr(0, 1, 8, ColorType::Rgb)));
+        assert!(invalid(Png::builder().ihdr(1, 1, 4, ColorType::Rgba)));
+        assert!(invalid(Png::builder().text("", "no keyword")));
+        assert!(invalid(Png::builder().text("Title", "\u{1F600}")));
+        let huge = MAXIMUM_LENGTH;
+        assert!(invalid(Png::builder().ihdr(
+            huge,
+            huge,
+            16,
+            ColorType::Rgba
+        )));
+        assert!(!invalid(
+            Png::builder()
+                .ihdr(huge, huge, 16, ColorType::Rgba)
+                .idat(vec![0])
+        ));
+    }
+}
@@ -0,0 +1,242 @@
+use reqwest::blocking::Response;
+use reqwest::header::{ETAG, LAST_MODIFIED};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::PathBuf;
+use tracing::debug;
+
+/// Validators the server sent with a cached download.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+pub(crate) struct Meta {
+    pub(crate) url: String,
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<String>,
+}
+
+impl Meta {
+    /// Validators of the given response.  `None` if it has none, in which
+    /// case there is no way to check a cached copy and it isn't kept.
+    pub(crate) fn from_response(response: &Response) -> Option<Meta> {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let meta = Meta {
+            url: response.url().to_string(),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        };
+        if meta.etag.is_none() && meta.last_modified.is_none() {
+            return None;
+        }
+        Some(meta)
+    }
+}
+
+/// A cached download.
+#[derive(Debug)]
+pub(crate) struct Entry {
+    pub(crate) meta: Meta,
+    pub(crate) body: PathBuf,
+}
+
+/// A download into the cache that was cut short.
+#[derive(Debug)]
+pub(crate) struct Partial {
+    /// Validators of the response the bytes so far came from.
+    pub(crate) meta: Meta,
+    /// Bytes downloaded so far.
+    pub(crate) len: u64,
+}
+
+/// Downloads kept on disk, keyed by URL.
+pub(crate) struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Cache in the given directory.
+    pub(crate) fn new(dir: PathBuf) -> Cache {
+        Cache { dir }
+    }
+
+    /// Default cache location, honoring `XDG_CACHE_HOME`.
+    pub(crate) fn default_dir() -> Option<PathBuf> {
+        let base = match env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+        };
+        Some(base.join("pngme"))
+    }
+
+    /// File name stem for the given url.
+    fn key(url: &Url) -> String {
+        format!("{:x}", Sha256::digest(url.as_str().as_bytes()))
+    }
+
+    fn body_path(&self, url: &Url) -> PathBuf {
+        self.dir.join(format!("{}.png", Cache::key(url)))
+    }
+
+    fn meta_path(&self, url: &Url) -> PathBuf {
+        self.dir.join(format!("{}.toml", Cache::key(url)))
+    }
+
+    fn partial_path(&self, url: &Url) -> PathBuf {
+        self.dir.join(format!("{}.part", Cache::key(url)))
+    }
+
+    fn partial_meta_path(&self, url: &Url) -> PathBuf {
+        self.dir.join(format!("{}.part.toml", Cache::key(url)))
+    }
+
+    /// Cached download of the given url, if any.
+    pub(crate) fn lookup(&self, url: &Url) -> Option<Entry> {
+        let body = self.body_path(url);
+        if !body.exists() {
+            return None;
+        }
+        let meta: Meta = toml::from_str(&fs::read_to_string(self.meta_path(url)).ok()?).ok()?;
+        Some(Entry { meta, body })
+    }
+
+    /// Interrupted download of the given url, if any bytes of it were kept.
+    pub(crate) fn partial(&self, url: &Url) -> Option<Partial> {
+        let len = fs::metadata(self.partial_path(url)).ok()?.len();
+        if len == 0 {
+            return None;
+        }
+        let meta = fs::read_to_string(self.partial_meta_path(url)).ok()?;
+        let meta = toml::from_str(&meta).ok()?;
+        Some(Partial { meta, len })
+    }
+
+    /// Drop the interrupted download of the given url, if any.
+    pub(crate) fn discard_partial(&self, url: &Url) {
+        let _ = fs::remove_file(self.partial_path(url));
+        let _ = fs::remove_file(self.partial_meta_path(url));
+    }
+
+    /// Copy the body into the cache under the given url, returning the
+    /// cached file.  A half-written body never replaces a complete one, and
+    /// is kept for `resume`.
+    pub(crate) fn store(
+        &self,
+        url: &Url,
+        meta: &Meta,
+        body: &mut dyn Read,
+    ) -> crate::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.partial_meta_path(url), toml::to_string(meta)?)?;
+        let file = File::create(self.partial_path(url))?;
+        self.finish(url, meta, body, file)
+    }
+
+    /// Append the rest of the body to the interrupted download of the given
+    /// url, returning the cached file.
+    pub(crate) fn resume(
+        &self,
+        url: &Url,
+        partial: &Partial,
+        rest: &mut dyn Read,
+    ) -> crate::Result<PathBuf> {
+        let file = fs::OpenOptions::new()
+            .append(true)
+            .open(self.partial_path(url))?;
+        debug!(offset = partial.len, "resuming cached download");
+        self.finish(url, &partial.meta, rest, file)
+    }
+
+    /// Copy the body to the end of the partial file, then make it the
+    /// cached download.
+    fn finish(
+        &self,
+        url: &Url,
+        meta: &Meta,
+        body: &mut dyn Read,
+        mut file: File,
+    ) -> crate::Result<PathBuf> {
+        io::copy(body, &mut file)?;
+        let path = self.body_path(url);
+        fs::rename(self.partial_path(url), &path)?;
+        fs::write(self.meta_path(url), toml::to_string(meta)?)?;
+        let _ = fs::remove_file(self.partial_meta_path(url));
+        debug!(path = %path.display(), "cached download");
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_store_and_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path().to_path_buf());
+        let url = Url::parse("https://example.com/a.png").unwrap();
+        assert!(cache.lookup(&url).is_none());
+
+        let meta = Meta {
+            url: url.to_string(),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+        };
+        let path = cache
+            .store(&url, &meta, &mut Cursor::new(b"body".to_vec()))
+            .unwrap();
+        assert_eq!(fs::read(path).unwrap(), b"body");
+
+        let entry = cache.lookup(&url).unwrap();
+        assert_eq!(entry.meta, meta);
+
+        let other = Url::parse("https://example.com/b.png").unwrap();
+        assert!(cache.lookup(&other).is_none());
+    }
+
+    /// Reader that fails after the given bytes, like a dropped connection.
+    struct Cut(Cursor<Vec<u8>>);
+
+    impl Read for Cut {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.read(buf)? {
+                0 => Err(io::ErrorKind::ConnectionReset.into()),
+                n => Ok(n),
+            }
+        }
+    }
+
+    #[test]
+    fn test_resume_partial() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path().to_path_buf());
+        let url = Url::parse("https://example.com/a.png").unwrap();
+        let meta = Meta {
+            url: url.to_string(),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+        };
+        assert!(cache
+            .store(&url, &meta, &mut Cut(Cursor::new(b"bo".to_vec())))
+            .is_err());
+        assert!(cache.lookup(&url).is_none());
+
+        let partial = cache.partial(&url).unwrap();
+        assert_eq!(partial.len, 2);
+        assert_eq!(partial.meta, meta);
+        let path = cache
+            .resume(&url, &partial, &mut Cursor::new(b"dy".to_vec()))
+            .unwrap();
+        assert_eq!(fs::read(path).unwrap(), b"body");
+        assert_eq!(cache.lookup(&url).unwrap().meta, meta);
+        assert!(cache.partial(&url).is_none());
+    }
+}
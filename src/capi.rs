@@ -0,0 +1,276 @@
+//! C ABI over PNG bytes, so C, C++ and Go programs can link pngme
+//! directly.  `include/pngme.h` declares it.
+//!
+//! Every function returns `PNGME_OK` on success.  On `PNGME_ERROR`,
+//! `pngme_last_error` describes what went wrong on the calling thread.
+
+use crate::bindings;
+use crate::{chunk_type::ChunkType, png::Png};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::{ptr, slice};
+
+/// The call succeeded.
+pub const PNGME_OK: c_int = 0;
+/// The call failed, see `pngme_last_error`.
+pub const PNGME_ERROR: c_int = -1;
+/// The PNG has no matching chunk.
+pub const PNGME_NOT_FOUND: c_int = 1;
+
+/// Bytes allocated by pngme, released with `pngme_buffer_free`.
+#[repr(C)]
+pub struct PngmeBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl PngmeBuffer {
+    fn new(bytes: Vec<u8>) -> PngmeBuffer {
+        let bytes = Box::leak(bytes.into_boxed_slice());
+        PngmeBuffer {
+            data: bytes.as_mut_ptr(),
+            len: bytes.len(),
+        }
+    }
+}
+
+/// Failures here include bad arguments as well as `PngmeError`s.
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Remember the error for `pngme_last_error` and return `PNGME_ERROR`.
+fn fail(e: Box<dyn std::error::Error>) -> c_int {
+    let message = CString::new(e.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    PNGME_ERROR
+}
+
+/// The `len` bytes at `buf`.
+unsafe fn bytes<'a>(buf: *const u8, len: usize) -> Result<&'a [u8]> {
+    if buf.is_null() {
+        Err("buf is NULL")?
+    }
+    Ok(slice::from_raw_parts(buf, len))
+}
+
+/// The Png held by `len` bytes at `buf`.
+unsafe fn png(buf: *const u8, len: usize) -> Result<Png> {
+    Ok(Png::try_from(bytes(buf, len)?)?)
+}
+
+/// The text of a NUL-terminated string.
+unsafe fn text<'a>(s: *const c_char, name: &str) -> Result<&'a str> {
+    if s.is_null() {
+        Err(format!("{} is NULL", name))?
+    }
+    Ok(CStr::from_ptr(s).to_str()?)
+}
+
+unsafe fn chunk_type(s: *const c_char) -> Result<ChunkType> {
+    Ok(text(s, "chunk_type")?.parse()?)
+}
+
+/// Add `message` to the PNG in a chunk of type `chunk_type`, storing the new
+/// PNG in `out`.
+///
+/// # Safety
+///
+/// `buf` must point to `len` readable bytes, `chunk_type` and `message` to
+/// NUL-terminated strings and `out` to writable memory for a `PngmeBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_encode(
+    buf: *const u8,
+    len: usize,
+    chunk_type: *const c_char,
+    message: *const c_char,
+    out: *mut PngmeBuffer,
+) -> c_int {
+    let encoded = (|| -> Result<Vec<u8>> {
+        let png = bytes(buf, len)?;
+        let chunk_type = text(chunk_type, "chunk_type")?;
+        Ok(bindings::encode(
+            png,
+            chunk_type,
+            text(message, "message")?,
+        )?)
+    })();
+    match (encoded, out.is_null()) {
+        (Err(e), _) => fail(e),
+        (Ok(_), true) => fail("out is NULL".into()),
+        (Ok(bytes), false) => {
+            out.write(PngmeBuffer::new(bytes));
+            PNGME_OK
+        }
+    }
+}
+
+/// Store the message in the first chunk of type `chunk_type` in `out`, as a
+/// NUL-terminated string released with `pngme_string_free`.  Returns
+/// `PNGME_NOT_FOUND`, leaving `out` alone, if there is no such chunk.
+///
+/// # Safety
+///
+/// `buf` must point to `len` readable bytes, `chunk_type` to a
+/// NUL-terminated string and `out` to a writable `char *`.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_decode(
+    buf: *const u8,
+    len: usize,
+    chunk_type: *const c_char,
+    out: *mut *mut c_char,
+) -> c_int {
+    let decoded = (|| -> Result<Option<CString>> {
+        let png = bytes(buf, len)?;
+        let message = bindings::decode(png, text(chunk_type, "chunk_type")?)?;
+        Ok(message.map(CString::new).transpose()?)
+    })();
+    match (decoded, out.is_null()) {
+        (Err(e), _) => fail(e),
+        (Ok(_), true) => fail("out is NULL".into()),
+        (Ok(None), false) => PNGME_NOT_FOUND,
+        (Ok(Some(message)), false) => {
+            out.write(message.into_raw());
+            PNGME_OK
+        }
+    }
+}
+
+/// Remove the first chunk of type `chunk_type`, storing the new PNG in
+/// `out`.  Returns `PNGME_NOT_FOUND`, leaving `out` alone, if there is no
+/// such chunk.
+///
+/// # Safety
+///
+/// `buf` must point to `len` readable bytes, `chunk_type` to a
+/// NUL-terminated string and `out` to writable memory for a `PngmeBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_remove(
+    buf: *const u8,
+    len: usize,
+    chunk_type: *const c_char,
+    out: *mut PngmeBuffer,
+) -> c_int {
+    let removed = (|| -> Result<Option<Vec<u8>>> {
+        let mut png = png(buf, len)?;
+        Ok(png
+            .remove_chunk(self::chunk_type(chunk_type)?)
+            .ok()
+            .map(|_| png.as_bytes()))
+    })();
+    match (removed, out.is_null()) {
+        (Err(e), _) => fail(e),
+        (Ok(_), true) => fail("out is NULL".into()),
+        (Ok(None), false) => PNGME_NOT_FOUND,
+        (Ok(Some(bytes)), false) => {
+            out.write(PngmeBuffer::new(bytes));
+            PNGME_OK
+        }
+    }
+}
+
+/// Release a buffer filled in by pngme.
+///
+/// # Safety
+///
+/// `buffer` must come from pngme and not have been released before.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_buffer_free(buffer: PngmeBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+/// Release a string filled in by pngme.
+///
+/// # Safety
+///
+/// `s` must come from pngme and not have been released before.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Description of the last failure on this thread, or NULL.  Valid until
+/// the next pngme call on the same thread.
+#[no_mangle]
+pub extern "C" fn pngme_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match &*last.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    #[test]
+    fn test_encode_decode_remove() {
+        unsafe {
+            let (chunk_type, message) = (c("teSt"), c("Secret"));
+            let mut encoded = PngmeBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            let status = pngme_encode(
+                TURTLE.as_ptr(),
+                TURTLE.len(),
+                chunk_type.as_ptr(),
+                message.as_ptr(),
+                &mut encoded,
+            );
+            assert_eq!(status, PNGME_OK);
+
+            let mut decoded = ptr::null_mut();
+            let status = pngme_decode(encoded.data, encoded.len, chunk_type.as_ptr(), &mut decoded);
+            assert_eq!(status, PNGME_OK);
+            assert_eq!(CStr::from_ptr(decoded).to_str().unwrap(), "Secret");
+            pngme_string_free(decoded);
+
+            let mut removed = PngmeBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            let status = pngme_remove(encoded.data, encoded.len, chunk_type.as_ptr(), &mut removed);
+            assert_eq!(status, PNGME_OK);
+            assert_eq!(slice::from_raw_parts(removed.data, removed.len), TURTLE);
+            pngme_buffer_free(encoded);
+
+            let status = pngme_decode(removed.data, removed.len, chunk_type.as_ptr(), &mut decoded);
+            assert_eq!(status, PNGME_NOT_FOUND);
+            pngme_buffer_free(removed);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            let chunk_type = c("teSt");
+            let mut out = ptr::null_mut();
+            let status = pngme_decode(b"nope".as_ptr(), 4, chunk_type.as_ptr(), &mut out);
+            assert_eq!(status, PNGME_ERROR);
+            assert!(!pngme_last_error().is_null());
+
+            let status = pngme_decode(ptr::null(), 0, chunk_type.as_ptr(), &mut out);
+            assert_eq!(status, PNGME_ERROR);
+            let error = CStr::from_ptr(pngme_last_error()).to_str().unwrap();
+            assert_eq!(error, "buf is NULL");
+        }
+    }
+}
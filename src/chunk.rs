@@ -1,12 +1,36 @@
-use crate::{chunk_type::ChunkType, encrypt::decrypt, Error, Result};
-use std::{
-    env, fmt,
-    io::{BufReader, Read},
-};
+use crate::chunk_type::{ChunkType, RawChunkType};
+#[cfg(feature = "crypto")]
+use crate::encrypt::{decrypt, DEFAULT_KEY};
+use crate::error::{PngmeError, Result};
+use std::borrow::Cow;
+#[cfg(feature = "crypto")]
+use std::env;
+use std::io::Write;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::{fmt, fs, io, str};
+
+pub(crate) const MAXIMUM_LENGTH: u32 = (1 << 31) - 1;
+
+/// The length of chunk data `len` bytes long.  `Err` if a PNG chunk can't
+/// hold that many.
+fn checked_length(len: usize) -> Result<u32> {
+    match u32::try_from(len) {
+        Ok(length) if length <= MAXIMUM_LENGTH => Ok(length),
+        _ => Err(PngmeError::ChunkTooLong { length: len as u64 }),
+    }
+}
 
-const MAXIMUM_LENGTH: u32 = (1 << 31) - 1;
+/// CRC of a chunk's type and data, as stored after them.
+pub(crate) fn checksum(type_bytes: [u8; 4], chunk_data: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&type_bytes);
+    hasher.update(chunk_data);
+    hasher.finalize()
+}
 
-/// PNG chunk data.
+/// PNG chunk data.  Chunks are equal when their bytes are.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Chunk {
     /// Length of this chunk data in bytes.
     length: u32,
@@ -19,11 +43,19 @@ pub struct Chunk {
 }
 
 impl Chunk {
-    /// Construct a chunk with the given type and data.
-    #[allow(dead_code)]
-    pub(crate) fn new(chunk_type: ChunkType, chunk_data: Vec<u8>) -> Chunk {
+    /// Construct a chunk with the given type and data.  `Err` if the data
+    /// is longer than a PNG chunk may hold.
+    pub fn new(chunk_type: ChunkType, chunk_data: Vec<u8>) -> Result<Chunk> {
+        checked_length(chunk_data.len())?;
+        Ok(Chunk::new_unchecked(chunk_type, chunk_data))
+    }
+
+    /// Construct a chunk with the given type and data, which the caller
+    /// knows is no longer than 2^31 - 1 bytes.
+    pub fn new_unchecked(chunk_type: ChunkType, chunk_data: Vec<u8>) -> Chunk {
+        debug_assert!(chunk_data.len() <= MAXIMUM_LENGTH as usize);
         let length: u32 = chunk_data.len() as u32;
-        let crc = crc::crc32::checksum_ieee(&[&chunk_type.bytes(), chunk_data.as_slice()].concat());
+        let crc = checksum(chunk_type.bytes(), &chunk_data);
         Chunk {
             length,
             chunk_type,
@@ -32,6 +64,25 @@ impl Chunk {
         }
     }
 
+    /// Construct a chunk read with the given crc, as stored after its data.
+    /// `Err` if it isn't the crc of the type and data.
+    pub(crate) fn with_crc(chunk_type: ChunkType, chunk_data: Vec<u8>, crc: u32) -> Result<Chunk> {
+        let expected = checksum(chunk_type.bytes(), &chunk_data);
+        if expected != crc {
+            return Err(PngmeError::CrcMismatch {
+                expected,
+                found: crc,
+                offset: 0,
+            });
+        }
+        Ok(Chunk {
+            length: checked_length(chunk_data.len())?,
+            chunk_type,
+            chunk_data,
+            crc,
+        })
+    }
+
     /// Length of chunk data.
     pub fn length(&self) -> u32 {
         self.length
@@ -48,99 +99,386 @@ impl Chunk {
     }
 
     /// Cyclic redundancy check.
-    fn crc(&self) -> u32 {
+    pub fn crc(&self) -> u32 {
         self.crc
     }
 
+    /// Take the chunk data, dropping the rest of the chunk.
+    #[allow(dead_code)]
+    pub fn into_data(self) -> Vec<u8> {
+        self.chunk_data
+    }
+
+    /// Replace the chunk data, updating the length and crc.  `Err`, leaving
+    /// the chunk as it was, if the data is longer than a PNG chunk may hold.
+    #[allow(dead_code)]
+    pub fn set_data(&mut self, chunk_data: Vec<u8>) -> Result<()> {
+        *self = Chunk::new(self.chunk_type, chunk_data)?;
+        Ok(())
+    }
+
+    /// Edit the chunk data in place.  Its length can't change, and the crc
+    /// is recomputed once the returned guard is dropped.
+    #[allow(dead_code)]
+    pub fn data_mut(&mut self) -> DataMut<'_> {
+        DataMut { chunk: self }
+    }
+
     /// Chunk data as string.  `Err` if failed to decode.
-    pub(crate) fn data_as_string(&self) -> Result<String> {
-        Ok(String::from_utf8(self.chunk_data.clone())?)
+    #[allow(dead_code)]
+    pub fn data_as_string(&self) -> Result<String> {
+        ChunkRef::from(self).data_as_string()
+    }
+
+    /// Chunk data as borrowed string.  `Err` if failed to decode.
+    pub fn data_as_str(&self) -> Result<&str> {
+        ChunkRef::from(self).data_as_str()
+    }
+
+    /// Chunk data as string, with invalid UTF-8 replaced by `U+FFFD`.
+    #[allow(dead_code)]
+    pub fn data_as_string_lossy(&self) -> Cow<'_, str> {
+        ChunkRef::from(self).data_as_string_lossy()
     }
 
     /// All chunk content as bytes.
     pub fn as_bytes(&self) -> Vec<u8> {
-        self.length()
-            .to_be_bytes()
-            .iter()
-            .chain(self.chunk_type().bytes().iter())
-            .chain(self.data().iter())
-            .chain(self.crc().to_be_bytes().iter())
-            .copied()
-            .collect::<Vec<u8>>()
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        self.write_bytes(&mut bytes)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    /// Number of bytes the chunk takes up: its length, type, data and crc.
+    pub fn encoded_len(&self) -> usize {
+        4 + 4 + self.chunk_data.len() + 4
+    }
+
+    /// Write all chunk content: its length, type, data and crc.
+    pub fn write_bytes(&self, writer: &mut impl Write) -> Result<()> {
+        writer.write_all(&self.length.to_be_bytes())?;
+        writer.write_all(&self.chunk_type.bytes())?;
+        writer.write_all(&self.chunk_data)?;
+        writer.write_all(&self.crc.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Construct a chunk with the given type and the contents of the file at
+    /// `path` as its data.
+    pub fn from_file(chunk_type: ChunkType, path: impl AsRef<Path>) -> Result<Chunk> {
+        Chunk::new(chunk_type, fs::read(path)?)
+    }
+
+    /// Write the data of this chunk, without its length, type or crc.
+    pub fn write_data_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(&self.chunk_data)?;
+        Ok(writer.flush()?)
+    }
+}
+
+/// Mutable view of a chunk's data, from [`Chunk::data_mut`].  Dropping it
+/// recomputes the chunk's crc.
+pub struct DataMut<'a> {
+    chunk: &'a mut Chunk,
+}
+
+impl Deref for DataMut<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.chunk.chunk_data
+    }
+}
+
+impl DerefMut for DataMut<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.chunk.chunk_data
+    }
+}
+
+impl Drop for DataMut<'_> {
+    fn drop(&mut self) {
+        self.chunk.crc = checksum(self.chunk.chunk_type.bytes(), &self.chunk.chunk_data);
+    }
+}
+
+/// Characters of chunk data shown by Display before it is cut short.
+const DISPLAY_LIMIT: usize = 256;
+
+/// Bytes of chunk data shown as hex by the alternate Display.
+const HEX_PREVIEW: usize = 16;
+
+/// Is this a character that changes how a terminal shows what follows:
+/// control characters, including escape, and bidirectional overrides.
+fn is_unsafe(c: char) -> bool {
+    c.is_control()
+        || matches!(c, '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+/// `text` made safe to print in full, with unsafe characters escaped.
+pub(crate) fn escape(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(is_unsafe) {
+        return Cow::Borrowed(text);
     }
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if is_unsafe(c) {
+            escaped.extend(c.escape_default());
+        } else {
+            escaped.push(c);
+        }
+    }
+    Cow::Owned(escaped)
 }
 
+/// `text` made safe to print: unsafe characters escaped, and anything past
+/// `DISPLAY_LIMIT` characters left out with a note of the full `length`.
+fn write_escaped(f: &mut fmt::Formatter<'_>, text: &str, length: usize) -> fmt::Result {
+    match text.char_indices().nth(DISPLAY_LIMIT) {
+        Some((end, _)) => write!(f, "{}... ({} bytes)", escape(&text[..end]), length),
+        None => write!(f, "{}", escape(text)),
+    }
+}
+
+/// Chunk as `type<TAB>data`, with the data escaped and cut short to be safe
+/// to print.  Data that isn't text shows as `[data]`.  The alternate form,
+/// `{:#}`, shows a hex preview of the data and its length instead.
 impl fmt::Display for Chunk {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}\t{}",
-            self.chunk_type(),
-            if env::var("ENCRYPT").is_err() {
-                self.data_as_string()
-                    .unwrap_or_else(|_| "[data]".to_string())
+        if f.alternate() {
+            write!(f, "{}\t", self.chunk_type())?;
+            for (i, byte) in self.data().iter().take(HEX_PREVIEW).enumerate() {
+                write!(f, "{}{:02x}", if i == 0 { "" } else { " " }, byte)?;
+            }
+            let more = if self.data().len() > HEX_PREVIEW {
+                " ..."
             } else {
-                decrypt(
-                    self.data_as_string()
-                        .unwrap_or_else(|_| "[data]".to_string()),
-                )
-            },
-        )
+                ""
+            };
+            return write!(f, "{} ({} bytes)", more, self.data().len());
+        }
+        let data = self.data_as_str().unwrap_or("[data]");
+        write!(f, "{}\t", self.chunk_type())?;
+        #[cfg(feature = "crypto")]
+        if env::var("ENCRYPT").is_ok() {
+            if let Ok(decrypted) = decrypt(data.to_string(), DEFAULT_KEY) {
+                return write_escaped(f, &decrypted, decrypted.len());
+            }
+        }
+        write_escaped(f, data, self.data().len())
     }
 }
 
 impl TryFrom<&[u8]> for Chunk {
-    type Error = Error;
+    type Error = PngmeError;
 
     fn try_from(bytes: &[u8]) -> Result<Self> {
-        let mut reader = BufReader::new(bytes);
-        let mut buffer: [u8; 4] = Default::default();
+        ChunkRef::try_from(bytes).map(Chunk::from)
+    }
+}
+
+/// PNG chunk borrowed from the buffer it was parsed from, for reading chunks
+/// without copying their data.
+#[derive(Clone, Copy)]
+pub struct ChunkRef<'a> {
+    /// Chunk type.
+    chunk_type: ChunkType,
+    /// Chunk data bytes, within the parsed buffer.
+    chunk_data: &'a [u8],
+    /// Cyclic redundancy check.
+    crc: u32,
+}
+
+impl<'a> ChunkRef<'a> {
+    /// Length of chunk data.
+    pub fn length(&self) -> u32 {
+        self.chunk_data.len() as u32
+    }
+
+    /// Chunk type.
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    /// Chunk data.
+    pub fn data(&self) -> &'a [u8] {
+        self.chunk_data
+    }
 
+    /// Chunk data as string.  `Err` if failed to decode.
+    #[allow(dead_code)]
+    pub fn data_as_string(&self) -> Result<String> {
+        self.data_as_str().map(str::to_owned)
+    }
+
+    /// Chunk data as borrowed string.  `Err` if failed to decode.
+    pub fn data_as_str(&self) -> Result<&'a str> {
+        Ok(str::from_utf8(self.chunk_data)?)
+    }
+
+    /// Chunk data as string, with invalid UTF-8 replaced by `U+FFFD`.
+    #[allow(dead_code)]
+    pub fn data_as_string_lossy(&self) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.chunk_data)
+    }
+
+    /// Number of bytes this chunk takes up in its buffer: length, type,
+    /// data and crc.
+    pub fn encoded_len(&self) -> usize {
+        4 + 4 + self.chunk_data.len() + 4
+    }
+}
+
+/// The next `n` bytes of `bytes`, advancing past them.
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    let (taken, rest) = bytes
+        .split_at_checked(n)
+        .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    *bytes = rest;
+    Ok(taken)
+}
+
+/// Big-endian u32 at the start of `bytes`, advancing past it.
+fn take_u32(bytes: &mut &[u8]) -> Result<u32> {
+    let mut buffer: [u8; 4] = Default::default();
+    buffer.copy_from_slice(take(bytes, 4)?);
+    Ok(u32::from_be_bytes(buffer))
+}
+
+impl<'a> TryFrom<&'a [u8]> for ChunkRef<'a> {
+    type Error = PngmeError;
+
+    /// Parse the chunk at the start of `bytes`, ignoring anything after it.
+    fn try_from(bytes: &'a [u8]) -> Result<Self> {
+        RawChunkRef::parse(bytes).and_then(ChunkRef::try_from)
+    }
+}
+
+impl<'a> TryFrom<RawChunkRef<'a>> for ChunkRef<'a> {
+    type Error = PngmeError;
+
+    /// The raw chunk, if its type is four ASCII letters.
+    fn try_from(raw: RawChunkRef<'a>) -> Result<Self> {
+        Ok(ChunkRef {
+            chunk_type: ChunkType::try_from(raw.raw_type)?,
+            chunk_data: raw.chunk_data,
+            crc: raw.crc,
+        })
+    }
+}
+
+/// PNG chunk borrowed like a [`ChunkRef`], but with a type of any four
+/// bytes rather than only the letters a [`ChunkType`] holds.
+#[derive(Clone, Copy)]
+pub struct RawChunkRef<'a> {
+    /// Chunk type, as it is.
+    raw_type: RawChunkType,
+    /// Chunk data bytes, within the parsed buffer.
+    chunk_data: &'a [u8],
+    /// Cyclic redundancy check.
+    crc: u32,
+}
+
+impl<'a> RawChunkRef<'a> {
+    /// Parse the chunk at the start of `bytes`, whatever its type, ignoring
+    /// anything after it.
+    pub(crate) fn parse(mut bytes: &'a [u8]) -> Result<Self> {
         // Read length bytes
-        reader.read_exact(&mut buffer)?;
-        let length = u32::from_be_bytes(buffer);
+        let length = take_u32(&mut bytes)?;
 
         if length > MAXIMUM_LENGTH {
-            return Err(format!("Length is too long ({} > 2^31 - 1)", length))?;
+            return Err(PngmeError::ChunkTooLong {
+                length: length.into(),
+            });
         }
 
         // Read chunk type bytes
-        reader.read_exact(&mut buffer)?;
-        let chunk_type = ChunkType::try_from(buffer)?;
+        let raw_type = RawChunkType::new(take_u32(&mut bytes)?.to_be_bytes());
 
         // Read chunk data bytes
-        let mut chunk_data = vec![0; usize::try_from(length)?];
-        reader.read_exact(&mut chunk_data)?;
-
-        if chunk_data.len() != length.try_into()? {
-            return Err(format!(
-                "Data (len {}) is the wrong length (expected {})",
-                chunk_data.len(),
-                length
-            ))?;
-        }
+        let chunk_data = take(&mut bytes, length as usize)?;
 
         // Read crc
-        let mut crc_buffer: [u8; 4] = Default::default();
-        reader.read_exact(&mut crc_buffer)?;
-        let crc = u32::from_be_bytes(crc_buffer);
+        let crc = take_u32(&mut bytes)?;
 
-        let expected_crc =
-            crc::crc32::checksum_ieee(&[&chunk_type.bytes(), chunk_data.as_slice()].concat());
+        let expected_crc = checksum(raw_type.bytes(), chunk_data);
         if expected_crc != crc {
-            return Err(format!(
-                "Invalid checksum {}, expected {}",
-                crc, expected_crc,
-            ))?;
+            return Err(PngmeError::CrcMismatch {
+                expected: expected_crc,
+                found: crc,
+                offset: 0,
+            });
         }
 
-        Ok(Chunk {
-            length,
-            chunk_type,
+        Ok(RawChunkRef {
+            raw_type,
             chunk_data,
             crc,
         })
     }
+
+    /// Chunk type, letters or not.
+    pub fn raw_type(&self) -> RawChunkType {
+        self.raw_type
+    }
+
+    /// Chunk data.
+    pub fn data(&self) -> &'a [u8] {
+        self.chunk_data
+    }
+
+    /// Number of bytes this chunk takes up in its buffer: length, type,
+    /// data and crc.
+    pub fn encoded_len(&self) -> usize {
+        4 + 4 + self.chunk_data.len() + 4
+    }
+
+    /// Write all chunk content, as it was parsed: its length, type, data
+    /// and crc.
+    pub fn write_bytes(&self, writer: &mut impl Write) -> Result<()> {
+        writer.write_all(&(self.chunk_data.len() as u32).to_be_bytes())?;
+        writer.write_all(&self.raw_type.bytes())?;
+        writer.write_all(self.chunk_data)?;
+        writer.write_all(&self.crc.to_be_bytes())?;
+        Ok(())
+    }
+}
+
+impl<'a> From<&'a Chunk> for ChunkRef<'a> {
+    fn from(chunk: &'a Chunk) -> Self {
+        ChunkRef {
+            chunk_type: chunk.chunk_type,
+            chunk_data: &chunk.chunk_data,
+            crc: chunk.crc,
+        }
+    }
+}
+
+impl<'a> From<ChunkRef<'a>> for RawChunkRef<'a> {
+    fn from(chunk: ChunkRef<'a>) -> Self {
+        RawChunkRef {
+            raw_type: chunk.chunk_type.into(),
+            chunk_data: chunk.chunk_data,
+            crc: chunk.crc,
+        }
+    }
+}
+
+impl<'a> From<&'a Chunk> for RawChunkRef<'a> {
+    fn from(chunk: &'a Chunk) -> Self {
+        ChunkRef::from(chunk).into()
+    }
+}
+
+impl From<ChunkRef<'_>> for Chunk {
+    fn from(chunk: ChunkRef<'_>) -> Self {
+        Chunk {
+            length: chunk.length(),
+            chunk_type: chunk.chunk_type,
+            chunk_data: chunk.chunk_data.to_vec(),
+            crc: chunk.crc,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -239,6 +577,125 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_chunk_str() {
+        let chunk = testing_chunk();
+        assert_eq!(
+            chunk.data_as_str().unwrap(),
+            "This is where your secret message will be!"
+        );
+        assert!(matches!(chunk.data_as_string_lossy(), Cow::Borrowed(_)));
+
+        let chunk_type = ChunkType::try_from(*b"RuSt").unwrap();
+        let binary = Chunk::new(chunk_type, vec![b'h', b'i', 0xff]).unwrap();
+        assert!(matches!(
+            binary.data_as_str(),
+            Err(PngmeError::InvalidUtf8(_))
+        ));
+        assert_eq!(binary.data_as_string_lossy(), "hi\u{fffd}");
+        assert_eq!(binary.to_string(), "RuSt\t[data]");
+    }
+
+    #[test]
+    fn test_chunk_display() {
+        let chunk_type = ChunkType::try_from(*b"RuSt").unwrap();
+        let hostile = Chunk::new(chunk_type, b"\x1b[2Jhi\n\xe2\x80\xaeok".to_vec()).unwrap();
+        assert_eq!(hostile.to_string(), "RuSt\t\\u{1b}[2Jhi\\n\\u{202e}ok");
+
+        let long = Chunk::new(chunk_type, vec![b'a'; 300]).unwrap();
+        let shown = long.to_string();
+        assert!(shown.ends_with("a... (300 bytes)"));
+        assert_eq!(shown.len(), "RuSt\t".len() + 256 + "... (300 bytes)".len());
+
+        let binary = Chunk::new(chunk_type, vec![b'h', b'i', 0xff]).unwrap();
+        assert_eq!(format!("{:#}", binary), "RuSt\t68 69 ff (3 bytes)");
+        assert_eq!(
+            format!("{:#}", long),
+            format!("RuSt\t{} ... (300 bytes)", ["61"; 16].join(" "))
+        );
+    }
+
+    #[test]
+    fn test_chunk_eq() {
+        use std::collections::HashSet;
+
+        let chunk = testing_chunk();
+        let copy = Chunk::try_from(chunk.as_bytes().as_slice()).unwrap();
+        assert_eq!(chunk.clone(), copy);
+        let other = Chunk::new(*chunk.chunk_type(), b"other".to_vec()).unwrap();
+        assert_ne!(chunk, other);
+        assert_eq!(HashSet::from([chunk, copy, other]).len(), 2);
+    }
+
+    #[test]
+    fn test_chunk_mutation() {
+        let mut chunk = testing_chunk();
+        chunk.set_data(b"new message".to_vec()).unwrap();
+        assert_eq!(chunk.length(), 11);
+        let expected = Chunk::new(*chunk.chunk_type(), b"new message".to_vec()).unwrap();
+        assert_eq!(chunk, expected);
+
+        chunk.data_mut()[..3].copy_from_slice(b"old");
+        assert_eq!(chunk.data(), b"old message");
+        let expected = Chunk::new(*chunk.chunk_type(), b"old message".to_vec()).unwrap();
+        assert_eq!(chunk.crc(), expected.crc());
+        assert!(Chunk::try_from(chunk.as_bytes().as_slice()).is_ok());
+
+        assert_eq!(chunk.into_data(), b"old message");
+    }
+
+    #[test]
+    fn test_chunk_file() {
+        let chunk = testing_chunk();
+        let mut data = Vec::new();
+        chunk.write_data_to(&mut data).unwrap();
+        assert_eq!(data, chunk.data());
+
+        let path = std::env::temp_dir().join(format!("pngme-chunk-{}.bin", std::process::id()));
+        fs::write(&path, &data).unwrap();
+        let read = Chunk::from_file(*chunk.chunk_type(), &path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.unwrap(), chunk);
+    }
+
+    #[test]
+    fn test_new_too_long() {
+        let chunk_type = ChunkType::try_from(*b"RuSt").unwrap();
+        let max = MAXIMUM_LENGTH as usize;
+        assert_eq!(checked_length(0).unwrap(), 0);
+        assert_eq!(checked_length(max).unwrap(), MAXIMUM_LENGTH);
+        assert!(matches!(
+            checked_length(max + 1),
+            Err(PngmeError::ChunkTooLong { length }) if length == 1 << 31
+        ));
+        #[cfg(target_pointer_width = "64")]
+        assert!(matches!(
+            checked_length(1 << 32),
+            Err(PngmeError::ChunkTooLong { length }) if length == 1 << 32
+        ));
+
+        let chunk = Chunk::new_unchecked(chunk_type, b"hi".to_vec());
+        assert_eq!(
+            chunk.as_bytes(),
+            Chunk::new(chunk_type, b"hi".to_vec()).unwrap().as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_chunk_ref() {
+        let bytes = testing_chunk().as_bytes();
+        let padded: Vec<u8> = bytes.iter().copied().chain([1, 2, 3]).collect();
+
+        let chunk = ChunkRef::try_from(padded.as_slice()).unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "RuSt");
+        assert_eq!(chunk.length(), 42);
+        assert_eq!(chunk.encoded_len(), bytes.len());
+        assert!(std::ptr::eq(chunk.data(), &padded[8..50]));
+        assert_eq!(Chunk::from(chunk).as_bytes(), bytes);
+
+        assert!(ChunkRef::try_from(&bytes[..bytes.len() - 1]).is_err());
+    }
+
     #[test]
     pub(crate) fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -1,17 +1,25 @@
-use crate::{Error, Result};
+use crate::error::{PngmeError, Result};
 use std::{fmt, str};
 
 /// 4-byte PNG chunk type code.
 /// See section 3.2 [The PNG spec](http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html)
 /// Type codes are restricted to consist of uppercase and lowercase ASCII letters
-/// (A-Z and a-z, or 65-90 and 97-122 decimal)
-#[derive(Debug, Eq, PartialEq)]
+/// (A-Z and a-z, or 65-90 and 97-122 decimal), which every way of making
+/// one holds to; four bytes of any other kind are a [`RawChunkType`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct ChunkType {
     bytes: [u8; 4],
 }
 
 #[allow(dead_code)]
 impl ChunkType {
+    /// Chunk type with the given bytes, which the caller knows are ASCII
+    /// letters.  Meant for constants; use `TryFrom` or `FromStr` for
+    /// anything else.
+    pub(crate) const fn new_unchecked(bytes: [u8; 4]) -> ChunkType {
+        ChunkType { bytes }
+    }
+
     /// Must be ASCII letters (A-Z and a-z, or 65-90 and 97-122 decimal).
     fn is_valid_bytes(bytes: [u8; 4]) -> bool {
         bytes
@@ -21,64 +29,234 @@ impl ChunkType {
     }
 
     /// All bytes in this chunk type.
-    pub fn bytes(&self) -> [u8; 4] {
+    pub const fn bytes(&self) -> [u8; 4] {
         self.bytes
     }
 
     /// Is this chunk type valid.
-    fn is_valid(&self) -> bool {
+    pub const fn is_valid(&self) -> bool {
         self.is_reserved_bit_valid()
     }
 
     /// This chunk type is critical if ancillary bit is 0.
     /// Ancillary bit is the 5th bit of first byte.
-    fn is_critical(&self) -> bool {
+    pub const fn is_critical(&self) -> bool {
         self.bytes[0] & (0b1 << 5) == 0
     }
 
     /// This chunk is public if the private bit is 0.
     /// Private bit is the 5th bit of second byte.
-    fn is_public(&self) -> bool {
+    pub const fn is_public(&self) -> bool {
         self.bytes[1] & (0b1 << 5) == 0
     }
 
     /// The reserved bit is the 5th bit of third byte.
-    fn is_reserved_bit_valid(&self) -> bool {
+    pub const fn is_reserved_bit_valid(&self) -> bool {
         self.bytes[2] & (0b1 << 5) == 0
     }
 
     /// This chunk is safe to copy if its copy bit is 1.
     /// Copy bit is the 5th bit of fourth byte.
-    fn is_safe_to_copy(&self) -> bool {
+    pub const fn is_safe_to_copy(&self) -> bool {
         self.bytes[3] & (0b1 << 5) != 0
     }
 }
 
+/// Chunk types defined by the PNG specification, including the APNG and
+/// HDR additions of its third edition.
+#[allow(dead_code)]
+impl ChunkType {
+    /// `IHDR`: image header.
+    pub const IHDR: ChunkType = ChunkType::new_unchecked(*b"IHDR");
+    /// `PLTE`: palette.
+    pub const PLTE: ChunkType = ChunkType::new_unchecked(*b"PLTE");
+    /// `IDAT`: image data.
+    pub const IDAT: ChunkType = ChunkType::new_unchecked(*b"IDAT");
+    /// `IEND`: image trailer.
+    pub const IEND: ChunkType = ChunkType::new_unchecked(*b"IEND");
+    /// `tRNS`: transparency.
+    pub const TRNS: ChunkType = ChunkType::new_unchecked(*b"tRNS");
+    /// `cHRM`: primary chromaticities and white point.
+    pub const CHRM: ChunkType = ChunkType::new_unchecked(*b"cHRM");
+    /// `gAMA`: image gamma.
+    pub const GAMA: ChunkType = ChunkType::new_unchecked(*b"gAMA");
+    /// `iCCP`: embedded ICC profile.
+    pub const ICCP: ChunkType = ChunkType::new_unchecked(*b"iCCP");
+    /// `sBIT`: significant bits.
+    pub const SBIT: ChunkType = ChunkType::new_unchecked(*b"sBIT");
+    /// `sRGB`: standard RGB colour space.
+    pub const SRGB: ChunkType = ChunkType::new_unchecked(*b"sRGB");
+    /// `cICP`: coding-independent code points.
+    pub const CICP: ChunkType = ChunkType::new_unchecked(*b"cICP");
+    /// `mDCV`: mastering display colour volume.
+    pub const MDCV: ChunkType = ChunkType::new_unchecked(*b"mDCV");
+    /// `cLLI`: content light level information.
+    pub const CLLI: ChunkType = ChunkType::new_unchecked(*b"cLLI");
+    /// `tEXt`: textual data.
+    pub const TEXT: ChunkType = ChunkType::new_unchecked(*b"tEXt");
+    /// `zTXt`: compressed textual data.
+    pub const ZTXT: ChunkType = ChunkType::new_unchecked(*b"zTXt");
+    /// `iTXt`: international textual data.
+    pub const ITXT: ChunkType = ChunkType::new_unchecked(*b"iTXt");
+    /// `bKGD`: background colour.
+    pub const BKGD: ChunkType = ChunkType::new_unchecked(*b"bKGD");
+    /// `hIST`: image histogram.
+    pub const HIST: ChunkType = ChunkType::new_unchecked(*b"hIST");
+    /// `pHYs`: physical pixel dimensions.
+    pub const PHYS: ChunkType = ChunkType::new_unchecked(*b"pHYs");
+    /// `sPLT`: suggested palette.
+    pub const SPLT: ChunkType = ChunkType::new_unchecked(*b"sPLT");
+    /// `eXIf`: Exif metadata.
+    pub const EXIF: ChunkType = ChunkType::new_unchecked(*b"eXIf");
+    /// `tIME`: image last-modification time.
+    pub const TIME: ChunkType = ChunkType::new_unchecked(*b"tIME");
+    /// `acTL`: animation control.
+    pub const ACTL: ChunkType = ChunkType::new_unchecked(*b"acTL");
+    /// `fcTL`: frame control.
+    pub const FCTL: ChunkType = ChunkType::new_unchecked(*b"fcTL");
+    /// `fdAT`: frame data.
+    pub const FDAT: ChunkType = ChunkType::new_unchecked(*b"fdAT");
+
+    /// Is this one of the chunk types defined by the PNG specification.
+    pub fn is_standard(&self) -> bool {
+        self.description().is_some()
+    }
+
+    /// What a standard chunk of this type holds, like "image header" for
+    /// `IHDR`.  `None` for any other type.
+    pub fn description(&self) -> Option<&'static str> {
+        STANDARD
+            .iter()
+            .find(|(chunk_type, _)| chunk_type == self)
+            .map(|(_, description)| *description)
+    }
+}
+
+/// Every standard chunk type with its description.
+const STANDARD: [(ChunkType, &str); 25] = [
+    (ChunkType::IHDR, "image header"),
+    (ChunkType::PLTE, "palette"),
+    (ChunkType::IDAT, "image data"),
+    (ChunkType::IEND, "image trailer"),
+    (ChunkType::TRNS, "transparency"),
+    (ChunkType::CHRM, "primary chromaticities and white point"),
+    (ChunkType::GAMA, "image gamma"),
+    (ChunkType::ICCP, "embedded ICC profile"),
+    (ChunkType::SBIT, "significant bits"),
+    (ChunkType::SRGB, "standard RGB colour space"),
+    (ChunkType::CICP, "coding-independent code points"),
+    (ChunkType::MDCV, "mastering display colour volume"),
+    (ChunkType::CLLI, "content light level information"),
+    (ChunkType::TEXT, "textual data"),
+    (ChunkType::ZTXT, "compressed textual data"),
+    (ChunkType::ITXT, "international textual data"),
+    (ChunkType::BKGD, "background colour"),
+    (ChunkType::HIST, "image histogram"),
+    (ChunkType::PHYS, "physical pixel dimensions"),
+    (ChunkType::SPLT, "suggested palette"),
+    (ChunkType::EXIF, "Exif metadata"),
+    (ChunkType::TIME, "image last-modification time"),
+    (ChunkType::ACTL, "animation control"),
+    (ChunkType::FCTL, "frame control"),
+    (ChunkType::FDAT, "frame data"),
+];
+
 impl fmt::Display for ChunkType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", str::from_utf8(&self.bytes()).unwrap())
+        RawChunkType::from(*self).fmt(f)
+    }
+}
+
+/// Four bytes where a chunk type goes, whether or not they are the ASCII
+/// letters the spec asks for.  Some real-world files hold types with digits
+/// or other bytes; [`Png::raw_chunk_refs`](crate::png::Png::raw_chunk_refs)
+/// keeps those chunks, their type as it is, instead of failing the file.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RawChunkType {
+    bytes: [u8; 4],
+}
+
+impl RawChunkType {
+    /// Raw chunk type with the given bytes.
+    pub const fn new(bytes: [u8; 4]) -> RawChunkType {
+        RawChunkType { bytes }
+    }
+
+    /// All bytes in this chunk type.
+    pub const fn bytes(&self) -> [u8; 4] {
+        self.bytes
+    }
+
+    /// Are the bytes ASCII letters, as the spec asks.
+    pub fn is_conforming(&self) -> bool {
+        ChunkType::is_valid_bytes(self.bytes)
+    }
+}
+
+impl From<ChunkType> for RawChunkType {
+    fn from(chunk_type: ChunkType) -> Self {
+        RawChunkType::new(chunk_type.bytes())
+    }
+}
+
+/// The bytes as ASCII, with any that aren't printable as `\xNN`.
+impl fmt::Display for RawChunkType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.bytes {
+            if b.is_ascii_graphic() {
+                write!(f, "{}", b as char)?;
+            } else {
+                write!(f, "\\x{:02x}", b)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl str::FromStr for RawChunkType {
+    type Err = PngmeError;
+
+    /// Four printable ASCII characters, like `r2St`.
+    fn from_str(str: &str) -> Result<RawChunkType> {
+        let bytes: [u8; 4] = str
+            .as_bytes()
+            .try_into()
+            .ok()
+            .filter(|bytes: &[u8; 4]| bytes.iter().all(u8::is_ascii_graphic))
+            .ok_or_else(|| PngmeError::InvalidChunkType(str.to_string()))?;
+        Ok(RawChunkType::new(bytes))
+    }
+}
+
+impl TryFrom<RawChunkType> for ChunkType {
+    type Error = PngmeError;
+
+    /// The raw type, if its bytes are letters.
+    fn try_from(raw: RawChunkType) -> Result<Self> {
+        ChunkType::try_from(raw.bytes)
     }
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
-    type Error = Error;
+    type Error = PngmeError;
 
     fn try_from(bytes: [u8; 4]) -> Result<Self> {
         if !ChunkType::is_valid_bytes(bytes) {
-            return Err("invalid bytes")?;
+            let name = String::from_utf8_lossy(&bytes).into_owned();
+            return Err(PngmeError::InvalidChunkType(name));
         }
         Ok(ChunkType { bytes })
     }
 }
 
 impl str::FromStr for ChunkType {
-    type Err = Error;
+    type Err = PngmeError;
 
     fn from_str(str: &str) -> Result<ChunkType> {
-        if str.len() != 4 {
-            return Err("expected 4 bytes")?;
-        }
-        let bytes: [u8; 4] = str.as_bytes()[..4].try_into()?;
+        let bytes: [u8; 4] = str
+            .as_bytes()
+            .try_into()
+            .map_err(|_| PngmeError::InvalidChunkType(str.to_string()))?;
         ChunkType::try_from(bytes)
     }
 }
@@ -170,10 +348,65 @@ mod tests {
     #[test]
     pub(crate) fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
-        println!("{}", chunk.to_string());
+        println!("{}", chunk);
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[test]
+    pub(crate) fn test_new_unchecked() {
+        const RUST: ChunkType = ChunkType::new_unchecked(*b"RuSt");
+        assert_eq!(RUST, ChunkType::from_str("RuSt").unwrap());
+    }
+
+    #[test]
+    pub(crate) fn test_chunk_type_ordering_and_hash() {
+        use std::collections::HashSet;
+
+        let mut types: Vec<ChunkType> = ["ruSt", "IHDR", "IEND", "RuSt"]
+            .iter()
+            .map(|s| ChunkType::from_str(s).unwrap())
+            .collect();
+        types.sort();
+        let sorted: Vec<String> = types.iter().map(ChunkType::to_string).collect();
+        assert_eq!(sorted, ["IEND", "IHDR", "RuSt", "ruSt"]);
+
+        let copy = types[0];
+        let set: HashSet<ChunkType> = types.iter().copied().chain([copy]).collect();
+        assert_eq!(set.len(), 4);
+    }
+
+    #[test]
+    pub(crate) fn test_standard_chunk_types() {
+        assert_eq!(ChunkType::IHDR, ChunkType::from_str("IHDR").unwrap());
+        assert_eq!(ChunkType::TEXT.to_string(), "tEXt");
+        assert!(ChunkType::IDAT.is_critical());
+        assert!(!ChunkType::TIME.is_critical());
+        assert!(ChunkType::ITXT.is_standard());
+        assert_eq!(ChunkType::IEND.description(), Some("image trailer"));
+
+        let private = ChunkType::from_str("ruSt").unwrap();
+        assert!(!private.is_standard());
+        assert_eq!(private.description(), None);
+    }
+
+    #[test]
+    pub(crate) fn test_raw_chunk_type() {
+        let raw = RawChunkType::from_str("r2St").unwrap();
+        assert!(!raw.is_conforming());
+        assert_eq!(raw.bytes(), *b"r2St");
+        assert_eq!(raw.to_string(), "r2St");
+        assert!(ChunkType::try_from(raw).is_err());
+
+        let rust = ChunkType::from_str("ruSt").unwrap();
+        assert!(RawChunkType::from(rust).is_conforming());
+        assert_eq!(ChunkType::try_from(RawChunkType::from(rust)).unwrap(), rust);
+
+        let raw = RawChunkType::new([b'a', 0, b' ', 0xff]);
+        assert_eq!(raw.to_string(), "a\\x00\\x20\\xff");
+        assert!(RawChunkType::from_str("a b ").is_err());
+        assert!(RawChunkType::from_str("long!").is_err());
+    }
+
     #[test]
     pub(crate) fn test_chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
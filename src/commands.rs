@@ -1,125 +1,3328 @@
 use crate::args::*;
-use crate::chunk::Chunk;
+use crate::audit::{self, AuditEntry};
+use crate::cache::{Cache, Meta};
+use crate::chunk::{escape, Chunk, ChunkRef, RawChunkRef, MAXIMUM_LENGTH};
+use crate::chunk_type::{ChunkType, RawChunkType};
+use crate::config::Config;
+use crate::detect;
+use crate::encrypt::{decrypt, encrypt_bytes, DEFAULT_KEY};
+use crate::envelope::Envelope;
+use crate::error::PngmeError;
+use crate::exif;
+use crate::index::PngIndex;
+use crate::info::{self, Tag};
+use crate::journal::{self, JournalEntry};
+use crate::layout::Layout;
+use crate::observer::Observer;
+use crate::plan::{self, Operation, Plan};
 use crate::png::Png;
-use crate::encrypt::encrypt;
-use std::fs;
-use std::{convert::TryFrom, env};
+use crate::seal;
+use crate::strip::{self, Preset};
+use crate::suggest;
+use crate::text::{self, InternationalText};
+use crate::watermark::Watermark;
+use crate::webdav;
+use crate::{azure, data_uri, ftp, gcs, grpc, ipfs, qr, remote, s3, server, sftp};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use indicatif::{ProgressBar, ProgressStyle};
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+use regex::Regex;
+use reqwest::{Method, StatusCode, Url};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::{iter, slice, thread};
+use tempfile::SpooledTempFile;
+use tracing::{debug, error, info, warn, Level};
+use walkdir::WalkDir;
 
-fn encode(args: EncodeArgs) -> crate::Result<()> {
-    let msg = if env::var("ENCRYPT").is_err() {
-        args.message
+/// Path standing in for stdin (as input) or stdout (as output).
+const STDIO_PATH: &str = "-";
+
+/// Is the given path the stdin/stdout placeholder.
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new(STDIO_PATH)
+}
+
+/// Open the given path for reading, or stdin if the path is `-`.
+fn open_input(path: &Path) -> crate::Result<Box<dyn Read>> {
+    if is_stdio(path) {
+        Ok(Box::new(io::stdin()))
+    } else {
+        Ok(Box::new(File::open(path)?))
+    }
+}
+
+/// Open the given path for writing, or stdout if the path is `-`.
+fn open_output(path: &Path) -> crate::Result<Box<dyn Write>> {
+    if is_stdio(path) {
+        Ok(Box::new(io::stdout()))
+    } else {
+        Ok(Box::new(File::create(path)?))
+    }
+}
+
+/// Stream for status messages.  Stderr when the PNG itself goes to stdout.
+fn status_output(path: &Path) -> Box<dyn Write> {
+    if is_stdio(path) {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    }
+}
+
+/// Shared progress reporting for long operations.  Bars draw on stderr, and
+/// only when it is a terminal, so piped output is left alone.
+#[derive(Clone, Copy)]
+struct Progress {
+    enabled: bool,
+}
+
+impl Progress {
+    fn new(enabled: bool) -> Self {
+        Progress { enabled }
+    }
+
+    /// Bar with the given length and template, hidden if disabled.
+    fn bar(&self, len: u64, template: &str) -> ProgressBar {
+        if !self.enabled {
+            return ProgressBar::hidden();
+        }
+        let style =
+            ProgressStyle::with_template(template).unwrap_or_else(|_| ProgressStyle::default_bar());
+        ProgressBar::new(len).with_style(style)
+    }
+
+    /// Bar counting files in a batch.
+    fn files(&self, len: usize) -> ProgressBar {
+        self.bar(len as u64, "{bar:40} {pos}/{len} files {wide_msg}")
+    }
+
+    /// Bar counting downloaded bytes, or a spinner if the size is unknown.
+    fn download(&self, len: Option<u64>) -> ProgressBar {
+        match len {
+            Some(len) => self.bar(len, "{bar:40} {bytes}/{total_bytes} {bytes_per_sec}"),
+            None if self.enabled => ProgressBar::new_spinner(),
+            None => ProgressBar::hidden(),
+        }
+    }
+}
+
+/// Settings shared by every subcommand.
+#[derive(Clone)]
+struct Context {
+    config: Config,
+    config_path: Option<PathBuf>,
+    profile: Option<String>,
+    /// Encryption key, if encryption is on.
+    key: Option<String>,
+    progress: Progress,
+    /// Lock PNGs while changing them.
+    lock: bool,
+    /// Make the output depend only on the inputs.
+    deterministic: bool,
+    /// Let `print` and `remove` read chunk types that aren't letters.
+    relaxed: bool,
+    /// Set to stop a batch before its next file.
+    cancel: &'static AtomicBool,
+    /// Most data a chunk read may hold.
+    max_chunk_length: u32,
+}
+
+impl Context {
+    /// Message bytes to embed, encrypted if encryption is on.
+    fn message_bytes(&self, message: Vec<u8>) -> Vec<u8> {
+        match &self.key {
+            Some(key) => encrypt_bytes(&message, key).into_bytes(),
+            None => message,
+        }
+    }
+
+    /// Chunk data as text, decrypted if encryption is on.  For iTXt, just
+    /// the text, and for an envelope, just the message.
+    fn text<'a>(&self, chunk: impl Into<ChunkRef<'a>>) -> String {
+        let stored = stored_text(chunk.into());
+        let data = stored.as_deref().unwrap_or("[data]");
+        let envelope = Envelope::parse(data.as_bytes());
+        let data = envelope.as_ref().map_or(data, |e| &e.message);
+        match &self.key {
+            Some(key) => decrypt(data.to_string(), key).unwrap_or_else(|_| data.to_string()),
+            None => data.to_string(),
+        }
+    }
+
+    /// Message bytes of the chunk: its text, as `text` gives it, or for
+    /// binary data, the data itself.
+    fn payload<'a>(&self, chunk: impl Into<ChunkRef<'a>>) -> Vec<u8> {
+        let chunk = chunk.into();
+        match stored_text(chunk) {
+            Some(_) => self.text(chunk).into_bytes(),
+            None => chunk.data().to_vec(),
+        }
+    }
+
+    /// Chunk as `type<TAB>data`, decrypting the data if encryption is on,
+    /// and escaping what a terminal would act on as `Chunk`'s Display does.
+    /// Standard chunks other than text hold binary data, so they show what
+    /// they are instead.
+    fn show<'a>(&self, chunk: impl Into<ChunkRef<'a>>) -> String {
+        let chunk = chunk.into();
+        summary(chunk.chunk_type())
+            .unwrap_or_else(|| format!("{}\t{}", chunk.chunk_type(), escape(&self.text(chunk))))
+    }
+
+    /// Chunk as `show` has it, for one read with `--relaxed`.  One whose
+    /// type isn't letters shows its data as it is, never decrypted.
+    fn show_raw(&self, chunk: RawChunkRef<'_>) -> String {
+        match ChunkRef::try_from(chunk) {
+            Ok(chunk) => self.show(chunk),
+            Err(_) => {
+                let data = String::from_utf8_lossy(chunk.data());
+                format!("{}\t{}", chunk.raw_type(), escape(&data))
+            }
+        }
+    }
+}
+
+/// What a chunk stores as text: for iTXt just the text, else the data.
+/// `None` if it isn't UTF-8.
+fn stored_text(chunk: ChunkRef<'_>) -> Option<Cow<'_, str>> {
+    if chunk.chunk_type() == &ChunkType::ITXT {
+        if let Ok(itxt) = InternationalText::from_data(chunk.data()) {
+            return Some(Cow::Owned(itxt.text));
+        }
+    }
+    chunk.data_as_str().ok().map(Cow::Borrowed)
+}
+
+/// Bytes as lowercase hex.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `type<TAB>[description]` for standard chunks other than text, which
+/// `Context::show` prints without needing their data.
+fn summary(chunk_type: &ChunkType) -> Option<String> {
+    match chunk_type.description() {
+        Some(description) if *chunk_type != ChunkType::TEXT && *chunk_type != ChunkType::ITXT => {
+            Some(format!("{}\t[{}]", chunk_type, description))
+        }
+        _ => None,
+    }
+}
+
+/// Key to encrypt with: the passphrase, else the key file, else the default.
+fn encryption_key(config: &Config) -> crate::Result<String> {
+    if let Some(passphrase) = &config.passphrase {
+        return Ok(passphrase.clone());
+    }
+    if let Some(path) = &config.key_file {
+        return Ok(fs::read_to_string(path)?.trim_end().to_string());
+    }
+    Ok(DEFAULT_KEY.to_string())
+}
+
+/// Read a whole Png from the given reader, no chunk over `--max-chunk-size`.
+fn read_png(reader: &mut dyn Read, ctx: &Context) -> crate::Result<Png> {
+    let png = Png::from_reader_limited(reader, ctx.max_chunk_length, &mut ())?;
+    debug!(chunks = png.chunks().len(), "parsed PNG");
+    Ok(png)
+}
+
+/// All bytes of the given reader, for parsing chunks that borrow from them.
+fn read_bytes(reader: &mut dyn Read) -> crate::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    debug!(bytes = bytes.len(), "read PNG");
+    Ok(bytes)
+}
+
+/// Call `f` with the chunks of the Png at the given path, or of stdin for
+/// `-`, borrowed from its bytes.  No chunk may be over `--max-chunk-size`.
+fn with_chunk_refs<T>(
+    path: &Path,
+    ctx: &Context,
+    f: impl FnOnce(Vec<ChunkRef<'_>>) -> crate::Result<T>,
+) -> crate::Result<T> {
+    with_bytes(path, |bytes| {
+        f(Png::chunk_refs_limited(bytes, ctx.max_chunk_length)?)
+    })
+}
+
+/// Call `f` with the bytes of the file at the given path, or of stdin for
+/// `-`.  They are read rather than mapped: other processes may change the
+/// file meanwhile, which a mapping can't survive.
+fn with_bytes<T>(path: &Path, f: impl FnOnce(&[u8]) -> crate::Result<T>) -> crate::Result<T> {
+    f(&read_bytes(&mut open_input(path)?)?)
+}
+
+/// Write a whole Png to the given writer.
+fn write_png(writer: &mut dyn Write, png: &Png) -> crate::Result<()> {
+    let bytes = png.write_to(writer)?;
+    debug!(bytes, "wrote PNG");
+    Ok(())
+}
+
+/// Path of the backup kept for the given file.
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+/// Write a Png to the given path or stdout, all at once like
+/// `replace_png`, first backing up any file it replaces if the config asks
+/// for it, and recording how to undo it if the config asks for that.
+fn save_png(output: &Path, png: &Png, ctx: &Context) -> crate::Result<()> {
+    if !journaling(output, ctx) {
+        return replace_png(output, png, ctx);
+    }
+    // Only files that were PNGs already are journaled.
+    let before = Png::from_reader(File::open(output)?);
+    replace_png(output, png, ctx)?;
+    match before {
+        Ok(before) if before == *png => {}
+        Ok(before) => {
+            let digest = journal::digest_of(File::open(output)?)?;
+            let entry = JournalEntry::between(&before, png, digest, ctx.deterministic);
+            journal::append(output, &entry)?;
+        }
+        Err(e) => warn!(path = %output.display(), "not journaled: {}", e),
+    }
+    Ok(())
+}
+
+/// Does the config ask to journal changes to the file at the given path,
+/// and is there a file to journal.
+fn journaling(path: &Path, ctx: &Context) -> bool {
+    ctx.config.journal() && !is_stdio(path) && path.exists()
+}
+
+/// Back up the file at the given path, if there is one and the config asks
+/// for it.
+fn back_up(path: &Path, ctx: &Context) -> crate::Result<()> {
+    if ctx.config.backup() && !is_stdio(path) && path.exists() {
+        let backup = backup_path(path);
+        fs::copy(path, &backup)?;
+        debug!(path = %backup.display(), "kept backup");
+    }
+    Ok(())
+}
+
+/// Exclusive advisory lock on the file at the given path, held until the
+/// returned file is dropped, so pngme processes changing the same PNG take
+/// turns instead of the last writer winning.  `None` for stdio, for a path
+/// that doesn't exist yet, or with `--no-lock`.
+fn lock_file(path: &Path, ctx: &Context) -> crate::Result<Option<File>> {
+    if !ctx.lock || is_stdio(path) || !path.exists() {
+        return Ok(None);
+    }
+    loop {
+        let file = File::open(path)?;
+        file.lock()?;
+        // Whoever held the lock may have renamed a new file over the path
+        // (see `replace_png`), leaving this one unlinked: lock that instead.
+        if is_at(&file, path)? {
+            debug!(path = %path.display(), "locked");
+            return Ok(Some(file));
+        }
+        debug!(path = %path.display(), "replaced while waiting for the lock");
+    }
+}
+
+/// Is the open file the one at the path, not one since renamed over.
+#[cfg(unix)]
+fn is_at(file: &File, path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let (open, current) = (file.metadata()?, fs::metadata(path)?);
+    Ok(open.dev() == current.dev() && open.ino() == current.ino())
+}
+
+#[cfg(not(unix))]
+fn is_at(_file: &File, _path: &Path) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// Add the given chunk to the Png file at the given path without rewriting
+/// the rest of it, first backing it up if the config asks for it.
+fn append_in_place(path: &Path, chunk: &Chunk, ctx: &Context) -> crate::Result<()> {
+    back_up(path, ctx)?;
+    let mut file = fs::OpenOptions::new().read(true).write(true).open(path)?;
+    // Where the chunk goes, to journal it without reading any chunk data.
+    let at = if journaling(path, ctx) {
+        let index = PngIndex::new(&mut file)?;
+        Some(
+            index
+                .position(ChunkType::IEND)
+                .unwrap_or(index.entries().len()),
+        )
     } else {
-        encrypt(args.message)
+        None
     };
-    let input_bytes = fs::read(&args.input_file_path)?;
-    let output = args.output_file_path.unwrap_or(args.input_file_path);
-    let mut png = Png::try_from(input_bytes.as_slice())?;
-    let chunk = Chunk::new(args.chunk_type, msg.as_bytes().to_vec());
-    png.append_chunk(chunk);
-    fs::write(output, png.as_bytes())?;
+    Png::append_chunk_in_place(&mut file, chunk)?;
+    debug!(path = %path.display(), "appended chunk in place");
+    if let Some(at) = at {
+        file.rewind()?;
+        let digest = journal::digest_of(&mut file)?;
+        journal::append(path, &JournalEntry::inserted(at, digest, ctx.deterministic))?;
+    }
     Ok(())
 }
 
-fn encoder(args: EncodeRArgs) -> crate::Result<()> {
-    let msg = if env::var("ENCRYPT").is_err() {
-        args.message
+/// Downloads a non-HTTP url into a writer.
+type GetFn = fn(&Url, &RemoteArgs, &Config, &mut dyn Write) -> crate::Result<()>;
+
+/// Uploads PNG bytes to a non-HTTP url.
+type PutFn = fn(&Url, &RemoteArgs, &Config, Vec<u8>) -> crate::Result<()>;
+
+/// Download the Png at the given url, falling back to each `--mirror` in
+/// turn.  `Err` with the last failure if every source fails.
+fn fetch_png(url: Url, remote: &RemoteArgs, ctx: &Context) -> crate::Result<Png> {
+    let sources: Vec<Url> = iter::once(url)
+        .chain(remote.mirrors.iter().cloned())
+        .collect();
+    for (i, source) in sources.iter().enumerate() {
+        match fetch_source(source.clone(), remote, ctx) {
+            Ok(png) => {
+                if i > 0 {
+                    warn!("fetched from mirror {}", source);
+                }
+                return Ok(png);
+            }
+            Err(e) if i + 1 < sources.len() => warn!("{}: {}, trying next mirror", source, e),
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("there is always at least one source")
+}
+
+/// Download the Png at the given url.
+fn fetch_source(url: Url, remote: &RemoteArgs, ctx: &Context) -> crate::Result<Png> {
+    let get: GetFn = match url.scheme() {
+        ipfs::SCHEME => return fetch_source(ipfs::gateway_url(&url, &ctx.config)?, remote, ctx),
+        webdav::SCHEME | webdav::SECURE_SCHEME => {
+            return fetch_http(webdav::http_url(&url)?, remote, ctx)
+        }
+        data_uri::SCHEME => data_uri::get,
+        s3::SCHEME => s3::get,
+        gcs::SCHEME => gcs::get,
+        azure::SCHEME => azure::get,
+        sftp::SCHEME => sftp::get,
+        ftp::SCHEME => ftp::get,
+        _ => return fetch_http(url, remote, ctx),
+    };
+    if [s3::SCHEME, sftp::SCHEME, ftp::SCHEME].contains(&url.scheme()) {
+        // Unlike the others, these don't wait for the rate limit themselves.
+        remote::Retry::from_args(remote).throttle();
+    }
+    let mut body = SpooledTempFile::new(ctx.config.max_memory());
+    get(&url, remote, &ctx.config, &mut body)?;
+    body.rewind()?;
+    parse_download(body, remote, ctx)
+}
+
+/// Download over HTTP(S), through the cache.  A download cut short is
+/// resumed where it stopped next time, if the server takes range requests.
+fn fetch_http(url: Url, remote: &RemoteArgs, ctx: &Context) -> crate::Result<Png> {
+    let cache = if remote.no_cache {
+        None
     } else {
-        encrypt(args.message)
+        Cache::default_dir().map(Cache::new)
     };
-    let img_bytes = reqwest::blocking::get(args.url)?.bytes()?;
-    let image = image::load_from_memory(&img_bytes)?;
+    let cached = cache.as_ref().and_then(|cache| cache.lookup(&url));
+    let partial = cache.as_ref().and_then(|cache| cache.partial(&url));
+    if !remote.no_preflight && cached.is_none() {
+        remote::preflight(&url, remote, &ctx.config)?;
+    }
+    let response = remote::get(
+        &url,
+        remote,
+        &ctx.config,
+        cached.as_ref().map(|e| &e.meta),
+        partial.as_ref(),
+    )?;
+    if let (StatusCode::NOT_MODIFIED, Some(entry)) = (response.status(), &cached) {
+        debug!(path = %entry.body.display(), "using cached download");
+        return parse_download(File::open(&entry.body)?, remote, ctx);
+    }
+    let length = remote::content_length(response.headers());
+    if let (StatusCode::PARTIAL_CONTENT, Some(cache), Some(partial)) =
+        (response.status(), &cache, &partial)
+    {
+        let total = length.map(|length| partial.len + length);
+        if remote::range_start(response.headers()) != Some(partial.len) {
+            cache.discard_partial(&url);
+            Err(format!("{} resumed at the wrong offset, try again", url))?
+        }
+        if remote.max_download.is_some() && total.is_none() {
+            cache.discard_partial(&url);
+            Err(format!(
+                "{} resumed without a length to check against --max-download, try again",
+                url
+            ))?
+        }
+        remote::check_size(&url, total, remote)?;
+        info!(offset = partial.len, "resuming download of {}", url);
+        let bar = ctx.progress.download(total);
+        bar.set_position(partial.len);
+        let path = cache.resume(&url, partial, &mut bar.wrap_read(response))?;
+        bar.finish_and_clear();
+        return parse_download(File::open(path)?, remote, ctx);
+    }
+    remote::check_size(&url, length, remote)?;
+    let bar = ctx.progress.download(length);
+    // Without a length to check up front, the body is counted as it comes,
+    // so it can't go to the cache half read.
+    let unchecked = remote.max_download.is_some() && length.is_none();
+    if let (Some(cache), Some(meta), false) = (&cache, Meta::from_response(&response), unchecked) {
+        let path = cache
+            .store(&url, &meta, &mut bar.wrap_read(response))
+            .inspect_err(|_| warn!("kept what arrived of {}, to resume next time", url))?;
+        bar.finish_and_clear();
+        return parse_download(File::open(path)?, remote, ctx);
+    }
+    // Large downloads spill to disk rather than sitting in memory.
+    let mut body = SpooledTempFile::new(ctx.config.max_memory());
+    let max = remote.max_download.unwrap_or(u64::MAX);
+    let copied = io::copy(
+        &mut bar.wrap_read(response).take(max.saturating_add(1)),
+        &mut body,
+    )?;
+    bar.finish_and_clear();
+    if copied > max {
+        Err(format!("{} is more than --max-download {} bytes", url, max))?
+    }
+    if body.is_rolled() {
+        debug!("spooled download to a temp file");
+    }
+    body.rewind()?;
+    parse_download(body, remote, ctx)
+}
+
+/// Check a download against `--expected-sha256`, if given, then parse it.
+fn parse_download<R: Read + Seek>(
+    mut body: R,
+    remote: &RemoteArgs,
+    ctx: &Context,
+) -> crate::Result<Png> {
+    if let Some(expected) = &remote.expected_sha256 {
+        let mut hasher = Sha256::new();
+        io::copy(&mut body, &mut hasher)?;
+        let actual = format!("{:x}", hasher.finalize());
+        if actual != *expected {
+            Err(format!(
+                "SHA-256 mismatch: expected {}, downloaded {}",
+                expected, actual
+            ))?
+        }
+        body.rewind()?;
+    }
+    png_from_image(body, ctx)
+}
+
+/// Parse a PNG as it is, keeping every chunk.  Other image formats are
+/// converted to PNG first.
+fn png_from_image<R: Read + Seek>(mut reader: R, ctx: &Context) -> crate::Result<Png> {
+    let mut signature = Vec::new();
+    reader.by_ref().take(8).read_to_end(&mut signature)?;
+    reader.rewind()?;
+    if signature == Png::STANDARD_HEADER {
+        return read_png(&mut reader, ctx);
+    }
+    debug!("converting non-PNG image");
+    let image = image::io::Reader::new(io::BufReader::new(reader))
+        .with_guessed_format()?
+        .decode()?;
     let mut input_bytes: Vec<u8> = Vec::new();
     image.write_to(&mut input_bytes, image::ImageOutputFormat::Png)?;
-    let mut png = Png::try_from(input_bytes.as_slice())?;
-    let chunk = Chunk::new(args.chunk_type, msg.as_bytes().to_vec());
-    png.append_chunk(chunk);
-    let output = args.output_file_path;
-    fs::write(output, png.as_bytes())?;
+    Ok(Png::from_reader(input_bytes.as_slice())?)
+}
+
+/// The envelope the chunk holds, if its message is in one.
+fn envelope(chunk: ChunkRef<'_>) -> Option<Envelope> {
+    stored_text(chunk).and_then(|data| Envelope::parse(data.as_bytes()))
+}
+
+/// `Err` if the chunk holds a message that has expired.  If `ignore`, only
+/// warn.
+fn check_expiry(chunk: ChunkRef<'_>, ignore: bool) -> crate::Result<()> {
+    let expired = envelope(chunk).filter(|e| e.is_expired(SystemTime::now()));
+    let Some(expires_at) = expired.and_then(|e| e.expires_at) else {
+        return Ok(());
+    };
+    if !ignore {
+        Err(format!(
+            "The {} message expired at {}, use --ignore-expiry to decode it anyway",
+            chunk.chunk_type(),
+            expires_at
+        ))?
+    }
+    warn!(chunk_type = %chunk.chunk_type(), "The message expired at {}", expires_at);
     Ok(())
 }
 
-fn decode(args: DecodeArgs) -> crate::Result<()> {
-    let input_bytes = fs::read(&args.file_path)?;
-    let png = Png::try_from(input_bytes.as_slice())?;
-    let chunk = png.chunk_by_type(args.chunk_type);
-    if let Some(c) = chunk {
-        println!("{}", c);
+/// How `write_decoded` shows each chunk.
+#[derive(Clone, Copy, Default)]
+struct Reveal<'a> {
+    /// After its index among the chunks of its type.
+    numbered: bool,
+    /// Followed by the metadata of its envelope.
+    show_meta: bool,
+    /// Even if its message has expired.
+    ignore_expiry: bool,
+    /// As a QR code of its message, rather than text.
+    qr: bool,
+    /// Also saving a QR code of its message here.
+    qr_png: Option<&'a Path>,
+    /// How its message is printed, if not as a QR code.
+    encoding: Encoding,
+}
+
+/// Write the given chunks, refusing any whose message has expired.
+fn write_decoded<'a>(
+    chunks: impl Iterator<Item = (usize, ChunkRef<'a>)>,
+    reveal: Reveal<'_>,
+    out: &mut dyn Write,
+    ctx: &Context,
+) -> crate::Result<()> {
+    for (n, c) in chunks {
+        check_expiry(c, reveal.ignore_expiry)?;
+        if reveal.qr {
+            if reveal.numbered {
+                writeln!(out, "{}\t{}", n, c.chunk_type())?;
+            }
+            writeln!(out, "{}", qr::to_terminal(&ctx.text(c))?)?;
+        } else if reveal.encoding == Encoding::Raw {
+            out.write_all(&ctx.payload(c))?;
+        } else {
+            if reveal.numbered {
+                write!(out, "{}\t", n)?;
+            }
+            let shown = match reveal.encoding {
+                Encoding::Base64 => {
+                    format!("{}\t{}", c.chunk_type(), STANDARD.encode(ctx.payload(c)))
+                }
+                Encoding::Hex => format!("{}\t{}", c.chunk_type(), hex(&ctx.payload(c))),
+                _ => ctx.show(c),
+            };
+            writeln!(out, "{}", shown)?;
+        }
+        if let Some(path) = reveal.qr_png {
+            qr::save(&ctx.text(c), path)?;
+        }
+        if reveal.show_meta {
+            for line in envelope(c).map(|e| e.meta()).unwrap_or_default() {
+                writeln!(out, "\t{}", line)?;
+            }
+        }
     }
     Ok(())
 }
 
-fn decoder(args: DecodeRArgs) -> crate::Result<()> {
-    let img_bytes = reqwest::blocking::get(args.url)?.bytes()?;
-    let image = image::load_from_memory(&img_bytes)?;
-    let mut input_bytes: Vec<u8> = Vec::new();
-    image.write_to(&mut input_bytes, image::ImageOutputFormat::Png)?;
-    let png = Png::try_from(input_bytes.as_slice())?;
-    let chunk = png.chunk_by_type(args.chunk_type);
-    if let Some(c) = chunk {
-        println!("{}", c);
+/// Remove the selected chunks of the given type, then write the Png.
+/// Returns the removed chunks.
+fn write_removed(
+    mut png: Png,
+    chunk_type: ChunkType,
+    select: &SelectArgs,
+    output: &Path,
+    status: &mut dyn Write,
+    ctx: &Context,
+) -> crate::Result<Vec<Chunk>> {
+    let removed = if select.all {
+        png.remove_all_chunks(chunk_type)
+    } else {
+        png.remove_nth_chunk(chunk_type, select.nth.unwrap_or(0))
+            .into_iter()
+            .collect()
+    };
+    if removed.is_empty() {
+        warn_not_found(chunk_type, png.iter().map(Chunk::chunk_type));
+        return Ok(removed);
+    }
+    let payload: Vec<u8> = removed.iter().flat_map(|c| c.data()).copied().collect();
+    record_change(&mut png, "remove", Some(&payload), ctx)?;
+    save_png(output, &png, ctx)?;
+    for chunk in &removed {
+        writeln!(status, "Removed chunk: {}", ctx.show(chunk))?;
+    }
+    Ok(removed)
+}
+
+/// Remove chunks like `write_removed`, for `--relaxed`: from the raw
+/// chunks of the Png in `bytes`, matching a type that needn't be letters.
+/// The rest are written back as they were, and only once they all conform
+/// can the change be audited.  Returns the removed chunks.
+fn write_removed_raw<'a>(
+    bytes: &'a [u8],
+    raw_type: RawChunkType,
+    select: &SelectArgs,
+    output: &Path,
+    status: &mut dyn Write,
+    ctx: &Context,
+) -> crate::Result<Vec<RawChunkRef<'a>>> {
+    let chunks = Png::raw_chunk_refs(bytes, ctx.max_chunk_length)?;
+    let matching = (0..chunks.len()).filter(|&i| chunks[i].raw_type() == raw_type);
+    let picked: Vec<usize> = match select.all {
+        true => matching.collect(),
+        false => matching.skip(select.nth.unwrap_or(0)).take(1).collect(),
+    };
+    if picked.is_empty() {
+        warn!(chunk_type = %raw_type, "No matching chunk of type {}", raw_type);
+        return Ok(Vec::new());
+    }
+    let mut rest = Png::STANDARD_HEADER.to_vec();
+    let mut removed = Vec::new();
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        match picked.contains(&i) {
+            true => removed.push(chunk),
+            false => chunk.write_bytes(&mut rest)?,
+        }
+    }
+    match Png::try_from(rest.as_slice()) {
+        Ok(mut png) => {
+            let payload: Vec<u8> = removed.iter().flat_map(|c| c.data()).copied().collect();
+            record_change(&mut png, "remove", Some(&payload), ctx)?;
+            save_png(output, &png, ctx)?;
+        }
+        Err(e) => {
+            if ctx.config.audit() || journaling(output, ctx) {
+                warn!(path = %output.display(), "not audited or journaled: {}", e);
+            }
+            replace_file(output, ctx, |file| Ok(file.write_all(&rest)?))?;
+        }
+    }
+    for chunk in &removed {
+        writeln!(status, "Removed chunk: {}", ctx.show_raw(*chunk))?;
+    }
+    Ok(removed)
+}
+
+/// Warn there is no chunk of the type, suggesting near matches among the
+/// types `present`, like 'ruSt' for 'rust'.
+fn warn_not_found<'a>(chunk_type: ChunkType, present: impl IntoIterator<Item = &'a ChunkType>) {
+    match suggest::not_found(&chunk_type, present) {
+        Some(suggestion) => warn!(%chunk_type, "{}", suggestion),
+        None => warn!(%chunk_type, "{}", PngmeError::ChunkNotFound(chunk_type)),
+    }
+}
+
+/// Record the change in the Png's audit trail, if auditing is on.
+fn record_change(
+    png: &mut Png,
+    operation: &str,
+    payload: Option<&[u8]>,
+    ctx: &Context,
+) -> crate::Result<()> {
+    if ctx.config.audit() {
+        audit::record(png, &AuditEntry::new(operation, payload, ctx.deterministic))?;
     }
     Ok(())
 }
 
-fn remove(args: RemoveArgs) -> crate::Result<()> {
-    let input_bytes = fs::read(&args.file_path)?;
-    let mut png = Png::try_from(input_bytes.as_slice())?;
-    match png.remove_chunk(args.chunk_type) {
-        Ok(chunk) => {
-            fs::write(&args.file_path, png.as_bytes())?;
-            println!("Removed chunk: {}", chunk);
+/// Add the chunk to the Png at the given place, else before IEND, first
+/// removing any others of its type if `replace`.
+fn add_chunk(png: &mut Png, chunk: Chunk, replace: bool, place: Option<Place>) {
+    let replaced = match place {
+        None | Some(Place::End) if replace => png.upsert_chunk(chunk),
+        None | Some(Place::End) => {
+            png.append_chunk(chunk);
+            Vec::new()
+        }
+        Some(place) => {
+            let replaced = match replace {
+                true => png.remove_all_chunks(*chunk.chunk_type()),
+                false => Vec::new(),
+            };
+            let end = png.chunks().len();
+            let index = match place {
+                Place::Start => png.position_of(ChunkType::IHDR).map_or(0, |i| i + 1),
+                Place::BeforeIdat | Place::End => png
+                    .position_of(ChunkType::IDAT)
+                    .or_else(|| png.position_of(ChunkType::IEND))
+                    .unwrap_or(end),
+            };
+            png.insert_chunk_at(index, chunk);
+            replaced
+        }
+    };
+    for old in replaced {
+        debug!(chunk_type = %old.chunk_type(), length = old.length(), "replaced chunk");
+    }
+}
+
+/// Kind of change reported to `--notify-url`.
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Event {
+    Encode,
+    Remove,
+}
+
+/// Body of the `--notify-url` request.
+#[derive(Serialize)]
+struct Notification {
+    event: Event,
+    /// Path or URL of the PNG that changed.
+    file: String,
+    chunk_type: String,
+    /// SHA-256 of the chunk data as stored, so encrypted if encryption is on.
+    sha256: String,
+    /// Change in the size of the PNG, in bytes.
+    size_delta: i64,
+}
+
+impl Notification {
+    fn new<'a>(
+        event: Event,
+        file: impl ToString,
+        chunk: impl Into<RawChunkRef<'a>>,
+    ) -> Notification {
+        let chunk = chunk.into();
+        let size = chunk.encoded_len() as i64;
+        Notification {
+            event,
+            file: file.to_string(),
+            chunk_type: chunk.raw_type().to_string(),
+            sha256: format!("{:x}", Sha256::digest(chunk.data())),
+            size_delta: match event {
+                Event::Encode => size,
+                Event::Remove => -size,
+            },
+        }
+    }
+}
+
+/// Send the notification to `--notify-url`, if given.  A failure is only
+/// logged, since the change itself has already been saved.
+fn notify(url: Option<&Url>, notification: Notification, ctx: &Context) {
+    let url = match url {
+        Some(url) => url,
+        None => return,
+    };
+    let sent = serde_json::to_vec(&notification)
+        .map_err(Into::into)
+        .and_then(|body| remote::notify(url, body, &ctx.config));
+    match sent {
+        Ok(response) => debug!(status = %response.status(), "notified {}", url),
+        Err(e) => warn!("couldn't notify {}: {}", url, e),
+    }
+}
+
+/// Does the given path contain glob metacharacters.
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(&['*', '?', '['][..])
+}
+
+/// Expand glob patterns among the given paths, dropping any excluded path.
+/// Plain paths are kept as given.  `Err` if a pattern matches nothing.
+fn expand_paths(paths: &[PathBuf], glob: &GlobArgs) -> crate::Result<Vec<PathBuf>> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        if is_stdio(path) || !is_glob(path) {
+            expanded.push(path.clone());
+            continue;
+        }
+        let pattern = path.to_string_lossy();
+        let matches = glob::glob(&pattern)?.collect::<Result<Vec<PathBuf>, _>>()?;
+        if matches.is_empty() {
+            Err(format!("No files match '{}'", pattern))?
+        }
+        expanded.extend(matches);
+    }
+    expanded.retain(|p| !glob.exclude.iter().any(|e| e.matches_path(p)));
+    Ok(expanded)
+}
+
+/// Leading directories of a path that hold no glob metacharacters.  For a
+/// plain path, its parent directory.
+fn glob_base(path: &Path) -> PathBuf {
+    if !is_glob(path) {
+        return path.parent().map(Path::to_path_buf).unwrap_or_default();
+    }
+    path.components()
+        .take_while(|c| !is_glob(Path::new(c.as_os_str())))
+        .collect()
+}
+
+/// Path under `dir` mirroring the location of `path` relative to `base`.
+fn mirrored(dir: &Path, base: &Path, path: &Path) -> PathBuf {
+    dir.join(path.strip_prefix(base).unwrap_or(path))
+}
+
+/// Mirrored path under `dir`, creating its parent directories.
+fn create_mirrored(dir: &Path, base: &Path, path: &Path) -> crate::Result<PathBuf> {
+    if is_stdio(path) {
+        Err("--out-dir needs input files, not stdin")?
+    }
+    let output = mirrored(dir, base, path);
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(output)
+}
+
+/// Is the given path named like a PNG file.
+fn has_png_extension(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+}
+
+/// Set by Ctrl-C while a batch runs.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Make Ctrl-C set `INTERRUPTED`, so a batch stops between files rather
+/// than partway through writing one.  A second Ctrl-C exits at once.
+fn cancel_on_interrupt() {
+    #[cfg(unix)]
+    {
+        extern "C" fn on_interrupt(_: libc::c_int) {
+            if INTERRUPTED.swap(true, Ordering::Relaxed) {
+                // Safety: _exit is async-signal-safe.
+                unsafe { libc::_exit(130) };
+            }
         }
-        Err(e) => println!("Error: {}", e),
+        let handler: extern "C" fn(libc::c_int) = on_interrupt;
+        // Safety: the handler only touches an atomic and calls _exit.
+        unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+    }
+}
+
+/// `Err(Cancelled)` once the observer asks to stop.
+fn check_cancelled(observer: impl Observer) -> crate::Result<()> {
+    if observer.is_cancelled() {
+        Err(PngmeError::Cancelled)?
     }
     Ok(())
 }
 
-fn remover(args: RemoveRArgs) -> crate::Result<()> {
-    let img_bytes = reqwest::blocking::get(args.url)?.bytes()?;
-    let image = image::load_from_memory(&img_bytes)?;
-    let mut input_bytes: Vec<u8> = Vec::new();
-    image.write_to(&mut input_bytes, image::ImageOutputFormat::Png)?;
-    let mut png = Png::try_from(input_bytes.as_slice())?;
-    match png.remove_chunk(args.chunk_type) {
-        Ok(chunk) => {
-            fs::write(&args.output_file_path, png.as_bytes())?;
-            println!("Removed chunk: {}", chunk);
+/// Every PNG file under the given directory, in a stable order.  Stops
+/// with `Cancelled` once `cancel` is set.
+fn walk_pngs(dir: &Path, cancel: &AtomicBool) -> crate::Result<Vec<PathBuf>> {
+    cancel_on_interrupt();
+    let mut paths = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        check_cancelled(cancel)?;
+        let entry = entry?;
+        if entry.file_type().is_file() && has_png_extension(entry.path()) {
+            paths.push(entry.into_path());
+        }
+    }
+    Ok(paths)
+}
+
+/// Does the chunk match the grep criteria.  Without a type, any private
+/// chunk (like those pngme writes) is a candidate.
+fn grep_matches<'a>(
+    chunk: impl Into<ChunkRef<'a>>,
+    chunk_type: Option<&ChunkType>,
+    pattern: Option<&Regex>,
+) -> bool {
+    let chunk = chunk.into();
+    let type_matches = match chunk_type {
+        Some(ct) => chunk.chunk_type() == ct,
+        None => !chunk.chunk_type().is_public(),
+    };
+    type_matches && pattern.is_none_or(|re| re.is_match(&String::from_utf8_lossy(chunk.data())))
+}
+
+/// Most bytes `find` inflates compressed text to.
+const INFLATE_LIMIT: usize = 64 << 20;
+
+/// A match of `find`.
+#[derive(Debug, PartialEq)]
+struct Found {
+    /// Offset of the chunk from the start of the file.
+    offset: usize,
+    chunk_type: ChunkType,
+    /// What the regex matched.
+    matched: Vec<u8>,
+}
+
+/// The first match of the pattern in each chunk, also searching the text
+/// of compressed text chunks if `decompress`.
+fn find_matches(
+    chunks: &[ChunkRef<'_>],
+    pattern: &regex::bytes::Regex,
+    types: TypeFilter,
+    decompress: bool,
+) -> Vec<Found> {
+    let mut found = Vec::new();
+    let mut offset = Png::STANDARD_HEADER.len();
+    for chunk in chunks {
+        let chunk_type = *chunk.chunk_type();
+        let chunk_offset = offset;
+        offset += 12 + chunk.data().len();
+        if types == TypeFilter::Ancillary && chunk_type.is_critical() {
+            continue;
+        }
+        let inflated = text::compressed_text(&chunk_type, chunk.data())
+            .filter(|_| decompress)
+            .and_then(|stream| {
+                let inflated = decompress_to_vec_zlib_with_limit(stream, INFLATE_LIMIT);
+                inflated
+                    .inspect_err(|e| warn!(%chunk_type, offset = chunk_offset, "Can't decompress: {:?}", e.status))
+                    .ok()
+            });
+        let searchable = [Some(chunk.data()), inflated.as_deref()];
+        let matched = searchable
+            .into_iter()
+            .flatten()
+            .find_map(|data| pattern.find(data));
+        if let Some(m) = matched {
+            found.push(Found {
+                offset: chunk_offset,
+                chunk_type,
+                matched: m.as_bytes().to_vec(),
+            });
+        }
+    }
+    found
+}
+
+/// Writer that prefixes every line with a label, like grep does with file names.
+struct Prefixed<W: Write> {
+    label: String,
+    inner: W,
+    at_line_start: bool,
+}
+
+impl<W: Write> Prefixed<W> {
+    fn new(label: String, inner: W) -> Self {
+        Prefixed {
+            label,
+            inner,
+            at_line_start: true,
+        }
+    }
+}
+
+impl<W: Write> Write for Prefixed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for line in buf.split_inclusive(|&b| b == b'\n') {
+            if self.at_line_start {
+                write!(self.inner, "{}:", self.label)?;
+            }
+            self.inner.write_all(line)?;
+            self.at_line_start = line.ends_with(b"\n");
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Run `op` on every path.  With several paths, output is prefixed with the
+/// path, failures are reported on stderr, any failure yields a final `Err`,
+/// and Ctrl-C or `ctx.cancel` stops before the next path with `Cancelled`.
+fn for_each_path<F>(paths: &[PathBuf], ctx: &Context, mut op: F) -> crate::Result<()>
+where
+    F: FnMut(&Path, &mut dyn Write) -> crate::Result<()>,
+{
+    if let [path] = paths {
+        return op(path, &mut io::stdout());
+    }
+    cancel_on_interrupt();
+    let bar = ctx.progress.files(paths.len());
+    let mut failed = 0;
+    for path in paths {
+        if ctx.cancel.is_cancelled() {
+            bar.finish_and_clear();
+            Err(PngmeError::Cancelled)?
         }
-        Err(e) => println!("Error: {}", e),
+        let mut out = Prefixed::new(path.display().to_string(), io::stdout());
+        if let Err(e) = bar.suspend(|| op(path, &mut out)) {
+            bar.suspend(|| error!(path = %path.display(), "{}", e));
+            failed += 1;
+        }
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    if failed > 0 {
+        Err(format!("{} of {} files failed", failed, paths.len()))?
     }
     Ok(())
 }
 
-fn print(args: PrintArgs) -> crate::Result<()> {
-    let input_bytes = fs::read(&args.file_path)?;
-    let png = Png::try_from(input_bytes.as_slice())?;
-    for chunk in png.chunks() {
-        println!("{}", chunk);
+/// Write every chunk.
+fn write_chunks<'a>(
+    chunks: impl IntoIterator<Item = ChunkRef<'a>>,
+    out: &mut dyn Write,
+    ctx: &Context,
+) -> crate::Result<()> {
+    for chunk in chunks {
+        writeln!(out, "{}", ctx.show(chunk))?;
     }
     Ok(())
 }
 
-fn printr(args: PrintRArgs) -> crate::Result<()> {
-    let img_bytes = reqwest::blocking::get(args.url)?.bytes()?;
-    let image = image::load_from_memory(&img_bytes)?;
-    let mut input_bytes: Vec<u8> = Vec::new();
-    image.write_to(&mut input_bytes, image::ImageOutputFormat::Png)?;
-    let png = Png::try_from(input_bytes.as_slice())?;
-    for chunk in png.chunks() {
-        println!("{}", chunk);
+/// The value of a required argument.  `Err` naming it if it was not given.
+fn required<T>(value: Option<T>, name: &str) -> crate::Result<T> {
+    Ok(value.ok_or_else(|| format!("Missing {}", name))?)
+}
+
+/// The secret message: given by flag or positional, else read as is, text
+/// or not, from `--message-file` or `--message-stdin`.
+fn read_message(
+    message: Option<String>,
+    file: Option<&Path>,
+    stdin: bool,
+) -> crate::Result<Vec<u8>> {
+    let path = match (file, stdin) {
+        (Some(path), _) => path,
+        (None, true) => Path::new(STDIO_PATH),
+        (None, false) => return Ok(required(message, "message")?.into_bytes()),
+    };
+    read_bytes(&mut open_input(path)?)
+}
+
+fn encode(args: EncodeArgs, ctx: &Context) -> crate::Result<()> {
+    let input = required(args.input_file_path.clone(), "input path")?;
+    let message_file = args.message_file.as_deref();
+    let reads_stdin = args.message_stdin || message_file.is_some_and(is_stdio);
+    if reads_stdin && is_stdio(&input) {
+        Err("The message and the input PNG can't both come from stdin")?
+    }
+    // Without --chunk there is always the one chunk from ARGS, and with it,
+    // only if something of it was given.
+    let mut pairs = Vec::new();
+    if args.chunks.is_empty()
+        || args.chunk_type.is_some()
+        || args.message.is_some()
+        || args.message_stdin
+        || message_file.is_some()
+    {
+        let chunk_type = required(args.chunk_type, "chunk type")?;
+        let message = read_message(args.message, message_file, args.message_stdin)?;
+        pairs.push((chunk_type, message));
+    }
+    pairs.extend(
+        args.chunks
+            .iter()
+            .map(|(chunk_type, message)| (*chunk_type, message.clone().into_bytes())),
+    );
+    for (chunk_type, _) in &pairs {
+        args.guard.check(chunk_type)?;
+    }
+    if args.itxt.is_set() && !pairs.iter().any(|(ct, _)| *ct == ChunkType::ITXT) {
+        Err("--lang, --keyword and --translated-keyword need chunk type iTXt")?
+    }
+    let paths = expand_paths(slice::from_ref(&input), &args.glob)?;
+    if paths.len() > 1 && args.output_file_path.is_some() {
+        Err("An output path needs a single input PNG, use --out-dir instead")?
+    }
+    let mut payloads = Vec::new();
+    for (chunk_type, message) in pairs {
+        let data = ctx.message_bytes(message);
+        if !args.envelope.is_set() && chunk_type != ChunkType::ITXT {
+            payloads.push((chunk_type, data));
+            continue;
+        }
+        // Envelopes and iTXt hold the message as text.
+        let mut text = String::from_utf8(data)
+            .map_err(|e| format!("Message isn't UTF-8, as --envelope and iTXt need: {}", e))?;
+        if args.envelope.is_set() {
+            text = Envelope {
+                author: args.envelope.author.clone(),
+                created_at: (!ctx.deterministic).then(Envelope::now),
+                expires_at: args.envelope.expires.map(Envelope::timestamp),
+                content_type: args.envelope.content_type.clone(),
+                message: text,
+            }
+            .to_data();
+        }
+        let data = if chunk_type == ChunkType::ITXT {
+            let itxt = &args.itxt;
+            InternationalText {
+                keyword: itxt.keyword.as_deref().unwrap_or("Description").to_string(),
+                language: itxt.lang.clone().unwrap_or_default(),
+                translated_keyword: itxt.translated_keyword.clone().unwrap_or_default(),
+                text,
+            }
+            .to_data()?
+        } else {
+            text.into_bytes()
+        };
+        payloads.push((chunk_type, data));
+    }
+    let base = glob_base(&input);
+    let place = args.place;
+    for_each_path(&paths, ctx, |path, out| {
+        let chunks = payloads
+            .iter()
+            .map(|(chunk_type, data)| Chunk::new(*chunk_type, data.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let notifications: Vec<Notification> = chunks
+            .iter()
+            .map(|chunk| Notification::new(Event::Encode, path.display(), chunk))
+            .collect();
+        let output = match (&args.out_dir, &args.output_file_path) {
+            _ if args.output_data_uri => None,
+            (Some(dir), _) => Some(create_mirrored(dir, &base, path)?),
+            (None, Some(output)) => Some(output.clone()),
+            (None, None) => Some(path.to_path_buf()),
+        };
+        let _lock = match &output {
+            Some(output) if output == path => lock_file(path, ctx)?,
+            _ => None,
+        };
+        match output {
+            // Encoding a file into itself only needs to write the new chunk,
+            // unless the audit chunk needs updating too.  Several chunks
+            // are added in one rewrite instead.
+            Some(output)
+                if output == path
+                    && !is_stdio(path)
+                    && chunks.len() == 1
+                    && !args.replace
+                    && !ctx.config.audit()
+                    && !args.verify_pixels
+                    && matches!(place, None | Some(Place::End)) =>
+            {
+                append_in_place(path, &chunks[0], ctx)?;
+            }
+            output => {
+                let mut png = read_png(&mut open_input(path)?, ctx)?;
+                let input = args.verify_pixels.then(|| png.clone());
+                for (i, chunk) in chunks.into_iter().enumerate() {
+                    // With --replace, a type given twice keeps both new chunks.
+                    let (chunk_type, data) = &payloads[i];
+                    let repeated = payloads[..i].iter().any(|(ct, _)| ct == chunk_type);
+                    add_chunk(&mut png, chunk, args.replace && !repeated, place);
+                    record_change(&mut png, "encode", Some(data), ctx)?;
+                }
+                if let Some(input) = input {
+                    if let Some(difference) = seal::pixel_difference(&input, &png)? {
+                        Err(format!("Encoding changed the image: {}", difference))?
+                    }
+                    debug!("pixels unchanged");
+                }
+                match output {
+                    Some(output) => save_png(&output, &png, ctx)?,
+                    None => writeln!(out, "{}", data_uri::encode(&png.as_bytes()))?,
+                }
+            }
+        }
+        for notification in notifications {
+            notify(args.notify_url.as_ref(), notification, ctx);
+        }
+        Ok(())
+    })
+}
+
+/// Send the encoded Png to the given url.
+fn upload_png(
+    url: &Url,
+    method: &Method,
+    png: &Png,
+    remote: &RemoteArgs,
+    ctx: &Context,
+) -> crate::Result<()> {
+    let put: PutFn = match url.scheme() {
+        s3::SCHEME => s3::put,
+        gcs::SCHEME => gcs::put,
+        azure::SCHEME => azure::put,
+        _ if azure::is_blob_url(url) => azure::put,
+        webdav::SCHEME | webdav::SECURE_SCHEME => {
+            return webdav::put(url, png.as_bytes(), remote, &ctx.config)
+        }
+        sftp::SCHEME => sftp::put,
+        ftp::SCHEME => ftp::put,
+        _ => {
+            let response = remote::upload(url, method, png.as_bytes(), remote, &ctx.config)?;
+            info!(status = %response.status(), "uploaded to {}", url);
+            return Ok(());
+        }
+    };
+    put(url, remote, &ctx.config, png.as_bytes())
+}
+
+fn encoder(args: EncodeRArgs, ctx: &Context) -> crate::Result<()> {
+    let chunk_type = required(args.chunk_type, "chunk type")?;
+    let message = read_message(
+        args.message,
+        args.message_file.as_deref(),
+        args.message_stdin,
+    )?;
+    args.guard.check(&chunk_type)?;
+    if args.upload.is_none()
+        && !args.pin_to_ipfs
+        && !args.output_data_uri
+        && args.output_file_path.is_none()
+    {
+        Err("Missing output path (or --upload, --pin-to-ipfs or --output-data-uri)")?
     }
+    let mut png = fetch_png(args.url.clone(), &args.remote, ctx)?;
+    let data = ctx.message_bytes(message);
+    let chunk = Chunk::new(chunk_type, data.clone())?;
+    let notification = Notification::new(Event::Encode, &args.url, &chunk);
+    add_chunk(&mut png, chunk, args.replace, args.place);
+    record_change(&mut png, "encode-r", Some(&data), ctx)?;
+    if let Some(url) = &args.upload {
+        upload_png(url, &args.upload_method, &png, &args.remote, ctx)?;
+    }
+    if args.pin_to_ipfs {
+        println!("{}", ipfs::pin(png.as_bytes(), &args.remote, &ctx.config)?);
+    }
+    if args.output_data_uri {
+        println!("{}", data_uri::encode(&png.as_bytes()));
+    }
+    if let Some(output) = &args.output_file_path {
+        save_png(output, &png, ctx)?;
+    }
+    notify(args.notify_url.as_ref(), notification, ctx);
     Ok(())
 }
 
-pub(crate) fn run(subcommand: Subcommand) -> crate::Result<()> {
-    match subcommand {
-        Subcommand::Encode(args) => encode(args),
-        Subcommand::EncodeR(args) => encoder(args),
-        Subcommand::Decode(args) => decode(args),
-        Subcommand::DecodeR(args) => decoder(args),
-        Subcommand::Remove(args) => remove(args),
-        Subcommand::RemoveR(args) => remover(args),
-        Subcommand::Print(args) => print(args),
-        Subcommand::PrintR(args) => printr(args),
+fn decode(args: DecodeArgs, ctx: &Context) -> crate::Result<()> {
+    let chunk_type = required(args.chunk_type, "chunk type")?;
+    let paths = expand_paths(&args.file_paths, &args.glob)?;
+    let encoding = args.encoding.unwrap_or_default();
+    if encoding == Encoding::Raw && paths.len() > 1 {
+        Err(format!(
+            "--encoding raw writes a single message, from a single input PNG, not {}",
+            paths.len()
+        ))?
+    }
+    let in_language = |chunk: &ChunkRef| match &args.lang {
+        Some(lang) => InternationalText::from_data(chunk.data()).is_ok_and(|t| t.is_in(lang)),
+        None => true,
+    };
+    let reveal = Reveal {
+        numbered: args.select.all,
+        show_meta: args.show_meta,
+        ignore_expiry: args.ignore_expiry,
+        qr: args.qr,
+        qr_png: args.qr_png.as_deref(),
+        encoding,
+    };
+    for_each_path(&paths, ctx, |path, out| {
+        let select = &args.select;
+        if is_stdio(path) {
+            let png = read_png(&mut io::stdin(), ctx)?;
+            suggest_near(chunk_type, png.iter().map(Chunk::chunk_type));
+            let chunks = png.chunks_by_type(chunk_type).map(ChunkRef::from);
+            let chunks = select.pick(chunks.filter(in_language));
+            return write_decoded(chunks, reveal, out, ctx);
+        }
+        // Only the selected chunks are read, unless picking by language.
+        let mut index = PngIndex::with_limit(File::open(path)?, ctx.max_chunk_length)?;
+        suggest_near(chunk_type, index.entries().iter().map(|e| e.chunk_type()));
+        let positions: Vec<usize> = index.positions(chunk_type).collect();
+        let mut loaded = Vec::new();
+        if args.lang.is_some() {
+            let mut chunks = Vec::new();
+            for i in positions {
+                chunks.push(index.load(i)?);
+            }
+            let chunks = chunks.into_iter().filter(|c| in_language(&c.into()));
+            loaded.extend(select.pick(chunks));
+        } else {
+            for (n, i) in select.pick(positions.into_iter()) {
+                loaded.push((n, index.load(i)?));
+            }
+        }
+        let chunks = loaded.iter().map(|(n, c)| (*n, c.into()));
+        write_decoded(chunks, reveal, out, ctx)
+    })
+}
+
+/// If there is no chunk of the type among those `present` but one near
+/// it, warn with the suggestion.  Otherwise stay quiet, as decode does
+/// when there's no match.
+fn suggest_near<'a>(
+    chunk_type: ChunkType,
+    present: impl IntoIterator<Item = &'a ChunkType> + Clone,
+) {
+    if present.clone().into_iter().all(|t| *t != chunk_type) {
+        if let Some(suggestion) = suggest::not_found(&chunk_type, present) {
+            warn!(%chunk_type, "{}", suggestion);
+        }
+    }
+}
+
+/// One line of `decode-r --urls-from` output.
+#[derive(Serialize)]
+struct Decoded<'a> {
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chunk_type: Option<String>,
+    /// Decoded message, `null` if there is no matching chunk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// URLs listed one per line, skipping blank lines and `#` comments.
+fn read_urls(reader: &mut dyn Read) -> crate::Result<Vec<Url>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| Ok(Url::parse(line).map_err(|e| format!("{}: {}", line, e))?))
+        .collect()
+}
+
+/// Decode the `nth` chunk of the type from every url on `--jobs` threads,
+/// sending no more than `--rate-limit` requests a second between them, and
+/// printing an NDJSON line per url as it finishes.  Ctrl-C stops the
+/// threads before their next url.
+fn decode_urls(
+    urls: &[Url],
+    chunk_type: &ChunkType,
+    args: &DecodeRArgs,
+    ctx: &Context,
+) -> crate::Result<()> {
+    let nth = args.select.nth.unwrap_or(0);
+    let remote = &RemoteArgs {
+        rate_limit: args
+            .rate_limit
+            .map(|rate| Arc::new(remote::RateLimit::new(rate))),
+        ..args.remote.clone()
+    };
+    // One bar for the batch rather than one per download.
+    let quiet = Context {
+        progress: Progress::new(false),
+        ..ctx.clone()
+    };
+    cancel_on_interrupt();
+    let bar = ctx.progress.files(urls.len());
+    let next = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..args.jobs.get().min(urls.len()) {
+            scope.spawn(|| {
+                while let Some(url) = urls.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if ctx.cancel.is_cancelled() {
+                        break;
+                    }
+                    let mut line = Decoded {
+                        url: url.as_str(),
+                        chunk_type: None,
+                        message: None,
+                        error: None,
+                    };
+                    match fetch_png(url.clone(), remote, &quiet) {
+                        Ok(png) => {
+                            line.chunk_type = Some(chunk_type.to_string());
+                            let chunk = png.nth_chunk_by_type(*chunk_type, nth);
+                            match chunk.map(|c| check_expiry(c.into(), args.ignore_expiry)) {
+                                Some(Err(e)) => {
+                                    line.error = Some(e.to_string());
+                                    failed.fetch_add(1, Ordering::Relaxed);
+                                }
+                                _ => line.message = Some(chunk.map(|c| quiet.text(c))),
+                            }
+                        }
+                        Err(e) => {
+                            line.error = Some(e.to_string());
+                            failed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    let json = serde_json::to_string(&line).unwrap_or_default();
+                    bar.suspend(|| println!("{}", json));
+                    bar.inc(1);
+                }
+            });
+        }
+    });
+    bar.finish_and_clear();
+    check_cancelled(ctx.cancel)?;
+    match failed.into_inner() {
+        0 => Ok(()),
+        failed => Err(format!("{} of {} URLs failed", failed, urls.len()))?,
+    }
+}
+
+fn decoder(args: DecodeRArgs, ctx: &Context) -> crate::Result<()> {
+    let chunk_type = required(args.chunk_type, "chunk type")?;
+    if let Some(list) = &args.urls_from {
+        let urls = read_urls(&mut open_input(list)?)?;
+        return decode_urls(&urls, &chunk_type, &args, ctx);
+    }
+    let png = fetch_png(required(args.url, "URL")?, &args.remote, ctx)?;
+    let chunks = args
+        .select
+        .pick(png.chunks_by_type(chunk_type).map(ChunkRef::from));
+    let reveal = Reveal {
+        numbered: args.select.all,
+        ignore_expiry: args.ignore_expiry,
+        ..Reveal::default()
+    };
+    write_decoded(chunks, reveal, &mut io::stdout(), ctx)
+}
+
+fn remove(args: RemoveArgs, ctx: &Context) -> crate::Result<()> {
+    let raw_type = required(args.chunk_type, "chunk type")?;
+    let chunk_type = match ChunkType::try_from(raw_type) {
+        Ok(chunk_type) => Some(chunk_type),
+        Err(_) if ctx.relaxed => None,
+        Err(e) => Err(format!("{} (pass --relaxed to remove it anyway)", e))?,
+    };
+    let paths = expand_paths(&args.file_paths, &args.glob)?;
+    for_each_path(&paths, ctx, |path, out| {
+        let _lock = lock_file(path, ctx)?;
+        let mut stderr = io::stderr();
+        let status: &mut dyn Write = if is_stdio(path) { &mut stderr } else { out };
+        let notify_removed = |chunk| {
+            let notification = Notification::new(Event::Remove, path.display(), chunk);
+            notify(args.notify_url.as_ref(), notification, ctx);
+        };
+        match chunk_type.filter(|_| !ctx.relaxed) {
+            Some(chunk_type) => {
+                let png = read_png(&mut open_input(path)?, ctx)?;
+                let removed = write_removed(png, chunk_type, &args.select, path, status, ctx)?;
+                removed
+                    .iter()
+                    .map(RawChunkRef::from)
+                    .for_each(notify_removed);
+            }
+            None => {
+                let bytes = read_bytes(&mut open_input(path)?)?;
+                let select = &args.select;
+                let removed = write_removed_raw(&bytes, raw_type, select, path, status, ctx)?;
+                removed.into_iter().for_each(notify_removed);
+            }
+        }
+        Ok(())
+    })
+}
+
+fn strip_pngs(args: StripArgs, ctx: &Context) -> crate::Result<()> {
+    let preset = Preset {
+        spare: args.keep.clone(),
+        ..Preset::named(&args.preset, &ctx.config)?
+    };
+    let paths = expand_paths(&args.file_paths, &args.glob)?;
+    for_each_path(&paths, ctx, |path, out| {
+        let _lock = lock_file(path, ctx)?;
+        let mut png = read_png(&mut open_input(path)?, ctx)?;
+        let removed = strip::strip(&mut png, &preset);
+        let mut stderr = io::stderr();
+        let status: &mut dyn Write = if is_stdio(path) { &mut stderr } else { out };
+        let verb = if args.dry_run {
+            "Would remove"
+        } else {
+            "Removed"
+        };
+        for chunk in &removed {
+            writeln!(status, "{} chunk: {}", verb, ctx.show(chunk))?;
+        }
+        if args.dry_run || (removed.is_empty() && !is_stdio(path)) {
+            return Ok(());
+        }
+        record_change(&mut png, "strip", None, ctx)?;
+        save_png(path, &png, ctx)
+    })
+}
+
+/// The chunk with its message upgraded to the current envelope format, if
+/// it isn't in it already.
+fn migrated(chunk: &Chunk) -> crate::Result<Option<Chunk>> {
+    if chunk.chunk_type() == &ChunkType::ITXT {
+        let Ok(mut itxt) = InternationalText::from_data(chunk.data()) else {
+            return Ok(None);
+        };
+        let Some(text) = Envelope::migrate(itxt.text.as_bytes()) else {
+            return Ok(None);
+        };
+        itxt.text = text;
+        return Ok(Some(itxt.to_chunk()?));
+    }
+    let data = Envelope::migrate(chunk.data());
+    Ok(data
+        .map(|data| Chunk::new(*chunk.chunk_type(), data.into_bytes()))
+        .transpose()?)
+}
+
+fn migrate_format(args: MigrateFormatArgs, ctx: &Context) -> crate::Result<()> {
+    let chunk_type = required(args.chunk_type, "chunk type")?;
+    let paths = expand_paths(&args.file_paths, &args.glob)?;
+    for_each_path(&paths, ctx, |path, out| {
+        let _lock = lock_file(path, ctx)?;
+        let png = read_png(&mut open_input(path)?, ctx)?;
+        let mut count = 0;
+        let mut chunks = Vec::with_capacity(png.len());
+        for chunk in png {
+            let upgraded = if chunk.chunk_type() == &chunk_type {
+                migrated(&chunk)?
+            } else {
+                None
+            };
+            match upgraded {
+                Some(upgraded) => {
+                    chunks.push(upgraded);
+                    count += 1;
+                }
+                None => chunks.push(chunk),
+            }
+        }
+        let mut stderr = io::stderr();
+        let status: &mut dyn Write = if is_stdio(path) { &mut stderr } else { out };
+        let mut png = Png::from_chunks(chunks);
+        if count > 0 {
+            record_change(&mut png, "migrate-format", None, ctx)?;
+        }
+        // Unchanged files are left alone, but stdin always goes to stdout.
+        if count > 0 || is_stdio(path) {
+            save_png(path, &png, ctx)?;
+        }
+        writeln!(status, "Migrated {} chunk(s)", count)?;
+        Ok(())
+    })
+}
+
+fn remover(args: RemoveRArgs, ctx: &Context) -> crate::Result<()> {
+    let chunk_type = required(args.chunk_type, "chunk type")?;
+    let output = required(args.output_file_path, "output path")?;
+    let png = fetch_png(args.url.clone(), &args.remote, ctx)?;
+    let status = &mut status_output(&output);
+    for chunk in write_removed(png, chunk_type, &args.select, &output, status, ctx)? {
+        let notification = Notification::new(Event::Remove, &args.url, &chunk);
+        notify(args.notify_url.as_ref(), notification, ctx);
+    }
+    Ok(())
+}
+
+fn print(args: PrintArgs, ctx: &Context) -> crate::Result<()> {
+    let paths = expand_paths(&args.file_paths, &args.glob)?;
+    for_each_path(&paths, ctx, |path, out| {
+        if ctx.relaxed {
+            let bytes = read_bytes(&mut open_input(path)?)?;
+            for chunk in Png::raw_chunk_refs(&bytes, ctx.max_chunk_length)? {
+                writeln!(out, "{}", ctx.show_raw(chunk))?;
+            }
+            return Ok(());
+        }
+        if is_stdio(path) {
+            let bytes = read_bytes(&mut io::stdin())?;
+            let chunks = Png::chunk_refs_limited(&bytes, ctx.max_chunk_length)?;
+            return write_chunks(chunks, out, ctx);
+        }
+        // Only chunks that print their data are read.
+        let mut index = PngIndex::with_limit(File::open(path)?, ctx.max_chunk_length)?;
+        for i in 0..index.entries().len() {
+            let line = match summary(index.entries()[i].chunk_type()) {
+                Some(line) => line,
+                None => ctx.show(&index.load(i)?),
+            };
+            writeln!(out, "{}", line)?;
+        }
+        Ok(())
+    })
+}
+
+fn printr(args: PrintRArgs, ctx: &Context) -> crate::Result<()> {
+    let png = fetch_png(args.url, &args.remote, ctx)?;
+    write_chunks(
+        png.chunks().iter().map(ChunkRef::from),
+        &mut io::stdout(),
+        ctx,
+    )
+}
+
+fn verify(args: VerifyArgs, ctx: &Context) -> crate::Result<()> {
+    let paths = expand_paths(&args.file_paths, &args.glob)?;
+    for_each_path(&paths, ctx, |path, out| {
+        let violations = with_bytes(path, |bytes| Ok(Png::validate(bytes)))?;
+        for violation in &violations {
+            writeln!(out, "{}", violation)?;
+        }
+        match violations.len() {
+            0 => writeln!(out, "OK")?,
+            n => Err(format!("{} problem(s) found", n))?,
+        }
+        Ok(())
+    })
+}
+
+fn salvage(args: SalvageArgs, ctx: &Context) -> crate::Result<()> {
+    let salvaged = with_bytes(&args.file_path, |bytes| Ok(Png::salvage(bytes)?))?;
+    let png = &salvaged.png;
+    if salvaged.missing > 0 {
+        if let Some(last) = png.chunks().last() {
+            warn!(chunk_type = %last.chunk_type(), "Last chunk is missing {} bytes", salvaged.missing);
+        }
+    }
+    // The last chunk may end mid-character, so show what text there is.
+    for chunk in png.chunks_by_type(args.chunk_type) {
+        let data = String::from_utf8_lossy(chunk.data()).into_owned();
+        let text = match &ctx.key {
+            Some(key) => decrypt(data.clone(), key).unwrap_or(data),
+            None => data,
+        };
+        match args.raw {
+            true => println!("{}", text),
+            false => println!("{}", escape(&text)),
+        }
+    }
+    Ok(())
+}
+
+/// Remove the expired messages, of the given type if any, from the Png at
+/// the given path.  Returns how many were removed.
+fn prune_file(path: &Path, chunk_type: Option<ChunkType>, ctx: &Context) -> crate::Result<usize> {
+    let now = SystemTime::now();
+    let _lock = lock_file(path, ctx)?;
+    let mut png = read_png(&mut open_input(path)?, ctx)?;
+    let before = png.len();
+    png.retain(|chunk| {
+        let of_type = chunk_type.is_none_or(|t| chunk.chunk_type() == &t);
+        !(of_type && envelope(chunk.into()).is_some_and(|e| e.is_expired(now)))
+    });
+    let removed = before - png.len();
+    if removed > 0 {
+        record_change(&mut png, "prune", None, ctx)?;
+        save_png(path, &png, ctx)?;
+    }
+    Ok(removed)
+}
+
+fn history(args: HistoryArgs, ctx: &Context) -> crate::Result<()> {
+    let png = read_png(&mut open_input(&args.file_path)?, ctx)?;
+    for entry in audit::history(&png) {
+        println!("{}", entry);
+    }
+    Ok(())
+}
+
+fn undo(args: UndoArgs, ctx: &Context) -> crate::Result<()> {
+    let path = &args.file_path;
+    let _lock = lock_file(path, ctx)?;
+    let entry = journal::last(path)?.ok_or_else(|| {
+        format!(
+            "Nothing to undo for {} (changes are only journaled with --journal)",
+            path.display()
+        )
+    })?;
+    let bytes = fs::read(path)?;
+    if journal::digest(&bytes) != entry.digest && !args.force {
+        Err(format!(
+            "{} changed since pngme last wrote it, pass --force to undo anyway",
+            path.display()
+        ))?
+    }
+    let png = entry.revert(&read_png(&mut bytes.as_slice(), ctx)?)?;
+    replace_png(path, &png, ctx)?;
+    let left = journal::pop(path)?;
+    match &entry.at {
+        Some(at) => println!("Undid the change made at {} ({} more to undo)", at, left),
+        None => println!("Undid the last change ({} more to undo)", left),
+    }
+    Ok(())
+}
+
+fn watermark(args: WatermarkArgs, ctx: &Context) -> crate::Result<()> {
+    let output = args.output_file_path.as_ref().unwrap_or(&args.file_path);
+    let _lock = lock_file(output, ctx)?;
+    let png = read_png(&mut open_input(&args.file_path)?, ctx)?;
+    let watermark = Watermark {
+        text: args.text,
+        position: args.position,
+        opacity: args.opacity,
+    };
+    let mut png = watermark.apply(&png)?;
+    record_change(&mut png, "watermark", Some(watermark.text.as_bytes()), ctx)?;
+    save_png(output, &png, ctx)
+}
+
+/// Chunk statistics over every input of `stats`.
+#[derive(Debug, Default, Serialize)]
+struct Stats {
+    files: usize,
+    /// Bytes of every input together.
+    bytes: u64,
+    /// Per chunk type, most bytes first.
+    types: Vec<TypeStats>,
+    /// The largest chunks, largest first.
+    largest: Vec<LargeChunk>,
+}
+
+#[derive(Debug, Serialize)]
+struct TypeStats {
+    #[serde(rename = "type")]
+    chunk_type: String,
+    count: usize,
+    /// Bytes of the chunks, with their length, type and checksum.
+    bytes: u64,
+    /// Share of all the bytes of the inputs.
+    percent: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct LargeChunk {
+    file: String,
+    #[serde(rename = "type")]
+    chunk_type: String,
+    /// Position among the chunks of its file.
+    index: usize,
+    bytes: u64,
+}
+
+impl Stats {
+    /// Add the chunks of a file.
+    fn add(&mut self, file: &Path, chunks: &[ChunkRef<'_>]) {
+        self.files += 1;
+        self.bytes += Png::STANDARD_HEADER.len() as u64;
+        for (index, chunk) in chunks.iter().enumerate() {
+            let bytes = 12 + chunk.data().len() as u64;
+            self.bytes += bytes;
+            let chunk_type = chunk.chunk_type().to_string();
+            match self.types.iter_mut().find(|t| t.chunk_type == chunk_type) {
+                Some(stats) => {
+                    stats.count += 1;
+                    stats.bytes += bytes;
+                }
+                None => self.types.push(TypeStats {
+                    chunk_type: chunk_type.clone(),
+                    count: 1,
+                    bytes,
+                    percent: 0.0,
+                }),
+            }
+            self.largest.push(LargeChunk {
+                file: file.display().to_string(),
+                chunk_type,
+                index,
+                bytes,
+            });
+        }
+    }
+
+    /// Sort, work out the percentages and keep only the `top` largest
+    /// chunks.
+    fn finish(&mut self, top: usize) {
+        let total = self.bytes.max(1) as f64;
+        for stats in &mut self.types {
+            stats.percent = stats.bytes as f64 * 100.0 / total;
+        }
+        self.types
+            .sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.chunk_type.cmp(&b.chunk_type)));
+        self.largest.sort_by_key(|c| std::cmp::Reverse(c.bytes));
+        self.largest.truncate(top);
+    }
+
+    /// The statistics as a table per type, then the largest chunks.
+    fn write_table(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{} file(s), {} bytes", self.files, self.bytes)?;
+        writeln!(
+            out,
+            "{:<6}{:>8}{:>12}{:>9}",
+            "TYPE", "COUNT", "BYTES", "SHARE"
+        )?;
+        for t in &self.types {
+            let share = format!("{:.1}%", t.percent);
+            writeln!(
+                out,
+                "{:<6}{:>8}{:>12}{:>9}",
+                t.chunk_type, t.count, t.bytes, share
+            )?;
+        }
+        if !self.largest.is_empty() {
+            writeln!(out, "\nLargest chunks:")?;
+        }
+        for c in &self.largest {
+            writeln!(
+                out,
+                "{:>12}  {}  {}[{}]",
+                c.bytes, c.chunk_type, c.file, c.index
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn stats(args: StatsArgs, ctx: &Context) -> crate::Result<()> {
+    let paths = expand_paths(&args.file_paths, &args.glob)?;
+    cancel_on_interrupt();
+    let bar = ctx.progress.files(paths.len());
+    let mut stats = Stats::default();
+    let mut failed = 0;
+    for path in &paths {
+        if ctx.cancel.is_cancelled() {
+            bar.finish_and_clear();
+            Err(PngmeError::Cancelled)?
+        }
+        bar.inc(1);
+        let added = with_chunk_refs(path, ctx, |chunks| {
+            stats.add(path, &chunks);
+            Ok(())
+        });
+        if let Err(e) = added {
+            bar.suspend(|| error!(path = %path.display(), "{}", e));
+            failed += 1;
+        }
+    }
+    bar.finish_and_clear();
+    stats.finish(args.top);
+    if args.json || (ctx.config.json_output() && !args.table) {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        stats.write_table(&mut io::stdout())?;
+    }
+    if failed > 0 {
+        Err(format!("{} of {} files failed", failed, paths.len()))?
+    }
+    Ok(())
+}
+
+/// exiftool's tags for the PNG at the given path, those of the file first.
+fn info_tags(path: &Path, ctx: &Context) -> crate::Result<Vec<Tag>> {
+    // Metadata first, as reading the file may update its access time.
+    let (metadata, bytes) = if is_stdio(path) {
+        (None, read_bytes(&mut io::stdin())?)
+    } else {
+        (Some(fs::metadata(path)?), fs::read(path)?)
+    };
+    let png = read_png(&mut bytes.as_slice(), ctx)?;
+    let mut tags = info::file_tags(path, bytes.len() as u64, metadata.as_ref());
+    tags.extend(info::png_tags(&png));
+    Ok(tags)
+}
+
+fn info(args: InfoArgs, ctx: &Context) -> crate::Result<()> {
+    let paths = expand_paths(&args.file_paths, &args.glob)?;
+    if !args.exiftool {
+        return for_each_path(&paths, ctx, |path, out| {
+            Ok(info::write_tags(&info_tags(path, ctx)?, out)?)
+        });
+    }
+    let failed = write_exiftool_report(&paths, &mut io::stdout().lock(), ctx)?;
+    if failed > 0 {
+        Err(format!("{} of {} files failed", failed, paths.len()))?
+    }
+    Ok(())
+}
+
+/// Write the tags of every path as exiftool lays out several files: a
+/// header before each it could read, then a count of those read and of
+/// those it couldn't.  How many couldn't be read.
+fn write_exiftool_report(
+    paths: &[PathBuf],
+    out: &mut dyn Write,
+    ctx: &Context,
+) -> crate::Result<usize> {
+    let mut failed = 0;
+    for path in paths {
+        match info_tags(path, ctx) {
+            Ok(tags) => {
+                if paths.len() > 1 {
+                    writeln!(out, "======== {}", path.display())?;
+                }
+                info::write_exiftool(&tags, out)?;
+            }
+            Err(e) => {
+                error!(path = %path.display(), "{}", e);
+                failed += 1;
+            }
+        }
+    }
+    if paths.len() > 1 {
+        writeln!(out, "{:>5} image files read", paths.len() - failed)?;
+    }
+    if failed > 0 {
+        writeln!(out, "{:>5} files could not be read", failed)?;
+    }
+    Ok(failed)
+}
+
+fn seal(args: SealArgs, ctx: &Context) -> crate::Result<()> {
+    let _lock = lock_file(&args.file_path, ctx)?;
+    let mut png = read_png(&mut open_input(&args.file_path)?, ctx)?;
+    let hash = seal::seal(&mut png)?;
+    record_change(&mut png, "seal", None, ctx)?;
+    save_png(&args.file_path, &png, ctx)?;
+    println!("{}", hash.to_hex());
+    Ok(())
+}
+
+fn same_pixels(args: SamePixelsArgs, ctx: &Context) -> crate::Result<()> {
+    let first = read_png(&mut open_input(&args.first)?, ctx)?;
+    let second = read_png(&mut open_input(&args.second)?, ctx)?;
+    if let Some(difference) = seal::pixel_difference(&first, &second)? {
+        Err(format!("The pixels differ: {}", difference))?
+    }
+    println!("OK");
+    Ok(())
+}
+
+fn check_seal(args: CheckSealArgs, ctx: &Context) -> crate::Result<()> {
+    let paths = expand_paths(&args.file_paths, &args.glob)?;
+    for_each_path(&paths, ctx, |path, out| {
+        seal::check(&read_png(&mut open_input(path)?, ctx)?)?;
+        writeln!(out, "OK")?;
+        Ok(())
+    })
+}
+
+fn thumb(args: ThumbArgs, ctx: &Context) -> crate::Result<()> {
+    let png = read_png(&mut open_input(&args.file_path)?, ctx)?;
+    let embedded = png
+        .chunk_by_type(ChunkType::EXIF)
+        .and_then(|chunk| exif::thumbnail(chunk.data()));
+    let thumbnail = if args.embedded {
+        let jpeg = embedded.ok_or("No thumbnail in the PNG's Exif data")?;
+        png_from_image(io::Cursor::new(jpeg), ctx)?
+    } else {
+        if embedded.is_some() {
+            info!("The PNG has an embedded thumbnail, use --embedded to extract it");
+        }
+        let image = image::load_from_memory(&png.as_bytes())?.thumbnail(args.size, args.size);
+        let mut bytes = Vec::new();
+        image.write_to(&mut bytes, image::ImageOutputFormat::Png)?;
+        read_png(&mut bytes.as_slice(), ctx)?
+    };
+    write_png(&mut open_output(&args.output_file_path)?, &thumbnail)
+}
+
+fn scan(args: ScanArgs, ctx: &Context) -> crate::Result<()> {
+    let paths = walk_pngs(&args.dir, ctx.cancel)?;
+    let bar = ctx.progress.files(paths.len());
+    let (mut flagged, mut failed) = (0, 0);
+    for path in &paths {
+        if ctx.cancel.is_cancelled() {
+            bar.finish_and_clear();
+            Err(PngmeError::Cancelled)?
+        }
+        bar.inc(1);
+        match with_bytes(path, detect::scan) {
+            Ok(findings) if findings.is_empty() => {}
+            Ok(findings) => {
+                flagged += 1;
+                for finding in findings {
+                    bar.suspend(|| println!("{}: {}", path.display(), finding));
+                }
+            }
+            Err(e) => {
+                bar.suspend(|| error!(path = %path.display(), "{}", e));
+                failed += 1;
+            }
+        }
+    }
+    bar.finish_and_clear();
+    if failed > 0 {
+        Err(format!("{} of {} files failed", failed, paths.len()))?
+    }
+    if flagged > 0 {
+        Err(format!("{} of {} files flagged", flagged, paths.len()))?
+    }
+    Ok(())
+}
+
+fn prune(args: PruneArgs, ctx: &Context) -> crate::Result<()> {
+    let paths = walk_pngs(&args.dir, ctx.cancel)?;
+    let bar = ctx.progress.files(paths.len());
+    let mut failed = 0;
+    for path in &paths {
+        if ctx.cancel.is_cancelled() {
+            bar.finish_and_clear();
+            Err(PngmeError::Cancelled)?
+        }
+        bar.inc(1);
+        match prune_file(path, args.chunk_type, ctx) {
+            Ok(0) => {}
+            Ok(removed) => {
+                bar.suspend(|| println!("{}: removed {} expired chunk(s)", path.display(), removed))
+            }
+            Err(e) => {
+                bar.suspend(|| error!(path = %path.display(), "{}", e));
+                failed += 1;
+            }
+        }
+    }
+    bar.finish_and_clear();
+    if failed > 0 {
+        Err(format!("{} of {} files failed", failed, paths.len()))?
+    }
+    Ok(())
+}
+
+fn find(args: FindArgs, ctx: &Context) -> crate::Result<()> {
+    let pattern = regex::bytes::Regex::new(&args.pattern)?;
+    let paths = expand_paths(&args.file_paths, &args.glob)?;
+    for_each_path(&paths, ctx, |path, out| {
+        with_chunk_refs(path, ctx, |chunks| {
+            for found in find_matches(&chunks, &pattern, args.types, args.decompress) {
+                let matched = String::from_utf8_lossy(&found.matched);
+                let matched = escape(&matched);
+                writeln!(out, "{}\t{}\t{}", found.offset, found.chunk_type, matched)?;
+            }
+            Ok(())
+        })
+    })
+}
+
+fn grep(args: GrepArgs, ctx: &Context) -> crate::Result<()> {
+    let pattern = args.pattern.as_deref().map(Regex::new).transpose()?;
+    let chunk_type = match args.chunk_type {
+        Some(chunk_type) => Some(chunk_type),
+        None => ctx.config.chunk_type()?,
+    };
+    let paths = walk_pngs(&args.dir, ctx.cancel)?;
+    let bar = ctx.progress.files(paths.len());
+    let mut failed = 0;
+    for path in &paths {
+        if ctx.cancel.is_cancelled() {
+            bar.finish_and_clear();
+            Err(PngmeError::Cancelled)?
+        }
+        bar.inc(1);
+        let searched = with_chunk_refs(path, ctx, |chunks| {
+            for chunk in chunks {
+                if grep_matches(chunk, chunk_type.as_ref(), pattern.as_ref()) {
+                    bar.suspend(|| println!("{}:{}", path.display(), ctx.show(chunk)));
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = searched {
+            bar.suspend(|| error!(path = %path.display(), "{}", e));
+            failed += 1;
+        }
+    }
+    bar.finish_and_clear();
+    if failed > 0 {
+        Err(format!("{} of {} files failed", failed, paths.len()))?
+    }
+    Ok(())
+}
+
+fn export_layout(args: ExportLayoutArgs, ctx: &Context) -> crate::Result<()> {
+    let png = read_png(&mut open_input(&args.input_file_path)?, ctx)?;
+    let output = args
+        .output_file_path
+        .as_deref()
+        .unwrap_or(Path::new(STDIO_PATH));
+    let mut out = open_output(output)?;
+    serde_json::to_writer_pretty(&mut out, &Layout::of(&png))?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn import_layout(args: ImportLayoutArgs, ctx: &Context) -> crate::Result<()> {
+    let layout: Layout = serde_json::from_reader(open_input(&args.layout_path)?)?;
+    let mut png = layout.to_png()?;
+    record_change(&mut png, "import-layout", None, ctx)?;
+    save_png(&args.output_file_path, &png, ctx)
+}
+
+/// One line of `apply` output.
+#[derive(Serialize)]
+struct Applied<'a> {
+    path: &'a Path,
+    /// Where the result went, unless nothing was written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<&'a Path>,
+    ok: bool,
+    /// Operations that ran: all of them, or up to and including the one
+    /// that failed.
+    operations: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Run one operation of a plan on the Png.
+fn run_operation(
+    png: &mut Png,
+    operation: &Operation,
+    guard: &TypeGuardArgs,
+    ctx: &Context,
+) -> crate::Result<()> {
+    match operation {
+        Operation::Encode {
+            chunk_type,
+            message,
+            replace,
+            place,
+        } => {
+            guard.check(chunk_type)?;
+            let data = ctx.message_bytes(message.clone().into_bytes());
+            let chunk = Chunk::new(*chunk_type, data.clone())?;
+            add_chunk(png, chunk, *replace, *place);
+            record_change(png, "encode", Some(&data), ctx)
+        }
+        Operation::Remove {
+            chunk_type,
+            all,
+            nth,
+        } => {
+            let removed = if *all {
+                png.remove_all_chunks(*chunk_type)
+            } else {
+                png.remove_nth_chunk(*chunk_type, nth.unwrap_or(0))
+                    .into_iter()
+                    .collect()
+            };
+            if removed.is_empty() {
+                warn_not_found(*chunk_type, png.iter().map(Chunk::chunk_type));
+                return Ok(());
+            }
+            let payload: Vec<u8> = removed.iter().flat_map(|c| c.data()).copied().collect();
+            record_change(png, "remove", Some(&payload), ctx)
+        }
+        Operation::Strip { keep } => {
+            let removed = strip::strip(png, &Preset::keeping(keep));
+            debug!(removed = removed.len(), "stripped ancillary chunks");
+            record_change(png, "strip", None, ctx)
+        }
+        Operation::TextSet { keyword, text } => {
+            plan::set_text(png, keyword, text)?;
+            record_change(png, "text-set", Some(text.as_bytes()), ctx)
+        }
+    }
+}
+
+/// Write the Png over the file at the given path all at once: to a temp
+/// file beside it, with the file's permissions, then renamed into place, so
+/// it is never left half written.  A symlink keeps pointing at the file it
+/// did.  A new file, or stdout, is written directly.
+///
+/// The rename gives the path a new file, so a lock from `lock_file` held
+/// on the old one guards nothing once this returns.  Others waiting on it
+/// notice and lock the new file instead.
+fn replace_png(output: &Path, png: &Png, ctx: &Context) -> crate::Result<()> {
+    replace_file(output, ctx, |file| write_png(file, png))
+}
+
+/// Replace the file at the given path with what `write` writes, all at
+/// once like `replace_png`.
+fn replace_file(
+    output: &Path,
+    ctx: &Context,
+    write: impl FnOnce(&mut dyn Write) -> crate::Result<()>,
+) -> crate::Result<()> {
+    let permissions = match fs::metadata(output) {
+        Ok(metadata) if !is_stdio(output) => metadata.permissions(),
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => return write(&mut open_output(output)?),
+    };
+    back_up(output, ctx)?;
+    let target = match fs::read_link(output) {
+        Ok(_) => fs::canonicalize(output)?,
+        Err(_) => output.to_path_buf(),
+    };
+    let dir = target.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let mut file = tempfile::NamedTempFile::new_in(dir.unwrap_or(Path::new(".")))?;
+    write(&mut file)?;
+    file.as_file().set_permissions(permissions)?;
+    file.persist(&target)?;
+    Ok(())
+}
+
+/// Run every operation of a plan on each of its files, printing an NDJSON
+/// line per file.  A file is only written once all its operations have
+/// run, so one that fails is left as it was.
+fn apply(args: ApplyArgs, ctx: &Context) -> crate::Result<()> {
+    let mut yaml = String::new();
+    open_input(&args.plan_path)?.read_to_string(&mut yaml)?;
+    let dir = match args.plan_path.parent() {
+        Some(dir) if !is_stdio(&args.plan_path) => dir,
+        _ => Path::new(""),
+    };
+    let plan = Plan::parse(&yaml, dir)?;
+    let mut failed = 0;
+    for file in &plan.files {
+        let output = file.output.as_deref().unwrap_or(&file.path);
+        let mut line = Applied {
+            path: &file.path,
+            output: None,
+            ok: false,
+            operations: 0,
+            error: None,
+        };
+        let result = (|| {
+            let _lock = lock_file(&file.path, ctx)?;
+            let mut png = read_png(&mut File::open(&file.path)?, ctx)?;
+            for operation in &file.operations {
+                line.operations += 1;
+                run_operation(&mut png, operation, &args.guard, ctx)
+                    .map_err(|e| format!("{}: {}", operation.name(), e))?;
+            }
+            if !args.dry_run {
+                save_png(output, &png, ctx)?;
+                line.output = Some(output);
+            }
+            crate::Result::Ok(())
+        })();
+        match result {
+            Ok(()) => line.ok = true,
+            Err(e) => {
+                line.error = Some(e.to_string());
+                failed += 1;
+            }
+        }
+        println!("{}", serde_json::to_string(&line)?);
+    }
+    match failed {
+        0 => Ok(()),
+        failed => Err(format!("{} of {} files failed", failed, plan.files.len()))?,
+    }
+}
+
+fn chunk_command(command: ChunkCommand, ctx: &Context) -> crate::Result<()> {
+    match command {
+        ChunkCommand::Export(args) => {
+            let png = read_png(&mut open_input(&args.file_path)?, ctx)?;
+            let chunk = png
+                .nth_chunk_by_type(args.chunk_type, args.nth.unwrap_or(0))
+                .ok_or(PngmeError::ChunkNotFound(args.chunk_type))?;
+            chunk.write_data_to(open_output(&args.data_path)?)?;
+        }
+        ChunkCommand::Import(args) => {
+            args.guard.check(&args.chunk_type)?;
+            if is_stdio(&args.file_path) && is_stdio(&args.data_path) {
+                Err("The PNG and the chunk data can't both come from stdin")?
+            }
+            let chunk = if is_stdio(&args.data_path) {
+                Chunk::new(args.chunk_type, read_bytes(&mut io::stdin())?)?
+            } else {
+                Chunk::from_file(args.chunk_type, &args.data_path)?
+            };
+            let output = args.output_file_path.as_ref().unwrap_or(&args.file_path);
+            let _lock = if output == &args.file_path {
+                lock_file(output, ctx)?
+            } else {
+                None
+            };
+            let mut png = read_png(&mut open_input(&args.file_path)?, ctx)?;
+            let payload = chunk.data().to_vec();
+            add_chunk(&mut png, chunk, args.replace, args.place);
+            record_change(&mut png, "chunk import", Some(&payload), ctx)?;
+            save_png(output, &png, ctx)?;
+        }
+    }
+    Ok(())
+}
+
+fn serve(args: ServeArgs, ctx: &Context) -> crate::Result<()> {
+    let settings = server::Settings {
+        key: ctx.key.clone(),
+        chunk_type: ctx.config.chunk_type()?,
+        body_limit: ctx.config.max_memory(),
+    };
+    if args.grpc {
+        grpc::serve(args.listen, settings)
+    } else {
+        server::serve(args.listen, settings)
+    }
+}
+
+fn configure(command: ConfigCommand, ctx: &Context) -> crate::Result<()> {
+    match command {
+        ConfigCommand::Show => print!("{}", ctx.config.redacted().to_toml()?),
+        ConfigCommand::Set(args) => {
+            let path = ctx
+                .config_path
+                .as_deref()
+                .ok_or("No config file location")?;
+            let mut config = Config::load(path)?;
+            config.set(ctx.profile.as_deref(), &args.key, &args.value)?;
+            config.save(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Send logs to stderr at the level picked by `-v`, as text or JSON.
+fn init_logging(verbose: u8, format: LogFormat) {
+    let level = match verbose {
+        0 => Level::WARN,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr);
+    match format {
+        LogFormat::Text => builder.without_time().with_target(false).init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+pub(crate) fn run(mut cli: Cli) -> crate::Result<()> {
+    init_logging(cli.verbose, cli.log_format);
+    let config_path = cli.config.or_else(Config::default_path);
+    let mut config = match &config_path {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    // Setting a value may create the profile, so don't require it to exist.
+    let setting = matches!(cli.subcommand, Subcommand::Config(ConfigCommand::Set(_)));
+    if let (Some(profile), false) = (&cli.profile, setting) {
+        config = config.with_profile(profile)?;
+    }
+    if cli.no_backup {
+        config.backup = Some(false);
+    }
+    if cli.audit {
+        config.audit = Some(true);
+    }
+    if cli.record_journal {
+        config.journal = Some(true);
+    }
+    // An explicit key replaces whatever key the config holds and turns
+    // encryption on.
+    if let Some(key_file) = cli.key_file {
+        config.key_file = Some(key_file);
+        config.passphrase = None;
+        config.encrypt = Some(true);
+    }
+    if let Some(passphrase) = cli.passphrase {
+        config.passphrase = Some(passphrase);
+        config.encrypt = Some(true);
+    }
+    if let Some(token) = cli.http_token {
+        config.http_token = Some(token);
+    }
+    if let Some(bytes) = cli.max_memory {
+        config.max_memory = Some(bytes);
+    }
+    let key = if config.encrypt() || env::var("ENCRYPT").is_ok() {
+        Some(encryption_key(&config)?)
+    } else {
+        None
+    };
+    if let Subcommand::Decode(args) = &mut cli.subcommand {
+        if args.encoding.is_none() && !args.qr {
+            args.encoding = config.decode_encoding()?;
+        }
+    }
+    cli.subcommand.resolve(config.chunk_type()?.as_ref())?;
+    let ctx = Context {
+        config,
+        config_path,
+        profile: cli.profile,
+        key,
+        progress: Progress::new(!cli.no_progress),
+        lock: !cli.no_lock,
+        deterministic: cli.deterministic,
+        cancel: &INTERRUPTED,
+        max_chunk_length: cli.max_chunk_size.unwrap_or(MAXIMUM_LENGTH),
+        relaxed: cli.relaxed,
+    };
+    match cli.subcommand {
+        Subcommand::Encode(args) => encode(args, &ctx),
+        Subcommand::EncodeR(args) => encoder(args, &ctx),
+        Subcommand::Decode(args) => decode(args, &ctx),
+        Subcommand::DecodeR(args) => decoder(args, &ctx),
+        Subcommand::Remove(args) => remove(args, &ctx),
+        Subcommand::RemoveR(args) => remover(args, &ctx),
+        Subcommand::Print(args) => print(args, &ctx),
+        Subcommand::PrintR(args) => printr(args, &ctx),
+        Subcommand::Verify(args) => verify(args, &ctx),
+        Subcommand::Salvage(args) => salvage(args, &ctx),
+        Subcommand::MigrateFormat(args) => migrate_format(args, &ctx),
+        Subcommand::Grep(args) => grep(args, &ctx),
+        Subcommand::Find(args) => find(args, &ctx),
+        Subcommand::Scan(args) => scan(args, &ctx),
+        Subcommand::Prune(args) => prune(args, &ctx),
+        Subcommand::History(args) => history(args, &ctx),
+        Subcommand::Strip(args) => strip_pngs(args, &ctx),
+        Subcommand::Undo(args) => undo(args, &ctx),
+        Subcommand::Watermark(args) => watermark(args, &ctx),
+        Subcommand::Thumb(args) => thumb(args, &ctx),
+        Subcommand::Stats(args) => stats(args, &ctx),
+        Subcommand::Info(args) => info(args, &ctx),
+        Subcommand::Seal(args) => seal(args, &ctx),
+        Subcommand::CheckSeal(args) => check_seal(args, &ctx),
+        Subcommand::SamePixels(args) => same_pixels(args, &ctx),
+        Subcommand::ExportLayout(args) => export_layout(args, &ctx),
+        Subcommand::ImportLayout(args) => import_layout(args, &ctx),
+        Subcommand::Chunk(command) => chunk_command(command, &ctx),
+        Subcommand::Apply(args) => apply(args, &ctx),
+        Subcommand::Config(command) => configure(command, &ctx),
+        Subcommand::Serve(args) => serve(args, &ctx),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::{Format, MAGIC, VERSION};
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    fn testing_context(key: Option<&str>) -> Context {
+        Context {
+            config: Config::default(),
+            config_path: None,
+            profile: None,
+            key: key.map(str::to_string),
+            progress: Progress::new(false),
+            lock: true,
+            deterministic: false,
+            relaxed: false,
+            cancel: Box::leak(Box::new(AtomicBool::new(false))),
+            max_chunk_length: MAXIMUM_LENGTH,
+        }
+    }
+
+    #[test]
+    fn test_png_from_image_keeps_chunks() {
+        let png = png_from_image(Cursor::new(TURTLE), &testing_context(None)).unwrap();
+        assert_eq!(png.as_bytes(), TURTLE);
+        assert!(png
+            .chunk_by_type(ChunkType::from_str("ruSt").unwrap())
+            .is_some());
+    }
+
+    #[test]
+    fn test_png_from_image_converts_other_formats() {
+        let image = image::load_from_memory(TURTLE).unwrap();
+        let mut bmp = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bmp), image::ImageOutputFormat::Bmp)
+            .unwrap();
+
+        assert!(png_from_image(Cursor::new(bmp), &testing_context(None)).is_ok());
+    }
+
+    #[test]
+    fn test_parse_download_checks_sha256() {
+        let digest = format!("{:x}", Sha256::digest(TURTLE));
+        let mut remote = RemoteArgs {
+            expected_sha256: Some(digest),
+            ..RemoteArgs::default()
+        };
+        assert!(parse_download(Cursor::new(TURTLE), &remote, &testing_context(None)).is_ok());
+
+        remote.expected_sha256 = Some("0".repeat(64));
+        assert!(parse_download(Cursor::new(TURTLE), &remote, &testing_context(None)).is_err());
+    }
+
+    #[test]
+    fn test_read_urls() {
+        let list = "https://example.com/a.png\n\n# comment\n  data:,x  \n";
+        let urls = read_urls(&mut list.as_bytes()).unwrap();
+        assert_eq!(
+            urls.iter().map(Url::as_str).collect::<Vec<_>>(),
+            ["https://example.com/a.png", "data:,x"]
+        );
+
+        assert!(read_urls(&mut "not a url".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_decoded_json() {
+        let line = Decoded {
+            url: "https://example.com/a.png",
+            chunk_type: Some("ruSt".to_string()),
+            message: Some(None),
+            error: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&line).unwrap(),
+            r#"{"url":"https://example.com/a.png","chunk_type":"ruSt","message":null}"#
+        );
+    }
+
+    #[test]
+    fn test_notification_json() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hi".to_vec()).unwrap();
+        let added = Notification::new(Event::Encode, "dice.png", &chunk);
+        assert_eq!(
+            serde_json::to_string(&added).unwrap(),
+            concat!(
+                r#"{"event":"encode","file":"dice.png","chunk_type":"ruSt","#,
+                r#""sha256":"8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4","#,
+                r#""size_delta":14}"#
+            )
+        );
+
+        let removed = Notification::new(Event::Remove, "dice.png", &chunk);
+        assert_eq!(removed.size_delta, -14);
+    }
+
+    #[test]
+    fn test_show_decrypts_with_key() {
+        let ctx = testing_context(Some("hunter2"));
+        let chunk = Chunk::new(
+            ChunkType::from_str("teSt").unwrap(),
+            ctx.message_bytes(b"Secret".to_vec()),
+        )
+        .unwrap();
+        assert_ne!(chunk.data(), b"Secret");
+        assert_eq!(ctx.show(&chunk), "teSt\tSecret");
+
+        let plain = testing_context(None);
+        assert_ne!(plain.show(&chunk), "teSt\tSecret");
+    }
+
+    #[test]
+    fn test_show_describes_standard_chunks() {
+        let ctx = testing_context(None);
+        let header = Chunk::new(ChunkType::IHDR, vec![0, 0, 1, 0]).unwrap();
+        assert_eq!(ctx.show(&header), "IHDR\t[image header]");
+        let text = Chunk::new(ChunkType::TEXT, b"Title\0Turtle".to_vec()).unwrap();
+        assert_eq!(ctx.show(&text), "tEXt\tTitle\\u{0}Turtle");
+    }
+
+    #[test]
+    fn test_show_escapes_like_display() {
+        let ctx = testing_context(None);
+        let rust = ChunkType::from_str("ruSt").unwrap();
+        let hostile = Chunk::new(rust, b"\x1b[2Jhi\n\xe2\x80\xaeok".to_vec()).unwrap();
+        assert_eq!(ctx.show(&hostile), "ruSt\t\\u{1b}[2Jhi\\n\\u{202e}ok");
+        assert_eq!(ctx.show(&hostile), hostile.to_string());
+    }
+
+    #[test]
+    fn test_encryption_key() {
+        let mut config = Config::default();
+        assert_eq!(encryption_key(&config).unwrap(), DEFAULT_KEY);
+
+        config.passphrase = Some("hunter2".to_string());
+        assert_eq!(encryption_key(&config).unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn test_stdio_path() {
+        assert!(is_stdio(Path::new("-")));
+        assert!(!is_stdio(Path::new("./-")));
+        assert!(!is_stdio(Path::new("turtle.png")));
+    }
+
+    #[test]
+    fn test_read_write_png_round_trip() {
+        let png = read_png(&mut Cursor::new(TURTLE), &testing_context(None)).unwrap();
+        let mut out = Vec::new();
+        write_png(&mut out, &png).unwrap();
+        assert_eq!(out, TURTLE);
+    }
+
+    #[test]
+    fn test_add_chunk_place() {
+        let types = |place| {
+            let mut png = read_png(&mut Cursor::new(TURTLE), &testing_context(None)).unwrap();
+            let chunk = Chunk::new(ChunkType::from_str("teSt").unwrap(), b"x".to_vec()).unwrap();
+            add_chunk(&mut png, chunk, false, place);
+            png.chunks()
+                .iter()
+                .map(|c| c.chunk_type().to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(types(None), "IHDR PLTE IDAT IDAT IDAT teSt IEND ruSt");
+        assert_eq!(
+            types(Some(Place::Start)),
+            "IHDR teSt PLTE IDAT IDAT IDAT IEND ruSt"
+        );
+        assert_eq!(
+            types(Some(Place::BeforeIdat)),
+            "IHDR PLTE teSt IDAT IDAT IDAT IEND ruSt"
+        );
+        assert_eq!(
+            types(Some(Place::End)),
+            "IHDR PLTE IDAT IDAT IDAT teSt IEND ruSt"
+        );
+
+        // Appending in place puts it in the same place as rewriting.
+        let chunk = Chunk::new(ChunkType::from_str("teSt").unwrap(), b"x".to_vec()).unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.path(), TURTLE).unwrap();
+        append_in_place(file.path(), &chunk, &testing_context(None)).unwrap();
+        let mut rewritten = read_png(&mut Cursor::new(TURTLE), &testing_context(None)).unwrap();
+        add_chunk(&mut rewritten, chunk, false, None);
+        assert_eq!(fs::read(file.path()).unwrap(), rewritten.as_bytes());
+    }
+
+    /// The subcommand of the given command line, resolved as `main` does.
+    fn subcommand(argv: &[&str]) -> Subcommand {
+        use structopt::StructOpt;
+        let mut cli = Cli::from_iter(iter::once("pngme").chain(argv.iter().copied()));
+        cli.subcommand.resolve(None).unwrap();
+        cli.subcommand
+    }
+
+    #[test]
+    fn test_max_chunk_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        let big = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![b'a'; 100]).unwrap();
+        fs::write(&path, Png::builder().chunk(big).build().unwrap().as_bytes()).unwrap();
+        let path = path.to_str().unwrap();
+        let run = |argv: &[&str], max_chunk_length| {
+            let ctx = Context {
+                max_chunk_length,
+                ..testing_context(None)
+            };
+            match subcommand(argv) {
+                Subcommand::Print(args) => print(args, &ctx),
+                Subcommand::Decode(args) => decode(args, &ctx),
+                Subcommand::Stats(args) => stats(args, &ctx),
+                _ => unreachable!(),
+            }
+        };
+        for argv in [&["print", path][..], &["decode", path, "ruSt"]] {
+            run(argv, 100).unwrap();
+            let e = run(argv, 99).unwrap_err().to_string();
+            assert!(e.contains("over the limit of 99 bytes"), "{}", e);
+        }
+        // Batches report the file as failed.
+        run(&["stats", path], 100).unwrap();
+        assert!(run(&["stats", path], 99).is_err());
+    }
+
+    #[test]
+    fn test_encode_output_verifies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        fs::write(path("a.png"), Png::builder().build().unwrap().as_bytes()).unwrap();
+        let ctx = testing_context(None);
+        let run = |argv: &[&str]| match subcommand(argv) {
+            Subcommand::Encode(args) => encode(args, &ctx).unwrap(),
+            _ => unreachable!(),
+        };
+        // In place, which only appends, then rewritten to another file.
+        run(&["encode", &path("a.png"), "ruSt", "hi"]);
+        run(&[
+            "encode",
+            &path("a.png"),
+            "teSt",
+            "there",
+            "-o",
+            &path("b.png"),
+        ]);
+        for name in ["a.png", "b.png"] {
+            let bytes = fs::read(path(name)).unwrap();
+            assert_eq!(Png::validate(&bytes), [], "{}", name);
+            match subcommand(&["verify", &path(name)]) {
+                Subcommand::Verify(args) => verify(args, &ctx).unwrap(),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_relaxed_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        let rust_type = ChunkType::from_str("ruSt").unwrap();
+        let rust = Chunk::new(rust_type, b"kept".to_vec()).unwrap();
+        let kept = Png::builder().chunk(rust).build().unwrap();
+        // The Png with chunks whose types have digits just before ruSt.
+        let at = Png::STANDARD_HEADER.len()
+            + kept.chunks()[..kept.position_of(rust_type).unwrap()]
+                .iter()
+                .map(Chunk::encoded_len)
+                .sum::<usize>();
+        let with_odd = |types: &[&[u8; 4]]| {
+            let mut bytes = kept.as_bytes();
+            for raw in types.iter().rev() {
+                let mut odd = 3u32.to_be_bytes().to_vec();
+                odd.extend(*raw);
+                odd.extend(b"odd");
+                odd.extend(crate::chunk::checksum(**raw, b"odd").to_be_bytes());
+                bytes.splice(at..at, odd);
+            }
+            bytes
+        };
+        fs::write(&path, with_odd(&[b"r2St", b"r3St"])).unwrap();
+        let remove = |ctx: &Context, chunk_type| match subcommand(&[
+            "remove",
+            path.to_str().unwrap(),
+            chunk_type,
+        ]) {
+            Subcommand::Remove(args) => remove(args, ctx),
+            _ => unreachable!(),
+        };
+
+        let error = remove(&testing_context(None), "r2St").unwrap_err();
+        assert!(error.to_string().contains("--relaxed"), "{}", error);
+        let relaxed = Context {
+            relaxed: true,
+            ..testing_context(None)
+        };
+        // The other odd chunk is written back as it was.
+        remove(&relaxed, "r2St").unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(bytes, with_odd(&[b"r3St"]));
+        let chunks = Png::raw_chunk_refs(&bytes, MAXIMUM_LENGTH).unwrap();
+        let shown: Vec<String> = chunks.into_iter().map(|c| relaxed.show_raw(c)).collect();
+        assert!(shown.contains(&"r3St\todd".to_string()), "{:?}", shown);
+        assert!(shown.contains(&"ruSt\tkept".to_string()), "{:?}", shown);
+
+        remove(&relaxed, "r3St").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), kept.as_bytes());
+    }
+
+    #[test]
+    fn test_decode_raw_needs_single_png() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.png", "b.png"] {
+            fs::write(dir.path().join(name), TURTLE).unwrap();
+        }
+        let pattern = dir.path().join("*.png");
+        let argv = [
+            "decode",
+            pattern.to_str().unwrap(),
+            "ruSt",
+            "--encoding",
+            "raw",
+        ];
+        let error = match subcommand(&argv) {
+            Subcommand::Decode(args) => decode(args, &testing_context(None)).unwrap_err(),
+            _ => unreachable!(),
+        };
+        assert!(error.to_string().contains("not 2"), "{}", error);
+    }
+
+    #[test]
+    fn test_encode_binary_message_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        fs::write(path("a.png"), Png::builder().build().unwrap().as_bytes()).unwrap();
+        let message = [0x89, 0, 0xff, 0xfe, b'\n'];
+        fs::write(path("message.bin"), message).unwrap();
+        let ctx = testing_context(None);
+        match subcommand(&[
+            "encode",
+            &path("a.png"),
+            "ruSt",
+            "--message-file",
+            &path("message.bin"),
+        ]) {
+            Subcommand::Encode(args) => encode(args, &ctx).unwrap(),
+            _ => unreachable!(),
+        }
+
+        let png = Png::try_from(&fs::read(path("a.png")).unwrap()[..]).unwrap();
+        let chunk = png.chunk_by_type(ChunkType::from_str("ruSt").unwrap());
+        let reveal = Reveal {
+            encoding: Encoding::Raw,
+            ..Reveal::default()
+        };
+        let mut out = Vec::new();
+        let chunks = chunk.map(|c| (0, ChunkRef::from(c))).into_iter();
+        write_decoded(chunks, reveal, &mut out, &ctx).unwrap();
+        assert_eq!(out, message);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_undo_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let mut ctx = testing_context(None);
+        ctx.config.journal = Some(true);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        fs::write(&path, TURTLE).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let chunk = Chunk::new(ChunkType::from_str("teSt").unwrap(), b"x".to_vec()).unwrap();
+        append_in_place(&path, &chunk, &ctx).unwrap();
+        let args = UndoArgs {
+            file_path: path.clone(),
+            force: false,
+        };
+        undo(args, &ctx).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), TURTLE);
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+
+    #[test]
+    fn test_journaled_undo() {
+        let mut ctx = testing_context(None);
+        ctx.config.journal = Some(true);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        fs::write(&path, TURTLE).unwrap();
+
+        let chunk = Chunk::new(ChunkType::from_str("teSt").unwrap(), b"x".to_vec()).unwrap();
+        append_in_place(&path, &chunk, &ctx).unwrap();
+        let mut png = read_png(&mut File::open(&path).unwrap(), &testing_context(None)).unwrap();
+        png.remove_all_chunks(ChunkType::from_str("ruSt").unwrap());
+        save_png(&path, &png, &ctx).unwrap();
+
+        let undo_args = || UndoArgs {
+            file_path: path.clone(),
+            force: false,
+        };
+        undo(undo_args(), &ctx).unwrap();
+        undo(undo_args(), &ctx).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), TURTLE);
+        assert!(undo(undo_args(), &ctx).is_err());
+
+        // A change pngme didn't journal is not undone over.
+        append_in_place(&path, &chunk, &ctx).unwrap();
+        fs::write(&path, TURTLE).unwrap();
+        assert!(undo(undo_args(), &ctx).is_err());
+    }
+
+    #[test]
+    fn test_write_decoded() {
+        let mut png = read_png(&mut Cursor::new(TURTLE), &testing_context(None)).unwrap();
+        png.append_chunk(
+            Chunk::new(ChunkType::from_str("teSt").unwrap(), b"Secret".to_vec()).unwrap(),
+        );
+        png.append_chunk(
+            Chunk::new(ChunkType::from_str("teSt").unwrap(), b"Another".to_vec()).unwrap(),
+        );
+        let ctx = testing_context(None);
+        let decoded = |select: SelectArgs| {
+            let chunks = png.chunks_by_type(ChunkType::from_str("teSt").unwrap());
+            let mut out = Vec::new();
+            let reveal = Reveal {
+                numbered: select.all,
+                ..Reveal::default()
+            };
+            write_decoded(
+                select.pick(chunks.map(ChunkRef::from)),
+                reveal,
+                &mut out,
+                &ctx,
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(decoded(SelectArgs::default()), "teSt\tSecret\n");
+        let all = SelectArgs {
+            all: true,
+            nth: None,
+        };
+        assert_eq!(decoded(all), "0\tteSt\tSecret\n1\tteSt\tAnother\n");
+        let second = SelectArgs {
+            all: false,
+            nth: Some(1),
+        };
+        assert_eq!(decoded(second), "teSt\tAnother\n");
+        let missing = SelectArgs {
+            all: false,
+            nth: Some(2),
+        };
+        assert_eq!(decoded(missing), "");
+    }
+
+    #[test]
+    fn test_write_decoded_meta() {
+        let ctx = testing_context(Some("hunter2"));
+        let envelope = Envelope {
+            author: Some("alice".to_string()),
+            message: crate::encrypt::encrypt("Secret".to_string(), "hunter2"),
+            ..Envelope::default()
+        };
+        let chunk = Chunk::new(
+            ChunkType::from_str("teSt").unwrap(),
+            envelope.to_data().into_bytes(),
+        )
+        .unwrap();
+        assert_eq!(ctx.show(&chunk), "teSt\tSecret");
+
+        let mut out = Vec::new();
+        let chunks = iter::once((0, ChunkRef::from(&chunk)));
+        let reveal = Reveal {
+            show_meta: true,
+            ..Reveal::default()
+        };
+        write_decoded(chunks, reveal, &mut out, &ctx).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "teSt\tSecret\n\tauthor: alice\n"
+        );
+    }
+
+    #[test]
+    fn test_write_decoded_encoding() {
+        let ctx = testing_context(None);
+        let rust = ChunkType::from_str("ruSt").unwrap();
+        let text = Chunk::new(rust, b"hi".to_vec()).unwrap();
+        let binary = Chunk::new(rust, vec![0, 0xff]).unwrap();
+        let encoded = |chunk: &Chunk, encoding: Encoding| {
+            let mut out = Vec::new();
+            let reveal = Reveal {
+                encoding,
+                ..Reveal::default()
+            };
+            let chunks = iter::once((0, ChunkRef::from(chunk)));
+            write_decoded(chunks, reveal, &mut out, &ctx).unwrap();
+            out
+        };
+        assert_eq!(encoded(&text, Encoding::Base64), b"ruSt\taGk=\n");
+        assert_eq!(encoded(&text, Encoding::Hex), b"ruSt\t6869\n");
+        assert_eq!(encoded(&binary, Encoding::Utf8), b"ruSt\t[data]\n");
+        assert_eq!(encoded(&binary, Encoding::Hex), b"ruSt\t00ff\n");
+        assert_eq!(encoded(&binary, Encoding::Raw), [0, 0xff]);
+    }
+
+    #[test]
+    fn test_find_matches() {
+        let rust = ChunkType::from_str("ruSt").unwrap();
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(b"a hidden secret", 6);
+        let ztxt = [&b"Comment\0\0"[..], &compressed].concat();
+        let chunks = [
+            Chunk::new(ChunkType::IHDR, b"secret".to_vec()).unwrap(),
+            Chunk::new(rust, b"no match".to_vec()).unwrap(),
+            Chunk::new(ChunkType::ZTXT, ztxt).unwrap(),
+        ];
+        let chunks: Vec<ChunkRef> = chunks.iter().map(ChunkRef::from).collect();
+        let pattern = regex::bytes::Regex::new("se?cre+t").unwrap();
+
+        let found = find_matches(&chunks, &pattern, TypeFilter::All, false);
+        let ihdr = Found {
+            offset: 8,
+            chunk_type: ChunkType::IHDR,
+            matched: b"secret".to_vec(),
+        };
+        assert_eq!(found, [ihdr]);
+
+        let found = find_matches(&chunks, &pattern, TypeFilter::Ancillary, true);
+        let ztxt = Found {
+            offset: 8 + 18 + 20,
+            chunk_type: ChunkType::ZTXT,
+            matched: b"secret".to_vec(),
+        };
+        assert_eq!(found, [ztxt]);
+    }
+
+    /// exiftool 12.76 on a 1x1 PNG with mode 640 and a missing file, the
+    /// directory and local dates filled in.
+    const EXIFTOOL_OUTPUT: &str = "\
+======== {dir}/a.png
+ExifTool Version Number         : 12.76
+File Name                       : a.png
+Directory                       : {dir}
+File Size                       : {size} bytes
+File Modification Date/Time     : {modified}
+File Access Date/Time           : {modified}
+File Inode Change Date/Time     : {changed}
+File Permissions                : -rw-r-----
+File Type                       : PNG
+File Type Extension             : png
+MIME Type                       : image/png
+Image Width                     : 1
+Image Height                    : 1
+Bit Depth                       : 8
+Color Type                      : Grayscale
+Compression                     : Deflate/Inflate
+Filter                          : Adaptive
+Interlace                       : Noninterlaced
+Image Size                      : 1x1
+Megapixels                      : 0.000001
+    1 image files read
+    1 files could not be read
+";
+
+    #[cfg(unix)]
+    #[test]
+    fn test_info_matches_exiftool() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        use std::time::{Duration, SystemTime};
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        let png = Png::builder().build().unwrap().as_bytes();
+        fs::write(&path, &png).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_714_558_830);
+        let times = fs::FileTimes::new()
+            .set_modified(modified)
+            .set_accessed(modified);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_times(times)
+            .unwrap();
+        let changed = fs::metadata(&path).unwrap().ctime();
+        let changed = SystemTime::UNIX_EPOCH + Duration::from_secs(changed as u64);
+
+        let paths = [path, dir.path().join("missing.png")];
+        let mut out = Vec::new();
+        assert_eq!(
+            write_exiftool_report(&paths, &mut out, &testing_context(None)).unwrap(),
+            1
+        );
+        let expected = EXIFTOOL_OUTPUT
+            .replace("{dir}", &dir.path().display().to_string())
+            .replace("{size}", &png.len().to_string())
+            .replace("{modified}", &info::local_date(modified))
+            .replace("{changed}", &info::local_date(changed));
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_stats() {
+        let png = read_png(&mut Cursor::new(TURTLE), &testing_context(None)).unwrap();
+        let chunks: Vec<ChunkRef> = png.chunks().iter().map(ChunkRef::from).collect();
+        let mut stats = Stats::default();
+        stats.add(Path::new("a.png"), &chunks);
+        stats.add(Path::new("b.png"), &chunks);
+        stats.finish(2);
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.bytes, 2 * TURTLE.len() as u64);
+        let idat = &stats.types[0];
+        assert_eq!((idat.chunk_type.as_str(), idat.count), ("IDAT", 6));
+        let total: f64 = stats.types.iter().map(|t| t.percent).sum();
+        // The rest is the signatures.
+        let signatures = 2.0 * 8.0 * 100.0 / stats.bytes as f64;
+        assert!((total + signatures - 100.0).abs() < 1e-9);
+        assert_eq!(stats.largest.len(), 2);
+        assert_eq!(stats.largest[0].bytes, 12 + 8192);
+
+        let mut out = Vec::new();
+        stats.write_table(&mut out).unwrap();
+        let table = String::from_utf8(out).unwrap();
+        assert!(table.starts_with("2 file(s), "), "{}", table);
+        assert!(
+            table.contains("\nIDAT         6       41366    97.1%\n"),
+            "{}",
+            table
+        );
+        assert!(table.contains("IDAT  a.png[2]\n"), "{}", table);
+    }
+
+    #[test]
+    fn test_migrated() {
+        let rust = ChunkType::from_str("ruSt").unwrap();
+        let raw = Chunk::new(rust, b"Secret".to_vec()).unwrap();
+        let upgraded = migrated(&raw).unwrap().unwrap();
+        assert_eq!(Format::of(upgraded.data()), Format::Versioned(VERSION));
+        assert_eq!(testing_context(None).show(&upgraded), "ruSt\tSecret");
+        assert!(migrated(&upgraded).unwrap().is_none());
+
+        let itxt = InternationalText::new("Comment", "Hallo")
+            .to_chunk()
+            .unwrap();
+        let upgraded = migrated(&itxt).unwrap().unwrap();
+        let text = InternationalText::from_data(upgraded.data()).unwrap().text;
+        assert!(text.starts_with(MAGIC));
+        assert_eq!(testing_context(None).show(&upgraded), "iTXt\tHallo");
+    }
+
+    #[test]
+    fn test_expand_paths() {
+        let glob = GlobArgs {
+            exclude: vec![glob::Pattern::new("src/main.rs").unwrap()],
+        };
+        let paths = expand_paths(&[PathBuf::from("-"), PathBuf::from("src/*.rs")], &glob).unwrap();
+        assert_eq!(paths[0], PathBuf::from("-"));
+        assert!(paths.contains(&PathBuf::from("src/commands.rs")));
+        assert!(!paths.contains(&PathBuf::from("src/main.rs")));
+
+        assert!(expand_paths(&[PathBuf::from("src/*.nope")], &glob).is_err());
+    }
+
+    #[test]
+    fn test_lock_file() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let ctx = testing_context(None);
+        let lock = lock_file(file.path(), &ctx).unwrap();
+        assert!(lock.is_some());
+        let other = File::open(file.path()).unwrap();
+        assert!(other.try_lock().is_err());
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+
+        assert!(lock_file(Path::new("-"), &ctx).unwrap().is_none());
+        let unlocked = Context {
+            lock: false,
+            ..testing_context(None)
+        };
+        assert!(lock_file(file.path(), &unlocked).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_lock_after_replace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        fs::write(&path, TURTLE).unwrap();
+        let ctx = testing_context(None);
+        let lock = lock_file(&path, &ctx).unwrap().unwrap();
+        // Waits for the lock, then finds the file replaced under it.
+        let waiter = thread::spawn({
+            let path = path.clone();
+            move || lock_file(&path, &testing_context(None)).unwrap().unwrap()
+        });
+        thread::sleep(std::time::Duration::from_millis(50));
+        replace_png(&path, &Png::try_from(TURTLE).unwrap(), &ctx).unwrap();
+        drop(lock);
+        let relocked = waiter.join().unwrap();
+        assert!(is_at(&relocked, &path).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replace_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        fs::write(&path, TURTLE).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        replace_png(
+            &path,
+            &Png::try_from(TURTLE).unwrap(),
+            &testing_context(None),
+        )
+        .unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o644);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_replaces_whole_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        let link = dir.path().join("link.png");
+        fs::write(&path, TURTLE).unwrap();
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        let mut old = File::open(&path).unwrap();
+
+        let mut png = Png::try_from(TURTLE).unwrap();
+        png.append_chunk(Chunk::new(ChunkType::from_str("teSt").unwrap(), b"x".to_vec()).unwrap());
+        save_png(&link, &png, &testing_context(None)).unwrap();
+        // Written beside and renamed over, not truncated in place.
+        assert_eq!(read_bytes(&mut old).unwrap(), TURTLE);
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read(&path).unwrap(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_expiry() {
+        let rust = ChunkType::from_str("ruSt").unwrap();
+        let sealed = |expires_at: &str| {
+            let envelope = Envelope {
+                expires_at: Some(expires_at.to_string()),
+                message: "Secret".to_string(),
+                ..Envelope::default()
+            };
+            Chunk::new(rust, envelope.to_data().into_bytes()).unwrap()
+        };
+        let expired = sealed("2000-01-01T00:00:00Z");
+        let current = sealed("9999-01-01T00:00:00Z");
+        assert!(check_expiry((&expired).into(), false).is_err());
+        assert!(check_expiry((&expired).into(), true).is_ok());
+        assert!(check_expiry((&current).into(), false).is_ok());
+
+        let mut png = read_png(&mut Cursor::new(TURTLE), &testing_context(None)).unwrap();
+        png.append_chunk(expired);
+        png.append_chunk(current);
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.path(), png.as_bytes()).unwrap();
+        let ctx = testing_context(None);
+        assert_eq!(
+            prune_file(file.path(), Some(ChunkType::IEND), &ctx).unwrap(),
+            0
+        );
+        assert_eq!(prune_file(file.path(), None, &ctx).unwrap(), 1);
+        let pruned = read_png(
+            &mut File::open(file.path()).unwrap(),
+            &testing_context(None),
+        )
+        .unwrap();
+        assert_eq!(pruned.len(), png.len() - 1);
+        let end = pruned.position_of(ChunkType::IEND).unwrap();
+        assert_eq!(ctx.text(&pruned[end - 1]), "Secret");
+    }
+
+    #[test]
+    fn test_backup_path() {
+        assert_eq!(
+            backup_path(Path::new("a/b.png")),
+            PathBuf::from("a/b.png.bak")
+        );
+    }
+
+    #[test]
+    fn test_glob_base() {
+        assert_eq!(glob_base(Path::new("a/b/c.png")), PathBuf::from("a/b"));
+        assert_eq!(glob_base(Path::new("a/**/*.png")), PathBuf::from("a"));
+        assert_eq!(glob_base(Path::new("a/b*/c.png")), PathBuf::from("a"));
+        assert_eq!(glob_base(Path::new("*.png")), PathBuf::new());
+    }
+
+    #[test]
+    fn test_mirrored() {
+        let out = Path::new("/out");
+        assert_eq!(
+            mirrored(out, Path::new("a"), Path::new("a/b/c.png")),
+            PathBuf::from("/out/b/c.png")
+        );
+        assert_eq!(
+            mirrored(out, Path::new(""), Path::new("c.png")),
+            PathBuf::from("/out/c.png")
+        );
+    }
+
+    #[test]
+    fn test_grep_matches() {
+        let chunk = Chunk::new(ChunkType::from_str("teSt").unwrap(), b"Secret".to_vec()).unwrap();
+        let other = ChunkType::from_str("ruSt").unwrap();
+        let re = Regex::new("^Sec").unwrap();
+
+        assert!(grep_matches(&chunk, None, None));
+        assert!(grep_matches(&chunk, None, Some(&re)));
+        assert!(!grep_matches(&chunk, Some(&other), None));
+        assert!(!grep_matches(
+            &chunk,
+            None,
+            Some(&Regex::new("nope").unwrap())
+        ));
+
+        let public = Chunk::new(ChunkType::from_str("TEST").unwrap(), b"Secret".to_vec()).unwrap();
+        assert!(!grep_matches(&public, None, Some(&re)));
+    }
+
+    #[test]
+    fn test_batches_stop_once_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = [dir.path().join("a.png"), dir.path().join("b.png")];
+        for path in &paths {
+            fs::write(path, TURTLE).unwrap();
+        }
+        let ctx = testing_context(None);
+        assert_eq!(walk_pngs(dir.path(), ctx.cancel).unwrap(), paths);
+
+        ctx.cancel.store(true, Ordering::Relaxed);
+        let cancelled = PngmeError::Cancelled.to_string();
+        let walked = walk_pngs(dir.path(), ctx.cancel);
+        assert_eq!(walked.unwrap_err().to_string(), cancelled);
+        let mut visited = 0;
+        let each = for_each_path(&paths, &ctx, |_, _| {
+            visited += 1;
+            Ok(())
+        });
+        assert_eq!(each.unwrap_err().to_string(), cancelled);
+        assert_eq!(visited, 0);
+        let scanned = scan(
+            ScanArgs {
+                dir: dir.path().to_path_buf(),
+            },
+            &ctx,
+        );
+        assert_eq!(scanned.unwrap_err().to_string(), cancelled);
+    }
+
+    #[test]
+    fn test_disabled_progress_is_hidden() {
+        let progress = Progress::new(false);
+        assert!(progress.files(3).is_hidden());
+        assert!(progress.download(Some(10)).is_hidden());
+        assert!(progress.download(None).is_hidden());
+    }
+
+    #[test]
+    fn test_prefixed() {
+        let mut out = Prefixed::new("a.png".to_string(), Vec::new());
+        write!(out, "IHDR\tfoo\nIE").unwrap();
+        writeln!(out, "ND").unwrap();
+        assert_eq!(out.inner, b"a.png:IHDR\tfoo\na.png:IEND\n");
     }
 }
@@ -0,0 +1,451 @@
+use crate::args::Encoding;
+use crate::chunk_type::ChunkType;
+use crate::strip::PresetConfig;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::{env, fs};
+
+/// Defaults loaded from `~/.config/pngme/config.toml`.  CLI flags win.
+///
+/// Named profiles under `[profiles.<name>]` hold the same settings and
+/// override the top-level ones when selected with `--profile`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Chunk type used when none is given.
+    pub(crate) chunk_type: Option<String>,
+    /// Encrypt messages on encode and decrypt them on display, like `ENCRYPT`.
+    pub(crate) encrypt: Option<bool>,
+    /// Encryption passphrase.
+    pub(crate) passphrase: Option<String>,
+    /// File holding the encryption passphrase.
+    pub(crate) key_file: Option<PathBuf>,
+    /// Keep a `.bak` copy of a PNG before overwriting it in place.
+    pub(crate) backup: Option<bool>,
+    /// Record every change to a PNG in its audit chunk.
+    pub(crate) audit: Option<bool>,
+    /// Keep an undo journal beside each PNG changed in place.
+    pub(crate) journal: Option<bool>,
+    /// Timeout for remote requests, in seconds.
+    pub(crate) timeout: Option<u64>,
+    /// Bearer token sent with remote requests.
+    pub(crate) http_token: Option<String>,
+    /// Bytes of a download held in memory before it spills to a temp file.
+    pub(crate) max_memory: Option<usize>,
+    /// Gateway that `ipfs://` URLs are fetched through.
+    pub(crate) ipfs_gateway: Option<String>,
+    /// IPFS node API that `--pin-to-ipfs` adds files through.
+    pub(crate) ipfs_api: Option<String>,
+    /// User-Agent sent with remote requests.
+    pub(crate) user_agent: Option<String>,
+    /// Extra headers sent with remote requests, by name.  `--header` wins
+    /// over one of the same name.
+    pub(crate) headers: Option<BTreeMap<String, String>>,
+    /// `text` or `json`, for commands that can print either, like `stats`.
+    pub(crate) output_format: Option<String>,
+    /// How `decode` prints messages unless `--encoding` is given.
+    pub(crate) decode_encoding: Option<String>,
+    /// Presets for `strip --preset`, by name, on top of the built-in ones.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) strip_presets: BTreeMap<String, PresetConfig>,
+    /// Named sets of settings.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) profiles: BTreeMap<String, Config>,
+}
+
+impl Config {
+    /// Keys accepted by `set`.
+    pub(crate) const KEYS: [&'static str; 16] = [
+        "chunk_type",
+        "encrypt",
+        "passphrase",
+        "key_file",
+        "backup",
+        "audit",
+        "journal",
+        "timeout",
+        "http_token",
+        "max_memory",
+        "ipfs_gateway",
+        "ipfs_api",
+        "user_agent",
+        "headers",
+        "output_format",
+        "decode_encoding",
+    ];
+
+    /// Default config file location, honoring `XDG_CONFIG_HOME`.
+    pub(crate) fn default_path() -> Option<PathBuf> {
+        let base = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("pngme").join("config.toml"))
+    }
+
+    /// Load the config at the given path.  A missing file is an empty config.
+    pub(crate) fn load(path: &Path) -> crate::Result<Config> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let config: Config = toml::from_str(&fs::read_to_string(path)?)?;
+        config.check()?;
+        for (name, profile) in &config.profiles {
+            if !profile.profiles.is_empty() {
+                Err(format!("Profile '{}' can't hold other profiles", name))?
+            }
+            profile.check()?;
+        }
+        Ok(config)
+    }
+
+    /// `Err` if a chunk type, output format, encoding or strip preset in the
+    /// settings is invalid.
+    fn check(&self) -> crate::Result<()> {
+        self.chunk_type()?;
+        if let Some(format) = &self.output_format {
+            check_output_format(format)?;
+        }
+        self.decode_encoding()?;
+        for (name, preset) in &self.strip_presets {
+            preset
+                .to_preset()
+                .map_err(|e| format!("Strip preset '{}': {}", name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Write this config to the given path, creating its directory.
+    pub(crate) fn save(&self, path: &Path) -> crate::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_toml()?)?;
+        Ok(())
+    }
+
+    /// This config as TOML.
+    pub(crate) fn to_toml(&self) -> crate::Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Settings of the named profile laid over the top-level ones.  `Err` if
+    /// there is no such profile.
+    pub(crate) fn with_profile(mut self, name: &str) -> crate::Result<Config> {
+        let profile = self
+            .profiles
+            .remove(name)
+            .ok_or_else(|| format!("Unknown profile '{}'", name))?;
+        self.merge(profile);
+        self.profiles.clear();
+        Ok(self)
+    }
+
+    /// Take every setting present in `other`.
+    fn merge(&mut self, other: Config) {
+        macro_rules! take {
+            ($($field:ident),*) => {
+                $(if other.$field.is_some() {
+                    self.$field = other.$field;
+                })*
+            };
+        }
+        take!(
+            chunk_type,
+            encrypt,
+            passphrase,
+            key_file,
+            backup,
+            audit,
+            journal,
+            timeout,
+            http_token,
+            max_memory,
+            ipfs_gateway,
+            ipfs_api,
+            user_agent,
+            headers,
+            output_format,
+            decode_encoding
+        );
+        self.strip_presets.extend(other.strip_presets);
+    }
+
+    /// Copy of the settings with secrets hidden, for display.
+    pub(crate) fn redacted(&self) -> Config {
+        let hide = |secret: &Option<String>| secret.as_ref().map(|_| "********".to_string());
+        Config {
+            chunk_type: self.chunk_type.clone(),
+            encrypt: self.encrypt,
+            passphrase: hide(&self.passphrase),
+            key_file: self.key_file.clone(),
+            backup: self.backup,
+            audit: self.audit,
+            journal: self.journal,
+            timeout: self.timeout,
+            http_token: hide(&self.http_token),
+            max_memory: self.max_memory,
+            ipfs_gateway: self.ipfs_gateway.clone(),
+            ipfs_api: self.ipfs_api.clone(),
+            user_agent: self.user_agent.clone(),
+            headers: self.headers.as_ref().map(|headers| {
+                headers
+                    .keys()
+                    .map(|name| (name.clone(), "********".to_string()))
+                    .collect()
+            }),
+            output_format: self.output_format.clone(),
+            decode_encoding: self.decode_encoding.clone(),
+            strip_presets: self.strip_presets.clone(),
+            profiles: BTreeMap::new(),
+        }
+    }
+
+    /// Default chunk type, if set.  `Err` if it is not a valid chunk type.
+    pub(crate) fn chunk_type(&self) -> crate::Result<Option<ChunkType>> {
+        Ok(self
+            .chunk_type
+            .as_deref()
+            .map(ChunkType::from_str)
+            .transpose()?)
+    }
+
+    /// Should commands that can print JSON do so.
+    pub(crate) fn json_output(&self) -> bool {
+        self.output_format.as_deref() == Some("json")
+    }
+
+    /// Default `decode` encoding, if set.  `Err` if it is not one.
+    pub(crate) fn decode_encoding(&self) -> crate::Result<Option<Encoding>> {
+        Ok(self
+            .decode_encoding
+            .as_deref()
+            .map(Encoding::from_str)
+            .transpose()?)
+    }
+
+    /// Is encryption turned on.
+    pub(crate) fn encrypt(&self) -> bool {
+        self.encrypt.unwrap_or(false)
+    }
+
+    /// Are backups turned on.
+    pub(crate) fn backup(&self) -> bool {
+        self.backup.unwrap_or(false)
+    }
+
+    /// Is the audit trail turned on.
+    pub(crate) fn audit(&self) -> bool {
+        self.audit.unwrap_or(false)
+    }
+
+    /// Is the undo journal turned on.
+    pub(crate) fn journal(&self) -> bool {
+        self.journal.unwrap_or(false)
+    }
+
+    /// Download bytes held in memory, 64 MiB unless set.
+    pub(crate) fn max_memory(&self) -> usize {
+        self.max_memory.unwrap_or(64 * 1024 * 1024)
+    }
+
+    /// Set the given key from its string form, in the named profile if any.
+    /// `Err` on unknown key or bad value.
+    pub(crate) fn set(
+        &mut self,
+        profile: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> crate::Result<()> {
+        let target = match profile {
+            Some(name) => self.profiles.entry(name.to_string()).or_default(),
+            None => self,
+        };
+        match key {
+            "chunk_type" => {
+                ChunkType::from_str(value)?;
+                target.chunk_type = Some(value.to_string());
+            }
+            "encrypt" => target.encrypt = Some(value.parse()?),
+            "passphrase" => target.passphrase = Some(value.to_string()),
+            "key_file" => target.key_file = Some(PathBuf::from(value)),
+            "backup" => target.backup = Some(value.parse()?),
+            "audit" => target.audit = Some(value.parse()?),
+            "journal" => target.journal = Some(value.parse()?),
+            "timeout" => target.timeout = Some(value.parse()?),
+            "http_token" => target.http_token = Some(value.to_string()),
+            "max_memory" => target.max_memory = Some(value.parse()?),
+            "ipfs_gateway" => target.ipfs_gateway = Some(Url::parse(value)?.to_string()),
+            "ipfs_api" => target.ipfs_api = Some(Url::parse(value)?.to_string()),
+            "user_agent" => target.user_agent = Some(value.to_string()),
+            "headers" => {
+                let (name, header) = value
+                    .split_once(':')
+                    .filter(|(name, _)| !name.trim().is_empty())
+                    .ok_or_else(|| format!("expected 'Name: value', got '{}'", value))?;
+                let headers = target.headers.get_or_insert_with(BTreeMap::new);
+                headers.insert(name.trim().to_string(), header.trim().to_string());
+            }
+            "output_format" => {
+                check_output_format(value)?;
+                target.output_format = Some(value.to_string());
+            }
+            "decode_encoding" => {
+                Encoding::from_str(value)?;
+                target.decode_encoding = Some(value.to_string());
+            }
+            _ => Err(format!(
+                "Unknown config key '{}' (expected one of {})",
+                key,
+                Config::KEYS.join(", ")
+            ))?,
+        }
+        Ok(())
+    }
+}
+
+/// `Err` unless the output format is `text` or `json`.
+fn check_output_format(format: &str) -> crate::Result<()> {
+    match format {
+        "text" | "json" => Ok(()),
+        _ => Err(format!("unknown output format '{}'", format))?,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            chunk_type = "ruSt"
+            encrypt = true
+            timeout = 10
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.chunk_type().unwrap().unwrap().to_string(), "ruSt");
+        assert!(config.encrypt());
+        assert!(!config.backup());
+        assert_eq!(config.timeout, Some(10));
+    }
+
+    #[test]
+    fn test_unknown_key_is_err() {
+        assert!(toml::from_str::<Config>("colour = true").is_err());
+    }
+
+    #[test]
+    fn test_set() {
+        let mut config = Config::default();
+        config.set(None, "chunk_type", "teSt").unwrap();
+        config.set(None, "backup", "true").unwrap();
+        config.set(None, "audit", "true").unwrap();
+        config.set(None, "journal", "true").unwrap();
+        config.set(None, "timeout", "5").unwrap();
+        config.set(None, "max_memory", "1024").unwrap();
+
+        assert_eq!(config.chunk_type.as_deref(), Some("teSt"));
+        assert_eq!(config.max_memory(), 1024);
+        assert!(config.backup());
+        assert!(config.audit());
+        assert!(config.journal());
+        assert_eq!(config.timeout, Some(5));
+
+        assert!(config.set(None, "chunk_type", "Ru1t").is_err());
+        assert!(config.set(None, "encrypt", "maybe").is_err());
+        assert!(config.set(None, "ipfs_gateway", "not a url").is_err());
+        assert!(config.set(None, "colour", "true").is_err());
+    }
+
+    #[test]
+    fn test_output_defaults() {
+        let mut config = Config::default();
+        assert!(!config.json_output());
+        assert_eq!(config.decode_encoding().unwrap(), None);
+
+        config.set(None, "output_format", "json").unwrap();
+        config.set(None, "decode_encoding", "hex").unwrap();
+        assert!(config.json_output());
+        assert_eq!(config.decode_encoding().unwrap(), Some(Encoding::Hex));
+
+        assert!(config.set(None, "output_format", "xml").is_err());
+        assert!(config.set(None, "decode_encoding", "utf16").is_err());
+        assert!(toml::from_str::<Config>("output_format = 'yaml'")
+            .unwrap()
+            .check()
+            .is_err());
+    }
+
+    #[test]
+    fn test_set_headers() {
+        let mut config = Config::default();
+        config.set(None, "user_agent", "Mozilla/5.0").unwrap();
+        config.set(None, "headers", "X-Api-Key: abc").unwrap();
+        config.set(None, "headers", "Accept: image/png").unwrap();
+        assert!(config.set(None, "headers", "no colon").is_err());
+
+        assert_eq!(config.user_agent.as_deref(), Some("Mozilla/5.0"));
+        let headers = config.headers.as_ref().unwrap();
+        assert_eq!(headers["X-Api-Key"], "abc");
+        assert_eq!(headers["Accept"], "image/png");
+
+        let parsed: Config = toml::from_str(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(config, parsed);
+        let redacted = config.redacted().headers.unwrap();
+        assert_ne!(redacted["X-Api-Key"], "abc");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut config = Config::default();
+        config.set(None, "chunk_type", "teSt").unwrap();
+        config.set(Some("work"), "encrypt", "true").unwrap();
+
+        let parsed: Config = toml::from_str(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(config, parsed);
+    }
+
+    #[test]
+    fn test_with_profile() {
+        let config: Config = toml::from_str(
+            r#"
+            chunk_type = "ruSt"
+            timeout = 10
+
+            [profiles.work]
+            chunk_type = "woRk"
+            passphrase = "hunter2"
+            "#,
+        )
+        .unwrap();
+
+        let work = config.with_profile("work").unwrap();
+        assert_eq!(work.chunk_type.as_deref(), Some("woRk"));
+        assert_eq!(work.passphrase.as_deref(), Some("hunter2"));
+        assert_eq!(work.timeout, Some(10));
+        assert!(work.profiles.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_profile_is_err() {
+        assert!(Config::default().with_profile("nope").is_err());
+    }
+
+    #[test]
+    fn test_redacted() {
+        let mut config = Config::default();
+        config.set(None, "passphrase", "hunter2").unwrap();
+        config.set(None, "timeout", "5").unwrap();
+
+        let redacted = config.redacted();
+        assert_ne!(redacted.passphrase.as_deref(), Some("hunter2"));
+        assert_eq!(redacted.timeout, Some(5));
+    }
+}
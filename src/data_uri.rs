@@ -0,0 +1,70 @@
+use crate::args::RemoteArgs;
+use crate::config::Config;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use percent_encoding::percent_decode_str;
+use reqwest::Url;
+use std::io::Write;
+
+/// URL scheme of inline data.
+pub(crate) const SCHEME: &str = "data";
+
+/// Bytes held by a `data:[<type>][;base64],<data>` url.
+pub(crate) fn decode(url: &Url) -> crate::Result<Vec<u8>> {
+    let (header, data) = url
+        .as_str()
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(','))
+        .ok_or("Expected data:[<type>][;base64],<data>")?;
+    if header.ends_with(";base64") {
+        let data: String = percent_decode_str(data)
+            .decode_utf8()?
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect();
+        Ok(STANDARD.decode(data)?)
+    } else {
+        Ok(percent_decode_str(data).collect())
+    }
+}
+
+/// PNG bytes as a base64 data URI.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    format!("data:image/png;base64,{}", STANDARD.encode(bytes))
+}
+
+/// Write the bytes held by the given url into `out`.
+pub(crate) fn get(
+    url: &Url,
+    _remote: &RemoteArgs,
+    _config: &Config,
+    out: &mut dyn Write,
+) -> crate::Result<()> {
+    out.write_all(&decode(url)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = [137, 80, 78, 71, 0, 255];
+        let url = Url::parse(&encode(&bytes)).unwrap();
+        assert_eq!(decode(&url).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_decode_plain() {
+        let url = Url::parse("data:,hello%20world").unwrap();
+        assert_eq!(decode(&url).unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let url = Url::parse("data:image/png;base64,!!!").unwrap();
+        assert!(decode(&url).is_err());
+        assert!(decode(&Url::parse("data:no-comma").unwrap()).is_err());
+    }
+}
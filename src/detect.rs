@@ -0,0 +1,243 @@
+use crate::chunk::MAXIMUM_LENGTH;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use std::fmt;
+
+/// Shannon entropy, in bits per byte, above which data looks compressed or
+/// encrypted.
+const ENTROPY_THRESHOLD: f64 = 7.5;
+
+/// Fewest bytes whose entropy says anything.
+const ENTROPY_MIN_LENGTH: usize = 256;
+
+/// Ancillary chunks whose data is compressed by design, so high entropy is
+/// expected.
+const COMPRESSED: [ChunkType; 4] = [
+    ChunkType::FDAT,
+    ChunkType::ZTXT,
+    ChunkType::ITXT,
+    ChunkType::ICCP,
+];
+
+/// Something about a PNG that could be hiding data.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Finding {
+    /// A chunk of a type the PNG specification doesn't define.
+    Nonstandard {
+        offset: usize,
+        chunk_type: ChunkType,
+    },
+    /// A chunk after IEND, which readers ignore.
+    AfterIend {
+        offset: usize,
+        chunk_type: ChunkType,
+    },
+    /// Bytes after IEND that aren't chunks.
+    TrailingData { offset: usize, length: usize },
+    /// Chunk data, or trailing data, that looks compressed or encrypted
+    /// though nothing says it should be.
+    HighEntropy {
+        offset: usize,
+        chunk_type: Option<ChunkType>,
+        bits: f64,
+    },
+    /// The chunks stop making sense here, so nothing after is checked.
+    Malformed { offset: usize },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::Nonstandard { offset, chunk_type } if chunk_type.is_public() => {
+                write!(f, "nonstandard chunk {} at byte {}", chunk_type, offset)
+            }
+            Finding::Nonstandard { offset, chunk_type } => {
+                write!(f, "private chunk {} at byte {}", chunk_type, offset)
+            }
+            Finding::AfterIend { offset, chunk_type } => {
+                write!(f, "chunk {} after IEND at byte {}", chunk_type, offset)
+            }
+            Finding::TrailingData { offset, length } => {
+                write!(
+                    f,
+                    "{} bytes of trailing data after IEND at byte {}",
+                    length, offset
+                )
+            }
+            Finding::HighEntropy {
+                offset,
+                chunk_type: Some(chunk_type),
+                bits,
+            } => write!(
+                f,
+                "high entropy ({:.2} bits/byte) in {} at byte {}",
+                bits, chunk_type, offset
+            ),
+            Finding::HighEntropy { offset, bits, .. } => write!(
+                f,
+                "high entropy ({:.2} bits/byte) in trailing data at byte {}",
+                bits, offset
+            ),
+            Finding::Malformed { offset } => write!(f, "malformed chunk at byte {}", offset),
+        }
+    }
+}
+
+/// Everything in the PNG bytes that could be hiding data, in order.
+/// `Err` if they aren't a PNG.
+pub(crate) fn scan(bytes: &[u8]) -> crate::Result<Vec<Finding>> {
+    let mut rest = bytes
+        .strip_prefix(&Png::STANDARD_HEADER[..])
+        .ok_or("Not a PNG")?;
+    let mut findings = Vec::new();
+    let mut offset = Png::STANDARD_HEADER.len();
+    let mut iend = false;
+    while !rest.is_empty() {
+        let Some((chunk_type, data, tail)) = split_chunk(rest) else {
+            findings.push(match iend {
+                true => Finding::TrailingData {
+                    offset,
+                    length: rest.len(),
+                },
+                false => Finding::Malformed { offset },
+            });
+            if iend {
+                findings.extend(high_entropy(offset, None, rest));
+            }
+            break;
+        };
+        if iend {
+            findings.push(Finding::AfterIend { offset, chunk_type });
+        } else if chunk_type.description().is_none() {
+            findings.push(Finding::Nonstandard { offset, chunk_type });
+        }
+        // Critical chunks are image data and palettes, dense by nature.
+        if !chunk_type.is_critical() && !COMPRESSED.contains(&chunk_type) {
+            findings.extend(high_entropy(offset, Some(chunk_type), data));
+        }
+        iend |= chunk_type == ChunkType::IEND;
+        offset = bytes.len() - tail.len();
+        rest = tail;
+    }
+    Ok(findings)
+}
+
+/// Type and data of the chunk at the start of the bytes, then the bytes
+/// after it.  `None` if they don't start with a whole chunk.
+fn split_chunk(bytes: &[u8]) -> Option<(ChunkType, &[u8], &[u8])> {
+    let (length, rest) = bytes.split_first_chunk::<4>()?;
+    let (type_bytes, rest) = rest.split_first_chunk::<4>()?;
+    let length = u32::from_be_bytes(*length);
+    let chunk_type = ChunkType::try_from(*type_bytes).ok()?;
+    if length > MAXIMUM_LENGTH {
+        return None;
+    }
+    let (data, rest) = rest.split_at_checked(length as usize)?;
+    let (_crc, rest) = rest.split_at_checked(4)?;
+    Some((chunk_type, data, rest))
+}
+
+/// A finding if the data is long enough and its entropy high.
+fn high_entropy(offset: usize, chunk_type: Option<ChunkType>, data: &[u8]) -> Option<Finding> {
+    if data.len() < ENTROPY_MIN_LENGTH {
+        return None;
+    }
+    let bits = entropy(data);
+    (bits > ENTROPY_THRESHOLD).then_some(Finding::HighEntropy {
+        offset,
+        chunk_type,
+        bits,
+    })
+}
+
+/// Shannon entropy of the bytes, in bits per byte, from 0 to 8.
+fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for byte in data {
+        counts[usize::from(*byte)] += 1;
+    }
+    let total = data.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::str::FromStr;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    fn bytes(chunks: &[Chunk]) -> Vec<u8> {
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        for chunk in chunks {
+            bytes.extend(chunk.as_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_scan_turtle() {
+        // Its message is in a chunk after IEND.
+        let findings = scan(TURTLE).unwrap();
+        let [Finding::AfterIend { chunk_type, .. }] = &findings[..] else {
+            panic!("{:?}", findings);
+        };
+        assert_eq!(chunk_type.to_string(), "ruSt");
+    }
+
+    #[test]
+    fn test_scan() {
+        let rust = ChunkType::from_str("ruSt").unwrap();
+        let noise: Vec<u8> = (0..4096u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let chunks = [
+            Chunk::new(ChunkType::IHDR, vec![0; 13]).unwrap(),
+            Chunk::new(rust, noise.clone()).unwrap(),
+            Chunk::new(ChunkType::IDAT, noise).unwrap(),
+            Chunk::new(ChunkType::IEND, Vec::new()).unwrap(),
+        ];
+        let mut png = bytes(&chunks);
+        let iend = png.len();
+        png.extend(b"junk");
+        let findings = scan(&png).unwrap();
+        assert_eq!(
+            findings[0],
+            Finding::Nonstandard {
+                offset: 33,
+                chunk_type: rust
+            }
+        );
+        assert!(matches!(
+            findings[1],
+            Finding::HighEntropy { offset: 33, chunk_type: Some(t), .. } if t == rust
+        ));
+        assert_eq!(
+            findings[2..],
+            [Finding::TrailingData {
+                offset: iend,
+                length: 4
+            }]
+        );
+        assert_eq!(
+            findings[2].to_string(),
+            format!("4 bytes of trailing data after IEND at byte {}", iend)
+        );
+        assert!(scan(b"not a png").is_err());
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(&[7; 100]), 0.0);
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(entropy(&all), 8.0);
+    }
+}
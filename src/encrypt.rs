@@ -1,12 +1,24 @@
+use crate::error::{PngmeError, Result};
 use magic_crypt::MagicCryptTrait;
 
+/// Key used when no passphrase is configured.
+pub const DEFAULT_KEY: &str = "grassfed_butter";
+
 #[allow(dead_code)]
-pub fn encrypt(msg: String) -> String {
-    let mc = new_magic_crypt!("grassfed_butter", 256);
+pub fn encrypt(msg: String, key: &str) -> String {
+    let mc = new_magic_crypt!(key, 256);
     mc.encrypt_str_to_base64(msg)
 }
 
-pub fn decrypt(msg: String) -> String {
-    let mc = new_magic_crypt!("grassfed_butter", 256);
-    mc.decrypt_base64_to_string(msg).unwrap()
-}
\ No newline at end of file
+/// Like `encrypt`, for a message that may not be text.
+#[allow(dead_code)]
+pub fn encrypt_bytes(msg: &[u8], key: &str) -> String {
+    let mc = new_magic_crypt!(key, 256);
+    mc.encrypt_bytes_to_base64(msg)
+}
+
+pub fn decrypt(msg: String, key: &str) -> Result<String> {
+    let mc = new_magic_crypt!(key, 256);
+    mc.decrypt_base64_to_string(msg)
+        .map_err(|_| PngmeError::DecryptionFailed)
+}
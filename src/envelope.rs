@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use std::str;
+use std::time::SystemTime;
+
+/// Start of every envelope, before its version.
+pub(crate) const MAGIC: &str = "PNGM";
+/// Version of the envelopes written, a byte after the magic.
+pub(crate) const VERSION: u8 = 1;
+
+/// How chunk data holds a message.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Format {
+    /// The bare message, as written before envelopes.
+    Raw,
+    /// An envelope as JSON with no magic or version, as first written.
+    Unversioned,
+    /// An envelope after the magic and the given version.
+    Versioned(u8),
+}
+
+impl Format {
+    /// Format of the given chunk data.
+    pub(crate) fn of(data: &[u8]) -> Format {
+        match data.strip_prefix(MAGIC.as_bytes()) {
+            Some([version, ..]) => Format::Versioned(*version),
+            _ if Envelope::parse(data).is_some() => Format::Unversioned,
+            _ => Format::Raw,
+        }
+    }
+}
+
+/// A message stored with where it came from: the magic, the version, then
+/// JSON, in place of the bare message.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Envelope {
+    /// Who wrote the message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) author: Option<String>,
+    /// When the message was encoded, as RFC 3339.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) created_at: Option<String>,
+    /// When the message should no longer be decoded, as RFC 3339.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) expires_at: Option<String>,
+    /// Media type of the message, like `text/plain`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) content_type: Option<String>,
+    /// The message, encrypted if encryption was on.
+    pub(crate) message: String,
+}
+
+impl Envelope {
+    /// The envelope stored in the given chunk data, if it holds one of this
+    /// version or an unversioned one, rather than a bare message.
+    pub(crate) fn parse(data: &[u8]) -> Option<Envelope> {
+        match data.strip_prefix(MAGIC.as_bytes()) {
+            Some([VERSION, json @ ..]) => serde_json::from_slice(json).ok(),
+            // Written by a newer pngme.
+            Some(_) => None,
+            None if data.starts_with(b"{") => serde_json::from_slice(data).ok(),
+            None => None,
+        }
+    }
+
+    /// Chunk data holding this envelope.  Always UTF-8, so it can also be
+    /// iTXt text.
+    pub(crate) fn to_data(&self) -> String {
+        let json = serde_json::to_string(self).expect("an envelope is always valid JSON");
+        format!("{}{}{}", MAGIC, char::from(VERSION), json)
+    }
+
+    /// The given chunk data upgraded to an envelope of this version: a bare
+    /// message becomes one with no metadata.  `None` if it is already
+    /// versioned, or a bare message that isn't UTF-8.
+    pub(crate) fn migrate(data: &[u8]) -> Option<String> {
+        match Format::of(data) {
+            Format::Versioned(_) => None,
+            Format::Unversioned => Envelope::parse(data).map(|e| e.to_data()),
+            Format::Raw => {
+                let message = str::from_utf8(data).ok()?.to_string();
+                Some(
+                    Envelope {
+                        message,
+                        ..Envelope::default()
+                    }
+                    .to_data(),
+                )
+            }
+        }
+    }
+
+    /// The current time as RFC 3339, for `created_at`.
+    pub(crate) fn now() -> String {
+        Envelope::timestamp(SystemTime::now())
+    }
+
+    /// The given time as RFC 3339, to the second.
+    pub(crate) fn timestamp(time: SystemTime) -> String {
+        humantime::format_rfc3339_seconds(time).to_string()
+    }
+
+    /// Has the message expired by the given time.  Never, if the expiry
+    /// can't be read.
+    pub(crate) fn is_expired(&self, now: SystemTime) -> bool {
+        let expires_at = self.expires_at.as_deref();
+        expires_at
+            .and_then(|t| humantime::parse_rfc3339_weak(t).ok())
+            .is_some_and(|t| t <= now)
+    }
+
+    /// Metadata as `name: value` lines, in a fixed order.
+    pub(crate) fn meta(&self) -> Vec<String> {
+        [
+            ("author", &self.author),
+            ("created-at", &self.created_at),
+            ("expires-at", &self.expires_at),
+            ("content-type", &self.content_type),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_ref().map(|v| format!("{}: {}", name, v)))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_round_trip() {
+        let envelope = Envelope {
+            author: Some("alice".to_string()),
+            created_at: Some("2024-05-01T12:00:00Z".to_string()),
+            message: "hi".to_string(),
+            ..Envelope::default()
+        };
+        let data = envelope.to_data();
+        assert_eq!(
+            data,
+            "PNGM\x01{\"author\":\"alice\",\"created_at\":\"2024-05-01T12:00:00Z\",\"message\":\"hi\"}"
+        );
+        assert_eq!(Envelope::parse(data.as_bytes()), Some(envelope));
+        assert_eq!(Format::of(data.as_bytes()), Format::Versioned(VERSION));
+    }
+
+    #[test]
+    fn test_parse_legacy() {
+        let unversioned = br#"{"author":"alice","message":"hi"}"#;
+        assert_eq!(Envelope::parse(unversioned).unwrap().message, "hi");
+        assert_eq!(Format::of(unversioned), Format::Unversioned);
+
+        assert_eq!(Envelope::parse(b"hi"), None);
+        assert_eq!(Format::of(b"hi"), Format::Raw);
+        assert_eq!(Envelope::parse(b"{not json"), None);
+        assert_eq!(Envelope::parse(br#"{"message":"hi","extra":1}"#), None);
+        assert_eq!(Envelope::parse(br#"{"author":"alice"}"#), None);
+
+        let newer = b"PNGM\x02{\"message\":\"hi\"}";
+        assert_eq!(Envelope::parse(newer), None);
+        assert_eq!(Format::of(newer), Format::Versioned(2));
+    }
+
+    #[test]
+    fn test_migrate() {
+        let raw = Envelope::migrate(b"hi").unwrap();
+        assert_eq!(Envelope::parse(raw.as_bytes()).unwrap().message, "hi");
+        assert_eq!(Envelope::migrate(raw.as_bytes()), None);
+
+        let unversioned = Envelope::migrate(br#"{"author":"alice","message":"hi"}"#).unwrap();
+        assert!(unversioned.starts_with("PNGM\x01"));
+        let envelope = Envelope::parse(unversioned.as_bytes()).unwrap();
+        assert_eq!(envelope.author.as_deref(), Some("alice"));
+
+        assert_eq!(Envelope::migrate(&[0xff, 0xfe]), None);
+    }
+
+    #[test]
+    fn test_meta() {
+        let envelope = Envelope {
+            author: Some("alice".to_string()),
+            content_type: Some("text/plain".to_string()),
+            ..Envelope::default()
+        };
+        assert_eq!(
+            envelope.meta(),
+            ["author: alice", "content-type: text/plain"]
+        );
+        assert!(Envelope::now().ends_with('Z'));
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let envelope = Envelope {
+            expires_at: Some("2025-01-01T00:00:00Z".to_string()),
+            ..Envelope::default()
+        };
+        let expiry = humantime::parse_rfc3339("2025-01-01T00:00:00Z").unwrap();
+        assert!(envelope.is_expired(expiry));
+        assert!(!envelope.is_expired(expiry - Duration::from_secs(1)));
+        assert!(!Envelope::default().is_expired(expiry));
+        assert_eq!(Envelope::timestamp(expiry), "2025-01-01T00:00:00Z");
+    }
+}
@@ -0,0 +1,99 @@
+use crate::chunk_type::{ChunkType, RawChunkType};
+use std::io;
+use std::str::Utf8Error;
+use std::string::FromUtf8Error;
+use thiserror::Error;
+
+/// Everything that can go wrong reading, changing or fetching a PNG.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+#[allow(dead_code)]
+pub enum PngmeError {
+    /// The bytes don't start with the PNG signature.
+    #[error("Invalid header: not a PNG")]
+    InvalidSignature,
+    /// A chunk type isn't four ASCII letters.
+    #[error("Invalid chunk type {0:?}: expected 4 ASCII letters")]
+    InvalidChunkType(String),
+    /// A chunk claims more data than a PNG chunk may hold.
+    #[error("Length is too long ({length} > 2^31 - 1)")]
+    ChunkTooLong { length: u64 },
+    /// The parts given don't make a valid PNG.
+    #[error("Invalid PNG: {0}")]
+    InvalidImage(String),
+    /// A chunk claims more data than the parser was told to accept.
+    #[error("Length {length} is over the limit of {limit} bytes")]
+    ChunkOverLimit { length: u32, limit: u32 },
+    /// A chunk's checksum doesn't match its type and data.
+    #[error("Invalid checksum {found:#010x} at byte {offset}, expected {expected:#010x}")]
+    CrcMismatch {
+        expected: u32,
+        found: u32,
+        /// Offset of the chunk from the start of the input.
+        offset: usize,
+    },
+    /// A chunk of a PNG couldn't be parsed.
+    #[error(
+        "Chunk {index} at byte {offset} ({}): {source}",
+        match last {
+            Some(chunk_type) => format!("after {}", chunk_type),
+            None => "the first".to_string(),
+        }
+    )]
+    Parse {
+        /// Offset of the chunk from the start of the input.
+        offset: usize,
+        /// Index of the chunk, counting from 0.
+        index: usize,
+        /// Type of the last chunk parsed before it, if any.
+        last: Option<RawChunkType>,
+        source: Box<PngmeError>,
+    },
+    /// There is no chunk of the given type.
+    #[error("No matching chunk of type {0}")]
+    ChunkNotFound(ChunkType),
+    /// The data isn't a message encrypted with the given key.
+    #[error("Couldn't decrypt the message")]
+    DecryptionFailed,
+    /// Chunk data isn't UTF-8 text.
+    #[error("Chunk data isn't UTF-8: {0}")]
+    InvalidUtf8(#[from] Utf8Error),
+    /// An [`Observer`](crate::Observer) cancelled the operation.
+    #[error("Cancelled")]
+    Cancelled,
+    /// Reading or writing failed, including input that ends mid-chunk.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// A download failed.
+    #[cfg(feature = "remote")]
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+}
+
+impl From<FromUtf8Error> for PngmeError {
+    fn from(e: FromUtf8Error) -> Self {
+        PngmeError::InvalidUtf8(e.utf8_error())
+    }
+}
+
+impl PngmeError {
+    /// The same error for a chunk that starts `by` bytes further into the
+    /// input.
+    pub(crate) fn shifted(self, by: usize) -> PngmeError {
+        match self {
+            PngmeError::CrcMismatch {
+                expected,
+                found,
+                offset,
+            } => PngmeError::CrcMismatch {
+                expected,
+                found,
+                offset: offset + by,
+            },
+            e => e,
+        }
+    }
+}
+
+/// Holds a `Result` of a pngme operation.
+pub type Result<T> = std::result::Result<T, PngmeError>;
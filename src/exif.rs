@@ -0,0 +1,119 @@
+/// Tag of the offset of the JPEG thumbnail, in the second image directory.
+const THUMBNAIL_OFFSET: u16 = 0x0201;
+/// Tag of the length of the JPEG thumbnail.
+const THUMBNAIL_LENGTH: u16 = 0x0202;
+
+/// Exif data: a TIFF header, then image file directories (IFDs) of tagged
+/// values, at offsets from the start of the header.
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    /// `None` without a TIFF header.  Some writers put the `Exif\0\0` of a
+    /// JPEG segment before it, which is skipped.
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let data = data.strip_prefix(b"Exif\0\0").unwrap_or(data);
+        let big_endian = match data.get(..4)? {
+            b"II*\0" => false,
+            b"MM\0*" => true,
+            _ => return None,
+        };
+        Some(Tiff { data, big_endian })
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes = *self.data.get(at..)?.first_chunk::<2>()?;
+        Some(match self.big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes = *self.data.get(at..)?.first_chunk::<4>()?;
+        Some(match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    }
+
+    /// Tags and values of the IFD at the given offset, then the offset of
+    /// the next IFD, 0 after the last.  Values are read as SHORT or LONG,
+    /// others as 0.
+    fn ifd(&self, at: usize) -> Option<(Vec<(u16, u32)>, usize)> {
+        let count = usize::from(self.u16(at)?);
+        let mut entries = Vec::with_capacity(count);
+        for entry in (at + 2..).step_by(12).take(count) {
+            let value = match self.u16(entry + 2)? {
+                3 => u32::from(self.u16(entry + 8)?),
+                4 => self.u32(entry + 8)?,
+                _ => 0,
+            };
+            entries.push((self.u16(entry)?, value));
+        }
+        let next = self.u32(at + 2 + 12 * count)?;
+        Some((entries, next as usize))
+    }
+}
+
+/// The JPEG thumbnail in the given Exif data, which the second IFD points
+/// to.  `None` if there is none or the data is malformed.
+pub(crate) fn thumbnail(exif: &[u8]) -> Option<&[u8]> {
+    let tiff = Tiff::new(exif)?;
+    let (_, ifd1) = tiff.ifd(tiff.u32(4)? as usize)?;
+    if ifd1 == 0 {
+        return None;
+    }
+    let (entries, _) = tiff.ifd(ifd1)?;
+    let value = |tag| entries.iter().find(|(t, _)| *t == tag).map(|(_, v)| *v);
+    let offset = value(THUMBNAIL_OFFSET)? as usize;
+    let length = value(THUMBNAIL_LENGTH)? as usize;
+    tiff.data.get(offset..offset.checked_add(length)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian Exif data with an empty IFD0, then an IFD1 pointing
+    /// to the given thumbnail.
+    fn exif(thumbnail: &[u8]) -> Vec<u8> {
+        let mut data = b"II*\0".to_vec();
+        data.extend(8u32.to_le_bytes());
+        // IFD0: no entries, IFD1 at 14.
+        data.extend(0u16.to_le_bytes());
+        data.extend(14u32.to_le_bytes());
+        // IFD1: offset and length as LONGs, no next IFD.
+        data.extend(2u16.to_le_bytes());
+        for (tag, value) in [(THUMBNAIL_OFFSET, 44), (THUMBNAIL_LENGTH, thumbnail.len())] {
+            data.extend(tag.to_le_bytes());
+            data.extend(4u16.to_le_bytes());
+            data.extend(1u32.to_le_bytes());
+            data.extend((value as u32).to_le_bytes());
+        }
+        data.extend(0u32.to_le_bytes());
+        data.extend(thumbnail);
+        data
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        let data = exif(b"\xff\xd8jpeg");
+        assert_eq!(thumbnail(&data), Some(&b"\xff\xd8jpeg"[..]));
+        let prefixed = [&b"Exif\0\0"[..], &data].concat();
+        assert_eq!(thumbnail(&prefixed), Some(&b"\xff\xd8jpeg"[..]));
+    }
+
+    #[test]
+    fn test_no_thumbnail() {
+        let data = exif(b"\xff\xd8jpeg");
+        assert_eq!(thumbnail(&data[..data.len() - 1]), None);
+        assert_eq!(thumbnail(b"not exif"), None);
+        // IFD0 with no next IFD.
+        let mut single = data[..10].to_vec();
+        single.extend(0u32.to_le_bytes());
+        assert_eq!(thumbnail(&single), None);
+    }
+}
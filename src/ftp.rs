@@ -0,0 +1,93 @@
+#[cfg(not(feature = "ftp"))]
+use crate::args::RemoteArgs;
+#[cfg(not(feature = "ftp"))]
+use crate::config::Config;
+#[cfg(not(feature = "ftp"))]
+use reqwest::Url;
+
+/// URL scheme of files on FTP servers.
+pub(crate) const SCHEME: &str = "ftp";
+
+#[cfg(feature = "ftp")]
+mod client {
+    use crate::args::RemoteArgs;
+    use crate::config::Config;
+    use crate::remote;
+    use reqwest::Url;
+    use std::io::{self, Cursor, Write};
+    use suppaftp::types::FileType;
+    use suppaftp::FtpStream;
+    use tracing::debug;
+
+    /// Binary-mode connection to the host of the given url, logged in with
+    /// the url's credentials or anonymously.
+    fn connect(url: &Url) -> crate::Result<FtpStream> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| format!("No host in '{}'", url))?;
+        let port = url.port().unwrap_or(21);
+        let mut ftp = FtpStream::connect((host, port))?;
+        let (user, password) = remote::credentials(url);
+        let user = user.unwrap_or_else(|| "anonymous".to_string());
+        ftp.login(user.as_str(), password.as_deref().unwrap_or("anonymous"))?;
+        ftp.transfer_type(FileType::Binary)?;
+        debug!(%user, "connected to {}:{}", host, port);
+        Ok(ftp)
+    }
+
+    /// Download the file at the given url into `out`.
+    pub(crate) fn get(
+        url: &Url,
+        _remote: &RemoteArgs,
+        _config: &Config,
+        out: &mut dyn Write,
+    ) -> crate::Result<()> {
+        let mut ftp = connect(url)?;
+        let mut download = ftp.retr_as_stream(remote::path(url))?;
+        io::copy(&mut download, out)?;
+        download.finish()?;
+        ftp.quit()?;
+        Ok(())
+    }
+
+    /// Upload PNG bytes as the file at the given url.
+    pub(crate) fn put(
+        url: &Url,
+        _remote: &RemoteArgs,
+        _config: &Config,
+        bytes: Vec<u8>,
+    ) -> crate::Result<()> {
+        let mut ftp = connect(url)?;
+        ftp.put_file(remote::path(url), &mut Cursor::new(bytes))?;
+        ftp.quit()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ftp")]
+pub(crate) use client::{get, put};
+
+#[cfg(not(feature = "ftp"))]
+const NOT_BUILT: &str = "ftp:// URLs need pngme built with the `ftp` feature";
+
+/// Download the file at the given url into `out`.
+#[cfg(not(feature = "ftp"))]
+pub(crate) fn get(
+    _url: &Url,
+    _remote: &RemoteArgs,
+    _config: &Config,
+    _out: &mut dyn std::io::Write,
+) -> crate::Result<()> {
+    Err(NOT_BUILT)?
+}
+
+/// Upload PNG bytes as the file at the given url.
+#[cfg(not(feature = "ftp"))]
+pub(crate) fn put(
+    _url: &Url,
+    _remote: &RemoteArgs,
+    _config: &Config,
+    _bytes: Vec<u8>,
+) -> crate::Result<()> {
+    Err(NOT_BUILT)?
+}
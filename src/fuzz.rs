@@ -0,0 +1,73 @@
+//! Entry point for fuzzing the parsers, shared by the cargo-fuzz targets
+//! under `fuzz/` and the tests here.
+
+use crate::{Chunk, ChunkRef, Png, PngIndex};
+use arbitrary::Arbitrary;
+use std::convert::TryFrom;
+use std::io::Cursor;
+
+/// Input the fuzzer varies: bytes to parse and the chunk length limit to
+/// parse them with.
+#[derive(Arbitrary, Debug)]
+pub struct Input<'a> {
+    pub bytes: &'a [u8],
+    pub max_chunk_length: u32,
+}
+
+/// Parse `input` every way pngme can, panicking if any parser panics or
+/// they disagree: whatever one accepts, the others accept with the same
+/// chunks, and writing it back gives the same bytes.
+pub fn parse(input: &Input) {
+    let bytes = input.bytes;
+    let _ = Png::validate(bytes);
+    let _ = Chunk::try_from(bytes);
+    let _ = ChunkRef::try_from(bytes);
+    let _ = Png::from_reader_limited(bytes, input.max_chunk_length, &mut ());
+
+    let png = Png::try_from(bytes);
+    let refs = Png::chunk_refs(bytes);
+    let index = PngIndex::new(Cursor::new(bytes));
+    if let Ok(png) = png {
+        let refs = refs.expect("chunk_refs rejected what from_reader accepted");
+        assert_eq!(refs.len(), png.len());
+        let mut index = index.expect("PngIndex rejected what from_reader accepted");
+        assert_eq!(index.entries().len(), png.len());
+        for (i, chunk) in png.iter().enumerate() {
+            assert_eq!(&Chunk::from(refs[i]), chunk);
+            assert_eq!(
+                &index.load(i).expect("loading an indexed chunk failed"),
+                chunk
+            );
+        }
+        let written = png.as_bytes();
+        assert_eq!(Png::try_from(written.as_slice()).ok(), Some(png));
+        assert!(bytes.starts_with(&written));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arbitrary::Unstructured;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    #[test]
+    fn test_parse() {
+        parse(&Input {
+            bytes: TURTLE,
+            max_chunk_length: u32::MAX,
+        });
+        for cut in [0, 8, 12, 33, TURTLE.len() - 1] {
+            parse(&Input {
+                bytes: &TURTLE[..cut],
+                max_chunk_length: 16,
+            });
+        }
+        // Inputs derived from the bytes of a Png, like the fuzzer makes.
+        for start in (0..TURTLE.len()).step_by(97) {
+            let mut raw = Unstructured::new(&TURTLE[start..]);
+            parse(&Input::arbitrary(&mut raw).unwrap());
+        }
+    }
+}
@@ -0,0 +1,197 @@
+#[cfg(not(feature = "gcs"))]
+use crate::args::RemoteArgs;
+#[cfg(not(feature = "gcs"))]
+use crate::config::Config;
+use reqwest::header::{HeaderMap, LOCATION};
+use reqwest::Url;
+
+/// URL scheme of Google Cloud Storage objects.
+pub(crate) const SCHEME: &str = "gs";
+
+/// Bucket and object name of a `gs://bucket/object` url.
+pub(crate) fn location(url: &Url) -> crate::Result<(&str, &str)> {
+    let bucket = url.host_str().filter(|b| !b.is_empty());
+    let object = url.path().trim_start_matches('/');
+    match bucket {
+        Some(bucket) if !object.is_empty() => Ok((bucket, object)),
+        _ => Err(format!("Expected gs://bucket/object, got '{}'", url))?,
+    }
+}
+
+/// JSON API url downloading the object at the given url.
+#[cfg_attr(not(feature = "gcs"), allow(dead_code))]
+fn download_url(url: &Url) -> crate::Result<Url> {
+    let (bucket, object) = location(url)?;
+    let mut api = Url::parse("https://storage.googleapis.com/storage/v1/b")?;
+    // Pushed as single segments, so the slashes in the name are escaped.
+    api.path_segments_mut()
+        .map_err(|_| "bad API url")?
+        .extend([bucket, "o", object]);
+    api.query_pairs_mut().append_pair("alt", "media");
+    Ok(api)
+}
+
+/// JSON API url starting a resumable upload to the object at the given
+/// url.  The bytes then go in a PUT to the session url it answers with,
+/// which can be retried, where a POST of them couldn't.
+#[cfg_attr(not(feature = "gcs"), allow(dead_code))]
+fn upload_url(url: &Url) -> crate::Result<Url> {
+    let (bucket, object) = location(url)?;
+    let mut api = Url::parse("https://storage.googleapis.com/upload/storage/v1/b")?;
+    api.path_segments_mut()
+        .map_err(|_| "bad API url")?
+        .extend([bucket, "o"]);
+    api.query_pairs_mut()
+        .append_pair("uploadType", "resumable")
+        .append_pair("name", object);
+    Ok(api)
+}
+
+/// Session url of a resumable upload, from the Location header of the
+/// answer starting it.
+#[cfg_attr(not(feature = "gcs"), allow(dead_code))]
+fn session_url(headers: &HeaderMap) -> crate::Result<Url> {
+    let location = headers.get(LOCATION).and_then(|v| v.to_str().ok());
+    Ok(Url::parse(
+        location.ok_or("No upload session url in the answer")?,
+    )?)
+}
+
+#[cfg(feature = "gcs")]
+mod api {
+    use super::{download_url, session_url, upload_url};
+    use crate::args::RemoteArgs;
+    use crate::config::Config;
+    use crate::remote::{self, Retry};
+    use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE};
+    use reqwest::{Method, Url};
+    use std::io::Write;
+    use tokio::runtime::Builder;
+    use tracing::debug;
+
+    /// Scope of the access token: reading and writing objects.
+    const SCOPE: &str = "https://www.googleapis.com/auth/devstorage.read_write";
+
+    /// Access token from the application-default credentials
+    /// (`GOOGLE_APPLICATION_CREDENTIALS`, gcloud's login, or the metadata
+    /// server on GCP).
+    fn token() -> crate::Result<String> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        runtime.block_on(async {
+            let provider = gcp_auth::provider().await?;
+            Ok(provider.token(&[SCOPE]).await?.as_str().to_string())
+        })
+    }
+
+    /// Download the object at the given url into `out`.
+    pub(crate) fn get(
+        url: &Url,
+        remote: &RemoteArgs,
+        config: &Config,
+        out: &mut dyn Write,
+    ) -> crate::Result<()> {
+        let api = download_url(url)?;
+        let token = token()?;
+        let client = remote::client(remote, config)?;
+        let mut response = remote::send(Retry::from_args(remote), Method::GET, |method| {
+            remote::request_without_auth(&client, method, &api, remote, config).bearer_auth(&token)
+        })?;
+        debug!(content_length = ?response.content_length(), "fetched {}", url);
+        response.copy_to(out)?;
+        Ok(())
+    }
+
+    /// Upload PNG bytes as the object at the given url, through a
+    /// resumable upload session.
+    pub(crate) fn put(
+        url: &Url,
+        remote: &RemoteArgs,
+        config: &Config,
+        bytes: Vec<u8>,
+    ) -> crate::Result<()> {
+        let api = upload_url(url)?;
+        let token = token()?;
+        let client = remote::client(remote, config)?;
+        let retry = Retry::from_args(remote);
+        // Sent once, but a duplicate would only start an unused session.
+        let started = remote::send(retry.clone(), Method::POST, |method| {
+            remote::request_without_auth(&client, method, &api, remote, config)
+                .bearer_auth(&token)
+                .header("X-Upload-Content-Type", "image/png")
+                .header("X-Upload-Content-Length", bytes.len())
+                .header(CONTENT_LENGTH, 0)
+        })?;
+        let session = session_url(started.headers())?;
+        remote::send(retry, Method::PUT, |method| {
+            remote::request_without_auth(&client, method, &session, remote, config)
+                .header(CONTENT_TYPE, "image/png")
+                .body(bytes.clone())
+        })?;
+        debug!("uploaded to {}", url);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "gcs")]
+pub(crate) use api::{get, put};
+
+#[cfg(not(feature = "gcs"))]
+const NOT_BUILT: &str = "gs:// URLs need pngme built with the `gcs` feature";
+
+/// Download the object at the given url into `out`.
+#[cfg(not(feature = "gcs"))]
+pub(crate) fn get(
+    url: &Url,
+    _remote: &RemoteArgs,
+    _config: &Config,
+    _out: &mut dyn std::io::Write,
+) -> crate::Result<()> {
+    location(url)?;
+    Err(NOT_BUILT)?
+}
+
+/// Upload PNG bytes as the object at the given url.
+#[cfg(not(feature = "gcs"))]
+pub(crate) fn put(
+    url: &Url,
+    _remote: &RemoteArgs,
+    _config: &Config,
+    _bytes: Vec<u8>,
+) -> crate::Result<()> {
+    location(url)?;
+    Err(NOT_BUILT)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        let url = Url::parse("gs://assets/carriers/turtle.png").unwrap();
+        assert_eq!(location(&url).unwrap(), ("assets", "carriers/turtle.png"));
+
+        for bad in ["gs://assets", "gs://assets/", "gs:///object"] {
+            assert!(location(&Url::parse(bad).unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_api_urls() {
+        let url = Url::parse("gs://assets/carriers/turtle.png").unwrap();
+        assert_eq!(
+            download_url(&url).unwrap().as_str(),
+            "https://storage.googleapis.com/storage/v1/b/assets/o/carriers%2Fturtle.png?alt=media"
+        );
+        assert_eq!(
+            upload_url(&url).unwrap().as_str(),
+            "https://storage.googleapis.com/upload/storage/v1/b/assets/o?uploadType=resumable&name=carriers%2Fturtle.png"
+        );
+
+        let mut headers = HeaderMap::new();
+        assert!(session_url(&headers).is_err());
+        let session = "https://storage.googleapis.com/upload/storage/v1/b/assets/o?upload_id=xa298";
+        headers.insert(LOCATION, session.parse().unwrap());
+        assert_eq!(session_url(&headers).unwrap().as_str(), session);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::server::Settings;
+#[cfg(not(feature = "grpc"))]
+use std::net::SocketAddr;
+
+#[cfg(feature = "grpc")]
+mod service {
+    use super::Settings;
+    use crate::server::Failure;
+    use std::net::SocketAddr;
+    use tonic::transport::Server;
+    use tonic::{Request, Response, Status};
+    use tracing::{debug, info};
+
+    mod proto {
+        tonic::include_proto!("pngme");
+    }
+
+    use proto::pngme_server::{Pngme, PngmeServer};
+    use proto::{
+        Chunk, DecodeReply, DecodeRequest, EncodeReply, EncodeRequest, PrintReply, PrintRequest,
+    };
+
+    impl From<Failure> for Status {
+        fn from(failure: Failure) -> Status {
+            match failure {
+                Failure::Invalid(message) => Status::invalid_argument(message),
+                Failure::NotFound(message) => Status::not_found(message),
+            }
+        }
+    }
+
+    /// The `pngme.Pngme` service, answering with the same settings as REST.
+    struct Service {
+        settings: Settings,
+    }
+
+    #[tonic::async_trait]
+    impl Pngme for Service {
+        async fn encode(
+            &self,
+            request: Request<EncodeRequest>,
+        ) -> Result<Response<EncodeReply>, Status> {
+            let request = request.into_inner();
+            let png = self.settings.encode(
+                Some(&request.image),
+                request.chunk_type.as_deref(),
+                Some(request.message),
+            )?;
+            Ok(Response::new(EncodeReply {
+                image: png.as_bytes(),
+            }))
+        }
+
+        async fn decode(
+            &self,
+            request: Request<DecodeRequest>,
+        ) -> Result<Response<DecodeReply>, Status> {
+            let request = request.into_inner();
+            let message = self
+                .settings
+                .decode(Some(&request.image), request.chunk_type.as_deref())?;
+            Ok(Response::new(DecodeReply { message }))
+        }
+
+        async fn print(
+            &self,
+            request: Request<PrintRequest>,
+        ) -> Result<Response<PrintReply>, Status> {
+            let png = Settings::png(Some(&request.into_inner().image))?;
+            let chunks = png
+                .chunks()
+                .iter()
+                .map(|chunk| Chunk {
+                    chunk_type: chunk.chunk_type().to_string(),
+                    data: chunk.data().to_vec(),
+                })
+                .collect();
+            Ok(Response::new(PrintReply { chunks }))
+        }
+    }
+
+    /// Answer gRPC calls on the given address until interrupted.
+    pub(crate) fn serve(listen: SocketAddr, settings: Settings) -> crate::Result<()> {
+        let body_limit = settings.body_limit;
+        let service = PngmeServer::new(Service { settings }).max_decoding_message_size(body_limit);
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            info!("listening for gRPC on {}", listen);
+            Server::builder().add_service(service).serve(listen).await?;
+            debug!("server stopped");
+            Ok(())
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use tonic::Code;
+
+        const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+        fn service() -> Service {
+            Service {
+                settings: Settings {
+                    key: None,
+                    chunk_type: None,
+                    body_limit: 1024 * 1024,
+                },
+            }
+        }
+
+        fn block_on<F: std::future::Future>(future: F) -> F::Output {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(future)
+        }
+
+        #[test]
+        fn test_encode_decode_print() {
+            block_on(async {
+                let service = service();
+                let encoded = service
+                    .encode(Request::new(EncodeRequest {
+                        image: TURTLE.to_vec(),
+                        chunk_type: Some("teSt".to_string()),
+                        message: "Secret".to_string(),
+                    }))
+                    .await
+                    .unwrap()
+                    .into_inner()
+                    .image;
+
+                let decoded = service
+                    .decode(Request::new(DecodeRequest {
+                        image: encoded.clone(),
+                        chunk_type: Some("teSt".to_string()),
+                    }))
+                    .await
+                    .unwrap()
+                    .into_inner();
+                assert_eq!(decoded.message, "Secret");
+
+                let printed = service
+                    .print(Request::new(PrintRequest { image: encoded }))
+                    .await
+                    .unwrap()
+                    .into_inner();
+                assert!(printed
+                    .chunks
+                    .iter()
+                    .any(|chunk| chunk.chunk_type == "teSt" && chunk.data == b"Secret"));
+            })
+        }
+
+        #[test]
+        fn test_bad_requests() {
+            block_on(async {
+                let service = service();
+                let status = service
+                    .decode(Request::new(DecodeRequest {
+                        image: TURTLE.to_vec(),
+                        chunk_type: None,
+                    }))
+                    .await
+                    .unwrap_err();
+                assert_eq!(status.code(), Code::InvalidArgument);
+
+                let status = service
+                    .decode(Request::new(DecodeRequest {
+                        image: TURTLE.to_vec(),
+                        chunk_type: Some("teSt".to_string()),
+                    }))
+                    .await
+                    .unwrap_err();
+                assert_eq!(status.code(), Code::NotFound);
+            })
+        }
+    }
+}
+
+#[cfg(feature = "grpc")]
+pub(crate) use service::serve;
+
+/// Answer gRPC calls on the given address until interrupted.
+#[cfg(not(feature = "grpc"))]
+pub(crate) fn serve(_listen: SocketAddr, _settings: Settings) -> crate::Result<()> {
+    Err("serve --grpc needs pngme built with the `grpc` feature")?
+}
@@ -0,0 +1,202 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::chunk::{Chunk, MAXIMUM_LENGTH};
+use crate::chunk_type::{ChunkType, RawChunkType};
+use crate::error::{PngmeError, Result};
+use crate::png::{check_length, parse_error, Png};
+use tracing::trace;
+
+/// Where a chunk sits in a Png, without its data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Chunk type.
+    chunk_type: ChunkType,
+    /// Length of chunk data.
+    length: u32,
+    /// Offset of the chunk from the start of the input.
+    offset: u64,
+    /// Cyclic redundancy check, as stored.
+    crc: u32,
+}
+
+#[allow(dead_code)]
+impl IndexEntry {
+    /// Chunk type.
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    /// Length of chunk data.
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Offset of the chunk from the start of the input.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Cyclic redundancy check, as stored.  Checked only once the chunk is
+    /// loaded.
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
+}
+
+/// Png read lazily: parsing records where each chunk is and skips over its
+/// data, which is only read when that chunk is loaded.
+pub struct PngIndex<R> {
+    reader: R,
+    entries: Vec<IndexEntry>,
+}
+
+impl<R: Read + Seek> PngIndex<R> {
+    /// Index the Png the given reader is positioned at the start of.
+    pub fn new(reader: R) -> Result<Self> {
+        PngIndex::with_limit(reader, MAXIMUM_LENGTH)
+    }
+
+    /// Index the Png like [`PngIndex::new`], failing on any chunk with more
+    /// than `max_chunk_length` bytes of data, so loading a chunk never
+    /// allocates more.
+    pub fn with_limit(mut reader: R, max_chunk_length: u32) -> Result<Self> {
+        let mut header_buffer: [u8; 8] = Default::default();
+        reader.read_exact(&mut header_buffer)?;
+        if header_buffer != Png::STANDARD_HEADER {
+            return Err(PngmeError::InvalidSignature);
+        }
+
+        let mut entries: Vec<IndexEntry> = Vec::new();
+        let mut offset = Png::STANDARD_HEADER.len() as u64;
+        // Length and type
+        let mut head_buffer: [u8; 8] = Default::default();
+        loop {
+            let (index, last) = (
+                entries.len(),
+                entries.last().map(|e| RawChunkType::from(e.chunk_type)),
+            );
+            let context = |e: PngmeError| parse_error(e, offset as usize, index, last);
+            match reader.read_exact(&mut head_buffer) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(context(e.into())),
+            }
+
+            let mut length_buffer: [u8; 4] = Default::default();
+            length_buffer.copy_from_slice(&head_buffer[..4]);
+            let length = u32::from_be_bytes(length_buffer);
+            check_length(length, max_chunk_length).map_err(context)?;
+            let mut type_buffer: [u8; 4] = Default::default();
+            type_buffer.copy_from_slice(&head_buffer[4..]);
+            let chunk_type = ChunkType::try_from(type_buffer).map_err(context)?;
+
+            // Skip the data, then read the crc
+            reader
+                .seek(SeekFrom::Current(length.into()))
+                .map_err(|e| context(e.into()))?;
+            let mut crc_buffer: [u8; 4] = Default::default();
+            reader
+                .read_exact(&mut crc_buffer)
+                .map_err(|e| context(e.into()))?;
+
+            trace!(offset, %chunk_type, length, "indexed chunk");
+            entries.push(IndexEntry {
+                chunk_type,
+                length,
+                offset,
+                crc: u32::from_be_bytes(crc_buffer),
+            });
+            offset += 4 + 4 + u64::from(length) + 4;
+        }
+
+        Ok(PngIndex { reader, entries })
+    }
+
+    /// Chunks of the Png, in order.
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Position of the first chunk matching the given type.
+    #[allow(dead_code)]
+    pub fn position(&self, chunk_type: ChunkType) -> Option<usize> {
+        self.entries.iter().position(|e| e.chunk_type == chunk_type)
+    }
+
+    /// Positions of all chunks matching the given type, in order.
+    pub fn positions(&self, chunk_type: ChunkType) -> impl Iterator<Item = usize> + '_ {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(move |(_, e)| e.chunk_type == chunk_type)
+            .map(|(i, _)| i)
+    }
+
+    /// Read the chunk at the given position, checking its crc.
+    pub fn load(&mut self, position: usize) -> Result<Chunk> {
+        let entry = self.entries[position];
+        let mut bytes = vec![0; 4 + 4 + entry.length as usize + 4];
+        self.reader.seek(SeekFrom::Start(entry.offset))?;
+        self.reader.read_exact(&mut bytes)?;
+        Chunk::try_from(bytes.as_slice()).map_err(|e| e.shifted(entry.offset as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    fn testing_png() -> Png {
+        let mut png = Png::try_from(&Png::STANDARD_HEADER[..]).unwrap();
+        for (chunk_type, data) in [("FrSt", "first"), ("miDl", "middle"), ("LASt", "last")] {
+            let chunk_type = ChunkType::from_str(chunk_type).unwrap();
+            png.append_chunk(Chunk::new(chunk_type, data.as_bytes().to_vec()).unwrap());
+        }
+        png
+    }
+
+    #[test]
+    fn test_index() {
+        let bytes = testing_png().as_bytes();
+        let mut index = PngIndex::new(Cursor::new(&bytes)).unwrap();
+        assert_eq!(index.entries().len(), 3);
+
+        let middle = index.entries()[1];
+        assert_eq!(middle.chunk_type().to_string(), "miDl");
+        assert_eq!(middle.length(), 6);
+        assert_eq!(middle.offset(), 8 + 12 + 5);
+
+        let position = index.position(ChunkType::from_str("LASt").unwrap());
+        assert_eq!(position, Some(2));
+        let last = index.load(2).unwrap();
+        assert_eq!(last.data(), b"last");
+        assert!(index
+            .position(ChunkType::from_str("TeSt").unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn test_index_errors() {
+        let mut bytes = testing_png().as_bytes();
+        assert!(matches!(
+            PngIndex::new(Cursor::new(&bytes[1..])),
+            Err(PngmeError::InvalidSignature)
+        ));
+        assert!(matches!(
+            PngIndex::new(Cursor::new(&bytes[..bytes.len() - 2])),
+            Err(PngmeError::Parse { index: 2, .. })
+        ));
+
+        // A bad crc only shows once the chunk is loaded.
+        let end = bytes.len();
+        bytes[end - 1] ^= 0xff;
+        let mut index = PngIndex::new(Cursor::new(&bytes)).unwrap();
+        assert!(index.load(0).is_ok());
+        assert!(matches!(
+            index.load(2),
+            Err(PngmeError::CrcMismatch { offset, .. }) if offset == 8 + 12 + 5 + 12 + 6
+        ));
+    }
+}
@@ -0,0 +1,578 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::text::{self, InternationalText};
+use chrono::{DateTime, Local, TimeZone};
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+use std::fmt::Display;
+use std::fs::Metadata;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Most bytes compressed text is inflated to.
+const INFLATE_LIMIT: usize = 16 << 20;
+
+/// Width of the tag column in exiftool's output, before the `: `.
+const EXIFTOOL_WIDTH: usize = 32;
+
+/// Version of exiftool whose output `info --exiftool` follows.
+pub(crate) const EXIFTOOL_VERSION: &str = "12.76";
+
+/// A fact about a PNG, named as exiftool names it.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Tag {
+    /// Tag name, like `ImageWidth`, as `exiftool -s` prints.
+    pub(crate) name: String,
+    /// Description, like `Image Width`, as exiftool prints by default.
+    pub(crate) description: String,
+    pub(crate) value: String,
+}
+
+impl Tag {
+    fn new(name: &str, description: &str, value: impl ToString) -> Tag {
+        Tag {
+            name: name.to_string(),
+            description: description.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+/// What exiftool prints for data it won't show inline.
+fn binary(data: &[u8]) -> String {
+    format!(
+        "(Binary data {} bytes, use -b option to extract)",
+        data.len()
+    )
+}
+
+/// Value of a lookup table, or `Unknown (n)` as exiftool writes a value
+/// missing from one.
+fn lookup(names: &[(u8, &str)], value: u8) -> String {
+    names.iter().find(|(n, _)| *n == value).map_or_else(
+        || format!("Unknown ({})", value),
+        |(_, name)| name.to_string(),
+    )
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(*data.get(at..)?.first_chunk()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(*data.get(at..)?.first_chunk()?))
+}
+
+/// The 16-bit samples of the data, separated by spaces.
+fn samples(data: &[u8]) -> String {
+    let samples: Vec<String> = (0..data.len() / 2)
+        .filter_map(|i| u16_at(data, i * 2))
+        .map(|s| s.to_string())
+        .collect();
+    samples.join(" ")
+}
+
+/// Tags of IHDR.
+fn header_tags(data: &[u8]) -> Vec<Tag> {
+    let (Some(width), Some(height), Some(&[depth, color, compression, filter, interlace])) =
+        (u32_at(data, 0), u32_at(data, 4), data.get(8..13))
+    else {
+        return Vec::new();
+    };
+    let color_types = [
+        (0, "Grayscale"),
+        (2, "RGB"),
+        (3, "Palette"),
+        (4, "Grayscale with Alpha"),
+        (6, "RGB with Alpha"),
+    ];
+    vec![
+        Tag::new("ImageWidth", "Image Width", width),
+        Tag::new("ImageHeight", "Image Height", height),
+        Tag::new("BitDepth", "Bit Depth", depth),
+        Tag::new("ColorType", "Color Type", lookup(&color_types, color)),
+        Tag::new(
+            "Compression",
+            "Compression",
+            lookup(&[(0, "Deflate/Inflate")], compression),
+        ),
+        Tag::new("Filter", "Filter", lookup(&[(0, "Adaptive")], filter)),
+        Tag::new(
+            "Interlace",
+            "Interlace",
+            lookup(&[(0, "Noninterlaced"), (1, "Adam7 Interlace")], interlace),
+        ),
+    ]
+}
+
+/// Tags of cHRM: the white point then each primary, as fractions.
+fn chromaticity_tags(data: &[u8]) -> Vec<Tag> {
+    let names = [
+        ("WhitePointX", "White Point X"),
+        ("WhitePointY", "White Point Y"),
+        ("RedX", "Red X"),
+        ("RedY", "Red Y"),
+        ("GreenX", "Green X"),
+        ("GreenY", "Green Y"),
+        ("BlueX", "Blue X"),
+        ("BlueY", "Blue Y"),
+    ];
+    names
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (name, description))| {
+            let value = u32_at(data, i * 4)?;
+            Some(Tag::new(name, description, f64::from(value) / 100_000.0))
+        })
+        .collect()
+}
+
+/// Name exiftool makes up for text under a keyword it doesn't know:
+/// the keyword in CamelCase, keeping only letters, digits, `-` and `_`.
+fn text_name(keyword: &str) -> String {
+    let mut name = String::new();
+    let mut upper = true;
+    for c in keyword.chars() {
+        if c.is_whitespace() {
+            upper = true;
+        } else if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+            name.push(if upper { c.to_ascii_uppercase() } else { c });
+            upper = false;
+        }
+    }
+    match keyword {
+        "XML:com.adobe.xmp" => "XMP".to_string(),
+        _ => name,
+    }
+}
+
+/// Description exiftool gives a tag name: its words split apart, like
+/// `Creation Time` for `CreationTime`.
+fn describe(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut description = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if i > 0 {
+            let before = chars[i - 1];
+            let after = chars.get(i + 1).copied();
+            let new_word = (before.is_ascii_lowercase()
+                && (c.is_ascii_uppercase() || c.is_ascii_digit()))
+                || (before.is_ascii_uppercase()
+                    && c.is_ascii_uppercase()
+                    && after.is_some_and(|a| a.is_ascii_lowercase()));
+            if new_word {
+                description.push(' ');
+            }
+        }
+        description.push(if c == '_' { ' ' } else { c });
+    }
+    description
+}
+
+/// Tag of a tEXt, zTXt or iTXt chunk, named after its keyword.  `None` if
+/// the chunk is malformed.
+fn text_tag(chunk: &Chunk) -> Option<Tag> {
+    let chunk_type = chunk.chunk_type();
+    let data = chunk.data();
+    let null = data.iter().position(|b| *b == 0)?;
+    let keyword: String = data[..null].iter().map(|&b| char::from(b)).collect();
+    let (text, language) = if let Some(stream) = text::compressed_text(chunk_type, data) {
+        let inflated = decompress_to_vec_zlib_with_limit(stream, INFLATE_LIMIT).ok()?;
+        if *chunk_type == ChunkType::ZTXT {
+            (inflated.iter().map(|&b| char::from(b)).collect(), None)
+        } else {
+            // Only the text of iTXt is compressed: parse the rest as if it
+            // weren't.
+            let stored = data.len() - stream.len();
+            let mut uncompressed = data[..stored].to_vec();
+            uncompressed[null + 1] = 0;
+            uncompressed.extend(inflated);
+            let itxt = InternationalText::from_data(&uncompressed).ok()?;
+            (itxt.text, Some(itxt.language))
+        }
+    } else if *chunk_type == ChunkType::ITXT {
+        let itxt = InternationalText::from_data(data).ok()?;
+        (itxt.text, Some(itxt.language))
+    } else {
+        let text = data[null + 1..].iter().map(|&b| char::from(b)).collect();
+        (text, None)
+    };
+    let name = text_name(&keyword);
+    let description = describe(&name);
+    Some(match language.filter(|l| !l.is_empty()) {
+        Some(language) => Tag {
+            name: format!("{}-{}", name, language),
+            description: format!("{} ({})", description, language),
+            value: text,
+        },
+        None => Tag {
+            name,
+            description,
+            value: text,
+        },
+    })
+}
+
+/// Tags of one chunk, given the color type from IHDR.
+fn chunk_tags(chunk: &Chunk, color_type: Option<u8>) -> Vec<Tag> {
+    let data = chunk.data();
+    let chunk_type = *chunk.chunk_type();
+    let one = |tag: Option<Tag>| tag.into_iter().collect();
+    match chunk_type {
+        ChunkType::IHDR => header_tags(data),
+        ChunkType::PLTE => vec![Tag::new("Palette", "Palette", binary(data))],
+        ChunkType::TRNS if color_type == Some(3) => {
+            vec![Tag::new("Transparency", "Transparency", binary(data))]
+        }
+        ChunkType::TRNS => vec![Tag::new("Transparency", "Transparency", samples(data))],
+        ChunkType::BKGD if color_type == Some(3) => one(data
+            .first()
+            .map(|i| Tag::new("BackgroundColor", "Background Color", i))),
+        ChunkType::BKGD => vec![Tag::new(
+            "BackgroundColor",
+            "Background Color",
+            samples(data),
+        )],
+        ChunkType::SBIT => {
+            let bits: Vec<String> = data.iter().map(|b| b.to_string()).collect();
+            vec![Tag::new(
+                "SignificantBits",
+                "Significant Bits",
+                bits.join(" "),
+            )]
+        }
+        ChunkType::GAMA => one(u32_at(data, 0).filter(|g| *g > 0).map(|gamma| {
+            // Stored as 100000 over the gamma, shown to 3 decimals.
+            let gamma = (100_000_000.0 / f64::from(gamma)).round() / 1000.0;
+            Tag::new("Gamma", "Gamma", gamma)
+        })),
+        ChunkType::CHRM => chromaticity_tags(data),
+        ChunkType::SRGB => {
+            let intents = [
+                (0, "Perceptual"),
+                (1, "Relative Colorimetric"),
+                (2, "Saturation"),
+                (3, "Absolute Colorimetric"),
+            ];
+            one(data
+                .first()
+                .map(|&i| Tag::new("SRGBRendering", "SRGB Rendering", lookup(&intents, i))))
+        }
+        ChunkType::ICCP => {
+            let name = data.iter().take_while(|b| **b != 0);
+            let name: String = name.map(|&b| char::from(b)).collect();
+            vec![Tag::new("ProfileName", "Profile Name", name)]
+        }
+        ChunkType::PHYS => match (u32_at(data, 0), u32_at(data, 4), data.get(8)) {
+            (Some(x), Some(y), Some(&unit)) => vec![
+                Tag::new("PixelsPerUnitX", "Pixels Per Unit X", x),
+                Tag::new("PixelsPerUnitY", "Pixels Per Unit Y", y),
+                Tag::new(
+                    "PixelUnits",
+                    "Pixel Units",
+                    lookup(&[(0, "Unknown"), (1, "meters")], unit),
+                ),
+            ],
+            _ => Vec::new(),
+        },
+        ChunkType::TIME => match (u16_at(data, 0), data.get(2..7)) {
+            (Some(year), Some(&[month, day, hour, minute, second])) => vec![Tag::new(
+                "ModifyDate",
+                "Modify Date",
+                format!(
+                    "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+                    year, month, day, hour, minute, second
+                ),
+            )],
+            _ => Vec::new(),
+        },
+        ChunkType::TEXT | ChunkType::ZTXT | ChunkType::ITXT => one(text_tag(chunk)),
+        _ => Vec::new(),
+    }
+}
+
+/// Tags of the PNG's chunks, in the order of their chunks, then those
+/// exiftool works out from them.  Exif data isn't decoded.
+pub(crate) fn png_tags(png: &Png) -> Vec<Tag> {
+    let color_type = png
+        .chunk_by_type(ChunkType::IHDR)
+        .and_then(|c| c.data().get(9).copied());
+    let mut tags: Vec<Tag> = png.iter().flat_map(|c| chunk_tags(c, color_type)).collect();
+    if let (Some(width), Some(height)) = (png.width(), png.height()) {
+        let megapixels = f64::from(width) * f64::from(height) / 1e6;
+        let decimals = match megapixels {
+            m if m >= 1.0 => 1,
+            m if m >= 0.001 => 3,
+            _ => 6,
+        };
+        tags.push(Tag::new(
+            "ImageSize",
+            "Image Size",
+            format!("{}x{}", width, height),
+        ));
+        tags.push(Tag::new(
+            "Megapixels",
+            "Megapixels",
+            format!("{:.*}", decimals, megapixels),
+        ));
+    }
+    tags
+}
+
+/// File size the way exiftool writes it, in bytes up to 2 kB.
+fn file_size(size: u64) -> String {
+    let size_f = size as f64;
+    match size {
+        0..=2047 => format!("{} bytes", size),
+        2048..=10_239 => format!("{:.1} kB", size_f / 1024.0),
+        10_240..=2_097_151 => format!("{:.0} kB", size_f / 1024.0),
+        2_097_152..=10_485_759 => format!("{:.1} MB", size_f / 1_048_576.0),
+        _ => format!("{:.0} MB", size_f / 1_048_576.0),
+    }
+}
+
+/// Time as exiftool writes dates, in the given time zone with its offset.
+fn exif_date<Tz: TimeZone>(time: SystemTime, zone: &Tz) -> String
+where
+    Tz::Offset: Display,
+{
+    DateTime::<chrono::Utc>::from(time)
+        .with_timezone(zone)
+        .format("%Y:%m:%d %H:%M:%S%:z")
+        .to_string()
+}
+
+/// Time as exiftool writes file dates, in local time.
+pub(crate) fn local_date(time: SystemTime) -> String {
+    exif_date(time, &Local)
+}
+
+/// Permissions as `ls -l` and exiftool write them, like `-rw-r--r--`.
+#[cfg(unix)]
+fn permissions(metadata: &Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = metadata.permissions().mode();
+    let kind = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        _ => '-',
+    };
+    let mut shown = kind.to_string();
+    // Each of user, group and other, with the setuid, setgid and sticky
+    // bit that shows in its execute column.
+    for (shift, special, set) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        shown.push(if bits & 4 != 0 { 'r' } else { '-' });
+        shown.push(if bits & 2 != 0 { 'w' } else { '-' });
+        shown.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => set.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    shown
+}
+
+/// Permissions as exiftool writes them where there are no mode bits.
+#[cfg(not(unix))]
+fn permissions(metadata: &Metadata) -> String {
+    match metadata.permissions().readonly() {
+        true => "-r--r--r--".to_string(),
+        false => "-rw-rw-rw-".to_string(),
+    }
+}
+
+/// When the file's inode last changed, where files have one.
+fn inode_changed(metadata: &Metadata) -> Option<SystemTime> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let since_epoch = std::time::Duration::new(
+            u64::try_from(metadata.ctime()).ok()?,
+            u32::try_from(metadata.ctime_nsec()).ok()?,
+        );
+        Some(SystemTime::UNIX_EPOCH + since_epoch)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Tags exiftool reports about the file itself, ahead of those of the PNG,
+/// with dates in local time.  `metadata` is `None` for stdin.
+pub(crate) fn file_tags(path: &Path, size: u64, metadata: Option<&Metadata>) -> Vec<Tag> {
+    let mut tags = vec![Tag::new(
+        "ExifToolVersion",
+        "ExifTool Version Number",
+        EXIFTOOL_VERSION,
+    )];
+    if let Some(metadata) = metadata {
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        tags.push(Tag::new("FileName", "File Name", name.to_string_lossy()));
+        tags.push(Tag::new("Directory", "Directory", directory.display()));
+        tags.push(Tag::new("FileSize", "File Size", file_size(size)));
+        let dates = [
+            (
+                "FileModifyDate",
+                "File Modification Date/Time",
+                metadata.modified().ok(),
+            ),
+            (
+                "FileAccessDate",
+                "File Access Date/Time",
+                metadata.accessed().ok(),
+            ),
+            (
+                "FileInodeChangeDate",
+                "File Inode Change Date/Time",
+                inode_changed(metadata),
+            ),
+        ];
+        for (name, description, time) in dates {
+            if let Some(time) = time {
+                tags.push(Tag::new(name, description, local_date(time)));
+            }
+        }
+        tags.push(Tag::new(
+            "FilePermissions",
+            "File Permissions",
+            permissions(metadata),
+        ));
+    } else {
+        tags.push(Tag::new("FileSize", "File Size", file_size(size)));
+    }
+    tags.push(Tag::new("FileType", "File Type", "PNG"));
+    tags.push(Tag::new("FileTypeExtension", "File Type Extension", "png"));
+    tags.push(Tag::new("MIMEType", "MIME Type", "image/png"));
+    tags
+}
+
+/// Write the tags as exiftool does by default: the description padded to
+/// 32 columns, then `: ` and the value.
+pub(crate) fn write_exiftool(tags: &[Tag], out: &mut dyn Write) -> io::Result<()> {
+    for tag in tags {
+        writeln!(
+            out,
+            "{:<width$}: {}",
+            tag.description,
+            tag.value,
+            width = EXIFTOOL_WIDTH
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the tags as lines of their name, a tab, then the value.
+pub(crate) fn write_tags(tags: &[Tag], out: &mut dyn Write) -> io::Result<()> {
+    for tag in tags {
+        writeln!(out, "{}\t{}", tag.name, tag.value)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec()).unwrap()
+    }
+
+    fn exiftool(tags: &[Tag]) -> String {
+        let mut out = Vec::new();
+        write_exiftool(tags, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_png_tags() {
+        let mut png = Png::try_from(TURTLE).unwrap();
+        png.insert_chunk_at(1, chunk("gAMA", &45455u32.to_be_bytes()));
+        png.insert_chunk_at(2, chunk("sRGB", &[0]));
+        png.insert_chunk_at(3, chunk("pHYs", &[0, 0, 11, 19, 0, 0, 11, 19, 1]));
+        png.insert_chunk_at(4, chunk("tIME", &[7, 232, 5, 1, 10, 20, 30]));
+        png.insert_chunk_at(5, chunk("tEXt", b"Creation Time\x002024"));
+        png.insert_chunk_at(
+            6,
+            chunk("iTXt", "Title\0\0\0de\0Titel\0Schildkr\u{f6}te".as_bytes()),
+        );
+        let tags = png_tags(&png);
+        let shown = exiftool(&tags);
+        let (width, height) = (png.width().unwrap(), png.height().unwrap());
+        let palette = png.chunk_by_type(ChunkType::PLTE).unwrap().data().len();
+        let expected = format!(
+            "\
+Image Width                     : {width}
+Image Height                    : {height}
+Bit Depth                       : {depth}
+Color Type                      : Palette
+Compression                     : Deflate/Inflate
+Filter                          : Adaptive
+Interlace                       : Noninterlaced
+Gamma                           : 2.2
+SRGB Rendering                  : Perceptual
+Pixels Per Unit X               : 2835
+Pixels Per Unit Y               : 2835
+Pixel Units                     : meters
+Modify Date                     : 2024:05:01 10:20:30
+Creation Time                   : 2024
+Title (de)                      : Schildkr\u{f6}te
+Palette                         : (Binary data {palette} bytes, use -b option to extract)
+Image Size                      : {width}x{height}
+Megapixels                      : {megapixels:.3}
+",
+            depth = png.bit_depth().unwrap(),
+            megapixels = f64::from(width * height) / 1e6,
+        );
+        assert_eq!(shown, expected);
+        let names: Vec<&str> = tags.iter().map(|t| t.name.as_str()).collect();
+        assert!(names.contains(&"CreationTime") && names.contains(&"Title-de"));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(text_name("Creation Time"), "CreationTime");
+        assert_eq!(text_name("my-key_1"), "My-key_1");
+        assert_eq!(text_name("XML:com.adobe.xmp"), "XMP");
+        assert_eq!(describe("CreationTime"), "Creation Time");
+        assert_eq!(describe("MIMEType"), "MIME Type");
+        assert_eq!(describe("Exposure2"), "Exposure 2");
+    }
+
+    #[test]
+    fn test_file_tags() {
+        assert_eq!(file_size(2047), "2047 bytes");
+        assert_eq!(file_size(2048), "2.0 kB");
+        assert_eq!(file_size(20_480), "20 kB");
+        assert_eq!(file_size(3 << 20), "3.0 MB");
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_714_558_830);
+        assert_eq!(exif_date(time, &chrono::Utc), "2024:05:01 10:20:30+00:00");
+        let paris = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+        assert_eq!(exif_date(time, &paris), "2024:05:01 12:20:30+02:00");
+
+        let tags = file_tags(Path::new("-"), 100, None);
+        assert_eq!(
+            exiftool(&tags),
+            "\
+ExifTool Version Number         : 12.76
+File Size                       : 100 bytes
+File Type                       : PNG
+File Type Extension             : png
+MIME Type                       : image/png
+"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::args::RemoteArgs;
+use crate::config::Config;
+use crate::remote;
+use reqwest::blocking::multipart::{Form, Part};
+use reqwest::{Method, Url};
+use serde::Deserialize;
+use tracing::debug;
+
+/// URL scheme of IPFS content.
+pub(crate) const SCHEME: &str = "ipfs";
+
+/// Gateway used unless the config names another.
+const DEFAULT_GATEWAY: &str = "https://ipfs.io/";
+
+/// Node API used unless the config names another.
+const DEFAULT_API: &str = "http://127.0.0.1:5001/";
+
+/// Gateway url serving the content of an `ipfs://<cid>/<path>` url.
+pub(crate) fn gateway_url(url: &Url, config: &Config) -> crate::Result<Url> {
+    let cid = url
+        .host_str()
+        .filter(|cid| !cid.is_empty())
+        .ok_or_else(|| format!("Expected ipfs://<cid>, got '{}'", url))?;
+    let gateway = Url::parse(config.ipfs_gateway.as_deref().unwrap_or(DEFAULT_GATEWAY))?;
+    let mut resolved = gateway.join(&format!("ipfs/{}{}", cid, url.path()))?;
+    resolved.set_query(url.query());
+    debug!(%resolved, "resolved {}", url);
+    Ok(resolved)
+}
+
+/// Reply of the node's `add` endpoint.
+#[derive(Deserialize)]
+struct Added {
+    #[serde(rename = "Hash")]
+    hash: String,
+}
+
+/// Add and pin PNG bytes on the configured IPFS node, returning their CID.
+pub(crate) fn pin(bytes: Vec<u8>, remote: &RemoteArgs, config: &Config) -> crate::Result<String> {
+    let api = Url::parse(config.ipfs_api.as_deref().unwrap_or(DEFAULT_API))?;
+    let url = api.join("api/v0/add?pin=true")?;
+    let client = remote::client(remote, config)?;
+    let response = remote::send(remote::Retry::from_args(remote), Method::POST, |method| {
+        let part = Part::bytes(bytes.clone()).file_name("image.png");
+        remote::request(&client, method, &url, remote, config)
+            .multipart(Form::new().part("file", part))
+    })?;
+    let added: Added = serde_json::from_str(&response.text()?)?;
+    Ok(added.hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gateway_url() {
+        let url = Url::parse("ipfs://bafybeigdyrzt/images/turtle.png").unwrap();
+        assert_eq!(
+            gateway_url(&url, &Config::default()).unwrap().as_str(),
+            "https://ipfs.io/ipfs/bafybeigdyrzt/images/turtle.png"
+        );
+
+        let config = Config {
+            ipfs_gateway: Some("http://localhost:8080/".to_string()),
+            ..Config::default()
+        };
+        let url = Url::parse("ipfs://bafybeigdyrzt").unwrap();
+        assert_eq!(
+            gateway_url(&url, &config).unwrap().as_str(),
+            "http://localhost:8080/ipfs/bafybeigdyrzt"
+        );
+
+        assert!(gateway_url(&Url::parse("ipfs:///x").unwrap(), &config).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use crate::chunk::Chunk;
+use crate::envelope::Envelope;
+use crate::layout::Layout;
+use crate::png::Png;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Most cells in the table matching up the changed chunks of a Png.  Past
+/// it, the whole changed stretch is kept instead of only what differs.
+const DIFF_LIMIT: usize = 1 << 22;
+
+/// Path of the undo journal kept beside the given file.
+pub(crate) fn journal_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".undo");
+    PathBuf::from(name)
+}
+
+/// One change pngme made to a file, a line of JSON in its journal, holding
+/// just what it takes to turn the file back: the chunks it removed and
+/// where the ones it added are.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub(crate) struct JournalEntry {
+    /// When, as RFC 3339.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) at: Option<String>,
+    /// SHA-256 of the file after the change, as hex, to tell whether
+    /// something else changed it since.
+    pub(crate) digest: String,
+    /// Edits turning the changed Png back into the old one, in order.
+    pub(crate) edits: Vec<Edit>,
+}
+
+/// Replace `remove` chunks from index `at` with the `insert` ones.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub(crate) struct Edit {
+    pub(crate) at: usize,
+    pub(crate) remove: usize,
+    pub(crate) insert: Layout,
+}
+
+/// SHA-256 of the bytes, as hex.
+pub(crate) fn digest(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// SHA-256 of everything the reader holds, as hex, read a piece at a time.
+pub(crate) fn digest_of(mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+impl JournalEntry {
+    /// Entry undoing the change from the `old` Png to the `new` one, the
+    /// file now having the given digest.  Deterministic entries leave out
+    /// the time.
+    pub(crate) fn between(
+        old: &Png,
+        new: &Png,
+        digest: String,
+        deterministic: bool,
+    ) -> JournalEntry {
+        JournalEntry {
+            at: (!deterministic).then(Envelope::now),
+            digest,
+            edits: diff(old.chunks(), new.chunks()),
+        }
+    }
+
+    /// Entry undoing the addition of a single chunk at index `at`, like
+    /// [`JournalEntry::between`] but needing neither Png.
+    pub(crate) fn inserted(at: usize, digest: String, deterministic: bool) -> JournalEntry {
+        JournalEntry {
+            at: (!deterministic).then(Envelope::now),
+            digest,
+            edits: vec![Edit {
+                at,
+                remove: 1,
+                insert: Layout::of_chunks(&[]),
+            }],
+        }
+    }
+
+    /// The Png as it was before this change, from the Png after it.
+    pub(crate) fn revert(&self, png: &Png) -> crate::Result<Png> {
+        let mut chunks = png.chunks().to_vec();
+        for edit in &self.edits {
+            if edit.at + edit.remove > chunks.len() {
+                Err(format!(
+                    "Undo journal expects {} chunks, the PNG has {}",
+                    edit.at + edit.remove,
+                    chunks.len()
+                ))?
+            }
+            let insert = edit.insert.to_png()?;
+            chunks.splice(
+                edit.at..edit.at + edit.remove,
+                insert.chunks().iter().cloned(),
+            );
+        }
+        Ok(Png::from_chunks(chunks))
+    }
+}
+
+/// Edits turning the `after` chunks back into the `before` ones, last
+/// first so each leaves the indexes of the rest alone.
+fn diff(before: &[Chunk], after: &[Chunk]) -> Vec<Edit> {
+    let prefix = before.iter().zip(after).take_while(|(a, b)| a == b).count();
+    let (before, after) = (&before[prefix..], &after[prefix..]);
+    let suffix = before
+        .iter()
+        .rev()
+        .zip(after.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &before[..before.len() - suffix];
+    let new = &after[..after.len() - suffix];
+
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let end = (old.len(), new.len());
+    for (next_i, next_j) in common(old, new).into_iter().chain([end]) {
+        if next_i > i || next_j > j {
+            edits.push(Edit {
+                at: prefix + j,
+                remove: next_j - j,
+                insert: Layout::of_chunks(&old[i..next_i]),
+            });
+        }
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+    edits.reverse();
+    edits
+}
+
+/// Indexes of a longest run of chunks the two lists share, in order.
+/// Nothing if the lists are too long to match up.
+fn common(old: &[Chunk], new: &[Chunk]) -> Vec<(usize, usize)> {
+    let width = new.len() + 1;
+    if (old.len() + 1) * width > DIFF_LIMIT {
+        return Vec::new();
+    }
+    // lengths[i * width + j]: longest shared run of old[i..] and new[j..].
+    let mut lengths = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            pairs.push((i, j));
+            (i, j) = (i + 1, j + 1);
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Add the entry to the end of the journal of the file at the given path.
+pub(crate) fn append(path: &Path, entry: &JournalEntry) -> crate::Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    let mut journal = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(journal_path(path))?;
+    journal.write_all(&line)?;
+    Ok(())
+}
+
+/// The latest entry in the journal of the file at the given path, if any.
+pub(crate) fn last(path: &Path) -> crate::Result<Option<JournalEntry>> {
+    let journal = match fs::read_to_string(journal_path(path)) {
+        Ok(journal) => journal,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    match journal.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) => Ok(Some(serde_json::from_str(line)?)),
+        None => Ok(None),
+    }
+}
+
+/// Drop the latest entry from the journal of the file at the given path,
+/// removing the journal once it is empty.  Returns how many are left.
+pub(crate) fn pop(path: &Path) -> crate::Result<usize> {
+    let journal_path = journal_path(path);
+    let journal = fs::read_to_string(&journal_path)?;
+    let mut lines: Vec<&str> = journal.lines().filter(|l| !l.trim().is_empty()).collect();
+    lines.pop();
+    if lines.is_empty() {
+        fs::remove_file(&journal_path)?;
+    } else {
+        fs::write(&journal_path, lines.join("\n") + "\n")?;
+    }
+    Ok(lines.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.into()).unwrap()
+    }
+
+    #[test]
+    fn test_revert() {
+        let before = Png::try_from(TURTLE).unwrap();
+        let mut after = Png::try_from(TURTLE).unwrap();
+        // Like `encode --replace`: the old chunk goes, a new one is added
+        // elsewhere.
+        after.remove_all_chunks(ChunkType::from_str("ruSt").unwrap());
+        after.insert_chunk_at(1, chunk("teSt", "hi"));
+        after.insert_chunk_at(5, chunk("ruSt", "new"));
+
+        let after_digest = digest_of(after.as_bytes().as_slice()).unwrap();
+        assert_eq!(after_digest, digest(&after.as_bytes()));
+        let entry = JournalEntry::between(&before, &after, after_digest.clone(), true);
+        assert_eq!(entry.at, None);
+        assert_eq!(entry.digest, after_digest);
+        // The image data isn't kept, only the removed ruSt chunk.
+        let kept: usize = entry.edits.iter().map(|e| e.insert.chunks.len()).sum();
+        assert_eq!(kept, 1);
+        assert_eq!(entry.revert(&after).unwrap(), before);
+
+        let unchanged = JournalEntry::between(&before, &before, digest(TURTLE), true);
+        assert!(unchanged.edits.is_empty());
+        assert!(entry.revert(&Png::from_chunks(vec![])).is_err());
+
+        let mut appended = Png::try_from(TURTLE).unwrap();
+        appended.append_chunk(chunk("teSt", "hi"));
+        let at = appended.position_of(ChunkType::IEND).unwrap() - 1;
+        let entry = JournalEntry::inserted(at, digest(&appended.as_bytes()), true);
+        assert_eq!(entry.revert(&appended).unwrap(), before);
+    }
+
+    #[test]
+    fn test_append_and_pop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        assert_eq!(last(&path).unwrap(), None);
+
+        let first = JournalEntry::inserted(1, digest(TURTLE), true);
+        let second = JournalEntry::inserted(2, "0".repeat(64), true);
+        append(&path, &first).unwrap();
+        append(&path, &second).unwrap();
+
+        assert_eq!(last(&path).unwrap(), Some(second));
+        assert_eq!(pop(&path).unwrap(), 1);
+        assert_eq!(last(&path).unwrap(), Some(first));
+        assert_eq!(pop(&path).unwrap(), 0);
+        assert!(!journal_path(&path).exists());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Every chunk of a Png, in order, as written by `export-layout`.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub(crate) struct Layout {
+    pub(crate) chunks: Vec<LayoutChunk>,
+}
+
+/// One chunk of a [`Layout`].
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub(crate) struct LayoutChunk {
+    #[serde(rename = "type")]
+    pub(crate) chunk_type: String,
+    /// Length of the data.  Only informational: importing uses the data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) length: Option<u32>,
+    /// Checksum, as hex.  Only informational: importing recomputes it, so
+    /// the data can be edited by hand.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) crc: Option<String>,
+    /// Base64 of the data.
+    pub(crate) data: String,
+}
+
+impl Layout {
+    /// Layout of the given Png.
+    pub(crate) fn of(png: &Png) -> Layout {
+        Layout::of_chunks(png.chunks())
+    }
+
+    /// Layout of the given chunks, in order.
+    pub(crate) fn of_chunks(chunks: &[Chunk]) -> Layout {
+        let chunks = chunks
+            .iter()
+            .map(|chunk| LayoutChunk {
+                chunk_type: chunk.chunk_type().to_string(),
+                length: Some(chunk.length()),
+                crc: Some(format!("{:08x}", chunk.crc())),
+                data: STANDARD.encode(chunk.data()),
+            })
+            .collect();
+        Layout { chunks }
+    }
+
+    /// The Png this layout describes.  `Err` naming the chunk whose type or
+    /// data is invalid.
+    pub(crate) fn to_png(&self) -> crate::Result<Png> {
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let invalid = |e: &dyn std::fmt::Display| format!("Chunk {}: {}", index, e);
+            let chunk_type = ChunkType::from_str(&chunk.chunk_type).map_err(|e| invalid(&e))?;
+            let data = STANDARD.decode(&chunk.data).map_err(|e| invalid(&e))?;
+            chunks.push(Chunk::new(chunk_type, data).map_err(|e| invalid(&e))?);
+        }
+        Ok(Png::from_chunks(chunks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    #[test]
+    fn test_round_trip() {
+        let png = Png::try_from(TURTLE).unwrap();
+        let layout = Layout::of(&png);
+        assert_eq!(layout.chunks[0].chunk_type, "IHDR");
+        assert_eq!(layout.chunks[0].length, Some(13));
+
+        let json = serde_json::to_string_pretty(&layout).unwrap();
+        let parsed: Layout = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, layout);
+        assert_eq!(parsed.to_png().unwrap(), png);
+    }
+
+    #[test]
+    fn test_to_png() {
+        let json = r#"{"chunks": [{"type": "ruSt", "crc": "00000000", "data": "aGk="}]}"#;
+        let layout: Layout = serde_json::from_str(json).unwrap();
+        let png = layout.to_png().unwrap();
+        assert_eq!(png[0].data(), b"hi");
+
+        let json =
+            r#"{"chunks": [{"type": "ruSt", "data": "aGk="}, {"type": "r5St", "data": ""}]}"#;
+        let layout: Layout = serde_json::from_str(json).unwrap();
+        let error = layout.to_png().unwrap_err().to_string();
+        assert!(error.starts_with("Chunk 1: "), "{}", error);
+    }
+}
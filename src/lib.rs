@@ -1,19 +1,58 @@
+#[cfg(feature = "crypto")]
 #[macro_use]
 extern crate magic_crypt;
 
+#[cfg(any(feature = "capi", feature = "node", feature = "wasm", test))]
+mod bindings;
+mod builder;
+#[cfg(feature = "capi")]
+pub mod capi;
 mod chunk;
 mod chunk_type;
+#[cfg(feature = "crypto")]
 mod encrypt;
+mod error;
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
+mod index;
+#[cfg(all(feature = "mmap", not(target_arch = "wasm32")))]
+mod mmap;
+#[cfg(feature = "node")]
+pub mod node;
+#[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+pub mod nonblocking;
+mod observer;
 mod png;
+mod salvage;
+mod text;
+mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 #[doc(inline)]
-pub use chunk::Chunk;
+pub use builder::PngBuilder;
 #[doc(inline)]
-pub use chunk_type::ChunkType;
+pub use chunk::{Chunk, ChunkRef, DataMut, RawChunkRef};
 #[doc(inline)]
-pub use png::Png;
+pub use chunk_type::{ChunkType, RawChunkType};
+#[doc(inline)]
+pub use index::{IndexEntry, PngIndex};
+#[cfg(all(feature = "mmap", not(target_arch = "wasm32")))]
+#[doc(inline)]
+pub use mmap::MappedPng;
+#[doc(inline)]
+pub use observer::Observer;
+#[doc(inline)]
+pub use png::{ColorType, Png};
+#[doc(inline)]
+pub use salvage::Salvaged;
+#[doc(inline)]
+pub use text::InternationalText;
+#[doc(inline)]
+pub use validate::Violation;
+
+#[doc(inline)]
+pub use error::{PngmeError, Result};
 
-/// Holds any kind of error.
-pub type Error = Box<dyn std::error::Error>;
-/// Holds a `Result` of any kind of error.
-pub type Result<T> = std::result::Result<T, Error>;
+/// Error of any pngme operation.
+pub type Error = PngmeError;
@@ -4,16 +4,53 @@ extern crate magic_crypt;
 use structopt::StructOpt;
 
 mod args;
+mod audit;
+mod azure;
+mod builder;
+mod cache;
 mod chunk;
 mod chunk_type;
 mod commands;
-mod png;
+mod config;
+mod data_uri;
+mod detect;
 mod encrypt;
+mod envelope;
+mod error;
+mod exif;
+mod ftp;
+mod gcs;
+mod grpc;
+mod index;
+mod info;
+mod ipfs;
+mod journal;
+mod layout;
+mod observer;
+mod plan;
+mod png;
+mod qr;
+mod remote;
+mod s3;
+mod salvage;
+mod seal;
+mod server;
+mod sftp;
+mod strip;
+mod suggest;
+mod text;
+mod validate;
+mod watermark;
+mod webdav;
 
 pub(crate) type Error = Box<dyn std::error::Error>;
 pub(crate) type Result<T> = std::result::Result<T, Error>;
 
-fn main() -> Result<()> {
+fn main() {
     let cli = args::Cli::from_args();
-    commands::run(cli.subcommand)
+    // Display rather than Debug, so errors read as sentences.
+    if let Err(e) = commands::run(cli) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,85 @@
+use std::fs::File;
+use std::path::Path;
+
+use crate::chunk::ChunkRef;
+use crate::error::{PngmeError, Result};
+use crate::png::Png;
+use memmap2::Mmap;
+use tracing::debug;
+
+/// Png file mapped into memory.  Its chunks borrow from the mapping, so
+/// reading them leaves the data in the page cache instead of copying it.
+pub struct MappedPng {
+    map: Mmap,
+}
+
+impl MappedPng {
+    /// Map the file at the given path into memory, whatever it holds.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Png::open_mmap`].
+    unsafe fn open_unchecked(path: impl AsRef<Path>) -> Result<MappedPng> {
+        let file = File::open(path.as_ref())?;
+        // SAFETY: the mapping is read-only, and the caller keeps the file
+        // from changing while it is mapped.
+        let map = unsafe { Mmap::map(&file)? };
+        debug!(path = %path.as_ref().display(), bytes = map.len(), "mapped file");
+        Ok(MappedPng { map })
+    }
+
+    /// All bytes of the file.
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Chunks of the Png, borrowing their data from the mapping.
+    pub fn chunk_refs(&self) -> Result<Vec<ChunkRef<'_>>> {
+        Png::chunk_refs(&self.map)
+    }
+}
+
+impl Png {
+    /// Map the Png file at the given path into memory.  `Err` if it can't be
+    /// mapped or doesn't start with the PNG signature.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or written to, by this process or any
+    /// other, until the returned `MappedPng` is dropped.  Its bytes would
+    /// change under the borrows of them, and reading past a truncated end
+    /// raises `SIGBUS`.  Advisory locks don't stop other writers, so only
+    /// map files nothing else changes.
+    pub unsafe fn open_mmap(path: impl AsRef<Path>) -> Result<MappedPng> {
+        // SAFETY: passed on to the caller.
+        let mapped = unsafe { MappedPng::open_unchecked(path)? };
+        if !mapped.map.starts_with(&Png::STANDARD_HEADER) {
+            return Err(PngmeError::InvalidSignature);
+        }
+        Ok(mapped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_mmap() {
+        // SAFETY: nothing writes to the repo's files during tests.
+        let open = |path| unsafe { Png::open_mmap(path) };
+        let mapped = open("turtle.png").unwrap();
+        let png = Png::try_from(&std::fs::read("turtle.png").unwrap()[..]).unwrap();
+        assert_eq!(mapped.bytes(), png.as_bytes());
+
+        let chunks = mapped.chunk_refs().unwrap();
+        assert_eq!(chunks.len(), png.chunks().len());
+        assert_eq!(chunks[0].chunk_type().to_string(), "IHDR");
+
+        assert!(matches!(
+            open("Cargo.toml"),
+            Err(PngmeError::InvalidSignature)
+        ));
+        assert!(open("no/such/file.png").is_err());
+    }
+}
@@ -0,0 +1,69 @@
+//! N-API bindings for Node.js and Electron, working on `Buffer`s so asset
+//! pipelines can call pngme without spawning the CLI.
+
+use crate::bindings;
+use crate::{png::Png, Result};
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+use std::convert::TryFrom;
+
+/// One chunk listed by `print`.
+#[napi(object)]
+pub struct ChunkEntry {
+    pub chunk_type: String,
+    pub data: Buffer,
+}
+
+fn print_png(image: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let png = Png::try_from(image)?;
+    Ok(png
+        .chunks()
+        .iter()
+        .map(|chunk| (chunk.chunk_type().to_string(), chunk.data().to_vec()))
+        .collect())
+}
+
+fn js_error(e: crate::Error) -> napi::Error {
+    napi::Error::from_reason(e.to_string())
+}
+
+/// The image with `message` added in a chunk of the given type.
+#[napi]
+pub fn encode(image: Buffer, chunk_type: String, message: String) -> napi::Result<Buffer> {
+    bindings::encode(&image, &chunk_type, &message)
+        .map(Buffer::from)
+        .map_err(js_error)
+}
+
+/// The message in the first chunk of the given type, `null` if none.
+#[napi]
+pub fn decode(image: Buffer, chunk_type: String) -> napi::Result<Option<String>> {
+    bindings::decode(&image, &chunk_type).map_err(js_error)
+}
+
+/// Every chunk in the image, in order.
+#[napi]
+pub fn print(image: Buffer) -> napi::Result<Vec<ChunkEntry>> {
+    let chunks = print_png(&image).map_err(js_error)?;
+    Ok(chunks
+        .into_iter()
+        .map(|(chunk_type, data)| ChunkEntry {
+            chunk_type,
+            data: Buffer::from(data),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    #[test]
+    fn test_print() {
+        let chunks = print_png(TURTLE).unwrap();
+        assert_eq!(chunks[0].0, "IHDR");
+        assert!(chunks.iter().any(|(chunk_type, _)| chunk_type == "ruSt"));
+    }
+}
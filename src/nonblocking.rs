@@ -0,0 +1,136 @@
+//! Async counterparts of reading, writing and fetching PNGs, for services
+//! that run on tokio and shouldn't block their worker threads.
+
+use crate::{
+    chunk::Chunk, chunk_type::ChunkType, observer::Observer, png::Png, PngmeError, Result,
+};
+use std::convert::TryFrom;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+impl Png {
+    /// Download and parse the Png at the given url.  `Err` if the request
+    /// fails, the server answers with an error status or the body isn't a
+    /// Png.
+    pub async fn from_url(url: &str) -> Result<Png> {
+        Png::from_url_observed(url, &mut ()).await
+    }
+
+    /// Download and parse the Png at the given url like [`Png::from_url`],
+    /// telling `observer` how much has arrived after each piece of the body.
+    /// `Err` if it cancels, which drops the connection.
+    pub async fn from_url_observed(url: &str, observer: &mut (dyn Observer + Send)) -> Result<Png> {
+        let mut response = reqwest::get(url).await?.error_for_status()?;
+        let total = response.content_length();
+        let mut bytes = Vec::with_capacity(total.unwrap_or(0) as usize);
+        observer.on_download_progress(0, total);
+        while let Some(piece) = response.chunk().await? {
+            if observer.is_cancelled() {
+                return Err(PngmeError::Cancelled);
+            }
+            bytes.extend_from_slice(&piece);
+            observer.on_download_progress(bytes.len() as u64, total);
+        }
+        Png::from_reader_observed(bytes.as_slice(), observer)
+    }
+}
+
+/// Read a whole Png from the given reader.
+pub async fn read_png<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Png> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).await?;
+    Png::try_from(bytes.as_slice())
+}
+
+/// Write a whole Png to the given writer.
+pub async fn write_png<W: AsyncWrite + Unpin>(writer: &mut W, png: &Png) -> Result<()> {
+    writer.write_all(&png.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Download the Png at the given url and add `message` to it in a chunk of
+/// the given type.
+pub async fn encode_remote(url: &str, chunk_type: ChunkType, message: &str) -> Result<Png> {
+    let mut png = Png::from_url(url).await?;
+    png.append_chunk(Chunk::new(chunk_type, message.as_bytes().to_vec())?);
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use tokio::net::TcpListener;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    /// Url of a server that answers one request with turtle.png.
+    async fn serve_turtle() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/turtle.png", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = socket.read(&mut request).await.unwrap();
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                TURTLE.len()
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(TURTLE).await.unwrap();
+        });
+        url
+    }
+
+    #[test]
+    fn test_read_write_round_trip() {
+        block_on(async {
+            let png = read_png(&mut &TURTLE[..]).await.unwrap();
+            let mut written = Vec::new();
+            write_png(&mut written, &png).await.unwrap();
+            assert_eq!(written, TURTLE);
+
+            assert!(read_png(&mut &b"not a png"[..]).await.is_err());
+        })
+    }
+
+    #[test]
+    fn test_from_url_observed() {
+        #[derive(Default)]
+        struct Downloads(Vec<(u64, Option<u64>)>);
+
+        impl Observer for Downloads {
+            fn on_download_progress(&mut self, received: u64, total: Option<u64>) {
+                self.0.push((received, total));
+            }
+        }
+
+        block_on(async {
+            let url = serve_turtle().await;
+            let mut downloads = Downloads::default();
+            Png::from_url_observed(&url, &mut downloads).await.unwrap();
+            let total = Some(TURTLE.len() as u64);
+            assert_eq!(downloads.0.first(), Some(&(0, total)));
+            assert_eq!(downloads.0.last(), Some(&(TURTLE.len() as u64, total)));
+        })
+    }
+
+    #[test]
+    fn test_encode_remote() {
+        block_on(async {
+            let url = serve_turtle().await;
+            let chunk_type = ChunkType::from_str("teSt").unwrap();
+            let png = encode_remote(&url, chunk_type, "Secret").await.unwrap();
+            let chunk = png.chunk_by_type(chunk_type).unwrap();
+            assert_eq!(chunk.data(), b"Secret");
+        })
+    }
+}
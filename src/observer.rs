@@ -0,0 +1,40 @@
+use crate::chunk::Chunk;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Callbacks for following a long parse, write or download, to show
+/// progress or cancel it.  Every method does nothing unless overridden, and
+/// `()` observes nothing.
+pub trait Observer {
+    /// The chunk numbered `index`, counting from 0, was parsed.
+    fn on_chunk_parsed(&mut self, index: usize, chunk: &Chunk) {
+        let _ = (index, chunk);
+    }
+
+    /// `written` bytes have been written so far.
+    fn on_bytes_written(&mut self, written: usize) {
+        let _ = written;
+    }
+
+    /// `received` bytes have been downloaded so far, out of `total` if the
+    /// server said how many.
+    fn on_download_progress(&mut self, received: u64, total: Option<u64>) {
+        let _ = (received, total);
+    }
+
+    /// Should the operation stop.  Checked between chunks and between
+    /// pieces of a download, which then fail with
+    /// [`PngmeError::Cancelled`](crate::PngmeError::Cancelled).
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+impl Observer for () {}
+
+/// A flag as a cancellation token: the operation stops once it is set,
+/// from any thread.
+impl Observer for &AtomicBool {
+    fn is_cancelled(&self) -> bool {
+        self.load(Ordering::Relaxed)
+    }
+}
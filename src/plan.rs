@@ -0,0 +1,235 @@
+use crate::args::Place;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::text::keyword_bytes;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Manifest read by `apply`: the files to change and what to do to each.
+///
+/// ```yaml
+/// files:
+///   - path: dice.png
+///     operations:
+///       - op: strip
+///         keep: [ruSt]
+///       - op: encode
+///         type: ruSt
+///         message: "This is a secret message!"
+///         replace: true
+///       - op: text-set
+///         keyword: Author
+///         text: Jackson
+/// ```
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Plan {
+    pub(crate) files: Vec<FilePlan>,
+}
+
+/// One file of a [`Plan`].
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FilePlan {
+    /// The PNG, relative to the manifest's directory.
+    pub(crate) path: PathBuf,
+    /// Where to write the result, if not over `path`.
+    #[serde(default)]
+    pub(crate) output: Option<PathBuf>,
+    /// Run in order.  If one fails the file is left as it was.
+    pub(crate) operations: Vec<Operation>,
+}
+
+/// A change to a Png, with the same meaning as the subcommand of that name.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "op", rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) enum Operation {
+    /// Add a message in a chunk of the type.
+    Encode {
+        #[serde(rename = "type", deserialize_with = "chunk_type")]
+        chunk_type: ChunkType,
+        message: String,
+        /// Remove the chunks of the type already there first.
+        #[serde(default)]
+        replace: bool,
+        #[serde(default, deserialize_with = "place")]
+        place: Option<Place>,
+    },
+    /// Remove the first chunk of the type, the `nth`, or `all` of them.
+    Remove {
+        #[serde(rename = "type", deserialize_with = "chunk_type")]
+        chunk_type: ChunkType,
+        #[serde(default)]
+        all: bool,
+        #[serde(default)]
+        nth: Option<usize>,
+    },
+    /// Remove every ancillary chunk but those of the `keep` types.
+    Strip {
+        #[serde(default, deserialize_with = "chunk_types")]
+        keep: Vec<ChunkType>,
+    },
+    /// Set the tEXt entry with the keyword, replacing any already there.
+    TextSet { keyword: String, text: String },
+}
+
+impl Operation {
+    /// Name of the operation, as written in the manifest.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Operation::Encode { .. } => "encode",
+            Operation::Remove { .. } => "remove",
+            Operation::Strip { .. } => "strip",
+            Operation::TextSet { .. } => "text-set",
+        }
+    }
+}
+
+fn chunk_type<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ChunkType, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    ChunkType::from_str(&s).map_err(de::Error::custom)
+}
+
+fn chunk_types<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<ChunkType>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|s| ChunkType::from_str(s).map_err(de::Error::custom))
+        .collect()
+}
+
+fn place<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Place>, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    Place::from_str(&s).map(Some).map_err(de::Error::custom)
+}
+
+impl Plan {
+    /// Parse a manifest, checking every chunk type and place in it, so a
+    /// typo fails before any file is touched.  Relative paths are taken
+    /// from `dir`, the manifest's directory.
+    pub(crate) fn parse(yaml: &str, dir: &Path) -> crate::Result<Plan> {
+        let mut plan: Plan = serde_yaml::from_str(yaml)?;
+        for file in &mut plan.files {
+            file.path = dir.join(&file.path);
+            file.output = file.output.as_ref().map(|output| dir.join(output));
+        }
+        Ok(plan)
+    }
+}
+
+/// Data of a tEXt chunk: the keyword, a null, then the text, which like the
+/// keyword must be Latin-1.
+pub(crate) fn text_data(keyword: &str, text: &str) -> crate::Result<Vec<u8>> {
+    let mut data = keyword_bytes(keyword)?;
+    data.push(0);
+    for c in text.chars() {
+        match u8::try_from(u32::from(c)) {
+            Ok(b) if b != 0 => data.push(b),
+            _ => Err(format!(
+                "{:?} can't go in tEXt, which is Latin-1 (encode it as iTXt instead)",
+                c
+            ))?,
+        }
+    }
+    Ok(data)
+}
+
+/// Set the tEXt entry with the keyword to the text, in place of the first
+/// entry with that keyword and dropping any others, else before IEND.
+pub(crate) fn set_text(png: &mut Png, keyword: &str, text: &str) -> crate::Result<()> {
+    let chunk = Chunk::new(ChunkType::TEXT, text_data(keyword, text)?)?;
+    let prefix = [keyword_bytes(keyword)?, vec![0]].concat();
+    let has_keyword =
+        |c: &Chunk| *c.chunk_type() == ChunkType::TEXT && c.data().starts_with(&prefix);
+    let at = png.chunks().iter().position(has_keyword);
+    png.retain(|c| !has_keyword(c));
+    let at = at
+        .or_else(|| png.position_of(ChunkType::IEND))
+        .unwrap_or(png.len());
+    png.insert_chunk_at(at, chunk);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    #[test]
+    fn test_parse() {
+        let yaml = r#"
+files:
+  - path: dice.png
+    output: out/dice.png
+    operations:
+      - op: encode
+        type: ruSt
+        message: hi
+        place: before-idat
+      - op: remove
+        type: teSt
+        all: true
+      - op: strip
+      - op: text-set
+        keyword: Author
+        text: me
+"#;
+        let plan = Plan::parse(yaml, Path::new("assets")).unwrap();
+        let file = &plan.files[0];
+        assert_eq!(file.path, Path::new("assets/dice.png"));
+        assert_eq!(
+            file.output.as_deref(),
+            Some(Path::new("assets/out/dice.png"))
+        );
+        let names: Vec<&str> = file.operations.iter().map(Operation::name).collect();
+        assert_eq!(names, ["encode", "remove", "strip", "text-set"]);
+        assert_eq!(
+            file.operations[0],
+            Operation::Encode {
+                chunk_type: ChunkType::from_str("ruSt").unwrap(),
+                message: "hi".to_string(),
+                replace: false,
+                place: Some(Place::BeforeIdat),
+            }
+        );
+        assert_eq!(file.operations[2], Operation::Strip { keep: vec![] });
+    }
+
+    #[test]
+    fn test_parse_is_strict() {
+        let dir = Path::new(".");
+        let bad = [
+            "files: [{path: a.png, operations: [{op: encode, type: Ru1t, message: hi}]}]",
+            "files: [{path: a.png, operations: [{op: encode, type: ruSt, message: hi, place: top}]}]",
+            "files: [{path: a.png, operations: [{op: strip, keep: [ruSt], colour: true}]}]",
+            "files: [{path: a.png, operations: [{op: resize}]}]",
+            "files: [{path: a.png}]",
+        ];
+        for yaml in bad {
+            assert!(Plan::parse(yaml, dir).is_err(), "{}", yaml);
+        }
+    }
+
+    #[test]
+    fn test_set_text() {
+        let mut png = Png::try_from(TURTLE).unwrap();
+        set_text(&mut png, "Author", "one").unwrap();
+        set_text(&mut png, "Title", "turtle").unwrap();
+        set_text(&mut png, "Author", "two").unwrap();
+
+        let texts: Vec<&[u8]> = png
+            .chunks_by_type(ChunkType::TEXT)
+            .map(Chunk::data)
+            .collect();
+        assert_eq!(texts, [&b"Author\0two"[..], &b"Title\0turtle"[..]]);
+        let end = png.position_of(ChunkType::IEND).unwrap();
+        assert_eq!(png[end - 1].data(), b"Title\0turtle");
+
+        assert!(set_text(&mut png, "Author", "日本").is_err());
+        assert!(set_text(&mut png, "", "empty").is_err());
+    }
+}
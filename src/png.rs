@@ -1,17 +1,80 @@
 use std::{
     fmt,
-    io::{BufReader, Read},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    ops::Index,
+    slice, vec,
 };
 
-use crate::{chunk::Chunk, chunk_type::ChunkType, Error, Result};
+use crate::error::{PngmeError, Result};
+use crate::index::PngIndex;
+use crate::observer::Observer;
+use crate::{
+    chunk::{Chunk, ChunkRef, RawChunkRef, MAXIMUM_LENGTH},
+    chunk_type::{ChunkType, RawChunkType},
+};
+use tracing::trace;
+
+/// How the pixels of a Png are stored, per its IHDR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorType {
+    /// Each pixel is a grayscale sample.
+    Grayscale,
+    /// Each pixel is an R, G, B triple.
+    Rgb,
+    /// Each pixel is an index into the PLTE palette.
+    Indexed,
+    /// Each pixel is a grayscale sample followed by an alpha sample.
+    GrayscaleAlpha,
+    /// Each pixel is an R, G, B triple followed by an alpha sample.
+    Rgba,
+}
+
+#[allow(dead_code)]
+impl ColorType {
+    /// The color type stored as the given IHDR byte.  `None` if the PNG
+    /// specification doesn't define it.
+    pub const fn from_byte(byte: u8) -> Option<ColorType> {
+        match byte {
+            0 => Some(ColorType::Grayscale),
+            2 => Some(ColorType::Rgb),
+            3 => Some(ColorType::Indexed),
+            4 => Some(ColorType::GrayscaleAlpha),
+            6 => Some(ColorType::Rgba),
+            _ => None,
+        }
+    }
+
+    /// The IHDR byte of this color type.
+    pub const fn byte(&self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
 
-/// Png.
+    /// Samples per pixel.
+    pub const fn channels(&self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+}
+
+/// Png.  Pngs are equal when their bytes are.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Png {
     chunks: Vec<Chunk>,
 }
 
 impl Png {
-    const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    /// Signature every Png starts with.
+    pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
     /// Construct a Png from chunks
     fn new(chunks: Vec<Chunk>) -> Self {
@@ -19,27 +82,143 @@ impl Png {
     }
 
     /// Construct a Png from chunks
-    fn from_chunks(chunks: Vec<Chunk>) -> Png {
+    pub(crate) fn from_chunks(chunks: Vec<Chunk>) -> Png {
         Png::new(chunks)
     }
 
-    /// Append the given chunk to the end of this Png.
+    /// Add the given chunk just before IEND, or at the end of a Png without
+    /// one.
     pub fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+        let at = self
+            .position_of(ChunkType::IEND)
+            .unwrap_or(self.chunks.len());
+        self.chunks.insert(at, chunk);
+    }
+
+    /// Add the given chunk to the Png in `file` just before IEND, like
+    /// [`Png::append_chunk`], by writing it over IEND followed by IEND and
+    /// whatever came after it, so only those bytes are written.  A Png
+    /// without IEND gets the chunk at its end instead.
+    pub fn append_chunk_in_place<F: Read + Write + Seek>(
+        file: &mut F,
+        chunk: &Chunk,
+    ) -> Result<()> {
+        file.rewind()?;
+        let mut index = PngIndex::new(&mut *file)?;
+        let iend = index.position(ChunkType::IEND);
+        let mut rest = Vec::new();
+        let mut offset = None;
+        if let Some(iend) = iend {
+            offset = Some(index.entries()[iend].offset());
+            for i in iend..index.entries().len() {
+                rest.push(index.load(i)?);
+            }
+        }
+        match offset {
+            Some(offset) => file.seek(SeekFrom::Start(offset))?,
+            None => file.seek(SeekFrom::End(0))?,
+        };
+        file.write_all(&chunk.as_bytes())?;
+        for chunk in &rest {
+            file.write_all(&chunk.as_bytes())?;
+        }
+        file.flush()?;
+        trace!(chunk_type = %chunk.chunk_type(), length = chunk.length(), "appended chunk in place");
+        Ok(())
+    }
+
+    /// Insert the given chunk so it becomes the chunk at `index`, shifting
+    /// the chunks after it along.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of chunks.
+    pub fn insert_chunk_at(&mut self, index: usize, chunk: Chunk) {
+        self.chunks.insert(index, chunk);
+    }
+
+    /// Index of the first chunk matching the given type.
+    pub fn position_of(&self, chunk_type: ChunkType) -> Option<usize> {
+        self.chunks
+            .iter()
+            .position(|c| c.chunk_type() == &chunk_type)
+    }
+
+    /// Put the given chunk in place of the first chunk matching the given
+    /// type, keeping its position, and return the chunk it replaced.  `Err`
+    /// if no matching chunk found.
+    #[allow(dead_code)]
+    pub fn replace_chunk(&mut self, chunk_type: ChunkType, chunk: Chunk) -> Result<Chunk> {
+        match self.position_of(chunk_type) {
+            Some(i) => Ok(std::mem::replace(&mut self.chunks[i], chunk)),
+            None => Err(PngmeError::ChunkNotFound(chunk_type)),
+        }
+    }
+
+    /// Replace every chunk of the given chunk's type with it, adding it just
+    /// before IEND like [`Png::append_chunk`].  Returns the chunks it
+    /// replaced.
+    pub fn upsert_chunk(&mut self, chunk: Chunk) -> Vec<Chunk> {
+        let removed = self.remove_all_chunks(*chunk.chunk_type());
+        self.append_chunk(chunk);
+        removed
     }
 
     /// Remove the chunk matching the given type from this Png. `Err` if no mathcing chunk found.
     pub fn remove_chunk(&mut self, chunk_type: ChunkType) -> Result<Chunk> {
+        self.remove_nth_chunk(chunk_type, 0)
+    }
+
+    /// Remove the `n`th chunk matching the given type, counting from 0.
+    /// `Err` if there are no more than `n` matching chunks.
+    pub fn remove_nth_chunk(&mut self, chunk_type: ChunkType, n: usize) -> Result<Chunk> {
         let op_idx = self
             .chunks
             .iter()
-            .position(|c| c.chunk_type() == &chunk_type);
+            .enumerate()
+            .filter(|(_, c)| c.chunk_type() == &chunk_type)
+            .nth(n)
+            .map(|(i, _)| i);
         match op_idx {
             Some(i) => Ok(self.chunks.remove(i)),
-            None => Err("No matching chunk")?,
+            None => Err(PngmeError::ChunkNotFound(chunk_type)),
         }
     }
 
+    /// Remove every chunk matching the given type, returning them in order.
+    pub fn remove_all_chunks(&mut self, chunk_type: ChunkType) -> Vec<Chunk> {
+        let (removed, kept) = self
+            .chunks
+            .drain(..)
+            .partition(|c| c.chunk_type() == &chunk_type);
+        self.chunks = kept;
+        removed
+    }
+
+    /// Keep only the chunks for which `keep` returns true, in order.
+    #[allow(dead_code)]
+    pub fn retain(&mut self, keep: impl FnMut(&Chunk) -> bool) {
+        self.chunks.retain(keep);
+    }
+
+    /// Number of chunks in this Png.
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Does this Png have no chunks.
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Iterate over the chunks of this Png, in order.
+    #[allow(dead_code)]
+    pub fn iter(&self) -> slice::Iter<'_, Chunk> {
+        self.chunks.iter()
+    }
+
     /// Standard Png header.
     #[allow(dead_code)]
     fn header(&self) -> &[u8; 8] {
@@ -56,14 +235,279 @@ impl Png {
         self.chunks.iter().find(|c| c.chunk_type() == &chunk_type)
     }
 
+    /// All chunks matching the given type, in order.
+    #[allow(dead_code)]
+    pub fn chunks_by_type(&self, chunk_type: ChunkType) -> impl Iterator<Item = &Chunk> {
+        self.chunks
+            .iter()
+            .filter(move |c| c.chunk_type() == &chunk_type)
+    }
+
+    /// The `n`th chunk matching the given type, counting from 0.
+    #[allow(dead_code)]
+    pub fn nth_chunk_by_type(&self, chunk_type: ChunkType, n: usize) -> Option<&Chunk> {
+        self.chunks_by_type(chunk_type).nth(n)
+    }
+
+    /// Data of the IHDR chunk, if it is first and long enough.
+    fn image_header(&self) -> Option<&[u8; 13]> {
+        let chunk = self.chunks.first()?;
+        if chunk.chunk_type() != &ChunkType::IHDR {
+            return None;
+        }
+        chunk.data().get(..13)?.try_into().ok()
+    }
+
+    /// Big-endian u32 at the given offset of the IHDR data.
+    fn header_u32(&self, at: usize) -> Option<u32> {
+        let header = self.image_header()?;
+        Some(u32::from_be_bytes([
+            header[at],
+            header[at + 1],
+            header[at + 2],
+            header[at + 3],
+        ]))
+    }
+
+    /// Width in pixels, from IHDR.  `None` without an IHDR first.
+    #[allow(dead_code)]
+    pub fn width(&self) -> Option<u32> {
+        self.header_u32(0)
+    }
+
+    /// Height in pixels, from IHDR.  `None` without an IHDR first.
+    #[allow(dead_code)]
+    pub fn height(&self) -> Option<u32> {
+        self.header_u32(4)
+    }
+
+    /// Bits per sample, or per palette index, from IHDR.  `None` without an
+    /// IHDR first.
+    #[allow(dead_code)]
+    pub fn bit_depth(&self) -> Option<u8> {
+        self.image_header().map(|header| header[8])
+    }
+
+    /// Color type, from IHDR.  `None` without an IHDR first, or if it holds
+    /// an undefined color type.
+    #[allow(dead_code)]
+    pub fn color_type(&self) -> Option<ColorType> {
+        self.image_header()
+            .and_then(|header| ColorType::from_byte(header[9]))
+    }
+
+    /// Parse a Png from the given reader, one chunk at a time, without
+    /// first collecting the whole stream into memory.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Png> {
+        Png::from_reader_observed(reader, &mut ())
+    }
+
+    /// Parse a Png from the given reader like [`Png::from_reader`], telling
+    /// `observer` about each chunk as it is parsed.  `Err` if it cancels.
+    pub fn from_reader_observed<R: Read>(reader: R, observer: &mut dyn Observer) -> Result<Png> {
+        Png::from_reader_limited(reader, MAXIMUM_LENGTH, observer)
+    }
+
+    /// Parse a Png from the given reader like [`Png::from_reader_observed`],
+    /// failing on any chunk with more than `max_chunk_length` bytes of data.
+    /// Memory grows with the data actually read, never with the length a
+    /// chunk claims.
+    pub fn from_reader_limited<R: Read>(
+        reader: R,
+        max_chunk_length: u32,
+        observer: &mut dyn Observer,
+    ) -> Result<Png> {
+        Png::read_chunks(reader, max_chunk_length, observer)
+    }
+
+    fn read_chunks<R: Read>(
+        reader: R,
+        max_chunk_length: u32,
+        observer: &mut dyn Observer,
+    ) -> Result<Png> {
+        let mut reader = BufReader::new(reader);
+        let mut header_buffer: [u8; 8] = Default::default();
+
+        // Read header
+        reader.read_exact(&mut header_buffer)?;
+        if header_buffer != Png::STANDARD_HEADER {
+            return Err(PngmeError::InvalidSignature);
+        }
+
+        // Read chunks
+        let mut chunks: Vec<Chunk> = Vec::new();
+        // Store length of following chunk
+        let mut length_buffer: [u8; 4] = Default::default();
+
+        // Offset of the chunk being read
+        let mut offset = Png::STANDARD_HEADER.len();
+
+        // While we can still read length bytes
+        loop {
+            if observer.is_cancelled() {
+                return Err(PngmeError::Cancelled);
+            }
+            let (index, last) = (
+                chunks.len(),
+                chunks.last().map(|c| RawChunkType::from(*c.chunk_type())),
+            );
+            let context = |e: PngmeError| parse_error(e, offset, index, last);
+            match reader.read_exact(&mut length_buffer) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(context(e.into())),
+            }
+
+            let length = u32::from_be_bytes(length_buffer);
+            check_length(length, max_chunk_length).map_err(context)?;
+
+            let mut type_buffer: [u8; 4] = Default::default();
+            reader
+                .read_exact(&mut type_buffer)
+                .map_err(|e| context(e.into()))?;
+
+            // Read the data, growing the buffer only as it arrives
+            let mut data_buffer = Vec::new();
+            (&mut reader)
+                .take(length.into())
+                .read_to_end(&mut data_buffer)
+                .map_err(|e| context(e.into()))?;
+            if data_buffer.len() < length as usize {
+                let e = io::Error::from(io::ErrorKind::UnexpectedEof);
+                return Err(context(e.into()));
+            }
+            let mut crc_buffer: [u8; 4] = Default::default();
+            reader
+                .read_exact(&mut crc_buffer)
+                .map_err(|e| context(e.into()))?;
+
+            let chunk = ChunkType::try_from(type_buffer)
+                .and_then(|chunk_type| {
+                    Chunk::with_crc(chunk_type, data_buffer, u32::from_be_bytes(crc_buffer))
+                })
+                .map_err(context)?;
+            trace!(
+                offset,
+                chunk_type = %chunk.chunk_type(),
+                length = chunk.length(),
+                "parsed chunk"
+            );
+            offset += chunk.encoded_len();
+
+            observer.on_chunk_parsed(index, &chunk);
+            chunks.push(chunk);
+        }
+
+        Ok(Png::from_chunks(chunks))
+    }
+
+    /// Chunks of the Png in `bytes`, borrowing their data from it rather
+    /// than copying it.
+    pub fn chunk_refs(bytes: &[u8]) -> Result<Vec<ChunkRef<'_>>> {
+        Png::chunk_refs_limited(bytes, MAXIMUM_LENGTH)
+    }
+
+    /// Chunks of the Png in `bytes` like [`Png::chunk_refs`], failing on
+    /// any chunk with more than `max_chunk_length` bytes of data.
+    pub fn chunk_refs_limited(bytes: &[u8], max_chunk_length: u32) -> Result<Vec<ChunkRef<'_>>> {
+        Png::parse_refs(bytes, max_chunk_length, ChunkRef::try_from)
+    }
+
+    /// Chunks of the Png in `bytes` like [`Png::chunk_refs_limited`], but
+    /// keeping any whose type isn't four ASCII letters, as some real-world
+    /// files have, instead of failing the whole file.
+    pub fn raw_chunk_refs(bytes: &[u8], max_chunk_length: u32) -> Result<Vec<RawChunkRef<'_>>> {
+        Png::parse_refs(bytes, max_chunk_length, Ok)
+    }
+
+    /// Chunks of the Png in `bytes`, each made from the raw chunk by `make`.
+    fn parse_refs<'a, T>(
+        bytes: &'a [u8],
+        max_chunk_length: u32,
+        make: impl Fn(RawChunkRef<'a>) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        if !bytes.starts_with(&Png::STANDARD_HEADER) {
+            return Err(PngmeError::InvalidSignature);
+        }
+
+        let mut chunks: Vec<T> = Vec::new();
+        let mut last = None;
+        let mut offset = Png::STANDARD_HEADER.len();
+        // Bytes too few for a length are ignored, as when reading a Png
+        while offset + 4 <= bytes.len() {
+            let index = chunks.len();
+            let length = bytes[offset..]
+                .first_chunk()
+                .map_or(0, |b| u32::from_be_bytes(*b));
+            let raw = check_length(length, max_chunk_length)
+                .and_then(|()| RawChunkRef::parse(&bytes[offset..]))
+                .map_err(|e| parse_error(e, offset, index, last))?;
+            let chunk = make(raw).map_err(|e| parse_error(e, offset, index, last))?;
+            trace!(
+                offset,
+                chunk_type = %raw.raw_type(),
+                length = raw.data().len(),
+                "parsed chunk"
+            );
+            offset += raw.encoded_len();
+            last = Some(raw.raw_type());
+            chunks.push(chunk);
+        }
+
+        Ok(chunks)
+    }
+
+    /// Write this Png to the given writer chunk by chunk, returning the
+    /// number of bytes written.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<usize> {
+        self.write_to_observed(writer, &mut ())
+    }
+
+    /// Write this Png to the given writer like [`Png::write_to`], telling
+    /// `observer` how much has been written after each chunk.  `Err` if it
+    /// cancels, leaving the output cut short.
+    pub fn write_to_observed<W: Write>(
+        &self,
+        writer: W,
+        observer: &mut dyn Observer,
+    ) -> Result<usize> {
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(self.header())?;
+        let mut written = self.header().len();
+        observer.on_bytes_written(written);
+        for chunk in &self.chunks {
+            if observer.is_cancelled() {
+                return Err(PngmeError::Cancelled);
+            }
+            chunk.write_bytes(&mut writer)?;
+            written += chunk.encoded_len();
+            observer.on_bytes_written(written);
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+
     /// All bytes of this Png.
     pub fn as_bytes(&self) -> Vec<u8> {
-        let chunk_bytes: Vec<u8> = self.chunks.iter().map(Chunk::as_bytes).flatten().collect();
-        self.header()
-            .iter()
-            .chain(chunk_bytes.iter())
-            .copied()
-            .collect()
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        self.write_bytes(&mut bytes)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    /// Number of bytes this Png takes up, signature included.
+    pub fn encoded_len(&self) -> usize {
+        self.header().len() + self.chunks.iter().map(Chunk::encoded_len).sum::<usize>()
+    }
+
+    /// Write all bytes of this Png, unbuffered: the signature, then every
+    /// chunk.
+    pub fn write_bytes(&self, writer: &mut impl Write) -> Result<()> {
+        writer.write_all(self.header())?;
+        for chunk in &self.chunks {
+            chunk.write_bytes(writer)?;
+        }
+        Ok(())
     }
 }
 
@@ -81,66 +525,85 @@ impl fmt::Display for Png {
     }
 }
 
-impl TryFrom<&[u8]> for Png {
-    type Error = Error;
+impl Index<usize> for Png {
+    type Output = Chunk;
 
-    fn try_from(bytes: &[u8]) -> Result<Self> {
-        let mut reader = BufReader::new(bytes);
-        let mut header_buffer: [u8; 8] = Default::default();
+    /// The chunk at the given position.  Panics if it is out of range.
+    fn index(&self, index: usize) -> &Chunk {
+        &self.chunks[index]
+    }
+}
 
-        // Read header
-        reader.read_exact(&mut header_buffer).unwrap();
-        if header_buffer != Png::STANDARD_HEADER {
-            Err("Invalid header")?
-        }
+impl IntoIterator for Png {
+    type Item = Chunk;
+    type IntoIter = vec::IntoIter<Chunk>;
 
-        // Read chunks
-        let mut chunks = Vec::new();
-        // Store length of following chunk
-        let mut length_buffer: [u8; 4] = Default::default();
-
-        // While we can still read length bytes
-        while let Ok(()) = reader.read_exact(&mut length_buffer) {
-            // type + body + crc
-            let end_pos = 4 + u32::from_be_bytes(length_buffer) + 4;
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks.into_iter()
+    }
+}
 
-            let mut data_buffer = vec![0; usize::try_from(end_pos)?];
+impl<'a> IntoIterator for &'a Png {
+    type Item = &'a Chunk;
+    type IntoIter = slice::Iter<'a, Chunk>;
 
-            // Ready chunk bytes
-            reader.read_exact(&mut data_buffer)?;
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks.iter()
+    }
+}
 
-            // Build chunk with length and body bytes
-            let chunk_data: Vec<u8> = length_buffer
-                .iter()
-                .copied()
-                .chain(data_buffer.into_iter())
-                .collect();
+/// `Err` if a chunk claiming `length` bytes of data is over `limit`, or
+/// over what any chunk may hold.
+pub(crate) fn check_length(length: u32, limit: u32) -> Result<()> {
+    let limit = limit.min(MAXIMUM_LENGTH);
+    match limit {
+        _ if length <= limit => Ok(()),
+        MAXIMUM_LENGTH => Err(PngmeError::ChunkTooLong {
+            length: length.into(),
+        }),
+        _ => Err(PngmeError::ChunkOverLimit { length, limit }),
+    }
+}
 
-            let chunk = Chunk::try_from(chunk_data.as_slice())?;
+/// `source` with where it happened: the chunk at `offset`, numbered `index`
+/// and following a chunk of type `last`.
+pub(crate) fn parse_error(
+    source: PngmeError,
+    offset: usize,
+    index: usize,
+    last: Option<RawChunkType>,
+) -> PngmeError {
+    PngmeError::Parse {
+        offset,
+        index,
+        last,
+        source: Box::new(source.shifted(offset)),
+    }
+}
 
-            chunks.push(chunk);
-        }
+impl TryFrom<&[u8]> for Png {
+    type Error = PngmeError;
 
-        Ok(Png::from_chunks(chunks))
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        Png::from_reader(bytes)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chunk::Chunk;
+    use crate::chunk::{checksum, Chunk};
     use crate::chunk_type::ChunkType;
     use std::convert::TryFrom;
     use std::str::FromStr;
+    use std::sync::atomic::{AtomicBool, Ordering};
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -148,11 +611,30 @@ mod tests {
         Png::from_chunks(chunks)
     }
 
+    #[test]
+    fn test_header() {
+        let mut data = Vec::new();
+        data.extend(640u32.to_be_bytes());
+        data.extend(480u32.to_be_bytes());
+        data.extend([8, 6, 0, 0, 0]);
+        let header = Chunk::new(ChunkType::IHDR, data).unwrap();
+        let png = Png::from_chunks(vec![header]);
+        assert_eq!(png.width(), Some(640));
+        assert_eq!(png.height(), Some(480));
+        assert_eq!(png.bit_depth(), Some(8));
+        assert_eq!(png.color_type(), Some(ColorType::Rgba));
+        assert_eq!(ColorType::Rgba.channels(), 4);
+
+        let png = testing_png();
+        assert_eq!(png.width(), None);
+        assert_eq!(png.color_type(), None);
+    }
+
     fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk> {
         let chunk_type = ChunkType::from_str(chunk_type)?;
         let data: Vec<u8> = data.bytes().collect();
 
-        Ok(Chunk::new(chunk_type, data))
+        Chunk::new(chunk_type, data)
     }
 
     #[test]
@@ -199,6 +681,13 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_truncated_header() {
+        let png = Png::try_from(&Png::STANDARD_HEADER[..4]);
+
+        assert!(png.is_err());
+    }
+
     #[test]
     fn test_invalid_chunk() {
         let mut chunk_bytes: Vec<u8> = testing_chunks()
@@ -221,6 +710,182 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_typed_errors() {
+        let mut bytes = testing_png().as_bytes();
+        assert!(matches!(
+            Png::try_from(&bytes[1..]),
+            Err(PngmeError::InvalidSignature)
+        ));
+
+        // Corrupt the CRC of the second chunk.
+        let second = Png::STANDARD_HEADER.len() + testing_chunks()[0].as_bytes().len();
+        let crc_end = second + testing_chunks()[1].as_bytes().len();
+        bytes[crc_end - 1] ^= 0xff;
+        match Png::try_from(bytes.as_ref()) {
+            Err(PngmeError::Parse { source, .. }) => match *source {
+                PngmeError::CrcMismatch { offset, .. } => assert_eq!(offset, second),
+                e => panic!("expected a CRC mismatch, got {}", e),
+            },
+            _ => panic!("expected a parse error"),
+        }
+
+        let mut png = testing_png();
+        let missing = ChunkType::from_str("TeSt").unwrap();
+        assert!(matches!(
+            png.remove_chunk(missing),
+            Err(PngmeError::ChunkNotFound(chunk_type)) if chunk_type == missing
+        ));
+    }
+
+    #[test]
+    fn test_bounded_allocation() {
+        // A few bytes claiming the largest chunk fail at the end of input.
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        bytes.extend(MAXIMUM_LENGTH.to_be_bytes());
+        bytes.extend(b"ruSt");
+        match Png::try_from(bytes.as_slice()) {
+            Err(PngmeError::Parse { source, .. }) => {
+                assert!(
+                    matches!(*source, PngmeError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof)
+                )
+            }
+            _ => panic!("expected a parse error"),
+        }
+
+        let bytes = testing_png().as_bytes();
+        assert!(Png::from_reader_limited(bytes.as_slice(), 20, &mut ()).is_ok());
+        match Png::from_reader_limited(bytes.as_slice(), 19, &mut ()) {
+            Err(PngmeError::Parse {
+                index: 0, source, ..
+            }) => assert!(matches!(
+                *source,
+                PngmeError::ChunkOverLimit {
+                    length: 20,
+                    limit: 19
+                }
+            )),
+            _ => panic!("expected a parse error"),
+        }
+
+        // The same limit when borrowing or indexing chunks.
+        let over = |e: PngmeError| match e {
+            PngmeError::Parse { source, .. } => {
+                matches!(*source, PngmeError::ChunkOverLimit { length: 20, .. })
+            }
+            _ => false,
+        };
+        assert!(Png::chunk_refs_limited(&bytes, 20).is_ok());
+        assert!(over(Png::chunk_refs_limited(&bytes, 19).err().unwrap()));
+        assert!(over(Png::raw_chunk_refs(&bytes, 19).err().unwrap()));
+        assert!(PngIndex::with_limit(io::Cursor::new(&bytes), 20).is_ok());
+        let index = PngIndex::with_limit(io::Cursor::new(&bytes), 19);
+        assert!(over(index.err().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_error_context() {
+        let bytes = testing_png().as_bytes();
+        let third = bytes.len() - testing_chunks()[2].as_bytes().len();
+        let truncated = &bytes[..third + 10];
+        match Png::try_from(truncated) {
+            Err(PngmeError::Parse {
+                offset,
+                index,
+                last,
+                source,
+            }) => {
+                assert_eq!(offset, third);
+                assert_eq!(index, 2);
+                assert_eq!(last, Some(RawChunkType::from_str("miDl").unwrap()));
+                assert!(matches!(*source, PngmeError::Io(_)));
+            }
+            _ => panic!("expected a parse error"),
+        }
+
+        let message = Png::try_from(truncated).err().unwrap().to_string();
+        assert!(message.starts_with(&format!("Chunk 2 at byte {} (after miDl): ", third)));
+    }
+
+    #[test]
+    fn test_from_reader_write_to() {
+        let png = Png::from_reader(io::Cursor::new(&PNG_FILE[..])).unwrap();
+        assert_eq!(
+            png.chunks().len(),
+            Png::try_from(&PNG_FILE[..]).unwrap().chunks().len()
+        );
+
+        let mut written = Vec::new();
+        assert_eq!(png.write_to(&mut written).unwrap(), PNG_FILE.len());
+        assert_eq!(written, PNG_FILE);
+
+        assert!(matches!(
+            Png::from_reader(&b"not a png"[..]),
+            Err(PngmeError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_chunk_refs() {
+        let owned = Png::try_from(&PNG_FILE[..]).unwrap();
+        let borrowed = Png::chunk_refs(&PNG_FILE).unwrap();
+        assert_eq!(borrowed.len(), owned.chunks().len());
+        for (chunk, owned) in borrowed.iter().zip(owned.chunks()) {
+            assert_eq!(chunk.chunk_type(), owned.chunk_type());
+            assert_eq!(chunk.data(), owned.data());
+        }
+
+        let bytes = testing_png().as_bytes();
+        let third = bytes.len() - testing_chunks()[2].as_bytes().len();
+        assert!(matches!(
+            Png::chunk_refs(&bytes[..third + 10]),
+            Err(PngmeError::Parse { offset, index: 2, .. }) if offset == third
+        ));
+        assert!(matches!(
+            Png::chunk_refs(&bytes[1..]),
+            Err(PngmeError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_append_chunk_in_place() {
+        let mut file = io::Cursor::new(PNG_FILE.to_vec());
+        let chunk = chunk_from_strings("TeSt", "Message").unwrap();
+        Png::append_chunk_in_place(&mut file, &chunk).unwrap();
+
+        let png = Png::try_from(file.get_ref().as_slice()).unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .rev()
+            .take(2)
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IEND", "TeSt"]);
+        assert_eq!(
+            file.get_ref().len(),
+            PNG_FILE.len() + chunk.as_bytes().len()
+        );
+
+        // It goes before IEND even with chunks after it, as with
+        // append_chunk.
+        let mut trailing = Png::try_from(&PNG_FILE[..]).unwrap();
+        trailing.insert_chunk_at(trailing.len(), chunk_from_strings("afTr", "x").unwrap());
+        let mut file = io::Cursor::new(trailing.as_bytes());
+        Png::append_chunk_in_place(&mut file, &chunk).unwrap();
+        trailing.append_chunk(chunk.clone());
+        let end = trailing.position_of(ChunkType::IEND).unwrap();
+        assert_eq!(trailing[end - 1], chunk);
+        assert_eq!(file.into_inner(), trailing.as_bytes());
+
+        // Without an IEND, the chunk goes at the end.
+        let mut file = io::Cursor::new(testing_png().as_bytes());
+        Png::append_chunk_in_place(&mut file, &chunk).unwrap();
+        let png = Png::try_from(file.get_ref().as_slice()).unwrap();
+        assert_eq!(png.chunks().len(), 4);
+        assert_eq!(png.chunks()[3].chunk_type().to_string(), "TeSt");
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -232,18 +897,39 @@ mod tests {
     fn test_chunk_by_type() {
         let png = testing_png();
         let chunk = png
-            .chunk_by_type(ChunkType::from_str(&"FrSt").unwrap())
+            .chunk_by_type(ChunkType::from_str("FrSt").unwrap())
             .unwrap();
         assert_eq!(&chunk.chunk_type().to_string(), "FrSt");
         assert_eq!(&chunk.data_as_string().unwrap(), "I am the first chunk");
     }
 
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am a second middle").unwrap());
+        let middle = ChunkType::from_str("miDl").unwrap();
+        let data: Vec<String> = png
+            .chunks_by_type(middle)
+            .map(|c| c.data_as_string().unwrap())
+            .collect();
+        assert_eq!(data, ["I am another chunk", "I am a second middle"]);
+
+        let second = png.nth_chunk_by_type(middle, 1).unwrap();
+        assert_eq!(second.data_as_string().unwrap(), "I am a second middle");
+        assert!(png.nth_chunk_by_type(middle, 2).is_none());
+        assert_eq!(
+            png.chunks_by_type(ChunkType::from_str("TeSt").unwrap())
+                .count(),
+            0
+        );
+    }
+
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
         png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
         let chunk = png
-            .chunk_by_type(ChunkType::from_str(&"TeSt").unwrap())
+            .chunk_by_type(ChunkType::from_str("TeSt").unwrap())
             .unwrap();
         assert_eq!(&chunk.chunk_type().to_string(), "TeSt");
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
@@ -255,10 +941,82 @@ mod tests {
         png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
         png.remove_chunk(ChunkType::from_str("TeSt").unwrap())
             .unwrap();
-        let chunk = png.chunk_by_type(ChunkType::from_str(&"TeSt").unwrap());
+        let chunk = png.chunk_by_type(ChunkType::from_str("TeSt").unwrap());
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_repeated_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am a second middle").unwrap());
+        png.append_chunk(chunk_from_strings("miDl", "I am a third middle").unwrap());
+        let middle = ChunkType::from_str("miDl").unwrap();
+
+        let second = png.remove_nth_chunk(middle, 1).unwrap();
+        assert_eq!(second.data_as_string().unwrap(), "I am a second middle");
+        assert!(png.remove_nth_chunk(middle, 2).is_err());
+
+        let removed = png.remove_all_chunks(middle);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[1].data_as_string().unwrap(), "I am a third middle");
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.remove_all_chunks(middle).is_empty());
+    }
+
+    #[test]
+    fn test_insert_chunk_at() {
+        let mut png = testing_png();
+        let middle = png.position_of(ChunkType::from_str("miDl").unwrap());
+        assert_eq!(middle, Some(1));
+        assert!(png
+            .position_of(ChunkType::from_str("TeSt").unwrap())
+            .is_none());
+
+        png.insert_chunk_at(1, chunk_from_strings("TeSt", "Message").unwrap());
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["FrSt", "TeSt", "miDl", "LASt"]);
+
+        png.insert_chunk_at(4, chunk_from_strings("EnDs", "Last").unwrap());
+        assert_eq!(png.chunks()[4].chunk_type().to_string(), "EnDs");
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+        let middle = ChunkType::from_str("miDl").unwrap();
+        let old = png
+            .replace_chunk(middle, chunk_from_strings("miDl", "I am new").unwrap())
+            .unwrap();
+        assert_eq!(old.data_as_string().unwrap(), "I am another chunk");
+        assert_eq!(png.chunks()[1].data_as_string().unwrap(), "I am new");
+        assert_eq!(png.chunks().len(), 3);
+
+        let missing = ChunkType::from_str("TeSt").unwrap();
+        let result = png.replace_chunk(missing, chunk_from_strings("TeSt", "x").unwrap());
+        assert!(matches!(result, Err(PngmeError::ChunkNotFound(_))));
+    }
+
+    #[test]
+    fn test_upsert_chunk() {
+        let mut png = testing_png();
+        assert!(png
+            .upsert_chunk(chunk_from_strings("TeSt", "Message").unwrap())
+            .is_empty());
+        let replaced = png.upsert_chunk(chunk_from_strings("TeSt", "Updated").unwrap());
+        assert_eq!(replaced[0].data_as_string().unwrap(), "Message");
+
+        let test = ChunkType::from_str("TeSt").unwrap();
+        assert_eq!(png.chunks_by_type(test).count(), 1);
+        assert_eq!(
+            png.chunk_by_type(test).unwrap().data_as_string().unwrap(),
+            "Updated"
+        );
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -269,8 +1027,13 @@ mod tests {
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected: Vec<u8> = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
+        assert_eq!(png.encoded_len(), PNG_FILE.len());
+
+        let mut written = Vec::new();
+        png.write_bytes(&mut written).unwrap();
+        assert_eq!(written, expected);
     }
 
     #[test]
@@ -291,6 +1054,114 @@ mod tests {
         let _png_string = format!("{}", png);
     }
 
+    #[test]
+    fn test_png_eq() {
+        let png = testing_png();
+        let copy = png.clone();
+        assert_eq!(png, copy);
+        assert_eq!(png, Png::try_from(png.as_bytes().as_slice()).unwrap());
+
+        let mut changed = copy;
+        changed.append_chunk(chunk_from_strings("TeSt", "more").unwrap());
+        assert_ne!(png, changed);
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        parsed: Vec<String>,
+        written: Vec<usize>,
+    }
+
+    impl Observer for Recorder {
+        fn on_chunk_parsed(&mut self, index: usize, chunk: &Chunk) {
+            self.parsed
+                .push(format!("{} {}", index, chunk.chunk_type()));
+        }
+
+        fn on_bytes_written(&mut self, written: usize) {
+            self.written.push(written);
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let mut recorder = Recorder::default();
+        let bytes = testing_png().as_bytes();
+        let png = Png::from_reader_observed(bytes.as_slice(), &mut recorder).unwrap();
+        assert_eq!(recorder.parsed, ["0 FrSt", "1 miDl", "2 LASt"]);
+
+        png.write_to_observed(Vec::new(), &mut recorder).unwrap();
+        assert_eq!(recorder.written.len(), 4);
+        assert_eq!(recorder.written.last(), Some(&bytes.len()));
+    }
+
+    #[test]
+    fn test_cancel() {
+        let bytes = testing_png().as_bytes();
+        let cancelled = AtomicBool::new(false);
+        assert!(Png::from_reader_observed(bytes.as_slice(), &mut &cancelled).is_ok());
+
+        cancelled.store(true, Ordering::Relaxed);
+        assert!(matches!(
+            Png::from_reader_observed(bytes.as_slice(), &mut &cancelled),
+            Err(PngmeError::Cancelled)
+        ));
+        assert!(matches!(
+            testing_png().write_to_observed(Vec::new(), &mut &cancelled),
+            Err(PngmeError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_collection() {
+        let mut png = testing_png();
+        assert_eq!(png.len(), 3);
+        assert!(!png.is_empty());
+        assert_eq!(png[1].chunk_type().to_string(), "miDl");
+        let types: Vec<String> = (&png)
+            .into_iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["FrSt", "miDl", "LASt"]);
+
+        png.retain(|c| c.chunk_type().is_critical());
+        assert_eq!(png.iter().count(), 2);
+        let chunks: Vec<Chunk> = png.into_iter().collect();
+        assert_eq!(chunks[1].chunk_type().to_string(), "LASt");
+
+        let mut png = testing_png();
+        png.retain(|_| false);
+        assert!(png.is_empty());
+    }
+
+    #[test]
+    fn test_raw_chunk_refs() {
+        let png = testing_png();
+        let mut bytes = png.as_bytes();
+        // A chunk whose type has a digit, after the first.
+        let mut odd = 3u32.to_be_bytes().to_vec();
+        odd.extend(b"r2Stodd");
+        odd.extend(checksum(*b"r2St", b"odd").to_be_bytes());
+        let second = Png::STANDARD_HEADER.len() + png[0].encoded_len();
+        bytes.splice(second..second, odd);
+
+        assert!(Png::from_reader(bytes.as_slice()).is_err());
+        assert!(Png::chunk_refs(&bytes).is_err());
+        assert!(PngIndex::new(io::Cursor::new(&bytes)).is_err());
+
+        let refs = Png::raw_chunk_refs(&bytes, MAXIMUM_LENGTH).unwrap();
+        assert_eq!(refs.len(), 4);
+        assert_eq!(refs[1].raw_type().to_string(), "r2St");
+        assert_eq!(refs[1].data(), b"odd");
+        assert!(ChunkRef::try_from(refs[1]).is_err());
+        assert_eq!(Chunk::from(ChunkRef::try_from(refs[2]).unwrap()), png[1]);
+        let mut written = Png::STANDARD_HEADER.to_vec();
+        for chunk in &refs {
+            chunk.write_bytes(&mut written).unwrap();
+        }
+        assert_eq!(written, bytes);
+    }
+
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
     const PNG_FILE: [u8; 4803] = [
         137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 50, 0, 0, 0, 50, 8,
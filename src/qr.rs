@@ -0,0 +1,67 @@
+use image::{GrayImage, Luma};
+use qrcode::render::unicode::Dense1x2;
+use qrcode::{Color, QrCode};
+use std::path::Path;
+
+/// Pixels per module in a saved QR code.
+const SCALE: u32 = 8;
+/// Modules of blank border a reader needs around the code.
+const QUIET_ZONE: u32 = 4;
+
+/// The text as a QR code of half-block characters, two modules to a
+/// character.  Meant for dark terminals: blank cells are the dark modules.
+pub(crate) fn to_terminal(text: &str) -> crate::Result<String> {
+    let code = QrCode::new(text)?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+/// The text as a QR code image, black on white.
+pub(crate) fn to_image(text: &str) -> crate::Result<GrayImage> {
+    let code = QrCode::new(text)?;
+    let width = code.width() as u32;
+    let colors = code.to_colors();
+    let side = (width + 2 * QUIET_ZONE) * SCALE;
+    Ok(GrayImage::from_fn(side, side, |x, y| {
+        let (x, y) = (x / SCALE, y / SCALE);
+        let inside = QUIET_ZONE..QUIET_ZONE + width;
+        let dark = inside.contains(&x)
+            && inside.contains(&y)
+            && colors[((y - QUIET_ZONE) * width + x - QUIET_ZONE) as usize] == Color::Dark;
+        Luma([if dark { 0 } else { 255 }])
+    }))
+}
+
+/// Save the text as a QR code PNG at the given path.
+pub(crate) fn save(text: &str, path: &Path) -> crate::Result<()> {
+    to_image(text)?.save_with_format(path, image::ImageFormat::Png)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_terminal() {
+        let code = to_terminal("hi").unwrap();
+        // Version 1 is 21 modules, plus the quiet zone on each side.
+        let lines: Vec<&str> = code.lines().collect();
+        assert_eq!(lines.len(), (21 + 2 * 4_usize).div_ceil(2));
+        assert!(lines.iter().all(|l| l.chars().count() == 21 + 2 * 4));
+    }
+
+    #[test]
+    fn test_to_image() {
+        let image = to_image("hi").unwrap();
+        assert_eq!(image.width(), (21 + 2 * QUIET_ZONE) * SCALE);
+        // Blank border, then the dark corner of a finder pattern.
+        assert_eq!(image.get_pixel(0, 0), &Luma([255]));
+        let corner = QUIET_ZONE * SCALE;
+        assert_eq!(image.get_pixel(corner, corner), &Luma([0]));
+        assert!(to_image(&"x".repeat(8000)).is_err());
+    }
+}
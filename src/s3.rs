@@ -0,0 +1,134 @@
+#[cfg(not(feature = "s3"))]
+use crate::args::RemoteArgs;
+#[cfg(not(feature = "s3"))]
+use crate::config::Config;
+use reqwest::Url;
+
+/// URL scheme of S3 objects.
+pub(crate) const SCHEME: &str = "s3";
+
+/// Bucket and key of an `s3://bucket/key` url.
+pub(crate) fn location(url: &Url) -> crate::Result<(&str, &str)> {
+    let bucket = url.host_str().filter(|b| !b.is_empty());
+    let key = url.path().trim_start_matches('/');
+    match bucket {
+        Some(bucket) if !key.is_empty() => Ok((bucket, key)),
+        _ => Err(format!("Expected s3://bucket/key, got '{}'", url))?,
+    }
+}
+
+#[cfg(feature = "s3")]
+mod sdk {
+    use super::location;
+    use crate::args::RemoteArgs;
+    use crate::config::Config;
+    use aws_sdk_s3::primitives::ByteStream;
+    use aws_sdk_s3::Client;
+    use reqwest::Url;
+    use std::io::Write;
+    use tokio::runtime::{Builder, Runtime};
+    use tracing::debug;
+
+    fn runtime() -> crate::Result<Runtime> {
+        Ok(Builder::new_current_thread().enable_all().build()?)
+    }
+
+    /// Client with credentials and region from the standard AWS chain
+    /// (environment, profile files, SSO, instance metadata).
+    async fn client() -> Client {
+        Client::new(&aws_config::load_from_env().await)
+    }
+
+    /// Download the object at the given url into `out`.
+    pub(crate) fn get(
+        url: &Url,
+        _remote: &RemoteArgs,
+        _config: &Config,
+        out: &mut dyn Write,
+    ) -> crate::Result<()> {
+        let (bucket, key) = location(url)?;
+        runtime()?.block_on(async {
+            let object = client()
+                .await
+                .get_object()
+                .bucket(bucket)
+                .key(key)
+                .send()
+                .await?;
+            debug!(content_length = ?object.content_length(), "fetched {}", url);
+            let mut body = object.body;
+            while let Some(bytes) = body.try_next().await? {
+                out.write_all(&bytes)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Upload PNG bytes as the object at the given url.
+    pub(crate) fn put(
+        url: &Url,
+        _remote: &RemoteArgs,
+        _config: &Config,
+        bytes: Vec<u8>,
+    ) -> crate::Result<()> {
+        let (bucket, key) = location(url)?;
+        runtime()?.block_on(async {
+            client()
+                .await
+                .put_object()
+                .bucket(bucket)
+                .key(key)
+                .content_type("image/png")
+                .body(ByteStream::from(bytes))
+                .send()
+                .await?;
+            debug!("uploaded to {}", url);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(feature = "s3")]
+pub(crate) use sdk::{get, put};
+
+#[cfg(not(feature = "s3"))]
+const NOT_BUILT: &str = "s3:// URLs need pngme built with the `s3` feature";
+
+/// Download the object at the given url into `out`.
+#[cfg(not(feature = "s3"))]
+pub(crate) fn get(
+    url: &Url,
+    _remote: &RemoteArgs,
+    _config: &Config,
+    _out: &mut dyn std::io::Write,
+) -> crate::Result<()> {
+    location(url)?;
+    Err(NOT_BUILT)?
+}
+
+/// Upload PNG bytes as the object at the given url.
+#[cfg(not(feature = "s3"))]
+pub(crate) fn put(
+    url: &Url,
+    _remote: &RemoteArgs,
+    _config: &Config,
+    _bytes: Vec<u8>,
+) -> crate::Result<()> {
+    location(url)?;
+    Err(NOT_BUILT)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        let url = Url::parse("s3://assets/carriers/turtle.png").unwrap();
+        assert_eq!(location(&url).unwrap(), ("assets", "carriers/turtle.png"));
+
+        for bad in ["s3://assets", "s3://assets/", "s3:///key"] {
+            assert!(location(&Url::parse(bad).unwrap()).is_err());
+        }
+    }
+}
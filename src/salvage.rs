@@ -0,0 +1,112 @@
+use crate::chunk::{Chunk, ChunkRef, MAXIMUM_LENGTH};
+use crate::chunk_type::{ChunkType, RawChunkType};
+use crate::error::{PngmeError, Result};
+use crate::png::{parse_error, Png};
+
+/// Chunks recovered from a PNG that may be cut short.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Salvaged {
+    /// Every chunk present, the last with only the data that was there.
+    pub png: Png,
+    /// Bytes of the last chunk missing from the input, 0 if it was whole.
+    pub missing: usize,
+}
+
+impl Png {
+    /// Parse the given bytes as far as they go, for a PNG whose transfer was
+    /// cut off.  The last chunk in the input keeps whatever data it has and
+    /// its checksum isn't checked, nor is there any need for IEND.  Every
+    /// other chunk is parsed and checked as usual.
+    pub fn salvage(bytes: &[u8]) -> Result<Salvaged> {
+        let mut rest = bytes
+            .strip_prefix(&Png::STANDARD_HEADER[..])
+            .ok_or(PngmeError::InvalidSignature)?;
+
+        let mut chunks: Vec<Chunk> = Vec::new();
+        let mut offset = Png::STANDARD_HEADER.len();
+        let mut missing = 0;
+        // Bytes too few for a length and type are ignored, as when reading
+        while rest.len() >= 8 {
+            let (index, last) = (
+                chunks.len(),
+                chunks.last().map(|c| RawChunkType::from(*c.chunk_type())),
+            );
+            let context = |e: PngmeError| parse_error(e, offset, index, last);
+            let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+            let whole = 4 + 4 + length as usize + 4;
+            if length <= MAXIMUM_LENGTH && rest.len() <= whole {
+                let chunk_type =
+                    ChunkType::try_from([rest[4], rest[5], rest[6], rest[7]]).map_err(context)?;
+                let data = &rest[8..rest.len().min(8 + length as usize)];
+                chunks.push(Chunk::new(chunk_type, data.to_vec())?);
+                missing = whole - rest.len();
+                break;
+            }
+            let chunk = ChunkRef::try_from(rest).map_err(context)?;
+            offset += chunk.encoded_len();
+            rest = &rest[chunk.encoded_len()..];
+            chunks.push(chunk.into());
+        }
+
+        Ok(Salvaged {
+            png: Png::from_chunks(chunks),
+            missing,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn testing_bytes() -> Vec<u8> {
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        for (chunk_type, data) in [("IHDR", ""), ("ruSt", "the message"), ("IEND", "")] {
+            let chunk_type = ChunkType::from_str(chunk_type).unwrap();
+            bytes.extend(
+                Chunk::new(chunk_type, data.as_bytes().to_vec())
+                    .unwrap()
+                    .as_bytes(),
+            );
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_salvage_whole() {
+        let bytes = testing_bytes();
+        let salvaged = Png::salvage(&bytes).unwrap();
+        assert_eq!(salvaged.png, Png::try_from(bytes.as_slice()).unwrap());
+        assert_eq!(salvaged.missing, 0);
+    }
+
+    #[test]
+    fn test_salvage_cut() {
+        let bytes = testing_bytes();
+        // Cut in the middle of the ruSt data, with no IEND.
+        let cut = &bytes[..8 + 12 + 8 + 7];
+        let salvaged = Png::salvage(cut).unwrap();
+        assert_eq!(salvaged.png.len(), 2);
+        assert_eq!(salvaged.png[1].data(), b"the mes");
+        assert_eq!(salvaged.missing, 4 + 4);
+
+        // A damaged checksum on the last chunk is ignored, not on others.
+        let mut damaged = bytes[..8 + 12 + 23].to_vec();
+        let end = damaged.len();
+        damaged[end - 1] ^= 0xff;
+        assert_eq!(
+            Png::salvage(&damaged).unwrap().png[1].data(),
+            b"the message"
+        );
+        damaged.extend(&bytes[8 + 12 + 23..]);
+        assert!(matches!(
+            Png::salvage(&damaged),
+            Err(PngmeError::Parse { index: 1, .. })
+        ));
+        assert!(matches!(
+            Png::salvage(&bytes[1..]),
+            Err(PngmeError::InvalidSignature)
+        ));
+    }
+}
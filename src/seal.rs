@@ -0,0 +1,121 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use image::{ImageBuffer, Rgba};
+
+/// Chunk type of the seal: private and ancillary, but unsafe to copy, since
+/// editing the pixels breaks it.
+pub(crate) const SEAL: ChunkType = ChunkType::new_unchecked(*b"seAL");
+
+/// Decoded pixels of the Png, as 16-bit RGBA whatever its color type.
+fn pixels(png: &Png) -> crate::Result<ImageBuffer<Rgba<u16>, Vec<u16>>> {
+    Ok(image::load_from_memory(&png.as_bytes())?.to_rgba16())
+}
+
+/// Where the decoded pixels of the two Pngs first differ, if they do.
+pub(crate) fn pixel_difference(a: &Png, b: &Png) -> crate::Result<Option<String>> {
+    let (a, b) = (pixels(a)?, pixels(b)?);
+    if a.dimensions() != b.dimensions() {
+        let (aw, ah) = a.dimensions();
+        let (bw, bh) = b.dimensions();
+        return Ok(Some(format!(
+            "sizes differ: {}x{} and {}x{}",
+            aw, ah, bw, bh
+        )));
+    }
+    let differing = a
+        .enumerate_pixels()
+        .find(|(x, y, pixel)| b.get_pixel(*x, *y) != *pixel);
+    Ok(differing.map(|(x, y, _)| format!("pixel ({}, {}) differs", x, y)))
+}
+
+/// BLAKE3 hash of the decoded pixels of the Png, as 16-bit RGBA after its
+/// width and height.  Unlike a hash of the IDAT bytes, it stays the same
+/// when the image is recompressed or stored with another color type, but
+/// changes with any visible edit.
+pub(crate) fn fingerprint(png: &Png) -> crate::Result<blake3::Hash> {
+    let image = pixels(png)?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&image.width().to_be_bytes());
+    hasher.update(&image.height().to_be_bytes());
+    for sample in image.as_raw() {
+        hasher.update(&sample.to_be_bytes());
+    }
+    Ok(hasher.finalize())
+}
+
+/// Store the fingerprint of the Png, as hex, in a seal chunk before IEND,
+/// replacing any earlier seal.
+pub(crate) fn seal(png: &mut Png) -> crate::Result<blake3::Hash> {
+    let hash = fingerprint(png)?;
+    png.remove_all_chunks(SEAL);
+    let end = png.position_of(ChunkType::IEND).unwrap_or(png.len());
+    png.insert_chunk_at(end, Chunk::new(SEAL, hash.to_hex().as_bytes().to_vec())?);
+    Ok(hash)
+}
+
+/// `Ok` if the Png has a seal that matches its pixels.
+pub(crate) fn check(png: &Png) -> crate::Result<()> {
+    let stored = png.chunk_by_type(SEAL).ok_or("No seal in the PNG")?;
+    let stored = blake3::Hash::from_hex(stored.data()).map_err(|_| "The seal isn't a hash")?;
+    if fingerprint(png)? != stored {
+        Err("The pixels have changed since the PNG was sealed")?
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    #[test]
+    fn test_seal() {
+        let mut png = Png::try_from(TURTLE).unwrap();
+        assert!(check(&png).is_err());
+        let hash = seal(&mut png).unwrap();
+        assert_eq!(
+            png.chunk_by_type(SEAL).unwrap().data(),
+            hash.to_hex().as_bytes()
+        );
+        assert!(check(&png).is_ok());
+        seal(&mut png).unwrap();
+        assert_eq!(png.chunks_by_type(SEAL).count(), 1);
+    }
+
+    #[test]
+    fn test_seal_survives_recompression() {
+        let mut png = Png::try_from(TURTLE).unwrap();
+        let hash = seal(&mut png).unwrap();
+
+        // Decoded and written again, the turtle is RGB instead of indexed.
+        let mut bytes = Vec::new();
+        image::load_from_memory(TURTLE)
+            .unwrap()
+            .write_to(&mut bytes, image::ImageOutputFormat::Png)
+            .unwrap();
+        let recompressed = Png::try_from(bytes.as_slice()).unwrap();
+        assert_ne!(
+            recompressed.chunk_by_type(ChunkType::IDAT),
+            png.chunk_by_type(ChunkType::IDAT)
+        );
+        assert_eq!(fingerprint(&recompressed).unwrap(), hash);
+
+        let mut image = image::load_from_memory(TURTLE).unwrap().to_rgba8();
+        image.get_pixel_mut(0, 0).0[0] ^= 1;
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgba8(image)
+            .write_to(&mut bytes, image::ImageOutputFormat::Png)
+            .unwrap();
+        let edited = Png::try_from(bytes.as_slice()).unwrap();
+        assert_ne!(fingerprint(&edited).unwrap(), hash);
+
+        assert_eq!(pixel_difference(&png, &recompressed).unwrap(), None);
+        assert_eq!(
+            pixel_difference(&png, &edited).unwrap().as_deref(),
+            Some("pixel (0, 0) differs")
+        );
+    }
+}
@@ -0,0 +1,384 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::encrypt::{decrypt, encrypt};
+use crate::png::Png;
+use std::convert::TryFrom;
+use std::fmt;
+#[cfg(not(feature = "server"))]
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+/// What the endpoints need from the command line and config.
+#[cfg_attr(not(any(feature = "server", feature = "grpc")), allow(dead_code))]
+pub(crate) struct Settings {
+    /// Encryption key, if encryption is on.
+    pub(crate) key: Option<String>,
+    /// Chunk type used when a request names none.
+    pub(crate) chunk_type: Option<ChunkType>,
+    /// Largest request body accepted, in bytes.
+    pub(crate) body_limit: usize,
+}
+
+/// Why a request couldn't be answered, shared by the REST and gRPC services.
+#[derive(Debug)]
+#[cfg_attr(not(any(feature = "server", feature = "grpc")), allow(dead_code))]
+pub(crate) enum Failure {
+    /// The request itself is wrong.
+    Invalid(String),
+    /// The image has no matching chunk.
+    NotFound(String),
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Invalid(message) | Failure::NotFound(message) => f.write_str(message),
+        }
+    }
+}
+
+#[cfg_attr(not(any(feature = "server", feature = "grpc")), allow(dead_code))]
+fn invalid(e: impl ToString) -> Failure {
+    Failure::Invalid(e.to_string())
+}
+
+#[cfg_attr(not(any(feature = "server", feature = "grpc")), allow(dead_code))]
+impl Settings {
+    /// The PNG held by `image`.
+    pub(crate) fn png(image: Option<&[u8]>) -> Result<Png, Failure> {
+        let image = image.ok_or_else(|| invalid("Missing image"))?;
+        Png::try_from(image).map_err(invalid)
+    }
+
+    /// The chunk type a request names, else the configured default.
+    pub(crate) fn chunk_type(&self, name: Option<&str>) -> Result<ChunkType, Failure> {
+        match (name, &self.chunk_type) {
+            (Some(name), _) => ChunkType::from_str(name).map_err(invalid),
+            (None, Some(default)) => Ok(*default),
+            (None, None) => Err(invalid("Missing chunk_type")),
+        }
+    }
+
+    /// `image` with `message` added in a chunk of the given type.
+    pub(crate) fn encode(
+        &self,
+        image: Option<&[u8]>,
+        chunk_type: Option<&str>,
+        message: Option<String>,
+    ) -> Result<Png, Failure> {
+        let mut png = Settings::png(image)?;
+        let chunk_type = self.chunk_type(chunk_type)?;
+        let message = message.ok_or_else(|| invalid("Missing message"))?;
+        let data = match &self.key {
+            Some(key) => encrypt(message, key),
+            None => message,
+        };
+        png.append_chunk(Chunk::new(chunk_type, data.into_bytes()).map_err(invalid)?);
+        Ok(png)
+    }
+
+    /// The message in the first chunk of the given type in `image`.
+    pub(crate) fn decode(
+        &self,
+        image: Option<&[u8]>,
+        chunk_type: Option<&str>,
+    ) -> Result<String, Failure> {
+        let png = Settings::png(image)?;
+        let chunk = png
+            .chunk_by_type(self.chunk_type(chunk_type)?)
+            .ok_or_else(|| Failure::NotFound("No matching chunk".to_string()))?;
+        self.text(chunk)
+    }
+
+    /// `image` without the first chunk of the given type.
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub(crate) fn remove(
+        &self,
+        image: Option<&[u8]>,
+        chunk_type: Option<&str>,
+    ) -> Result<Png, Failure> {
+        let mut png = Settings::png(image)?;
+        png.remove_chunk(self.chunk_type(chunk_type)?)
+            .map_err(|e| Failure::NotFound(e.to_string()))?;
+        Ok(png)
+    }
+
+    /// Data of `chunk` as text, decrypted if encryption is on.
+    pub(crate) fn text(&self, chunk: &Chunk) -> Result<String, Failure> {
+        let data = chunk.data_as_string().map_err(invalid)?;
+        Ok(match &self.key {
+            Some(key) => decrypt(data.clone(), key).unwrap_or(data),
+            None => data,
+        })
+    }
+}
+
+#[cfg(feature = "server")]
+mod api {
+    use super::{Failure, Settings};
+    use crate::png::Png;
+    use axum::extract::{DefaultBodyLimit, Multipart, State};
+    use axum::http::{header, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::post;
+    use axum::Router;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tracing::{debug, info};
+
+    /// Failed request, answered with its status and message.
+    struct ApiError(StatusCode, String);
+
+    impl IntoResponse for ApiError {
+        fn into_response(self) -> Response {
+            (self.0, self.1).into_response()
+        }
+    }
+
+    impl From<Failure> for ApiError {
+        fn from(failure: Failure) -> ApiError {
+            match failure {
+                Failure::Invalid(message) => ApiError(StatusCode::BAD_REQUEST, message),
+                Failure::NotFound(message) => ApiError(StatusCode::NOT_FOUND, message),
+            }
+        }
+    }
+
+    fn bad_request(e: impl ToString) -> ApiError {
+        ApiError(StatusCode::BAD_REQUEST, e.to_string())
+    }
+
+    /// Fields of a multipart request.
+    #[derive(Default)]
+    struct Form {
+        image: Option<Vec<u8>>,
+        chunk_type: Option<String>,
+        message: Option<String>,
+    }
+
+    impl Form {
+        async fn read(mut multipart: Multipart) -> Result<Form, ApiError> {
+            let mut form = Form::default();
+            while let Some(field) = multipart.next_field().await.map_err(bad_request)? {
+                match field.name() {
+                    Some("image") => {
+                        form.image = Some(field.bytes().await.map_err(bad_request)?.to_vec())
+                    }
+                    Some("chunk_type") => {
+                        form.chunk_type = Some(field.text().await.map_err(bad_request)?)
+                    }
+                    Some("message") => {
+                        form.message = Some(field.text().await.map_err(bad_request)?)
+                    }
+                    _ => {}
+                }
+            }
+            Ok(form)
+        }
+    }
+
+    fn png_response(png: &Png) -> Response {
+        ([(header::CONTENT_TYPE, "image/png")], png.as_bytes()).into_response()
+    }
+
+    /// `POST /encode` with `image`, `chunk_type` and `message`: the PNG with
+    /// the message added.
+    async fn encode(
+        State(settings): State<Arc<Settings>>,
+        multipart: Multipart,
+    ) -> Result<Response, ApiError> {
+        let form = Form::read(multipart).await?;
+        let png = settings.encode(
+            form.image.as_deref(),
+            form.chunk_type.as_deref(),
+            form.message,
+        )?;
+        Ok(png_response(&png))
+    }
+
+    /// `POST /decode` with `image` and `chunk_type`: the message as text.
+    async fn decode(
+        State(settings): State<Arc<Settings>>,
+        multipart: Multipart,
+    ) -> Result<Response, ApiError> {
+        let form = Form::read(multipart).await?;
+        let message = settings.decode(form.image.as_deref(), form.chunk_type.as_deref())?;
+        Ok(message.into_response())
+    }
+
+    /// `POST /remove` with `image` and `chunk_type`: the PNG without the
+    /// first matching chunk.
+    async fn remove(
+        State(settings): State<Arc<Settings>>,
+        multipart: Multipart,
+    ) -> Result<Response, ApiError> {
+        let form = Form::read(multipart).await?;
+        let png = settings.remove(form.image.as_deref(), form.chunk_type.as_deref())?;
+        Ok(png_response(&png))
+    }
+
+    fn router(settings: Settings) -> Router {
+        Router::new()
+            .route("/encode", post(encode))
+            .route("/decode", post(decode))
+            .route("/remove", post(remove))
+            .layer(DefaultBodyLimit::max(settings.body_limit))
+            .with_state(Arc::new(settings))
+    }
+
+    /// Answer requests on the given address until interrupted.
+    pub(crate) fn serve(listen: SocketAddr, settings: Settings) -> crate::Result<()> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind(listen).await?;
+            info!("listening on http://{}", listener.local_addr()?);
+            axum::serve(listener, router(settings)).await?;
+            debug!("server stopped");
+            Ok(())
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use axum::body::Body;
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+        fn multipart(fields: &[(&str, &[u8])]) -> Request<Body> {
+            let mut body = Vec::new();
+            for (name, value) in fields {
+                body.extend_from_slice(b"--XYZ\r\n");
+                body.extend_from_slice(
+                    format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name).as_bytes(),
+                );
+                body.extend_from_slice(value);
+                body.extend_from_slice(b"\r\n");
+            }
+            body.extend_from_slice(b"--XYZ--\r\n");
+            Request::post("/")
+                .header(header::CONTENT_TYPE, "multipart/form-data; boundary=XYZ")
+                .body(Body::from(body))
+                .unwrap()
+        }
+
+        async fn call(path: &str, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+            let (mut parts, body) = request.into_parts();
+            parts.uri = path.parse().unwrap();
+            let settings = Settings {
+                key: None,
+                chunk_type: None,
+                body_limit: 1024 * 1024,
+            };
+            let response = router(settings)
+                .oneshot(Request::from_parts(parts, body))
+                .await
+                .unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, bytes.to_vec())
+        }
+
+        fn block_on<F: std::future::Future>(future: F) -> F::Output {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(future)
+        }
+
+        #[test]
+        fn test_encode_then_decode() {
+            block_on(async {
+                let request = multipart(&[
+                    ("image", TURTLE),
+                    ("chunk_type", b"teSt"),
+                    ("message", b"Secret"),
+                ]);
+                let (status, encoded) = call("/encode", request).await;
+                assert_eq!(status, StatusCode::OK);
+
+                let request = multipart(&[("image", &encoded), ("chunk_type", b"teSt")]);
+                let (status, message) = call("/decode", request).await;
+                assert_eq!(status, StatusCode::OK);
+                assert_eq!(message, b"Secret");
+            })
+        }
+
+        #[test]
+        fn test_bad_requests() {
+            block_on(async {
+                let (status, _) = call("/decode", multipart(&[("chunk_type", b"teSt")])).await;
+                assert_eq!(status, StatusCode::BAD_REQUEST);
+
+                let request = multipart(&[("image", TURTLE), ("chunk_type", b"teSt")]);
+                let (status, _) = call("/decode", request).await;
+                assert_eq!(status, StatusCode::NOT_FOUND);
+            })
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+pub(crate) use api::serve;
+
+/// Answer requests on the given address until interrupted.
+#[cfg(not(feature = "server"))]
+pub(crate) fn serve(_listen: SocketAddr, _settings: Settings) -> crate::Result<()> {
+    Err("serve needs pngme built with the `server` feature")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        let settings = Settings {
+            key: Some("hunter2".to_string()),
+            chunk_type: Some(ChunkType::from_str("teSt").unwrap()),
+            body_limit: 1024 * 1024,
+        };
+        let png = settings
+            .encode(Some(TURTLE), None, Some("Secret".to_string()))
+            .unwrap();
+        let chunk = png.chunk_by_type(ChunkType::from_str("teSt").unwrap());
+        assert_ne!(chunk.unwrap().data(), b"Secret");
+
+        let bytes = png.as_bytes();
+        assert_eq!(settings.decode(Some(&bytes), None).unwrap(), "Secret");
+        let removed = settings.remove(Some(&bytes), Some("teSt")).unwrap();
+        assert!(matches!(
+            settings.decode(Some(&removed.as_bytes()), None),
+            Err(Failure::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let settings = Settings {
+            key: None,
+            chunk_type: None,
+            body_limit: 1024 * 1024,
+        };
+        assert!(matches!(
+            settings.decode(None, Some("teSt")),
+            Err(Failure::Invalid(_))
+        ));
+        assert!(matches!(
+            settings.decode(Some(TURTLE), None),
+            Err(Failure::Invalid(_))
+        ));
+        assert!(matches!(
+            settings.encode(Some(TURTLE), Some("teSt"), None),
+            Err(Failure::Invalid(_))
+        ));
+    }
+}
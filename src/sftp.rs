@@ -0,0 +1,121 @@
+#[cfg(not(feature = "sftp"))]
+use crate::args::RemoteArgs;
+#[cfg(not(feature = "sftp"))]
+use crate::config::Config;
+#[cfg(not(feature = "sftp"))]
+use reqwest::Url;
+
+/// URL scheme of files on SSH servers.
+pub(crate) const SCHEME: &str = "sftp";
+
+#[cfg(feature = "sftp")]
+mod ssh {
+    use crate::args::RemoteArgs;
+    use crate::config::Config;
+    use crate::remote;
+    use reqwest::Url;
+    use ssh2::{CheckResult, KnownHostFileKind, Session};
+    use std::env;
+    use std::io::{self, Write};
+    use std::net::TcpStream;
+    use std::path::{Path, PathBuf};
+    use tracing::debug;
+
+    /// Session with the host of the given url, once its host key checks out
+    /// against `~/.ssh/known_hosts`.  Authenticates with `--ssh-key`, else the
+    /// password in the url, else the SSH agent.
+    fn session(url: &Url, remote: &RemoteArgs) -> crate::Result<Session> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| format!("No host in '{}'", url))?;
+        let port = url.port().unwrap_or(22);
+        let mut session = Session::new()?;
+        session.set_tcp_stream(TcpStream::connect((host, port))?);
+        session.handshake()?;
+
+        let mut known_hosts = session.known_hosts()?;
+        if let Some(home) = env::var_os("HOME") {
+            let file = PathBuf::from(home).join(".ssh").join("known_hosts");
+            if file.exists() {
+                known_hosts.read_file(&file, KnownHostFileKind::OpenSSH)?;
+            }
+        }
+        let (key, _) = session.host_key().ok_or("Server sent no host key")?;
+        match known_hosts.check_port(host, port, key) {
+            CheckResult::Match => {}
+            CheckResult::NotFound => Err(format!(
+                "Host key of {} is not in ~/.ssh/known_hosts, connect with ssh once to add it",
+                host
+            ))?,
+            CheckResult::Mismatch => Err(format!("Host key of {} has changed", host))?,
+            CheckResult::Failure => Err(format!("Couldn't check the host key of {}", host))?,
+        }
+
+        let (user, password) = remote::credentials(url);
+        let user = match user {
+            Some(user) => user,
+            None => env::var("USER")?,
+        };
+        match (&remote.ssh_key, password) {
+            (Some(key), _) => session.userauth_pubkey_file(&user, None, key, None)?,
+            (None, Some(password)) => session.userauth_password(&user, &password)?,
+            (None, None) => session.userauth_agent(&user)?,
+        }
+        debug!(%user, "connected to {}:{}", host, port);
+        Ok(session)
+    }
+
+    /// Download the file at the given url into `out`.
+    pub(crate) fn get(
+        url: &Url,
+        remote: &RemoteArgs,
+        _config: &Config,
+        out: &mut dyn Write,
+    ) -> crate::Result<()> {
+        let sftp = session(url, remote)?.sftp()?;
+        let mut file = sftp.open(Path::new(&remote::path(url)))?;
+        io::copy(&mut file, out)?;
+        Ok(())
+    }
+
+    /// Upload PNG bytes as the file at the given url.
+    pub(crate) fn put(
+        url: &Url,
+        remote: &RemoteArgs,
+        _config: &Config,
+        bytes: Vec<u8>,
+    ) -> crate::Result<()> {
+        let sftp = session(url, remote)?.sftp()?;
+        sftp.create(Path::new(&remote::path(url)))?
+            .write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sftp")]
+pub(crate) use ssh::{get, put};
+
+#[cfg(not(feature = "sftp"))]
+const NOT_BUILT: &str = "sftp:// URLs need pngme built with the `sftp` feature";
+
+/// Download the file at the given url into `out`.
+#[cfg(not(feature = "sftp"))]
+pub(crate) fn get(
+    _url: &Url,
+    _remote: &RemoteArgs,
+    _config: &Config,
+    _out: &mut dyn std::io::Write,
+) -> crate::Result<()> {
+    Err(NOT_BUILT)?
+}
+
+/// Upload PNG bytes as the file at the given url.
+#[cfg(not(feature = "sftp"))]
+pub(crate) fn put(
+    _url: &Url,
+    _remote: &RemoteArgs,
+    _config: &Config,
+    _bytes: Vec<u8>,
+) -> crate::Result<()> {
+    Err(NOT_BUILT)?
+}
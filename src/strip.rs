@@ -0,0 +1,254 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::config::Config;
+use crate::png::Png;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Presets `strip` knows without any config.
+pub(crate) const BUILT_IN: [&str; 3] = ["social-media", "archive", "forensic"];
+
+/// Chunks that make the pixels mean what they should: transparency and
+/// the frames of an animation.
+const PIXELS: [ChunkType; 4] = [
+    ChunkType::TRNS,
+    ChunkType::ACTL,
+    ChunkType::FCTL,
+    ChunkType::FDAT,
+];
+
+/// Chunks that make the image look as it should: colour, background and
+/// physical size.
+const RENDERING: [ChunkType; 10] = [
+    ChunkType::CHRM,
+    ChunkType::GAMA,
+    ChunkType::ICCP,
+    ChunkType::SBIT,
+    ChunkType::SRGB,
+    ChunkType::CICP,
+    ChunkType::MDCV,
+    ChunkType::CLLI,
+    ChunkType::BKGD,
+    ChunkType::PHYS,
+];
+
+/// Which ancillary chunks `strip` removes.  Critical chunks always stay.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Preset {
+    /// Types kept, removing every other.  `None` keeps any not removed
+    /// otherwise.
+    pub(crate) keep: Option<Vec<ChunkType>>,
+    /// Types removed.
+    pub(crate) remove: Vec<ChunkType>,
+    /// Remove private chunks, like pngme's own.
+    pub(crate) private: bool,
+    /// Types kept whatever the rest says, like those of `--keep`.
+    pub(crate) spare: Vec<ChunkType>,
+}
+
+impl Preset {
+    /// Preset keeping only the given types.
+    pub(crate) fn keeping(keep: &[ChunkType]) -> Preset {
+        Preset {
+            keep: Some(keep.to_vec()),
+            ..Preset::default()
+        }
+    }
+
+    /// The built-in preset of the given name.
+    ///
+    /// - `social-media`: what's needed to show the image as intended, without
+    ///   text, Exif, timestamps or private chunks.
+    /// - `archive`: every standard chunk, without private ones.
+    /// - `forensic`: only what the pixels need, dropping even the colour
+    ///   profile, which can identify the device.
+    pub(crate) fn built_in(name: &str) -> Option<Preset> {
+        match name {
+            "social-media" => Some(Preset::keeping(&[&PIXELS[..], &RENDERING[..]].concat())),
+            "archive" => Some(Preset {
+                private: true,
+                ..Preset::default()
+            }),
+            "forensic" => Some(Preset::keeping(&PIXELS)),
+            _ => None,
+        }
+    }
+
+    /// The named preset: one from the config, which can replace a built-in
+    /// one, else a built-in one.
+    pub(crate) fn named(name: &str, config: &Config) -> crate::Result<Preset> {
+        if let Some(preset) = config.strip_presets.get(name) {
+            return preset.to_preset();
+        }
+        Preset::built_in(name).ok_or_else(|| {
+            let mut names: Vec<&str> = BUILT_IN.to_vec();
+            names.extend(config.strip_presets.keys().map(String::as_str));
+            format!(
+                "Unknown preset '{}' (expected one of {})",
+                name,
+                names.join(", ")
+            )
+            .into()
+        })
+    }
+
+    /// Does this preset remove chunks of the type.
+    pub(crate) fn removes(&self, chunk_type: &ChunkType) -> bool {
+        if chunk_type.is_critical() || self.spare.contains(chunk_type) {
+            return false;
+        }
+        let kept = self
+            .keep
+            .as_ref()
+            .is_none_or(|keep| keep.contains(chunk_type));
+        !kept || self.remove.contains(chunk_type) || (self.private && !chunk_type.is_public())
+    }
+}
+
+/// A preset as written in the config, under `[strip_presets.<name>]`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct PresetConfig {
+    /// Start from this built-in preset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) base: Option<String>,
+    /// Types kept, removing every other.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) keep: Option<Vec<String>>,
+    /// Types removed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) remove: Vec<String>,
+    /// Remove private chunks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) private: Option<bool>,
+}
+
+impl PresetConfig {
+    /// The preset this describes.  `Err` on an unknown base or a bad chunk
+    /// type.
+    pub(crate) fn to_preset(&self) -> crate::Result<Preset> {
+        let mut preset = match &self.base {
+            Some(base) => Preset::built_in(base).ok_or_else(|| {
+                format!(
+                    "Unknown base preset '{}' (expected one of {})",
+                    base,
+                    BUILT_IN.join(", ")
+                )
+            })?,
+            None => Preset::default(),
+        };
+        let parse = |types: &[String]| -> crate::Result<Vec<ChunkType>> {
+            Ok(types
+                .iter()
+                .map(|s| ChunkType::from_str(s))
+                .collect::<Result<_, _>>()?)
+        };
+        if let Some(keep) = &self.keep {
+            preset.keep = Some(parse(keep)?);
+        }
+        preset.remove.extend(parse(&self.remove)?);
+        if let Some(private) = self.private {
+            preset.private = private;
+        }
+        Ok(preset)
+    }
+}
+
+/// Remove every chunk of the Png the preset removes.  Returns the removed
+/// chunks.
+pub(crate) fn strip(png: &mut Png, preset: &Preset) -> Vec<Chunk> {
+    let (removed, kept) = png
+        .chunks()
+        .iter()
+        .cloned()
+        .partition(|c| preset.removes(c.chunk_type()));
+    *png = Png::from_chunks(kept);
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    fn types(png: &Png) -> String {
+        let types: Vec<String> = png.iter().map(|c| c.chunk_type().to_string()).collect();
+        types.join(" ")
+    }
+
+    fn sample() -> Png {
+        let mut png = Png::try_from(TURTLE).unwrap();
+        for chunk_type in ["sRGB", "tEXt", "eXIf", "tIME", "auDt"] {
+            let chunk_type = ChunkType::from_str(chunk_type).unwrap();
+            png.insert_chunk_at(1, Chunk::new(chunk_type, vec![0]).unwrap());
+        }
+        png
+    }
+
+    #[test]
+    fn test_built_in() {
+        let strip_with = |name| {
+            let mut png = sample();
+            strip(&mut png, &Preset::built_in(name).unwrap());
+            types(&png)
+        };
+        assert_eq!(
+            strip_with("social-media"),
+            "IHDR sRGB PLTE IDAT IDAT IDAT IEND"
+        );
+        assert_eq!(
+            strip_with("archive"),
+            "IHDR tIME eXIf tEXt sRGB PLTE IDAT IDAT IDAT IEND"
+        );
+        assert_eq!(strip_with("forensic"), "IHDR PLTE IDAT IDAT IDAT IEND");
+        for name in BUILT_IN {
+            assert!(Preset::built_in(name).is_some());
+        }
+        assert_eq!(Preset::built_in("nope"), None);
+    }
+
+    #[test]
+    fn test_from_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [strip_presets.web]
+            base = "social-media"
+            remove = ["pHYs"]
+
+            [strip_presets.archive]
+            remove = ["tIME"]
+            "#,
+        )
+        .unwrap();
+        let web = Preset::named("web", &config).unwrap();
+        assert!(web.removes(&ChunkType::PHYS));
+        assert!(web.removes(&ChunkType::TEXT));
+        assert!(!web.removes(&ChunkType::SRGB));
+
+        // A config preset replaces the built-in one of its name.
+        let archive = Preset::named("archive", &config).unwrap();
+        assert!(archive.removes(&ChunkType::TIME));
+        assert!(!archive.removes(&ChunkType::from_str("ruSt").unwrap()));
+
+        let error = Preset::named("nope", &config).unwrap_err().to_string();
+        assert!(
+            error.contains("social-media") && error.contains("web"),
+            "{}",
+            error
+        );
+
+        let spared = Preset {
+            spare: vec![ChunkType::TIME],
+            ..archive
+        };
+        assert!(!spared.removes(&ChunkType::TIME));
+
+        let bad = PresetConfig {
+            keep: Some(vec!["r5St".to_string()]),
+            ..PresetConfig::default()
+        };
+        assert!(bad.to_preset().is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::chunk_type::ChunkType;
+
+/// What the case of each letter of a chunk type says, as the meaning of
+/// uppercase then lowercase.
+const CASE_BITS: [(&str, &str); 4] = [
+    ("critical", "ancillary"),
+    ("public", "private"),
+    ("valid", "reserved bit set, which is invalid"),
+    ("unsafe to copy", "safe to copy"),
+];
+
+/// How far apart two chunk types are, ignoring case: the letters that
+/// differ, with two swapped neighbours counting as one.
+fn distance(a: &ChunkType, b: &ChunkType) -> usize {
+    let (a, b) = (a.bytes(), b.bytes());
+    let same = |i: usize, j: usize| a[i].eq_ignore_ascii_case(&b[j]);
+    let differ: Vec<usize> = (0..4).filter(|&i| !same(i, i)).collect();
+    match differ[..] {
+        [i, j] if j == i + 1 && same(i, j) && same(j, i) => 1,
+        _ => differ.len(),
+    }
+}
+
+/// Types among `present` that `wanted` may be a typo of: the same letters
+/// in another case first, then ones a letter off.
+pub(crate) fn near_matches<'a>(
+    wanted: &ChunkType,
+    present: impl IntoIterator<Item = &'a ChunkType>,
+) -> Vec<ChunkType> {
+    let mut matches: Vec<(usize, ChunkType)> = Vec::new();
+    for chunk_type in present {
+        let distance = distance(wanted, chunk_type);
+        let seen = matches.iter().any(|(_, t)| t == chunk_type);
+        if chunk_type != wanted && distance <= 1 && !seen {
+            matches.push((distance, *chunk_type));
+        }
+    }
+    matches.sort_by_key(|(distance, _)| *distance);
+    matches.into_iter().map(|(_, t)| t).collect()
+}
+
+/// What the letters that differ only in case between the two types mean,
+/// like "3rd letter uppercase = valid, lowercase = reserved bit set, which
+/// is invalid".  `None` if no letter does.
+fn case_note(wanted: &ChunkType, found: &ChunkType) -> Option<String> {
+    let (wanted, found) = (wanted.bytes(), found.bytes());
+    let notes: Vec<String> = (0..4)
+        .filter(|&i| wanted[i] != found[i] && wanted[i].eq_ignore_ascii_case(&found[i]))
+        .map(|i| {
+            let (upper, lower) = CASE_BITS[i];
+            let nth = ["1st", "2nd", "3rd", "4th"][i];
+            format!(
+                "{} letter uppercase = {}, lowercase = {}",
+                nth, upper, lower
+            )
+        })
+        .collect();
+    (!notes.is_empty()).then(|| notes.join("; "))
+}
+
+/// Saying there is no chunk of the type, suggesting near matches among the
+/// types present, if any, and why case matters when that is the difference.
+pub(crate) fn not_found<'a>(
+    wanted: &ChunkType,
+    present: impl IntoIterator<Item = &'a ChunkType>,
+) -> Option<String> {
+    let matches = near_matches(wanted, present);
+    let best = matches.first()?;
+    let names: Vec<String> = matches.iter().map(|t| format!("'{}'", t)).collect();
+    let mut message = format!(
+        "No chunk '{}'; did you mean {}?",
+        wanted,
+        names.join(" or ")
+    );
+    if let Some(note) = case_note(wanted, best) {
+        message.push_str(&format!(
+            " Chunk types are case-sensitive, each letter's case being a flag: {}",
+            note
+        ));
+    }
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn types(names: &[&str]) -> Vec<ChunkType> {
+        names
+            .iter()
+            .map(|s| ChunkType::from_str(s).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_near_matches() {
+        let present = types(&["IHDR", "ruSt", "rusT", "tEXt", "ruSt", "IEND"]);
+        let wanted = ChunkType::from_str("rust").unwrap();
+        assert_eq!(near_matches(&wanted, &present), types(&["ruSt", "rusT"]));
+
+        let typo = ChunkType::from_str("tEXT").unwrap();
+        assert_eq!(near_matches(&typo, &present), types(&["tEXt"]));
+        let swapped = ChunkType::from_str("rSut").unwrap();
+        assert_eq!(near_matches(&swapped, &present), types(&["ruSt", "rusT"]));
+        let off_by_one = ChunkType::from_str("ruSx").unwrap();
+        assert_eq!(
+            near_matches(&off_by_one, &present),
+            types(&["ruSt", "rusT"])
+        );
+
+        let far = ChunkType::from_str("abCd").unwrap();
+        assert!(near_matches(&far, &present).is_empty());
+    }
+
+    #[test]
+    fn test_not_found() {
+        let present = types(&["IHDR", "ruSt", "IEND"]);
+        let wanted = ChunkType::from_str("rust").unwrap();
+        assert_eq!(
+            not_found(&wanted, &present).unwrap(),
+            "No chunk 'rust'; did you mean 'ruSt'? Chunk types are case-sensitive, \
+             each letter's case being a flag: 3rd letter uppercase = valid, \
+             lowercase = reserved bit set, which is invalid"
+        );
+
+        let wanted = ChunkType::from_str("ruSx").unwrap();
+        assert_eq!(
+            not_found(&wanted, &present).unwrap(),
+            "No chunk 'ruSx'; did you mean 'ruSt'?"
+        );
+        let wanted = ChunkType::from_str("zzzz").unwrap();
+        assert_eq!(not_found(&wanted, &present), None);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::{PngmeError, Result};
+
+/// Text of an iTXt chunk: UTF-8, tagged with the language it is in and with
+/// its keyword translated into that language.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InternationalText {
+    /// Latin-1 keyword saying what the text is, like `Description`.
+    pub keyword: String,
+    /// Language tag of the text, like `de` or `pt-BR`.  Empty if unknown.
+    pub language: String,
+    /// The keyword in the language of the text.
+    pub translated_keyword: String,
+    /// The text.
+    pub text: String,
+}
+
+#[allow(dead_code)]
+impl InternationalText {
+    /// Uncompressed text, with the given keyword and no language tag.
+    pub fn new(keyword: &str, text: &str) -> Self {
+        InternationalText {
+            keyword: keyword.to_string(),
+            language: String::new(),
+            translated_keyword: String::new(),
+            text: text.to_string(),
+        }
+    }
+
+    /// Parse the data of an iTXt chunk.  `Err` if it is malformed or
+    /// compressed.
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        let invalid = |reason: &str| PngmeError::InvalidImage(format!("iTXt {}", reason));
+        let (keyword, rest) = split_null(data).ok_or_else(|| invalid("is cut short"))?;
+        if keyword.is_empty() {
+            return Err(invalid("has no keyword"));
+        }
+        // The compression flag and method, then the language tag.
+        let Some(([compressed, _method], rest)) = rest.split_first_chunk() else {
+            return Err(invalid("is cut short"));
+        };
+        if *compressed != 0 {
+            return Err(invalid("text is compressed, which isn't supported"));
+        }
+        let (language, rest) = split_null(rest).ok_or_else(|| invalid("is cut short"))?;
+        let (translated_keyword, text) = split_null(rest).ok_or_else(|| invalid("is cut short"))?;
+
+        let utf8 = |bytes: &[u8]| {
+            String::from_utf8(bytes.to_vec()).map_err(|_| invalid("text isn't UTF-8"))
+        };
+        Ok(InternationalText {
+            keyword: keyword.iter().map(|b| char::from(*b)).collect(),
+            language: utf8(language)?,
+            translated_keyword: utf8(translated_keyword)?,
+            text: utf8(text)?,
+        })
+    }
+
+    /// Data of the iTXt chunk holding this text, uncompressed.  `Err` if the
+    /// keyword isn't 1 to 79 Latin-1 characters, the language tag isn't
+    /// ASCII letters, digits and hyphens, or the translated keyword holds a
+    /// null.
+    pub fn to_data(&self) -> Result<Vec<u8>> {
+        let invalid = |reason: String| PngmeError::InvalidImage(reason);
+        let language = &self.language;
+        if !language
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return Err(invalid(format!("{:?} isn't a language tag", language)));
+        }
+        if self.translated_keyword.contains('\0') {
+            return Err(invalid(format!(
+                "{:?} isn't a translated keyword",
+                self.translated_keyword
+            )));
+        }
+        Ok([
+            &keyword_bytes(&self.keyword)?[..],
+            &[0, 0, 0],
+            language.as_bytes(),
+            &[0],
+            self.translated_keyword.as_bytes(),
+            &[0],
+            self.text.as_bytes(),
+        ]
+        .concat())
+    }
+
+    /// iTXt chunk holding this text.
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        Chunk::new(ChunkType::ITXT, self.to_data()?)
+    }
+
+    /// Is the text in the given language: the tags are equal ignoring case,
+    /// or the text's tag narrows the given one, as `de-CH` does `de`.
+    pub fn is_in(&self, language: &str) -> bool {
+        let tag = self.language.to_ascii_lowercase();
+        let language = language.to_ascii_lowercase();
+        tag == language
+            || tag
+                .strip_prefix(&language)
+                .is_some_and(|rest| rest.starts_with('-'))
+    }
+}
+
+/// The bytes before the first null and those after it.
+fn split_null(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let null = bytes.iter().position(|b| *b == 0)?;
+    Some((&bytes[..null], &bytes[null + 1..]))
+}
+
+/// The zlib stream of compressed text in a zTXt chunk, or in an iTXt chunk
+/// whose compression flag is set.  `None` for other chunks.
+#[allow(dead_code)]
+pub(crate) fn compressed_text<'a>(chunk_type: &ChunkType, data: &'a [u8]) -> Option<&'a [u8]> {
+    let (_, rest) = split_null(data)?;
+    if *chunk_type == ChunkType::ZTXT {
+        // After the compression method.
+        return rest.get(1..);
+    }
+    if *chunk_type != ChunkType::ITXT {
+        return None;
+    }
+    let ([1, _method], rest) = rest.split_first_chunk()? else {
+        return None;
+    };
+    let (_language, rest) = split_null(rest)?;
+    let (_translated_keyword, text) = split_null(rest)?;
+    Some(text)
+}
+
+/// Keyword of a text chunk: 1 to 79 Latin-1 characters other than null.
+pub(crate) fn keyword_bytes(keyword: &str) -> Result<Vec<u8>> {
+    keyword
+        .chars()
+        .map(|c| u8::try_from(u32::from(c)).ok())
+        .collect::<Option<Vec<u8>>>()
+        .filter(|k| (1..=79).contains(&k.len()) && !k.contains(&0))
+        .ok_or_else(|| PngmeError::InvalidImage(format!("{:?} isn't a text keyword", keyword)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compressed_text() {
+        let ztxt = b"Comment\0\0zlib";
+        assert_eq!(compressed_text(&ChunkType::ZTXT, ztxt), Some(&b"zlib"[..]));
+        let itxt = b"Comment\0\x01\0en\0Kommentar\0zlib";
+        assert_eq!(compressed_text(&ChunkType::ITXT, itxt), Some(&b"zlib"[..]));
+        let plain = b"Comment\0\0\0en\0\0text";
+        assert_eq!(compressed_text(&ChunkType::ITXT, plain), None);
+        assert_eq!(compressed_text(&ChunkType::TEXT, b"Comment\0text"), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = InternationalText {
+            keyword: "Description".to_string(),
+            language: "de-CH".to_string(),
+            translated_keyword: "Beschreibung".to_string(),
+            text: "Grüezi".to_string(),
+        };
+        let data = text.to_data().unwrap();
+        assert!(data.starts_with(b"Description\0\0\0de-CH\0Beschreibung\0"));
+        assert_eq!(InternationalText::from_data(&data).unwrap(), text);
+        assert_eq!(text.to_chunk().unwrap().chunk_type(), &ChunkType::ITXT);
+
+        let plain = InternationalText::new("Comment", "hi");
+        let data = plain.to_data().unwrap();
+        assert_eq!(data, b"Comment\0\0\0\0\0hi");
+        assert_eq!(InternationalText::from_data(&data).unwrap(), plain);
+    }
+
+    #[test]
+    fn test_is_in() {
+        let mut text = InternationalText::new("Title", "");
+        text.language = "de-CH".to_string();
+        assert!(text.is_in("de"));
+        assert!(text.is_in("DE-ch"));
+        assert!(!text.is_in("d"));
+        assert!(!text.is_in("en"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let invalid = |data: &[u8]| InternationalText::from_data(data).is_err();
+        assert!(invalid(b""));
+        assert!(invalid(b"\0\0\0\0\0hi"));
+        assert!(invalid(b"Title\0\0"));
+        assert!(invalid(b"Title\0\x01\0\0\0x\x9c"));
+
+        let mut text = InternationalText::new("Title", "hi");
+        text.language = "de CH".to_string();
+        assert!(text.to_data().is_err());
+        assert!(InternationalText::new("", "hi").to_data().is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use crate::chunk::{checksum, MAXIMUM_LENGTH};
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use thiserror::Error;
+
+/// One way the bytes of a PNG break its structure.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum Violation {
+    /// The bytes don't start with the PNG signature.
+    #[error("Not a PNG: no signature at byte 0")]
+    InvalidSignature,
+    /// The first chunk isn't IHDR.
+    #[error("First chunk at byte {offset} is {found}, expected IHDR")]
+    IhdrNotFirst { offset: usize, found: ChunkType },
+    /// The Png has no IEND chunk.
+    #[error("No IEND chunk")]
+    MissingIend,
+    /// A chunk comes after IEND.
+    #[error("Chunk {chunk_type} at byte {offset} comes after IEND")]
+    AfterIend {
+        offset: usize,
+        chunk_type: ChunkType,
+    },
+    /// A chunk's checksum doesn't match its type and data.
+    #[error(
+        "Chunk {chunk_type} at byte {offset} has checksum {found:#010x}, expected {expected:#010x}"
+    )]
+    CrcMismatch {
+        offset: usize,
+        chunk_type: ChunkType,
+        expected: u32,
+        found: u32,
+    },
+    /// A chunk type isn't four ASCII letters.  Nothing after it is checked.
+    #[error("Invalid chunk type {found:?} at byte {offset}")]
+    InvalidChunkType { offset: usize, found: String },
+    /// A chunk claims more data than a PNG chunk may hold.  Nothing after it
+    /// is checked.
+    #[error("Chunk at byte {offset} is too long ({length} > 2^31 - 1)")]
+    ChunkTooLong { offset: usize, length: u32 },
+    /// The bytes end mid-chunk.
+    #[error("Chunk at byte {offset} is cut short")]
+    Truncated { offset: usize },
+}
+
+#[allow(dead_code)]
+impl Violation {
+    /// Offset from the start of the input of the chunk at fault, if the
+    /// violation concerns one.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Violation::InvalidSignature | Violation::MissingIend => None,
+            Violation::IhdrNotFirst { offset, .. }
+            | Violation::AfterIend { offset, .. }
+            | Violation::CrcMismatch { offset, .. }
+            | Violation::InvalidChunkType { offset, .. }
+            | Violation::ChunkTooLong { offset, .. }
+            | Violation::Truncated { offset } => Some(*offset),
+        }
+    }
+}
+
+impl Png {
+    /// Check the given bytes against the structure of a PNG: its signature,
+    /// IHDR first, IEND last and every checksum.  Unlike parsing, this goes
+    /// on past the first problem and reports them all, in order.  Empty if
+    /// the bytes are a well-formed PNG.
+    pub fn validate(bytes: &[u8]) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let Some(mut rest) = bytes.strip_prefix(&Png::STANDARD_HEADER[..]) else {
+            return vec![Violation::InvalidSignature];
+        };
+
+        let mut offset = Png::STANDARD_HEADER.len();
+        let mut iend = false;
+        let mut first = true;
+        while !rest.is_empty() {
+            let Some((head, tail)) = rest.split_at_checked(8) else {
+                violations.push(Violation::Truncated { offset });
+                break;
+            };
+            let length = u32::from_be_bytes([head[0], head[1], head[2], head[3]]);
+            let type_bytes = [head[4], head[5], head[6], head[7]];
+            let Ok(chunk_type) = ChunkType::try_from(type_bytes) else {
+                let found = String::from_utf8_lossy(&type_bytes).into_owned();
+                violations.push(Violation::InvalidChunkType { offset, found });
+                break;
+            };
+            if length > MAXIMUM_LENGTH {
+                violations.push(Violation::ChunkTooLong { offset, length });
+                break;
+            }
+            let Some((data, tail)) = tail.split_at_checked(length as usize) else {
+                violations.push(Violation::Truncated { offset });
+                break;
+            };
+            let Some((crc, tail)) = tail.split_at_checked(4) else {
+                violations.push(Violation::Truncated { offset });
+                break;
+            };
+
+            if first && chunk_type != ChunkType::IHDR {
+                violations.push(Violation::IhdrNotFirst {
+                    offset,
+                    found: chunk_type,
+                });
+            }
+            if iend {
+                violations.push(Violation::AfterIend { offset, chunk_type });
+            }
+            let expected = checksum(chunk_type.bytes(), data);
+            let found = u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]);
+            if found != expected {
+                violations.push(Violation::CrcMismatch {
+                    offset,
+                    chunk_type,
+                    expected,
+                    found,
+                });
+            }
+
+            first = false;
+            iend |= chunk_type == ChunkType::IEND;
+            offset += 12 + length as usize;
+            rest = tail;
+        }
+        if !iend {
+            violations.push(Violation::MissingIend);
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        let chunk_type = ChunkType::from_str(chunk_type).unwrap();
+        Chunk::new(chunk_type, data.as_bytes().to_vec()).unwrap()
+    }
+
+    fn bytes(chunks: &[Chunk]) -> Vec<u8> {
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        for chunk in chunks {
+            bytes.extend(chunk.as_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_validate() {
+        let good = [chunk("IHDR", ""), chunk("ruSt", "hi"), chunk("IEND", "")];
+        assert!(Png::validate(&bytes(&good)).is_empty());
+        assert_eq!(
+            Png::validate(&bytes(&good)[1..]),
+            vec![Violation::InvalidSignature]
+        );
+
+        let late = [chunk("ruSt", "hi"), chunk("IEND", ""), chunk("taIl", "")];
+        let violations = Png::validate(&bytes(&late));
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].offset(), Some(8));
+        assert!(matches!(
+            violations[1],
+            Violation::AfterIend { offset: 34, .. }
+        ));
+    }
+
+    #[test]
+    fn test_validate_damaged() {
+        let good = [chunk("IHDR", ""), chunk("ruSt", "hi"), chunk("IEND", "")];
+        let mut damaged = bytes(&good);
+        damaged[8 + 12 + 8] ^= 0xff;
+        assert!(matches!(
+            Png::validate(&damaged)[..],
+            [Violation::CrcMismatch { offset: 20, .. }]
+        ));
+
+        let cut = &damaged[..damaged.len() - 2];
+        assert_eq!(
+            Png::validate(cut)[1..],
+            [Violation::Truncated { offset: 34 }, Violation::MissingIend]
+        );
+    }
+}
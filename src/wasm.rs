@@ -0,0 +1,81 @@
+//! Bindings for JavaScript, working only on PNG bytes so a web page can
+//! hide and read messages without a server.
+
+use crate::bindings;
+use crate::{png::Png, Result};
+use std::convert::TryFrom;
+use wasm_bindgen::prelude::*;
+
+/// Type and data of one chunk, as listed by `list_chunks`.
+#[wasm_bindgen]
+pub struct ChunkInfo {
+    chunk_type: String,
+    data: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl ChunkInfo {
+    /// Four-letter chunk type, like `ruSt`.
+    #[wasm_bindgen(getter, js_name = chunkType)]
+    pub fn chunk_type(&self) -> String {
+        self.chunk_type.clone()
+    }
+
+    /// Chunk data bytes.
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+}
+
+fn list(png: &[u8]) -> Result<Vec<ChunkInfo>> {
+    let png = Png::try_from(png)?;
+    Ok(png
+        .chunks()
+        .iter()
+        .map(|chunk| ChunkInfo {
+            chunk_type: chunk.chunk_type().to_string(),
+            data: chunk.data().to_vec(),
+        })
+        .collect())
+}
+
+fn js_error(e: crate::Error) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+/// The PNG with `message` added in a chunk of the given type.
+#[wasm_bindgen(js_name = encodeBytes)]
+pub fn encode_bytes(
+    png: &[u8],
+    chunk_type: &str,
+    message: &str,
+) -> std::result::Result<Vec<u8>, JsError> {
+    bindings::encode(png, chunk_type, message).map_err(js_error)
+}
+
+/// The message in the first chunk of the given type, `undefined` if none.
+#[wasm_bindgen(js_name = decodeBytes)]
+pub fn decode_bytes(png: &[u8], chunk_type: &str) -> std::result::Result<Option<String>, JsError> {
+    bindings::decode(png, chunk_type).map_err(js_error)
+}
+
+/// Every chunk in the PNG, in order.
+#[wasm_bindgen(js_name = listChunks)]
+pub fn list_chunks(png: &[u8]) -> std::result::Result<Vec<ChunkInfo>, JsError> {
+    list(png).map_err(js_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    #[test]
+    fn test_list() {
+        let chunks = list(TURTLE).unwrap();
+        assert_eq!(chunks[0].chunk_type(), "IHDR");
+        assert!(chunks.iter().any(|chunk| chunk.chunk_type() == "ruSt"));
+    }
+}
@@ -0,0 +1,229 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::{ColorType, Png};
+use font8x8::{UnicodeFonts, BASIC_FONTS};
+use image::{DynamicImage, GenericImage, GenericImageView, ImageOutputFormat, Rgba};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// Chunk type of a watermark record: private and ancillary, but unsafe to
+/// copy, since it describes the pixels and editing them makes it stale.
+pub(crate) const WATERMARK: ChunkType = ChunkType::new_unchecked(*b"wmRK");
+
+/// Colour the text is drawn in, before opacity.
+const INK: [u8; 3] = [0xd0, 0x20, 0x20];
+
+/// Corner, or the middle, of the image the text goes in.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Position {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top-left" => Ok(Position::TopLeft),
+            "top-right" => Ok(Position::TopRight),
+            "bottom-left" => Ok(Position::BottomLeft),
+            "bottom-right" => Ok(Position::BottomRight),
+            "center" => Ok(Position::Center),
+            _ => Err(format!("unknown position '{}'", s)),
+        }
+    }
+}
+
+/// Text drawn onto the pixels of a Png, recorded as JSON in a watermark
+/// chunk so it can be audited later.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub(crate) struct Watermark {
+    pub(crate) text: String,
+    pub(crate) position: Position,
+    /// From 0, invisible, to 1, opaque.
+    pub(crate) opacity: f32,
+}
+
+impl Watermark {
+    /// The Png with the text drawn onto its image and this watermark
+    /// recorded before IEND.  Other chunks are kept, except those whose
+    /// layout depends on the colour type or bit depth when drawing changes
+    /// either: an indexed image comes back as RGB, and one with tRNS gains
+    /// an alpha channel instead.
+    pub(crate) fn apply(&self, png: &Png) -> crate::Result<Png> {
+        let mut image = image::load_from_memory(&png.as_bytes())?;
+        self.draw(&mut image);
+        let mut bytes = Vec::new();
+        image.write_to(&mut bytes, ImageOutputFormat::Png)?;
+        let drawn = Png::try_from(bytes.as_slice())?;
+
+        let indexed = png.color_type() == Some(ColorType::Indexed);
+        let changed =
+            png.color_type() != drawn.color_type() || png.bit_depth() != drawn.bit_depth();
+        let depends_on_layout = [
+            ChunkType::TRNS,
+            ChunkType::SBIT,
+            ChunkType::BKGD,
+            ChunkType::HIST,
+        ];
+        let mut idat = Some(drawn.chunks_by_type(ChunkType::IDAT));
+        let mut chunks = Vec::with_capacity(png.len() + 1);
+        for chunk in png.chunks() {
+            match *chunk.chunk_type() {
+                ChunkType::IHDR => chunks.extend(drawn.chunk_by_type(ChunkType::IHDR).cloned()),
+                ChunkType::IDAT => chunks.extend(idat.take().into_iter().flatten().cloned()),
+                ChunkType::IEND => {
+                    chunks.push(Chunk::new(WATERMARK, serde_json::to_vec(self)?)?);
+                    chunks.push(chunk.clone());
+                }
+                ChunkType::PLTE if indexed => {}
+                t if changed && depends_on_layout.contains(&t) => {}
+                _ => chunks.push(chunk.clone()),
+            }
+        }
+        Ok(Png::from_chunks(chunks))
+    }
+
+    /// Draw the text onto the image in an 8x8 bitmap font, scaled so it
+    /// spans about a third of the width.  Characters outside ASCII are
+    /// drawn as `?`.
+    fn draw(&self, image: &mut DynamicImage) {
+        let (width, height) = image.dimensions();
+        let glyphs: Vec<[u8; 8]> = self
+            .text
+            .chars()
+            .map(|c| BASIC_FONTS.get(c).or_else(|| BASIC_FONTS.get('?')).unwrap())
+            .collect();
+        let scale = (width / 3 / (8 * glyphs.len().max(1) as u32)).max(1);
+        let (text_width, text_height) = (8 * scale * glyphs.len() as u32, 8 * scale);
+        let margin = 4 * scale;
+        let right = width.saturating_sub(text_width + margin);
+        let bottom = height.saturating_sub(text_height + margin);
+        let (left, top) = match self.position {
+            Position::TopLeft => (margin, margin),
+            Position::TopRight => (right, margin),
+            Position::BottomLeft => (margin, bottom),
+            Position::BottomRight => (right, bottom),
+            Position::Center => (
+                width.saturating_sub(text_width) / 2,
+                height.saturating_sub(text_height) / 2,
+            ),
+        };
+
+        for (i, glyph) in glyphs.iter().enumerate() {
+            for y in 0..text_height {
+                for x in 0..8 * scale {
+                    let on = glyph[(y / scale) as usize] >> (x / scale) & 1 == 1;
+                    let (px, py) = (left + 8 * scale * i as u32 + x, top + y);
+                    if on && px < width && py < height {
+                        let pixel = image.get_pixel(px, py);
+                        image.put_pixel(px, py, self.blend(pixel));
+                    }
+                }
+            }
+        }
+    }
+
+    /// The ink, at this watermark's opacity, over the given pixel.
+    fn blend(&self, Rgba([r, g, b, a]): Rgba<u8>) -> Rgba<u8> {
+        let ink = self.opacity.clamp(0.0, 1.0);
+        let under = f32::from(a) / 255.0 * (1.0 - ink);
+        let alpha = ink + under;
+        let mix = |ink_channel: u8, channel: u8| {
+            let mixed = (f32::from(ink_channel) * ink + f32::from(channel) * under) / alpha;
+            mixed.round() as u8
+        };
+        Rgba([
+            mix(INK[0], r),
+            mix(INK[1], g),
+            mix(INK[2], b),
+            (alpha * 255.0).round() as u8,
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::Violation;
+
+    const TURTLE: &[u8] = include_bytes!("../turtle.png");
+
+    fn watermark(opacity: f32) -> Watermark {
+        Watermark {
+            text: "CONFIDENTIAL".to_string(),
+            position: Position::BottomRight,
+            opacity,
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let png = Png::try_from(TURTLE).unwrap();
+        let marked = watermark(0.3).apply(&png).unwrap();
+        // The turtle is indexed, so it comes back as RGB without its
+        // palette.  Its chunk after IEND stays there.
+        assert_eq!(marked.color_type(), Some(ColorType::Rgb));
+        assert!(marked.chunk_by_type(ChunkType::PLTE).is_none());
+        assert!(matches!(
+            Png::validate(&marked.as_bytes())[..],
+            [Violation::AfterIend { .. }]
+        ));
+        let end = marked.position_of(ChunkType::IEND).unwrap();
+        assert_eq!(marked[end - 1].chunk_type(), &WATERMARK);
+        let recorded: Watermark = serde_json::from_slice(marked[end - 1].data()).unwrap();
+        assert_eq!(recorded, watermark(0.3));
+
+        let before = image::load_from_memory(TURTLE).unwrap().to_rgba8();
+        let after = image::load_from_memory(&marked.as_bytes())
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(before.dimensions(), after.dimensions());
+        assert_ne!(before, after);
+        // Nothing is drawn in the top-left corner.
+        assert_eq!(before.get_pixel(0, 0), after.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn test_apply_drops_chunks_for_old_color_type() {
+        let png = Png::builder()
+            .ihdr(16, 16, 8, ColorType::Rgb)
+            .chunk(Chunk::new(ChunkType::SBIT, vec![8, 8, 8]).unwrap())
+            .chunk(Chunk::new(ChunkType::BKGD, vec![0, 0, 0, 0, 0, 0]).unwrap())
+            .chunk(Chunk::new(ChunkType::TRNS, vec![0, 0, 0, 0, 0, 0]).unwrap())
+            .build()
+            .unwrap();
+        let marked = watermark(0.3).apply(&png).unwrap();
+        // The transparent colour becomes an alpha channel, which tRNS,
+        // sBIT and bKGD for RGB don't fit.
+        assert_eq!(marked.color_type(), Some(ColorType::Rgba));
+        for chunk_type in [ChunkType::TRNS, ChunkType::SBIT, ChunkType::BKGD] {
+            assert!(marked.chunk_by_type(chunk_type).is_none(), "{}", chunk_type);
+        }
+        assert!(Png::validate(&marked.as_bytes()).is_empty());
+    }
+
+    #[test]
+    fn test_blend() {
+        let pixel = Rgba([0, 0, 0, 255]);
+        assert_eq!(watermark(0.0).blend(pixel), pixel);
+        assert_eq!(watermark(1.0).blend(pixel), Rgba([0xd0, 0x20, 0x20, 255]));
+        let transparent = watermark(0.5).blend(Rgba([0, 0, 0, 0]));
+        assert_eq!(transparent, Rgba([0xd0, 0x20, 0x20, 128]));
+    }
+
+    #[test]
+    fn test_position() {
+        assert_eq!(
+            Position::from_str("bottom-right"),
+            Ok(Position::BottomRight)
+        );
+        assert!(Position::from_str("middle").is_err());
+    }
+}
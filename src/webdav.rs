@@ -0,0 +1,129 @@
+use crate::args::RemoteArgs;
+use crate::config::Config;
+use crate::remote::{self, Retry};
+use reqwest::{Method, StatusCode, Url};
+use tracing::{debug, info};
+
+/// URL scheme of files on a WebDAV share over HTTP.
+pub(crate) const SCHEME: &str = "dav";
+
+/// URL scheme of files on a WebDAV share over HTTPS.
+pub(crate) const SECURE_SCHEME: &str = "davs";
+
+/// HTTP(S) url of the file at a `dav://` or `davs://` url, like
+/// `davs://cloud.example.com/remote.php/dav/files/me/dice.png`.
+pub(crate) fn http_url(url: &Url) -> crate::Result<Url> {
+    let scheme = match url.scheme() {
+        SCHEME => "http",
+        SECURE_SCHEME => "https",
+        other => Err(format!("Expected dav:// or davs://, got {}://", other))?,
+    };
+    if url.host_str().is_none_or(str::is_empty) {
+        Err(format!(
+            "Expected {}://host/path, got '{}'",
+            url.scheme(),
+            url
+        ))?
+    }
+    // `Url::set_scheme` can't turn a custom scheme into a special one.
+    let rest = &url.as_str()[url.scheme().len()..];
+    Ok(Url::parse(&format!("{}{}", scheme, rest))?)
+}
+
+/// Urls of the collections holding the file at the given url, outermost
+/// first, not counting the root.
+fn parents(url: &Url) -> Vec<Url> {
+    let segments: Vec<&str> = url.path_segments().map_or(vec![], Iterator::collect);
+    (1..segments.len())
+        .map(|end| {
+            let mut parent = url.clone();
+            parent.set_query(None);
+            parent.set_path(&format!("{}/", segments[..end].join("/")));
+            parent
+        })
+        .collect()
+}
+
+/// Did the request fail with the given status.
+fn failed_with(error: &crate::Error, status: StatusCode) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .and_then(reqwest::Error::status)
+        == Some(status)
+}
+
+/// Create every collection holding the file at the given url.  Ones that
+/// already exist answer `405 Method Not Allowed`, which is fine.
+fn make_parents(url: &Url, remote: &RemoteArgs, config: &Config) -> crate::Result<()> {
+    let client = remote::client(remote, config)?;
+    let mkcol = Method::from_bytes(b"MKCOL")?;
+    for parent in parents(url) {
+        let made = remote::send(Retry::from_args(remote), mkcol.clone(), |method| {
+            remote::request(&client, method, &parent, remote, config)
+        });
+        match made {
+            Ok(_) => debug!("created collection {}", parent),
+            Err(e) if failed_with(&e, StatusCode::METHOD_NOT_ALLOWED) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// PUT PNG bytes as the file at the given url.  If the share says its
+/// folder is missing (`409 Conflict`), the folders are created and the PUT
+/// tried again.
+pub(crate) fn put(
+    url: &Url,
+    bytes: Vec<u8>,
+    remote: &RemoteArgs,
+    config: &Config,
+) -> crate::Result<()> {
+    let url = http_url(url)?;
+    let response = match remote::upload(&url, &Method::PUT, bytes.clone(), remote, config) {
+        Err(e) if failed_with(&e, StatusCode::CONFLICT) => {
+            make_parents(&url, remote, config)?;
+            remote::upload(&url, &Method::PUT, bytes, remote, config)?
+        }
+        response => response?,
+    };
+    info!(status = %response.status(), "uploaded to {}", url);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_url() {
+        let url =
+            Url::parse("davs://me:pw@cloud.example.com/remote.php/dav/files/me/a.png").unwrap();
+        assert_eq!(
+            http_url(&url).unwrap().as_str(),
+            "https://me:pw@cloud.example.com/remote.php/dav/files/me/a.png"
+        );
+        let url = Url::parse("dav://localhost:8080/share/a.png").unwrap();
+        assert_eq!(
+            http_url(&url).unwrap().as_str(),
+            "http://localhost:8080/share/a.png"
+        );
+
+        assert!(http_url(&Url::parse("https://example.com/a.png").unwrap()).is_err());
+        assert!(http_url(&Url::parse("dav:///a.png").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parents() {
+        let url = Url::parse("https://cloud.example.com/dav/me/photos/a.png").unwrap();
+        let parents: Vec<String> = parents(&url).iter().map(Url::to_string).collect();
+        assert_eq!(
+            parents,
+            [
+                "https://cloud.example.com/dav/",
+                "https://cloud.example.com/dav/me/",
+                "https://cloud.example.com/dav/me/photos/",
+            ]
+        );
+    }
+}